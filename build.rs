@@ -118,9 +118,6 @@ fn main() {
     println!("cargo:rustc-check-cfg=cfg(has_eof_trick)");
     // Re-run build script if filesystem list changes
 
-    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
-    println!("cargo:rustc-env=FDF_PAGE_SIZE={page_size}");
-
     #[cfg(target_os = "macos")]
     test_eof();
 
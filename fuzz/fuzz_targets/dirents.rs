@@ -0,0 +1,11 @@
+//! Feeds arbitrary bytes to the `dirent64` record parser, run with `cargo +nightly fuzz run dirents`
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Malformed input must be rejected cleanly, the parser panics if its fast and slow paths disagree
+    if let Ok(records) = fdf::fs::parse_dirents(data) {
+        assert!(records.iter().all(|record| !record.name.contains(&0)));
+    }
+});
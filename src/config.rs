@@ -1,11 +1,16 @@
 use crate::SearchConfigError;
-use crate::filters::{FileTypeFilter, SizeFilter, TimeFilter};
+use crate::filters::{FileTypeFilter, Filter, PermFilter, SizeFilter, TimeFilter};
 use crate::fs::{DirEntry, FileDes, FileType};
-use crate::util::glob_to_regex;
+use crate::util::{glob_to_path_regex, glob_to_regex, memrchr};
+use crate::walk::StatFilter;
 use core::num::NonZeroU32;
 use core::ops::Deref;
 use core::time::Duration;
-use regex::bytes::{Regex, RegexBuilder};
+use ignore::gitignore::Gitignore;
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::Path;
 use std::time::UNIX_EPOCH;
 use thread_local::ThreadLocal;
 
@@ -32,7 +37,7 @@ impl core::fmt::Debug for TLSRegex {
 }
 
 impl TLSRegex {
-    const fn new(regex: Regex) -> Self {
+    pub(crate) const fn new(regex: Regex) -> Self {
         Self {
             base: regex,
             local: ThreadLocal::new(),
@@ -45,6 +50,248 @@ impl TLSRegex {
     }
 }
 
+/// A [`RegexSet`] cloned per thread, as with [`TLSRegex`]
+pub struct TLSRegexSet {
+    base: RegexSet,
+    local: ThreadLocal<RegexSet>,
+}
+
+impl Clone for TLSRegexSet {
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            local: ThreadLocal::new(),
+        }
+    }
+}
+
+impl core::fmt::Debug for TLSRegexSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TLSRegexSet")
+            .field("base", &self.base)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TLSRegexSet {
+    const fn new(set: RegexSet) -> Self {
+        Self {
+            base: set,
+            local: ThreadLocal::new(),
+        }
+    }
+
+    /// Returns true if any pattern in the set matches
+    #[inline]
+    pub fn is_match(&self, path: &[u8]) -> bool {
+        self.local.get_or(|| self.base.clone()).is_match(path)
+    }
+}
+
+/**
+ Parses a pattern list, as read by [`FinderBuilder::pattern_files`](crate::walk::FinderBuilder::pattern_files).
+
+ Each line is one pattern, with surrounding whitespace trimmed. Blank lines and lines starting with `#` are skipped,
+ a pattern starting with `#` can be written as `\#`.
+*/
+pub(crate) fn parse_pattern_list(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+}
+
+/**
+ A curated list of patterns, see [`FinderBuilder::presets`](crate::walk::FinderBuilder::presets).
+
+ Each is a pattern list in the same format as a pattern file, written as regexes which match either the file name
+ or the path relative to the root, so they work whatever the other options are.
+
+ ```
+ use fdf::Preset;
+
+ let preset: Preset = "secrets".parse().unwrap();
+ assert!(preset.pattern_list().lines().any(|line| line.contains("id_")));
+ ```
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Build outputs, dependency directories and core dumps, which are usually safe to clean up
+    Artifacts,
+    /// Private keys, certificates and credential files, which shouldn't be lying around (or committed)
+    Secrets,
+}
+
+const ARTIFACT_PATTERNS: &str = r"# Dependency and build directories
+(?:^|/)node_modules$
+(?:^|/)target$
+(?:^|/)__pycache__$
+(?:^|/)\.(?:gradle|tox|pytest_cache|mypy_cache)$
+# Compiled objects
+\.(?:o|obj|pyc|class)$
+# Core dumps
+(?:^|/)core(?:\.\d+)?$
+\.(?:core|dmp)$
+";
+
+const SECRET_PATTERNS: &str = r"# SSH and other private keys
+(?:^|/)id_(?:rsa|dsa|ecdsa|ed25519)$
+\.(?:pem|key|p12|pfx|jks|keystore|kdbx)$
+# Environment and credential files
+(?:^|/)\.env(?:\.[^/]+)?$
+(?:^|/)\.(?:netrc|pgpass|htpasswd|git-credentials|npmrc|pypirc)$
+(?:^|/)credentials(?:\.json)?$
+";
+
+impl Preset {
+    /// The patterns of the preset, one per line (with `#` comments) as in a pattern file
+    #[inline]
+    #[must_use]
+    pub const fn pattern_list(self) -> &'static str {
+        match self {
+            Self::Artifacts => ARTIFACT_PATTERNS,
+            Self::Secrets => SECRET_PATTERNS,
+        }
+    }
+}
+
+impl core::str::FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "artifacts" => Ok(Self::Artifacts),
+            "secrets" => Ok(Self::Secrets),
+            _ => Err(format!(
+                "unknown preset '{s}', expected one of: artifacts, secrets"
+            )),
+        }
+    }
+}
+
+/// Checks `name` (of length >= 1) ends in `.` followed by `ext`, ignoring ASCII case
+#[inline]
+fn has_extension(name: &[u8], ext: &[u8]) -> bool {
+    let name_len = name.len(); // guaranteed >= 1
+    // Saturating arithmetic keeps both indices in-bounds with no branches.
+    // When name_len < ext_len + 1 the suffix slice will be shorter than ext,
+    // so eq_ignore_ascii_case returns false without any explicit length guard
+    let suffix_start = name_len.saturating_sub(ext.len());
+    let dot_idx = suffix_start.saturating_sub(1);
+    // SAFETY:
+    // - `suffix_start` is in  [0, name_len], so `name[suffix_start..]` is a valid subslice.
+    // - `dot_idx` saturates to 0 when suffix_start == 0; name_len >= 1 guarantees index 0 is valid.
+    let suffix = unsafe { name.get_unchecked(suffix_start..) };
+    // SAFETY: as above
+    let dot_byte = unsafe { *name.get_unchecked(dot_idx) };
+    dot_byte == b'.' && suffix.eq_ignore_ascii_case(ext)
+}
+
+/// Characters with a special meaning in a regex, a pattern without any (unescaped) can be matched literally
+const REGEX_META_CHARACTERS: &[char] = &[
+    '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$',
+];
+/// Characters `regex::escape` may put a backslash in front of
+const REGEX_ESCAPABLE_CHARACTERS: &[char] = &[
+    '\\', '.', '+', '*', '?', '(', ')', '|', '[', ']', '{', '}', '^', '$', '#', '&', '-', '~',
+];
+
+/**
+ Returns the literal text a regex pattern matches, if it contains no metacharacters.
+
+ Metacharacters escaped with a backslash (as produced by `regex::escape`, ie `--fixed-strings`) are accepted,
+ any other escape (eg `\d`) means the pattern is not a literal.
+*/
+fn regex_literal(pattern: &str) -> Option<String> {
+    let mut literal = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            literal.push(
+                chars
+                    .next()
+                    .filter(|next| REGEX_ESCAPABLE_CHARACTERS.contains(next))?,
+            );
+        } else if REGEX_META_CHARACTERS.contains(&ch) {
+            return None;
+        } else {
+            literal.push(ch);
+        }
+    }
+    Some(literal)
+}
+
+/**
+ A compiled search pattern.
+
+ Plain literals skip regex compilation entirely and are matched with `memmem`, which matters for
+ users passing fd style literals without `--fixed-strings`. ASCII literals are also matched
+ directly when searching case insensitively (the CLI default), folding only ASCII letters.
+*/
+#[derive(Clone, Debug)]
+#[expect(
+    clippy::large_enum_variant,
+    reason = "One per pattern, so the size difference doesn't matter"
+)]
+pub enum PathMatcher {
+    Regex(TLSRegex),
+    Literal(memchr::memmem::Finder<'static>),
+    /// An ASCII literal, stored lowercased and matched ignoring ASCII case.
+    AsciiCaseInsensitive(Box<[u8]>),
+}
+
+impl PathMatcher {
+    /// Compiles `pattern`, using a literal fast path when it has no metacharacters
+    /// (and any letters are ASCII if matching case insensitively).
+    fn new(pattern: &str, case_insensitive: bool) -> core::result::Result<Self, SearchConfigError> {
+        if let Some(literal) = regex_literal(pattern) {
+            if !(case_insensitive && literal.chars().any(char::is_alphabetic)) {
+                return Ok(Self::Literal(
+                    memchr::memmem::Finder::new(literal.as_bytes()).into_owned(),
+                ));
+            }
+            if literal.is_ascii() {
+                return Ok(Self::AsciiCaseInsensitive(
+                    literal.to_ascii_lowercase().into_bytes().into(),
+                ));
+            }
+        }
+
+        RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .dot_matches_new_line(false)
+            .build()
+            .map(|reg| Self::Regex(TLSRegex::new(reg)))
+            .map_err(SearchConfigError::RegexError)
+    }
+
+    #[inline]
+    pub fn is_match(&self, path: &[u8]) -> bool {
+        match self {
+            Self::Regex(reg) => reg.is_match(path),
+            Self::Literal(finder) => finder.find(path).is_some(),
+            Self::AsciiCaseInsensitive(needle) => contains_ignore_ascii_case(path, needle),
+        }
+    }
+}
+
+/// Checks if `haystack` contains the lowercase `needle`, ignoring ASCII case.
+///
+/// Candidates are found by searching for either case of the first byte with `memchr2`.
+#[inline]
+fn contains_ignore_ascii_case(haystack: &[u8], needle: &[u8]) -> bool {
+    let Some(&first) = needle.first() else {
+        return true;
+    };
+
+    memchr::memchr2_iter(first, first.to_ascii_uppercase(), haystack).any(|start| {
+        haystack
+            .get(start..start + needle.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(needle))
+    })
+}
+
 /**
 This struct holds the configuration for searching a File system via traversal
 
@@ -60,14 +307,23 @@ pub struct SearchConfig {
 
     If `None`, matches all files (equivalent to an empty pattern).
     When `file_name_only` is true, only matches against the base filename.
-    Uses thread-local storage for efficient multi-threaded regex matching.
+    Uses thread-local storage for efficient multi-threaded regex matching,
+    patterns without metacharacters are matched literally instead.
     */
-    pub(crate) regex_match: Option<TLSRegex>,
+    pub(crate) regex_match: Option<PathMatcher>,
 
     /// Additional required matchers added via `--and`.
     ///
     /// All compiled matchers in this list must match for a path to be accepted.
-    pub(crate) and_match: Vec<TLSRegex>,
+    pub(crate) and_match: Vec<PathMatcher>,
+
+    /// Negated patterns (`--not`), compiled into one matcher which must not match.
+    ///
+    /// Only consulted once the other patterns have matched.
+    pub(crate) not_match: Option<PathMatcher>,
+
+    /// Patterns from pattern files (`--pattern-file`), compiled into one set of which any must match.
+    pub(crate) any_match: Option<TLSRegexSet>,
 
     /**
     Whether to exclude hidden files and directories
@@ -78,12 +334,12 @@ pub struct SearchConfig {
     pub(crate) hide_hidden: bool,
 
     /**
-    File extension to filter by (case-insensitive)
+    File extensions to filter by (case-insensitive)
 
-    If `Some`, only files with this extension are matched.
-    The extension should not include the leading dot (e.g., `"txt"` not `".txt"`).
+    If not empty, only files with one of these extensions are matched.
+    The extensions should not include the leading dot (e.g., `"txt"` not `".txt"`).
     */
-    pub(crate) extension_match: Option<Box<[u8]>>,
+    pub(crate) extension_match: Box<[Box<[u8]>]>,
 
     /**
     Whether regex matching applies only to filename vs full path
@@ -101,6 +357,14 @@ pub struct SearchConfig {
     */
     pub(crate) depth: Option<NonZeroU32>,
 
+    /**
+    Minimum depth of the entries to emit
+
+    Shallower directories are still traversed, only their entries aren't emitted.
+    If `None`, entries are emitted from depth 1 (the root's own entries).
+    */
+    pub(crate) min_depth: Option<NonZeroU32>,
+
     /**
     Whether to follow symbolic links during traversal
 
@@ -133,6 +397,26 @@ pub struct SearchConfig {
     */
     pub(crate) time_filter: Option<TimeFilter>,
 
+    /// Filter based on file creation (birth) time, entries without one never match
+    pub(crate) created_filter: Option<TimeFilter>,
+
+    /// Filter based on file access time
+    pub(crate) accessed_filter: Option<TimeFilter>,
+
+    /**
+    Filter based on permission bits
+
+    If `Some`, only files whose permissions match are included.
+    Supports exact, all bits set and any bits set matching.
+    */
+    pub(crate) perm_filter: Option<PermFilter>,
+
+    /// A custom filter given the entry's `lstat`, run alongside the other metadata filters
+    pub(crate) stat_filter: Option<StatFilter>,
+
+    /// A filter expression (`--expr`), evaluated once every other filter has matched
+    pub(crate) expression: Option<Filter>,
+
     /**
     Whether to respect `.gitignore` files during traversal.
 
@@ -142,6 +426,22 @@ pub struct SearchConfig {
 
     /// Compiled ignore matcher (`--ignore` + `--ignoreg`) backed by thread-local regex clones.
     pub(crate) ignore_match: Option<TLSRegex>,
+
+    /**
+    Compiled exclude globs (`--exclude`), in `.gitignore` syntax rooted at the search root.
+
+    Checked before a directory is queued, so excluded subtrees are never opened.
+    */
+    pub(crate) exclude_match: Option<Gitignore>,
+
+    /**
+    Length of the search root as it prefixes every path, 0 if unknown.
+
+    Full path matching is done on the path relative to the root, so patterns don't depend on how the root was spelt.
+    */
+    pub(crate) root_len: usize,
+    /// Whether there are several search roots, when the path below the root is worked out from the depth instead
+    pub(crate) multiple_roots: bool,
 }
 impl SearchConfig {
     /**
@@ -160,17 +460,24 @@ impl SearchConfig {
         hide_hidden: bool,
         case_insensitive: bool,
         filenameonly: bool,
-        extension_match: Option<Box<[u8]>>,
+        extension_match: Box<[Box<[u8]>]>,
         depth: Option<NonZeroU32>,
         follow_symlinks: bool,
         size_filter: Option<SizeFilter>,
         type_filter: Option<FileTypeFilter>,
         time_filter: Option<TimeFilter>,
+        created_filter: Option<TimeFilter>,
+        accessed_filter: Option<TimeFilter>,
+        perm_filter: Option<PermFilter>,
         use_glob: bool,
         and_patterns: Vec<String>,
+        not_patterns: Vec<String>,
+        any_patterns: Vec<String>,
+        presets: &[Preset],
         respect_gitignore: bool,
         ignore_patterns: Vec<String>,
         ignore_glob_patterns: Vec<String>,
+        exclude_match: Option<Gitignore>,
     ) -> core::result::Result<Self, SearchConfigError> {
         let (file_nm, pattern_to_use) = if let Some(patt_ref) = pattern.as_ref() {
             let patt = patt_ref.as_ref();
@@ -181,7 +488,7 @@ impl SearchConfig {
             };
 
             let pattern_to_use = if use_glob {
-                glob_to_regex(patt).map_err(SearchConfigError::GlobToRegexError)?
+                glob_to_path_regex(patt).map_err(SearchConfigError::GlobToRegexError)?
             } else {
                 patt.into()
             };
@@ -196,15 +503,7 @@ impl SearchConfig {
             if pattern_to_use == "." || pattern_to_use == ".*" || pattern_to_use.is_empty() {
                 None
             } else {
-                let reg = RegexBuilder::new(&pattern_to_use)
-                    .case_insensitive(case_insensitive)
-                    .dot_matches_new_line(false)
-                    .build();
-
-                if let Err(regerror) = reg {
-                    return Err(SearchConfigError::RegexError(regerror));
-                }
-                reg.ok().map(TLSRegex::new)
+                Some(PathMatcher::new(&pattern_to_use, case_insensitive)?)
             };
 
         let mut and_match = Vec::with_capacity(and_patterns.len());
@@ -215,7 +514,7 @@ impl SearchConfig {
             }
 
             let f_pattern = if use_glob {
-                glob_to_regex(&patt).map_err(SearchConfigError::GlobToRegexError)?
+                glob_to_path_regex(&patt).map_err(SearchConfigError::GlobToRegexError)?
             } else {
                 patt
             };
@@ -224,13 +523,64 @@ impl SearchConfig {
                 continue;
             }
 
-            let reg = RegexBuilder::new(&f_pattern)
+            and_match.push(PathMatcher::new(&f_pattern, case_insensitive)?);
+        }
+
+        let not_patterns = not_patterns
+            .into_iter()
+            .filter(|patt| !patt.is_empty())
+            .map(|patt| {
+                if patt.contains('/') {
+                    file_name_only = false;
+                }
+                if use_glob {
+                    glob_to_path_regex(&patt).map_err(SearchConfigError::GlobToRegexError)
+                } else {
+                    Ok(patt)
+                }
+            })
+            .collect::<core::result::Result<Vec<_>, _>>()?;
+        // A lone pattern keeps the literal fast path, several are joined into one regex
+        let not_match = match not_patterns.as_slice() {
+            [] => None,
+            [patt] => Some(PathMatcher::new(patt, case_insensitive)?),
+            patts => {
+                let combined = patts
+                    .iter()
+                    .map(|patt| format!("(?:{patt})"))
+                    .collect::<Vec<_>>()
+                    .join("|");
+                Some(PathMatcher::new(&combined, case_insensitive)?)
+            }
+        };
+
+        let any_patterns = any_patterns
+            .into_iter()
+            .map(|patt| {
+                if patt.contains('/') {
+                    file_name_only = false;
+                }
+                if use_glob {
+                    glob_to_path_regex(&patt).map_err(SearchConfigError::GlobToRegexError)
+                } else {
+                    Ok(patt)
+                }
+            })
+            .chain(presets.iter().flat_map(|preset| {
+                // Already regexes which work on file names and paths alike, so taken as they are
+                parse_pattern_list(preset.pattern_list()).map(Ok)
+            }))
+            .collect::<core::result::Result<Vec<_>, _>>()?;
+        let any_match = if any_patterns.is_empty() {
+            None
+        } else {
+            let set = RegexSetBuilder::new(any_patterns)
                 .case_insensitive(case_insensitive)
                 .dot_matches_new_line(false)
                 .build()
                 .map_err(SearchConfigError::RegexError)?;
-            and_match.push(TLSRegex::new(reg));
-        }
+            Some(TLSRegexSet::new(set))
+        };
 
         let mut ignore_patterns_merged =
             Vec::with_capacity(ignore_patterns.len() + ignore_glob_patterns.len());
@@ -262,16 +612,27 @@ impl SearchConfig {
         Ok(Self {
             regex_match,
             and_match,
+            not_match,
+            any_match,
             hide_hidden,
             extension_match,
             file_name_only,
             depth,
+            min_depth: None,
             follow_symlinks,
             size_filter,
             type_filter,
             time_filter,
+            created_filter,
+            accessed_filter,
+            perm_filter,
+            stat_filter: None,
+            expression: None,
             respect_gitignore,
             ignore_match,
+            exclude_match,
+            root_len: 0,
+            multiple_roots: false,
         })
     }
 
@@ -284,6 +645,20 @@ impl SearchConfig {
             .is_some_and(|reg| reg.is_match(path))
     }
 
+    /// Returns true when the entry matches one of the exclude globs, directories are matched as such so `dir/` patterns work.
+    #[inline]
+    #[must_use]
+    pub fn matches_exclude(&self, entry: &DirEntry) -> bool {
+        self.exclude_match.as_ref().is_some_and(|matcher| {
+            let path = if self.multiple_roots {
+                Path::new(OsStr::from_bytes(self.relative_path(entry)))
+            } else {
+                entry.as_path()
+            };
+            matcher.matched(path, entry.is_dir()).is_ignore()
+        })
+    }
+
     /// Evaluates a custom predicate function against a path
     #[inline]
     #[must_use]
@@ -291,7 +666,14 @@ impl SearchConfig {
         predicate(path)
     }
 
-    /// Checks for extension match, branchlessly.
+    /// Checks the entry is at least as deep as the minimum depth
+    #[inline]
+    #[must_use]
+    pub fn matches_min_depth(&self, dir: &DirEntry) -> bool {
+        self.min_depth.is_none_or(|min| dir.depth >= min.get())
+    }
+
+    /// Checks for a match with any of the extensions, each check is branchless.
     /// The entry is guaranteed to be a filename (no slashes) of length >= 1.
     #[inline]
     pub fn matches_extension<S>(&self, entry: &S) -> bool
@@ -308,21 +690,11 @@ impl SearchConfig {
             "internal invariant should always have length>=1 in matches extension"
         );
 
-        self.extension_match.as_deref().is_none_or(|ext| {
-            let name_len = entry.len(); // guaranteed >= 1
-            // Saturating arithmetic keeps both indices in-bounds with no branches.
-            // When name_len < ext_len + 1 the suffix slice will be shorter than ext,
-            // so eq_ignore_ascii_case returns false without any explicit length guard
-            let suffix_start = name_len.saturating_sub(ext.len());
-            let dot_idx = suffix_start.saturating_sub(1);
-            // SAFETY:
-            // - `suffix_start` is in  [0, name_len], so `name[suffix_start..]` is a valid subslice.
-            // - `dot_idx` saturates to 0 when suffix_start == 0; name_len >= 1 guarantees index 0 is valid.
-            let suffix = unsafe { entry.get_unchecked(suffix_start..) };
-            // SAFETY: as above
-            let dot_byte = unsafe { *entry.get_unchecked(dot_idx) };
-            dot_byte == b'.' && suffix.eq_ignore_ascii_case(ext)
-        })
+        self.extension_match.is_empty()
+            || self
+                .extension_match
+                .iter()
+                .any(|ext| has_extension(entry, ext))
     }
 
     /**
@@ -374,10 +746,20 @@ impl SearchConfig {
                         .is_some_and(|sz| filter_size.is_within_size(sz))
                 },
                 |fd| {
-                    entry
-                        .get_lstatat(fd)
-                        .ok()
-                        .is_some_and(|statted| filter_size.is_within_size(statted.st_size as _))
+                    #[cfg(any(
+                        all(target_os = "linux", target_env = "gnu"),
+                        target_os = "android"
+                    ))]
+                    let size = entry
+                        .get_statxat(fd, libc::STATX_SIZE)
+                        .map(|statted| statted.stx_size);
+                    #[cfg(not(any(
+                        all(target_os = "linux", target_env = "gnu"),
+                        target_os = "android"
+                    )))]
+                    let size = entry.get_lstatat(fd).map(|statted| statted.st_size as u64);
+
+                    size.is_ok_and(|size| filter_size.is_within_size(size))
                 },
             ),
             // Check if it exists first, then call stat..
@@ -406,43 +788,14 @@ impl SearchConfig {
     #[inline]
     #[must_use]
     pub fn matches_type(&self, entry: &DirEntry) -> bool {
-        let Some(type_filter) = self.type_filter else {
-            return true;
-        };
-
-        match type_filter {
-            FileTypeFilter::File => entry.is_regular_file(),
-            FileTypeFilter::Directory => entry.is_dir(),
-            FileTypeFilter::Symlink => entry.is_symlink(),
-            FileTypeFilter::Pipe => entry.is_pipe(),
-            FileTypeFilter::CharDevice => entry.is_char_device(),
-            FileTypeFilter::BlockDevice => entry.is_block_device(),
-            FileTypeFilter::Socket => entry.is_socket(),
-            FileTypeFilter::Unknown => entry.is_unknown(),
-            FileTypeFilter::Executable => entry.is_executable(),
-            FileTypeFilter::Empty => entry.is_empty(),
-        }
+        self.matches_type_at(entry, None)
     }
 
     #[inline]
     #[must_use]
     pub(crate) fn matches_type_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
-        let Some(type_filter) = self.type_filter else {
-            return true;
-        };
-
-        match type_filter {
-            FileTypeFilter::File => entry.is_regular_file(),
-            FileTypeFilter::Directory => entry.is_dir(),
-            FileTypeFilter::Symlink => entry.is_symlink(),
-            FileTypeFilter::Pipe => entry.is_pipe(),
-            FileTypeFilter::CharDevice => entry.is_char_device(),
-            FileTypeFilter::BlockDevice => entry.is_block_device(),
-            FileTypeFilter::Socket => entry.is_socket(),
-            FileTypeFilter::Unknown => entry.is_unknown(),
-            FileTypeFilter::Executable => entry.is_executable_at(opt_fd),
-            FileTypeFilter::Empty => entry.is_empty_at(opt_fd),
-        }
+        self.type_filter
+            .is_none_or(|type_filter| type_filter.matches_at(entry, opt_fd))
     }
 
     /// Applies time-based filtering to files based on modification time
@@ -463,6 +816,147 @@ impl SearchConfig {
             .is_some_and(|systime| time_filter.matches_time(systime))
     }
 
+    /**
+    The first stage of filtering, using only what's known without a `stat` (the depth, name and `d_type`).
+
+    The executable and empty type filters are the exception, needing a `stat` of their own, as they decide what
+    an entry is rather than comparing its metadata.
+    */
+    #[inline]
+    #[must_use]
+    pub(crate) fn matches_name_stage(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        // arranged by order of costliness
+        self.matches_min_depth(entry)
+            && self.matches_extension(&entry.file_name())
+            && self.matches_path(entry, !self.file_name_only)
+            && self.matches_type_at(entry, opt_fd)
+    }
+
+    /**
+    The second stage of filtering, on the metadata of the entries which survived [`matches_name_stage`](Self::matches_name_stage).
+
+    New filters needing an `lstat` belong in [`matches_metadata_at`](Self::matches_metadata_at), which shares
+    the one call between them.
+    */
+    #[inline]
+    #[must_use]
+    pub(crate) fn matches_metadata_stage(
+        &self,
+        entry: &DirEntry,
+        opt_fd: Option<&FileDes>,
+    ) -> bool {
+        self.matches_metadata_at(entry, opt_fd)
+            && self.matches_created_and_accessed_at(entry, opt_fd)
+    }
+
+    /**
+    Applies the size, time, permission and custom `lstat` filters, sharing a single `lstat` between them when more
+    than one is set.
+
+    Symlinks still need a `stat` of their target for the size filter, as in [`matches_size`](Self::matches_size).
+    */
+    #[inline]
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)] // Sign loss does not matter here
+    pub(crate) fn matches_metadata_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        let stat_filters = usize::from(self.size_filter.is_some())
+            + usize::from(self.time_filter.is_some())
+            + usize::from(self.perm_filter.is_some())
+            + usize::from(self.stat_filter.is_some());
+
+        if stat_filters < 2 {
+            // At most one filter, which stats on its own (or not at all)
+            return self.matches_size_at(entry, opt_fd)
+                && self.matches_time_at(entry, opt_fd)
+                && self.matches_permissions_at(entry, opt_fd)
+                && self.stat_filter.is_none_or(|stat_filter| {
+                    opt_fd
+                        .map_or_else(|| entry.get_lstat(), |fd| entry.get_lstatat(fd))
+                        .is_ok_and(|statted| stat_filter(entry, &statted))
+                });
+        }
+
+        let Ok(statted) = opt_fd.map_or_else(|| entry.get_lstat(), |fd| entry.get_lstatat(fd))
+        else {
+            return false;
+        };
+
+        let size_matches = self
+            .size_filter
+            .is_none_or(|filter_size| match entry.file_type {
+                FileType::RegularFile => filter_size.is_within_size(statted.st_size as _),
+                FileType::Symlink => self.matches_size_at(entry, opt_fd),
+                _ => false,
+            });
+
+        size_matches
+            && self
+                .time_filter
+                .is_none_or(|time_filter| Self::stat_matches_time(&statted, time_filter))
+            && self
+                .perm_filter
+                .is_none_or(|perm_filter| Self::stat_matches_permissions(&statted, perm_filter))
+            && self
+                .stat_filter
+                .is_none_or(|stat_filter| stat_filter(entry, &statted))
+    }
+
+    /// Evaluates the filter expression, if any, with path tests given the path below the search root
+    #[inline]
+    #[must_use]
+    pub(crate) fn matches_expression_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        self.expression.as_ref().is_none_or(|expression| {
+            expression.matches_at(entry, self.relative_path(entry), opt_fd)
+        })
+    }
+
+    /// Applies the permission filter to a directory entry, if any, using the mode from `lstat`
+    #[inline]
+    #[must_use]
+    pub fn matches_permissions(&self, entry: &DirEntry) -> bool {
+        self.matches_permissions_at(entry, None)
+    }
+
+    #[inline]
+    #[must_use]
+    pub(crate) fn matches_permissions_at(
+        &self,
+        entry: &DirEntry,
+        opt_fd: Option<&FileDes>,
+    ) -> bool {
+        let Some(perm_filter) = self.perm_filter else {
+            return true; // No filter means always match
+        };
+
+        opt_fd
+            .map_or_else(|| entry.get_lstat(), |fd| entry.get_lstatat(fd))
+            .is_ok_and(|statted| Self::stat_matches_permissions(&statted, perm_filter))
+    }
+
+    /// Checks a `stat`'s mode against `perm_filter`
+    #[inline]
+    #[allow(clippy::useless_conversion)] // mode_t isn't u32 everywhere
+    fn stat_matches_permissions(statted: &libc::stat, perm_filter: PermFilter) -> bool {
+        perm_filter.matches_mode(u32::from(statted.st_mode))
+    }
+
+    /// Checks a `stat`'s modification time against `time_filter`
+    #[inline]
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn stat_matches_time(statted: &libc::stat, time_filter: TimeFilter) -> bool {
+        let secs: i64 = access_stat!(statted, st_mtime);
+        let nanos: i64 = access_stat!(statted, st_mtimensec);
+        let whole_secs = Duration::from_secs(secs.unsigned_abs());
+
+        if secs >= 0 {
+            UNIX_EPOCH.checked_add(whole_secs)
+        } else {
+            UNIX_EPOCH.checked_sub(whole_secs)
+        }
+        .and_then(|systime| systime.checked_add(Duration::from_nanos(nanos as u64)))
+        .is_some_and(|systime| time_filter.matches_time(systime))
+    }
+
     #[inline]
     #[must_use]
     pub(crate) fn matches_time_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
@@ -479,20 +973,81 @@ impl SearchConfig {
             .is_some_and(|systime| time_filter.matches_time(systime))
     }
 
+    /**
+    Applies the creation and access time filters, if any.
+
+    These are separate from [`matches_metadata_at`](Self::matches_metadata_at) as on Linux they use `statx`,
+    asking only for the timestamp needed.
+    */
+    #[inline]
+    #[must_use]
+    pub(crate) fn matches_created_and_accessed_at(
+        &self,
+        entry: &DirEntry,
+        opt_fd: Option<&FileDes>,
+    ) -> bool {
+        self.created_filter.is_none_or(|created_filter| {
+            Self::datetime_matches(entry.created_time_at(opt_fd), created_filter)
+        }) && self.accessed_filter.is_none_or(|accessed_filter| {
+            Self::datetime_matches(entry.accessed_time_at(opt_fd), accessed_filter)
+        })
+    }
+
+    /// Checks a timestamp read from an entry against `time_filter`, failing to read it never matches
+    #[inline]
+    fn datetime_matches(
+        datetime: crate::fs::Result<chrono::DateTime<chrono::Utc>>,
+        time_filter: TimeFilter,
+    ) -> bool {
+        datetime
+            .ok()
+            .and_then(|datetime| datetime.timestamp_nanos_opt())
+            .and_then(|nanos| UNIX_EPOCH.checked_add(Duration::from_nanos(nanos.cast_unsigned())))
+            .is_some_and(|systime| time_filter.matches_time(systime))
+    }
+
+    /// Returns the path of `dir` below the search root (eg `src/main.rs` when searching `./`)
+    ///
+    /// The root itself, or any path when the root isn't known, is returned whole.
+    #[inline]
+    #[must_use]
+    pub fn relative_path<'dir>(&self, dir: &'dir DirEntry) -> &'dir [u8] {
+        let path = dir.as_bytes();
+        if dir.depth == 0 || self.root_len == 0 {
+            return path;
+        }
+        if self.multiple_roots {
+            // Each level below the root adds one component, whichever root the entry was found under
+            let start = (0..dir.depth).try_fold(path.len(), |end, _| memrchr(b'/', &path[..end]));
+            return start.map_or(path, |start| &path[start + 1..]);
+        }
+        path.get(self.root_len..).map_or(path, |relative| {
+            relative.strip_prefix(b"/").unwrap_or(relative)
+        })
+    }
+
     /// Checks if the path or file name matches the regex filter
-    /// If `full_path` is false, only checks the filename
+    /// If `full_path` is false, only checks the filename, otherwise the path relative to the search root
     #[inline]
     #[must_use]
     pub fn matches_path(&self, dir: &DirEntry, full_path: bool) -> bool {
-        // Use arithmetic to avoid branching costs.
-        let index_amount = usize::from(!full_path) * dir.file_name_index();
-
-        // SAFETY: we are always indexing within bounds.
-        let candidate = unsafe { dir.get_unchecked(index_amount..) };
+        let candidate = if full_path {
+            self.relative_path(dir)
+        } else {
+            dir.file_name()
+        };
 
         self.regex_match
             .as_ref()
             .is_none_or(|reg| reg.is_match(candidate))
             && self.and_match.iter().all(|reg| reg.is_match(candidate))
+            && self
+                .any_match
+                .as_ref()
+                .is_none_or(|set| set.is_match(candidate))
+            && self
+                .not_match
+                .as_ref()
+                .is_none_or(|reg| !reg.is_match(candidate))
     }
 }
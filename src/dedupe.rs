@@ -0,0 +1,242 @@
+//! Finding files with identical contents, the library side of `fdf --duplicates`.
+//!
+//! [`Finder::find_duplicates`] narrows the candidates in stages, so most files are never read:
+//!
+//! 1. While walking, each worker thread buckets the regular files it finds by size.
+//! 2. Only files sharing a size with another file can be duplicates. Their contents are hashed in parallel,
+//!    streamed in fixed size chunks so large files don't need to fit in memory.
+//! 3. Files sharing both a size and a hash are reported as duplicates.
+//!
+//! ```
+//! use fdf::dedupe::HashAlgo;
+//! use fdf::walk::Finder;
+//! use std::fs;
+//!
+//! let dir = std::env::temp_dir().join("fdf_dedupe_doctest");
+//! let _ = fs::remove_dir_all(&dir);
+//! fs::create_dir_all(&dir).unwrap();
+//! fs::write(dir.join("a"), "same contents").unwrap();
+//! fs::write(dir.join("b"), "same contents").unwrap();
+//! fs::write(dir.join("c"), "same length!!").unwrap();
+//!
+//! let groups = Finder::init(&dir).build().unwrap().find_duplicates(HashAlgo::default()).unwrap();
+//! assert_eq!(groups.len(), 1);
+//! assert_eq!(groups[0].size, 13);
+//! let names: Vec<_> = groups[0].entries.iter().map(|entry| entry.file_name()).collect();
+//! assert_eq!(names, [b"a", b"b"]);
+//! fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use crate::{
+    SearchConfigError,
+    filters::open_regular_file,
+    fs::DirEntry,
+    walk::{ControlFlow, Finder},
+};
+use core::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read as _;
+use std::thread;
+use thread_local::ThreadLocal;
+#[cfg(feature = "xxhash")]
+use xxhash_rust::xxh64::Xxh64;
+
+/// How much of a file is read at once while hashing it
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The hash used to compare the contents of files of the same size, each behind the cargo feature of the same name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HashAlgo {
+    /// 64 bit xxHash (XXH64), fast and well distributed, the default
+    #[cfg(feature = "xxhash")]
+    Xxh64,
+    /// BLAKE3 cut to its first 64 bits, slower but far more collision resistant (the default without `xxhash`)
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+#[cfg(feature = "xxhash")]
+impl Default for HashAlgo {
+    #[inline]
+    fn default() -> Self {
+        Self::Xxh64
+    }
+}
+
+#[cfg(not(feature = "xxhash"))]
+impl Default for HashAlgo {
+    #[inline]
+    fn default() -> Self {
+        Self::Blake3
+    }
+}
+
+impl HashAlgo {
+    /// Hashes everything `reader` yields, `buffer` is scratch space for the reads
+    fn hash_reader(self, reader: &mut File, buffer: &mut [u8]) -> std::io::Result<u64> {
+        let mut hasher = match self {
+            #[cfg(feature = "xxhash")]
+            Self::Xxh64 => ContentHasher::Xxh64(Xxh64::new(0)),
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+        };
+
+        loop {
+            match reader.read(buffer) {
+                Ok(0) => break,
+                Ok(read) => hasher.update(&buffer[..read]),
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(hasher.finish())
+    }
+}
+
+enum ContentHasher {
+    #[cfg(feature = "xxhash")]
+    Xxh64(Xxh64),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl ContentHasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            #[cfg(feature = "xxhash")]
+            Self::Xxh64(hasher) => hasher.update(bytes),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match self {
+            #[cfg(feature = "xxhash")]
+            Self::Xxh64(hasher) => hasher.digest(),
+            #[cfg(feature = "blake3")]
+            Self::Blake3(hasher) => {
+                let mut first = [0; 8];
+                first.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+                u64::from_le_bytes(first)
+            }
+        }
+    }
+}
+
+/// Files with identical contents, as returned by [`Finder::find_duplicates`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct DuplicateGroup {
+    /// The size of each file in bytes
+    pub size: u64,
+    /// The hash of the contents, using the [`HashAlgo`] the search was run with
+    pub hash: u64,
+    /// The files, sorted by path
+    pub entries: Vec<DirEntry>,
+}
+
+impl Finder {
+    /**
+    Walks the filesystem and groups the regular files with identical contents, see the [module documentation](crate::dedupe).
+
+    Empty files are skipped, as are files which can't be read. Hard links to the same file have the same
+    contents, so they're reported as duplicates too (see [`group_by_inode`](Self::group_by_inode) to tell them apart).
+    Files are compared by their size and a 64 bit hash rather than byte for byte.
+
+    The groups are sorted largest files first (the most space to reclaim), then by path.
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn find_duplicates(self, algo: HashAlgo) -> Result<Vec<DuplicateGroup>, SearchConfigError> {
+        let thread_count = self.thread_count.get();
+        let per_thread: ThreadLocal<RefCell<HashMap<u64, Vec<DirEntry>>>> = ThreadLocal::new();
+
+        self.visit(|entry| {
+            if entry.is_regular_file()
+                && let Ok(size) = entry.file_size()
+                && size != 0
+            {
+                per_thread
+                    .get_or_default()
+                    .borrow_mut()
+                    .entry(size)
+                    .or_default()
+                    .push(entry.clone());
+            }
+            ControlFlow::Continue
+        })?;
+
+        let mut by_size: HashMap<u64, Vec<DirEntry>> = HashMap::new();
+        for (size, entries) in per_thread.into_iter().flat_map(RefCell::into_inner) {
+            by_size.entry(size).or_default().extend(entries);
+        }
+
+        // Entries can't be shared between threads, so the candidates are handed out through a channel instead
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for (size, entries) in by_size {
+            if entries.len() > 1 {
+                for entry in entries {
+                    let _ = sender.send((size, entry));
+                }
+            }
+        }
+        drop(sender);
+
+        let hashed: Vec<(u64, u64, DirEntry)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..thread_count)
+                .map(|_| {
+                    let receiver = receiver.clone();
+                    scope.spawn(move || {
+                        let mut buffer = vec![0; CHUNK_SIZE];
+                        receiver
+                            .iter()
+                            .filter_map(|(size, entry)| {
+                                let (file, _) = open_regular_file(&entry, None)?;
+                                let hash =
+                                    algo.hash_reader(&mut File::from(file), &mut buffer).ok()?;
+                                Some((size, hash, entry))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect()
+        });
+
+        let mut by_hash: HashMap<(u64, u64), Vec<DirEntry>> = HashMap::new();
+        for (size, hash, entry) in hashed {
+            by_hash.entry((size, hash)).or_default().push(entry);
+        }
+
+        let mut groups: Vec<DuplicateGroup> = by_hash
+            .into_iter()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|((size, hash), mut entries)| {
+                entries.sort_unstable_by(|left, right| left.as_bytes().cmp(right.as_bytes()));
+                DuplicateGroup {
+                    size,
+                    hash,
+                    entries,
+                }
+            })
+            .collect();
+        groups.sort_unstable_by(|left, right| {
+            right
+                .size
+                .cmp(&left.size)
+                .then_with(|| left.entries[0].as_bytes().cmp(right.entries[0].as_bytes()))
+        });
+        Ok(groups)
+    }
+}
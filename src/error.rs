@@ -28,6 +28,18 @@ impl TraversalError {
     pub const fn error(&self) -> &DirEntryError {
         &self.error
     }
+
+    /// The errno of the underlying error, if it came from the OS
+    #[must_use]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.error.raw_os_error()
+    }
+
+    /// Returns true if this reports the traversal running out of time, see [`FinderBuilder::timeout`](crate::walk::FinderBuilder::timeout)
+    #[must_use]
+    pub const fn is_timed_out(&self) -> bool {
+        matches!(self.error, DirEntryError::TimedOut)
+    }
 }
 
 impl fmt::Display for TraversalError {
@@ -129,6 +141,36 @@ where
 }
 
 impl FilesystemIOError {
+    /**
+    The errno behind this error, if it came from the OS.
+
+    Variants which don't keep the [`io::Error`] give the code they're mapped from (the first where several are).
+    */
+    #[must_use]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match *self {
+            Self::TemporarilyUnavailable => Some(EAGAIN),
+            Self::InvalidPath => Some(EINVAL),
+            Self::TooManySymbolicLinks => Some(ELOOP),
+            Self::NameTooLong => Some(ENAMETOOLONG),
+            Self::FileNotFound => Some(ENOENT),
+            Self::OutOfMemory => Some(ENOMEM),
+            Self::NotADirectory => Some(ENOTDIR),
+            Self::AccessDenied(ref error)
+            | Self::FilesystemIO(ref error)
+            | Self::BrokenPipe(ref error)
+            | Self::FileExists(ref error)
+            | Self::IsDirectory(ref error)
+            | Self::FileTooLarge(ref error)
+            | Self::ResourceBusy(ref error)
+            | Self::InvalidFileDescriptor(ref error)
+            | Self::ProcessFileLimitReached(ref error)
+            | Self::SystemFileLimitReached(ref error)
+            | Self::UnsupportedOperation(ref error)
+            | Self::Other(ref error) => error.raw_os_error(),
+        }
+    }
+
     /// Create a new `FilesystemIOError` from a `std::io::Error`
     #[must_use]
     pub fn from_io_error(error: io::Error) -> Self {
@@ -226,6 +268,44 @@ pub enum DirEntryError {
     or directory structure corruption. Contains detailed error context.
     */
     IOError(FilesystemIOError),
+    /**
+    The traversal ran out of time
+
+    Reported once, for the root, when a [`timeout`](crate::walk::FinderBuilder::timeout) cuts a traversal short.
+    */
+    TimedOut,
+    /**
+    The directory wasn't read because too many were already queued
+
+    See [`max_queued_dirs`](crate::walk::FinderBuilder::max_queued_dirs).
+    */
+    QueueFull,
+}
+
+impl DirEntryError {
+    /// The errno behind this error, if it came from the OS
+    #[must_use]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match *self {
+            Self::IOError(ref error) => error.raw_os_error(),
+            Self::TimeError
+            | Self::Utf8Error(_)
+            | Self::NulError(_)
+            | Self::TimedOut
+            | Self::QueueFull => None,
+        }
+    }
+
+    /// A copy of this error to hand out, I/O errors are kept as their message
+    pub(crate) fn duplicate(&self) -> Self {
+        match *self {
+            Self::TimedOut => Self::TimedOut,
+            Self::QueueFull => Self::QueueFull,
+            _ => Self::IOError(FilesystemIOError::from_io_error(io::Error::other(
+                self.to_string(),
+            ))),
+        }
+    }
 }
 
 impl From<io::Error> for DirEntryError {
@@ -254,6 +334,8 @@ impl fmt::Display for DirEntryError {
             Self::Utf8Error(e) => write!(f, "UTF-8 conversion error: {e}"),
             Self::NulError(e) => write!(f, "Invalid nulls detected in name {e}"),
             Self::IOError(e) => write!(f, "I/O error: {e}"),
+            Self::TimedOut => write!(f, "Traversal timed out"),
+            Self::QueueFull => write!(f, "Too many directories queued to read this one"),
         }
     }
 }
@@ -276,6 +358,10 @@ pub enum SearchConfigError {
     TraversalError(DirEntryError),
     /// Specified root path is not a directory
     NotADirectory,
+    /// The requested `getdents` buffer size (in bytes) is too small to hold a directory entry
+    InvalidBufferSize(usize),
+    /// A command template for `--exec` was given without a program
+    EmptyCommand,
 }
 
 impl From<io::Error> for SearchConfigError {
@@ -293,6 +379,11 @@ impl fmt::Display for SearchConfigError {
             Self::IOError(e) => write!(f, "IO error: {e}"),
             Self::NotADirectory => write!(f, "Path is not a directory"),
             Self::TraversalError(e) => write!(f, "Traversal error: {e}"),
+            Self::InvalidBufferSize(size) => write!(
+                f,
+                "Invalid getdents buffer size {size}, it must be able to hold a directory entry"
+            ),
+            Self::EmptyCommand => write!(f, "No command given to execute"),
         }
     }
 }
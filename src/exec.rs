@@ -0,0 +1,435 @@
+/*!
+ Command execution for search results, the library side of `fdf --exec`/`--exec-batch`.
+
+ A [`CommandTemplate`] is parsed once and expanded for every result, supporting the same
+ placeholders as `fd`:
+
+ | Placeholder | Expands to                                  | `./src/main.rs` |
+ |-------------|---------------------------------------------|-----------------|
+ | `{}`        | The path                                    | `./src/main.rs` |
+ | `{.}`       | The path without its extension              | `./src/main`    |
+ | `{/}`       | The file name                               | `main.rs`       |
+ | `{/.}`      | The file name without its extension         | `main`          |
+ | `{//}`      | The parent directory                        | `./src`         |
+
+ `{/.}` follows [`DirEntry::file_stem`], so unlike `{.}` it treats a leading dot as the start of an extension.
+ If no placeholder is present the path is appended as the final argument.
+
+ ```
+ use fdf::exec::CommandTemplate;
+ use std::ffi::OsString;
+
+ let template = CommandTemplate::new(["mv", "{}", "{.}.bak"]).unwrap();
+ assert_eq!(
+     template.argv(b"./src/main.rs"),
+     vec![OsString::from("mv"), "./src/main.rs".into(), "./src/main.bak".into()]
+ );
+ ```
+*/
+
+use crate::SearchConfigError;
+use crate::fs::DirEntry;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rayon::iter::{ParallelBridge as _, ParallelIterator as _};
+use std::ffi::OsString;
+use std::io::{self, Write as _};
+use std::os::unix::ffi::{OsStrExt as _, OsStringExt as _};
+use std::process::Command;
+
+/// Fallback for the argument size limit if `sysconf(_SC_ARG_MAX)` is unavailable
+const DEFAULT_ARG_MAX: usize = 128 * 1024;
+
+/// How results are passed to the command
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExecMode {
+    /// Run the command once per result (`-x`), in parallel
+    #[default]
+    PerResult,
+    /// Run the command once with as many results as fit on the command line (`-X`)
+    Batch,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    /// `{}`
+    Path,
+    /// `{.}`
+    NoExtension,
+    /// `{/}`
+    Basename,
+    /// `{/.}`, see [`DirEntry::file_stem`]
+    BasenameNoExtension,
+    /// `{//}`
+    Parent,
+}
+
+impl Placeholder {
+    // Longest tokens first so `{//}` isn't read as `{/}`
+    const TOKENS: [(&'static [u8], Self); 5] = [
+        (b"{//}", Self::Parent),
+        (b"{/.}", Self::BasenameNoExtension),
+        (b"{/}", Self::Basename),
+        (b"{.}", Self::NoExtension),
+        (b"{}", Self::Path),
+    ];
+
+    fn expand(self, path: &[u8]) -> &[u8] {
+        let name_start = crate::util::memrchr(b'/', path).map_or(0, |pos| pos + 1);
+        let (dir, name) = path.split_at(name_start);
+        match self {
+            Self::Path => path,
+            Self::Basename => name,
+            Self::BasenameNoExtension => crate::util::file_stem(name),
+            Self::Parent => match dir {
+                [] => b".",
+                [b'/'] => b"/",
+                _ => dir.strip_suffix(b"/").unwrap_or(dir),
+            },
+            Self::NoExtension => match crate::util::memrchr(b'.', name) {
+                // A leading dot is a hidden file, not an extension
+                Some(dot) if dot != 0 => path.split_at(name_start + dot).0,
+                _ => path,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Text(Vec<u8>),
+    Placeholder(Placeholder),
+}
+
+/// A single argument, split into literal text and placeholders
+#[derive(Clone, Debug)]
+struct ArgTemplate {
+    tokens: Vec<Token>,
+}
+
+impl ArgTemplate {
+    fn parse(arg: &[u8]) -> Self {
+        let mut tokens = Vec::new();
+        let mut text = Vec::new();
+        let mut rest = arg;
+
+        'outer: while let Some((&first, tail)) = rest.split_first() {
+            if first == b'{' {
+                for (token, placeholder) in Placeholder::TOKENS {
+                    if let Some(after) = rest.strip_prefix(token) {
+                        if !text.is_empty() {
+                            tokens.push(Token::Text(core::mem::take(&mut text)));
+                        }
+                        tokens.push(Token::Placeholder(placeholder));
+                        rest = after;
+                        continue 'outer;
+                    }
+                }
+            }
+            text.push(first);
+            rest = tail;
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        Self { tokens }
+    }
+
+    fn has_placeholder(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| matches!(token, Token::Placeholder(_)))
+    }
+
+    fn expand(&self, path: &[u8]) -> OsString {
+        let mut out = Vec::new();
+        for token in &self.tokens {
+            match token {
+                Token::Text(text) => out.extend_from_slice(text),
+                Token::Placeholder(placeholder) => {
+                    out.extend_from_slice(placeholder.expand(path));
+                }
+            }
+        }
+        OsString::from_vec(out)
+    }
+}
+
+/// Outcome of running a command over a set of results
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecSummary {
+    /// Number of commands that were run
+    pub spawned: usize,
+    /// Number of commands that exited unsuccessfully
+    pub failed: usize,
+}
+
+impl ExecSummary {
+    /// Returns true if every command exited successfully
+    #[must_use]
+    pub const fn success(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// Exit code to report for the whole run, `0` on success and `1` if any command failed (as `fd` does)
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        if self.success() { 0 } else { 1 }
+    }
+}
+
+/// A parsed command line with `{}`, `{.}`, `{/}`, `{/.}` and `{//}` placeholders, see the [module docs](self)
+#[derive(Clone, Debug)]
+pub struct CommandTemplate {
+    program: OsString,
+    args: Vec<ArgTemplate>,
+    has_placeholder: bool,
+    strip_leading_dot_slash: bool,
+}
+
+impl CommandTemplate {
+    /**
+    Parses a command and its arguments.
+
+    # Errors
+    Returns [`SearchConfigError::EmptyCommand`] if no program was given.
+    */
+    pub fn new<I, S>(command: I) -> Result<Self, SearchConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let mut command = command.into_iter().map(Into::into);
+        let program = command.next().ok_or(SearchConfigError::EmptyCommand)?;
+        let args: Vec<ArgTemplate> = command
+            .map(|arg| ArgTemplate::parse(arg.as_bytes()))
+            .collect();
+        let has_placeholder = args.iter().any(ArgTemplate::has_placeholder);
+
+        Ok(Self {
+            program,
+            args,
+            has_placeholder,
+            strip_leading_dot_slash: false,
+        })
+    }
+
+    /// Strip a leading `./` from each path before substitution (used for `--strip-cwd-prefix`)
+    #[must_use]
+    pub const fn strip_leading_dot_slash(mut self, yesorno: bool) -> Self {
+        self.strip_leading_dot_slash = yesorno;
+        self
+    }
+
+    fn displayed<'a>(&self, path: &'a [u8]) -> &'a [u8] {
+        if self.strip_leading_dot_slash {
+            path.strip_prefix(b"./").unwrap_or(path)
+        } else {
+            path
+        }
+    }
+
+    /// Builds the full argument vector (program included) for a single path
+    #[must_use]
+    pub fn argv(&self, path: &[u8]) -> Vec<OsString> {
+        let path = self.displayed(path);
+        let mut argv = Vec::with_capacity(self.args.len() + 2);
+        argv.push(self.program.clone());
+        argv.extend(self.args.iter().map(|arg| arg.expand(path)));
+        if !self.has_placeholder {
+            argv.push(OsString::from_vec(path.to_vec()));
+        }
+        argv
+    }
+
+    /**
+    Builds the full argument vector (program included) for a batch of paths.
+
+    Each argument containing a placeholder is repeated once per path, if there are no placeholders
+    the paths are appended at the end.
+    */
+    #[must_use]
+    pub fn batch_argv<P: AsRef<[u8]>>(&self, paths: &[P]) -> Vec<OsString> {
+        let mut argv = Vec::with_capacity(self.args.len() + paths.len() + 1);
+        argv.push(self.program.clone());
+        for arg in &self.args {
+            if arg.has_placeholder() {
+                argv.extend(
+                    paths
+                        .iter()
+                        .map(|path| arg.expand(self.displayed(path.as_ref()))),
+                );
+            } else {
+                argv.push(arg.expand(b""));
+            }
+        }
+        if !self.has_placeholder {
+            argv.extend(
+                paths
+                    .iter()
+                    .map(|path| OsString::from_vec(self.displayed(path.as_ref()).to_vec())),
+            );
+        }
+        argv
+    }
+
+    /// Runs a prepared argument vector, returning whether it exited successfully
+    #[allow(clippy::indexing_slicing)] // argv always contains the program
+    fn run(argv: &[OsString]) -> io::Result<bool> {
+        Ok(Command::new(&argv[0]).args(&argv[1..]).status()?.success())
+    }
+
+    /// Like [`run`](Self::run), capturing the output and printing it in one piece once the command exits
+    #[allow(clippy::indexing_slicing)] // argv always contains the program
+    fn run_captured(argv: &[OsString]) -> io::Result<bool> {
+        let output = Command::new(&argv[0]).args(&argv[1..]).output()?;
+        // Holding the stdout lock keeps concurrent commands from interleaving, stderr included
+        let mut stdout = io::stdout().lock();
+        // A closed stdout (eg piped into `head`) shouldn't stop the remaining commands
+        let _ = stdout
+            .write_all(&output.stdout)
+            .and_then(|()| stdout.flush());
+        let _ = io::stderr().lock().write_all(&output.stderr);
+        Ok(output.status.success())
+    }
+
+    /**
+    Runs the command over every path yielded by `paths`.
+
+    In [`ExecMode::PerResult`] up to `threads` commands run concurrently on a rayon pool, each printing its
+    output in one piece once it exits (pass [`NonZeroUsize::MIN`] to preserve the iterator's order and leave
+    the commands attached to the terminal), in [`ExecMode::Batch`] commands run one after another with as
+    many paths as fit within the system's argument size limit.
+
+    Commands that exit unsuccessfully are counted in the returned [`ExecSummary`] without stopping the run.
+
+    # Errors
+    Returns an IO error if a command could not be spawned at all (eg the program does not exist),
+    in which case no further commands are started.
+    */
+    pub fn execute<I>(
+        &self,
+        paths: I,
+        mode: ExecMode,
+        threads: NonZeroUsize,
+    ) -> Result<ExecSummary, SearchConfigError>
+    where
+        I: Iterator<Item = DirEntry> + Send,
+    {
+        match mode {
+            ExecMode::PerResult => self.execute_each(paths, threads),
+            ExecMode::Batch => self.execute_batches(paths),
+        }
+    }
+
+    fn execute_each<I>(
+        &self,
+        paths: I,
+        threads: NonZeroUsize,
+    ) -> Result<ExecSummary, SearchConfigError>
+    where
+        I: Iterator<Item = DirEntry> + Send,
+    {
+        if threads == NonZeroUsize::MIN {
+            // A single command at a time keeps the terminal, so interactive commands still work
+            let mut summary = ExecSummary::default();
+            for entry in paths {
+                let success = Self::run(&self.argv(entry.as_bytes()))?;
+                summary.spawned += 1;
+                summary.failed += usize::from(!success);
+            }
+            return Ok(summary);
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.get())
+            .build()
+            .map_err(|error| SearchConfigError::IOError(io::Error::other(error)))?;
+        let spawned = AtomicUsize::new(0);
+        let failed = AtomicUsize::new(0);
+
+        // The first spawn failure stops any further commands being started
+        pool.install(|| {
+            paths.par_bridge().try_for_each(|entry| {
+                let success = Self::run_captured(&self.argv(entry.as_bytes()))?;
+                spawned.fetch_add(1, Ordering::Relaxed);
+                if !success {
+                    failed.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok::<_, io::Error>(())
+            })
+        })?;
+
+        Ok(ExecSummary {
+            spawned: spawned.into_inner(),
+            failed: failed.into_inner(),
+        })
+    }
+
+    fn execute_batches<I>(&self, paths: I) -> Result<ExecSummary, SearchConfigError>
+    where
+        I: Iterator<Item = DirEntry>,
+    {
+        let limit = arg_max();
+        // Each argument costs its bytes, a null terminator and a pointer in argv
+        let arg_cost = |len: usize| len + 1 + size_of::<usize>();
+        let fixed: usize = self.args.iter().map(|_| arg_cost(0)).sum::<usize>()
+            + arg_cost(self.program.len())
+            + self
+                .args
+                .iter()
+                .flat_map(|arg| &arg.tokens)
+                .map(|token| match token {
+                    Token::Text(text) => text.len(),
+                    Token::Placeholder(_) => 0,
+                })
+                .sum::<usize>();
+        // Every path is substituted into each placeholder argument (or appended once)
+        let copies = self
+            .args
+            .iter()
+            .filter(|arg| arg.has_placeholder())
+            .count()
+            .max(1);
+
+        let mut summary = ExecSummary::default();
+        let mut batch: Vec<DirEntry> = Vec::new();
+        let mut used = fixed;
+
+        let mut flush = |batch: &mut Vec<DirEntry>| -> io::Result<()> {
+            if batch.is_empty() {
+                return Ok(());
+            }
+            let success = Self::run(&self.batch_argv(batch))?;
+            summary.spawned += 1;
+            summary.failed += usize::from(!success);
+            batch.clear();
+            Ok(())
+        };
+
+        for entry in paths {
+            let cost = copies * arg_cost(entry.len());
+            if !batch.is_empty() && used + cost > limit {
+                flush(&mut batch)?;
+                used = fixed;
+            }
+            used += cost;
+            batch.push(entry);
+        }
+        flush(&mut batch)?;
+
+        Ok(summary)
+    }
+}
+
+/// Usable size for command line arguments, leaving headroom for the environment
+fn arg_max() -> usize {
+    // SAFETY: `sysconf` has no preconditions
+    let max = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+    usize::try_from(max)
+        .ok()
+        .filter(|&max| max > 0)
+        .map_or(DEFAULT_ARG_MAX, |max| max / 2)
+}
@@ -0,0 +1,189 @@
+use crate::SearchConfigError;
+use crate::fs::{DirEntry, FileDes};
+use core::ptr::NonNull;
+use regex::bytes::{Regex, RegexBuilder};
+use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd};
+
+/// How far into a file to look for a NUL byte when deciding if it's binary, as `grep` does
+const BINARY_DETECTION_LEN: usize = 8 * 1024;
+
+/**
+ Filters regular files by whether their contents match a regex, used by `fdf --search-content`.
+
+ Files are memory mapped rather than read, binary files (those with a NUL byte near the start) never match,
+ and files larger than [`max_size`](Self::max_size) are skipped without being opened any further.
+ Symlinks are followed when checking contents, anything which isn't (or doesn't point to) a regular file
+ never matches.
+
+ ```
+ use fdf::filters::ContentFilter;
+
+ let filter = ContentFilter::new("fn main").unwrap();
+ assert!(filter.matches_bytes(b"fn main() {}"));
+ assert!(!filter.matches_bytes(b"\0ELF fn main"));
+
+ let finder = fdf::walk::Finder::init(env!("CARGO_MANIFEST_DIR"))
+     .extension("rs")
+     .search_content(Some(filter))
+     .build()
+     .unwrap();
+ assert!(finder.traverse().unwrap().any(|entry| entry.file_name() == b"main.rs"));
+ ```
+
+ As with `grep`, the file may be changed (or truncated) by another process while it's being searched,
+ which can raise `SIGBUS` on some platforms. This is only a concern for files being actively rewritten.
+*/
+#[derive(Clone, Debug)]
+pub struct ContentFilter {
+    regex: Regex,
+    max_size: u64,
+}
+
+impl ContentFilter {
+    /// Files larger than this are skipped unless [`max_size`](Self::max_size) says otherwise
+    pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+    /**
+     Compiles `pattern` (a regex, matched against raw bytes so any encoding works) into a content filter.
+
+     # Errors
+     Returns [`SearchConfigError::RegexError`] if the pattern isn't a valid regex.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new(pattern: &str) -> Result<Self, SearchConfigError> {
+        let regex = RegexBuilder::new(pattern)
+            .multi_line(true)
+            .build()
+            .map_err(SearchConfigError::RegexError)?;
+
+        Ok(Self {
+            regex,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        })
+    }
+
+    /// Set the largest file (in bytes) whose contents are searched, larger files never match
+    #[inline]
+    #[must_use]
+    pub const fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Returns true if `contents` isn't binary and matches the pattern
+    #[inline]
+    #[must_use]
+    pub fn matches_bytes(&self, contents: &[u8]) -> bool {
+        let head = &contents[..contents.len().min(BINARY_DETECTION_LEN)];
+        memchr::memchr(0, head).is_none() && self.regex.is_match(contents)
+    }
+
+    /// Returns true if `entry` is a regular file (or a symlink to one) whose contents match
+    #[inline]
+    #[must_use]
+    pub fn matches(&self, entry: &DirEntry) -> bool {
+        self.matches_at(entry, None)
+    }
+
+    /// Like [`matches`](Self::matches), opening the file relative to `opt_fd` when provided
+    #[inline]
+    pub(crate) fn matches_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        let Some((file, size)) = open_regular_file(entry, opt_fd) else {
+            return false;
+        };
+
+        if size > self.max_size {
+            return false;
+        }
+        if size == 0 {
+            return self.matches_bytes(&[]);
+        }
+
+        usize::try_from(size)
+            .ok()
+            .and_then(|len| Mapping::new(&file, len))
+            .is_some_and(|mapping| self.matches_bytes(mapping.as_bytes()))
+    }
+}
+
+/**
+ Opens `entry` for reading if it's a regular file (following symlinks), relative to `opt_fd` when provided.
+
+ Returns the open file and its size, or `None` if it isn't a regular file or can't be opened.
+*/
+pub(crate) fn open_regular_file(
+    entry: &DirEntry,
+    opt_fd: Option<&FileDes>,
+) -> Option<(OwnedFd, u64)> {
+    if !(entry.is_regular_file() || entry.is_symlink()) {
+        return None;
+    }
+
+    // Non blocking so a symlink to a FIFO can't hang the worker
+    const FLAGS: i32 = libc::O_RDONLY | libc::O_CLOEXEC | libc::O_NOCTTY | libc::O_NONBLOCK;
+    // SAFETY: both paths are null terminated, and the fd (if any) is the open parent directory
+    let raw_fd = unsafe {
+        opt_fd.map_or_else(
+            || libc::open(entry.as_ptr(), FLAGS),
+            |fd| libc::openat(fd.as_raw_fd(), entry.file_name_ptr(), FLAGS),
+        )
+    };
+    if raw_fd < 0 {
+        return None;
+    }
+    // SAFETY: the descriptor was just opened and nothing else owns it
+    let file = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+    let mut stat_buf = core::mem::MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: the descriptor is open and the buffer is valid for writes
+    if unsafe { libc::fstat(raw_fd, stat_buf.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: fstat succeeded so the buffer is initialised
+    let statted = unsafe { stat_buf.assume_init() };
+    if statted.st_mode & libc::S_IFMT != libc::S_IFREG {
+        return None;
+    }
+
+    Some((file, statted.st_size.cast_unsigned()))
+}
+
+/// A read only memory mapping of a whole file, unmapped on drop
+struct Mapping {
+    ptr: NonNull<libc::c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(file: &OwnedFd, len: usize) -> Option<Self> {
+        // SAFETY: mapping a valid descriptor read only, the result is checked before use
+        let ptr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        // SAFETY: mmap succeeded with the range given, the advice is only a hint
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        NonNull::new(ptr).map(|ptr| Self { ptr, len })
+    }
+
+    const fn as_bytes(&self) -> &[u8] {
+        // SAFETY: the mapping is `len` readable bytes and lives as long as `self`
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr().cast::<u8>(), self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmapping exactly the range mapped in `new`
+        unsafe { libc::munmap(self.ptr.as_ptr(), self.len) };
+    }
+}
@@ -0,0 +1,543 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_inline_in_public_items)]
+use super::{FileTypeFilter, PermFilter, SizeFilter, TimeFilter};
+use crate::SearchConfigError;
+use crate::config::TLSRegex;
+use crate::fs::{DirEntry, FileDes, FileType};
+use crate::util::{glob_to_path_regex, glob_to_regex};
+use clap::{
+    Arg, Command, Error,
+    builder::TypedValueParser,
+    error::{ContextKind, ContextValue, ErrorKind},
+};
+use core::fmt;
+use core::time::Duration;
+use regex::bytes::Regex;
+use std::ffi::OsStr;
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum ParseExprError {
+    /// The expression (or a group within it) is empty
+    Empty,
+    /// A test isn't written as `key:value`, or an operator is missing its operand
+    UnexpectedToken(String),
+    /// A `(` or quote was never closed
+    Unclosed,
+    /// A test with a key that isn't known
+    UnknownKey(String),
+    /// A test whose value couldn't be parsed for its key
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for ParseExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty expression"),
+            Self::UnexpectedToken(token) => write!(f, "unexpected '{token}'"),
+            Self::Unclosed => write!(f, "unclosed '(' or quote"),
+            Self::UnknownKey(key) => write!(
+                f,
+                "unknown test '{key}', expected one of: name, regex, path, ext, size, type, mtime, perm"
+            ),
+            Self::InvalidValue(key, err) => write!(f, "invalid value for '{key}': {err}"),
+        }
+    }
+}
+
+impl core::error::Error for ParseExprError {}
+
+/// A single test in an expression, see the constructors on [`Filter`]
+#[derive(Clone, Debug)]
+enum Test {
+    Name(TLSRegex),
+    Path(TLSRegex),
+    Extension(Box<[u8]>),
+    Size(SizeFilter),
+    Type(FileTypeFilter),
+    Modified(TimeFilter),
+    Perm(PermFilter),
+}
+
+/**
+ A composable filter expression, combining tests on an entry with `and`, `or` and `not`.
+
+ Expressions are evaluated per entry, left to right with short-circuiting, so put the tests on the name
+ before those needing a `stat` (size, modification time and permissions). They can be built in code or parsed
+ from the mini-language of `--expr`, see [`from_string`](Self::from_string).
+
+ # Examples
+
+ ```
+ use fdf::filters::{FileTypeFilter, Filter};
+ use std::ops::Not;
+
+ // Large logs, or sockets, but nothing hidden
+ let filter = Filter::glob("*.log")
+     .unwrap()
+     .and(Filter::size_gt(1024))
+     .or(Filter::type_(FileTypeFilter::Socket))
+     .and(Filter::glob(".*").unwrap().not());
+
+ // The same, parsed
+ let parsed = Filter::from_string("((name:*.log & size:+1k) | type:socket) & !name:.*").unwrap();
+
+ let manifest = fdf::fs::DirEntry::new(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+ assert!(!filter.matches(&manifest));
+ assert!(!parsed.matches(&manifest));
+ assert!(Filter::from_string("ext:toml & !size:+1G").unwrap().matches(&manifest));
+ ```
+*/
+#[derive(Clone, Debug)]
+pub struct Filter(Node);
+
+#[derive(Clone, Debug)]
+enum Node {
+    // Tests holding a regex are large, so boxed like the rest
+    Test(Box<Test>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+impl Filter {
+    /// Matches entries whose file name matches `regex`
+    #[must_use]
+    pub fn name(regex: Regex) -> Self {
+        Self(Node::test(Test::Name(TLSRegex::new(regex))))
+    }
+
+    /// Matches entries whose file name matches a glob, eg `*.log`
+    pub fn glob(pattern: &str) -> Result<Self, SearchConfigError> {
+        let regex = glob_to_regex(pattern).map_err(SearchConfigError::GlobToRegexError)?;
+        Regex::new(&regex)
+            .map(Self::name)
+            .map_err(SearchConfigError::RegexError)
+    }
+
+    /**
+     Matches entries whose path matches a glob, eg `src/**/*.rs`
+
+     Within a traversal this is the path relative to the search root, as with full path patterns,
+     while [`matches`](Self::matches) uses the entry's path as it is.
+    */
+    pub fn path(pattern: &str) -> Result<Self, SearchConfigError> {
+        let regex = glob_to_path_regex(pattern).map_err(SearchConfigError::GlobToRegexError)?;
+        Regex::new(&regex)
+            .map(|regex| Self(Node::test(Test::Path(TLSRegex::new(regex)))))
+            .map_err(SearchConfigError::RegexError)
+    }
+
+    /// Matches entries with an extension (without the leading dot), ignoring ASCII case
+    #[must_use]
+    pub fn extension<C: AsRef<str>>(extension: C) -> Self {
+        let extension = extension.as_ref();
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+        Self(Node::test(Test::Extension(extension.as_bytes().into())))
+    }
+
+    /// Matches regular files (or symlinks to them) within the size filter
+    #[must_use]
+    pub fn size(size: SizeFilter) -> Self {
+        Self(Node::test(Test::Size(size)))
+    }
+
+    /// Matches regular files (or symlinks to them) larger than `bytes`
+    #[must_use]
+    pub fn size_gt(bytes: u64) -> Self {
+        Self::size(SizeFilter::Min(bytes.saturating_add(1)))
+    }
+
+    /// Matches regular files (or symlinks to them) smaller than `bytes`
+    #[must_use]
+    pub fn size_lt(bytes: u64) -> Self {
+        Self::size(SizeFilter::Max(bytes.saturating_sub(1)))
+    }
+
+    /// Matches entries of a type, named with a trailing underscore as `type` is a keyword
+    #[must_use]
+    pub fn type_(file_type: FileTypeFilter) -> Self {
+        Self(Node::test(Test::Type(file_type)))
+    }
+
+    /// Matches entries whose modification time is within the time filter
+    #[must_use]
+    pub fn modified(time: TimeFilter) -> Self {
+        Self(Node::test(Test::Modified(time)))
+    }
+
+    /// Matches entries whose permission bits match the permission filter
+    #[must_use]
+    pub fn perm(perm: PermFilter) -> Self {
+        Self(Node::test(Test::Perm(perm)))
+    }
+
+    /// Matches entries matching both filters, `other` is only evaluated if `self` matches
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        Self(Node::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Matches entries matching either filter, `other` is only evaluated if `self` doesn't match
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self(Node::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /**
+     Parses an expression, as given to `--expr`.
+
+     # Format
+
+     Tests are written `key:value`, combined with `&` (and), `|` (or) and `!` (not), and grouped with parentheses.
+     `!` binds tightest and `&` tighter than `|`, tests next to each other without an operator are and-ed.
+     A value runs until whitespace or one of `&|()`, quote it (with `'` or `"`) to include them.
+
+     - `name:GLOB` - the file name matches a glob, eg `name:*.log`
+     - `regex:REGEX` - the file name matches a regex
+     - `path:GLOB` - the path below the search root matches a glob, eg `path:src/**/*.rs`
+     - `ext:EXT` - the file has an extension, eg `ext:rs`
+     - `size:SIZE` - the file size, as given to `--size`, eg `size:+1M`
+     - `type:TYPE` - the file type, as given to `--type`, eg `type:socket`
+     - `mtime:TIME` - the modification time, as given to `--time-modified`, eg `mtime:-2d`
+     - `perm:MODE` - the permission bits, as given to `--perm`, eg `perm:-u+w`
+
+     # Examples
+
+     ```
+     use fdf::filters::Filter;
+
+     assert!(Filter::from_string("(name:*.log & size:+1M) | type:socket").is_ok());
+     assert!(Filter::from_string("name:'a file' !ext:bak").is_ok());
+     assert!(Filter::from_string("name:*.log &").is_err());
+     assert!(Filter::from_string("colour:red").is_err());
+     ```
+    */
+    pub fn from_string(s: &str) -> Result<Self, ParseExprError> {
+        let mut parser = ExprParser { input: s, pos: 0 };
+        let node = parser.parse_or()?;
+        match parser.peek()? {
+            None => Ok(Self(node)),
+            Some(token) => Err(ParseExprError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    /// Returns true if the entry matches the expression, path tests see the entry's whole path
+    #[must_use]
+    pub fn matches(&self, entry: &DirEntry) -> bool {
+        self.0.matches(entry, entry.as_bytes(), None)
+    }
+
+    /// Like [`matches`](Self::matches), with path tests given `relative` and statting relative to `opt_fd`
+    pub(crate) fn matches_at(
+        &self,
+        entry: &DirEntry,
+        relative: &[u8],
+        opt_fd: Option<&FileDes>,
+    ) -> bool {
+        self.0.matches(entry, relative, opt_fd)
+    }
+}
+
+impl core::ops::Not for Filter {
+    type Output = Self;
+
+    /// Matches entries which don't match the filter
+    fn not(self) -> Self {
+        Self(Node::Not(Box::new(self.0)))
+    }
+}
+
+impl core::ops::BitAnd for Filter {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        self.and(other)
+    }
+}
+
+impl core::ops::BitOr for Filter {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.or(other)
+    }
+}
+
+impl core::str::FromStr for Filter {
+    type Err = ParseExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_string(s)
+    }
+}
+
+impl Node {
+    fn test(test: Test) -> Self {
+        Self::Test(Box::new(test))
+    }
+
+    fn matches(&self, entry: &DirEntry, relative: &[u8], opt_fd: Option<&FileDes>) -> bool {
+        match self {
+            Self::Test(test) => test.matches(entry, relative, opt_fd),
+            Self::And(lhs, rhs) => {
+                lhs.matches(entry, relative, opt_fd) && rhs.matches(entry, relative, opt_fd)
+            }
+            Self::Or(lhs, rhs) => {
+                lhs.matches(entry, relative, opt_fd) || rhs.matches(entry, relative, opt_fd)
+            }
+            Self::Not(inner) => !inner.matches(entry, relative, opt_fd),
+        }
+    }
+}
+
+impl Test {
+    #[allow(clippy::cast_sign_loss, clippy::useless_conversion)] // mode_t isn't u32 everywhere
+    fn matches(&self, entry: &DirEntry, relative: &[u8], opt_fd: Option<&FileDes>) -> bool {
+        let lstat = || opt_fd.map_or_else(|| entry.get_lstat(), |fd| entry.get_lstatat(fd));
+        match self {
+            Self::Name(regex) => regex.is_match(entry.file_name()),
+            Self::Path(regex) => regex.is_match(relative),
+            Self::Extension(extension) => entry
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(extension)),
+            Self::Size(size) => match entry.file_type() {
+                FileType::RegularFile => {
+                    lstat().is_ok_and(|statted| size.is_within_size(statted.st_size as _))
+                }
+                // Symlinks are sized by their target, if it's a regular file
+                FileType::Symlink => opt_fd
+                    .map_or_else(|| entry.get_stat(), |fd| entry.get_statat(fd))
+                    .is_ok_and(|statted| {
+                        FileType::from_stat(&statted) == FileType::RegularFile
+                            && size.is_within_size(statted.st_size as _)
+                    }),
+                _ => false,
+            },
+            Self::Type(file_type) => file_type.matches_at(entry, opt_fd),
+            Self::Modified(time) => entry
+                .modified_time_at(opt_fd)
+                .ok()
+                .and_then(|datetime| datetime.timestamp_nanos_opt())
+                .and_then(|nanos| {
+                    UNIX_EPOCH.checked_add(Duration::from_nanos(nanos.cast_unsigned()))
+                })
+                .is_some_and(|systime| time.matches_time(systime)),
+            Self::Perm(perm) => {
+                lstat().is_ok_and(|statted| perm.matches_mode(u32::from(statted.st_mode)))
+            }
+        }
+    }
+
+    /// Parses the value of a `key:value` test
+    fn parse(key: &str, value: &str) -> Result<Self, ParseExprError> {
+        let invalid =
+            |err: &dyn fmt::Display| ParseExprError::InvalidValue(key.into(), err.to_string());
+        let compile = |regex: Result<String, crate::util::Error>| {
+            regex
+                .map_err(|err| invalid(&err))
+                .and_then(|regex| Regex::new(&regex).map_err(|err| invalid(&err)))
+                .map(TLSRegex::new)
+        };
+
+        match key {
+            "name" => compile(glob_to_regex(value)).map(Self::Name),
+            "regex" => compile(Ok(value.into())).map(Self::Name),
+            "path" => compile(glob_to_path_regex(value)).map(Self::Path),
+            "ext" => match value.strip_prefix('.').unwrap_or(value) {
+                "" => Err(invalid(&"empty extension")),
+                extension => Ok(Self::Extension(extension.as_bytes().into())),
+            },
+            "size" => SizeFilter::from_string(value)
+                .map(Self::Size)
+                .map_err(|err| invalid(&err)),
+            "type" => FileTypeFilter::from_name(value)
+                .map(Self::Type)
+                .ok_or_else(|| invalid(&"unknown file type")),
+            "mtime" => TimeFilter::from_string(value)
+                .map(Self::Modified)
+                .map_err(|err| invalid(&err)),
+            "perm" => PermFilter::from_string(value)
+                .map(Self::Perm)
+                .map_err(|err| invalid(&err)),
+            _ => Err(ParseExprError::UnknownKey(key.into())),
+        }
+    }
+}
+
+/// A token of the expression language
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'src> {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    /// A `key:value` test, the value unquoted
+    Test(&'src str, String),
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::And => f.write_str("&"),
+            Self::Or => f.write_str("|"),
+            Self::Not => f.write_str("!"),
+            Self::Open => f.write_str("("),
+            Self::Close => f.write_str(")"),
+            Self::Test(key, value) => write!(f, "{key}:{value}"),
+        }
+    }
+}
+
+/// A recursive descent parser over the expression, tokenising as it goes
+struct ExprParser<'src> {
+    input: &'src str,
+    pos: usize,
+}
+
+impl<'src> ExprParser<'src> {
+    const fn is_delimiter(ch: char) -> bool {
+        ch.is_ascii_whitespace() || matches!(ch, '&' | '|' | '(' | ')')
+    }
+
+    /// Returns the next token without consuming it
+    fn peek(&mut self) -> Result<Option<Token<'src>>, ParseExprError> {
+        let start = self.pos;
+        let token = self.next_token();
+        self.pos = start;
+        token
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token<'src>>, ParseExprError> {
+        let rest = self.input[self.pos..].trim_start();
+        self.pos = self.input.len() - rest.len();
+
+        let Some(first) = rest.chars().next() else {
+            return Ok(None);
+        };
+        let simple = match first {
+            '&' => Some(Token::And),
+            '|' => Some(Token::Or),
+            '!' => Some(Token::Not),
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            _ => None,
+        };
+        if let Some(token) = simple {
+            self.pos += 1;
+            return Ok(Some(token));
+        }
+
+        let Some((key, after_key)) = rest.split_once(':').filter(|(key, _)| {
+            !key.is_empty() && !key.contains(Self::is_delimiter) && !key.contains('!')
+        }) else {
+            let word = rest.split(Self::is_delimiter).next().unwrap_or(rest);
+            return Err(ParseExprError::UnexpectedToken(word.into()));
+        };
+
+        let (value, consumed) = match after_key.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let quoted = &after_key[1..];
+                let end = quoted.find(quote).ok_or(ParseExprError::Unclosed)?;
+                (quoted[..end].to_owned(), end + 2)
+            }
+            _ => {
+                let end = after_key
+                    .find(Self::is_delimiter)
+                    .unwrap_or(after_key.len());
+                (after_key[..end].to_owned(), end)
+            }
+        };
+        self.pos += key.len() + 1 + consumed;
+        Ok(Some(Token::Test(key, value)))
+    }
+
+    /// `or := and ('|' and)*`
+    fn parse_or(&mut self) -> Result<Node, ParseExprError> {
+        let mut node = self.parse_and()?;
+        while self.peek()? == Some(Token::Or) {
+            self.next_token()?;
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    /// `and := unary ('&'? unary)*`
+    fn parse_and(&mut self) -> Result<Node, ParseExprError> {
+        let mut node = self.parse_unary()?;
+        loop {
+            match self.peek()? {
+                Some(Token::And) => {
+                    self.next_token()?;
+                }
+                // Juxtaposed tests are and-ed, as with find
+                Some(Token::Not | Token::Open | Token::Test(..)) => {}
+                _ => return Ok(node),
+            }
+            node = Node::And(Box::new(node), Box::new(self.parse_unary()?));
+        }
+    }
+
+    /// `unary := '!' unary | '(' or ')' | key:value`
+    fn parse_unary(&mut self) -> Result<Node, ParseExprError> {
+        match self.next_token()? {
+            None => Err(ParseExprError::Empty),
+            Some(Token::Not) => Ok(Node::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                if self.peek()? == Some(Token::Close) {
+                    return Err(ParseExprError::Empty);
+                }
+                let node = self.parse_or()?;
+                match self.next_token()? {
+                    Some(Token::Close) => Ok(node),
+                    None => Err(ParseExprError::Unclosed),
+                    Some(token) => Err(ParseExprError::UnexpectedToken(token.to_string())),
+                }
+            }
+            Some(Token::Test(key, value)) => Test::parse(key, &value).map(Node::test),
+            Some(token) => Err(ParseExprError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+/// A Custom parser that provides helpful error messages and examples for filter expressions
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_structs)]
+pub struct FilterParser;
+
+impl TypedValueParser for FilterParser {
+    type Value = Filter;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        _arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let value_str = value
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+
+        Filter::from_string(value_str).map_err(|err| {
+            let mut error = Error::new(ErrorKind::InvalidValue).with_cmd(cmd);
+
+            error.insert(
+                ContextKind::InvalidValue,
+                ContextValue::String(format!("{err}")),
+            );
+
+            error.insert(
+                ContextKind::SuggestedValue,
+                ContextValue::Strings(vec![
+                    "name:*.log & size:+1M".into(),
+                    "(ext:rs | ext:toml) & !path:target/**".into(),
+                    "type:socket | mtime:-1d".into(),
+                ]),
+            );
+
+            error
+        })
+    }
+}
@@ -1,4 +1,5 @@
 #![allow(clippy::missing_inline_in_public_items)]
+use crate::fs::{DirEntry, FileDes, FileType};
 use clap::{
     Arg, Command, Error,
     builder::{PossibleValue, TypedValueParser},
@@ -30,8 +31,24 @@ pub enum FileTypeFilter {
     Executable,
     /// Empty file
     Empty,
+    /// Any of several filters, a bitmask of their [`mask`](Self::mask)s, see [`any_of`](Self::any_of)
+    Multiple(u16),
 }
 
+/// Every filter other than [`FileTypeFilter::Multiple`], in the order of their bits
+const SINGLE_FILTERS: [FileTypeFilter; 10] = [
+    FileTypeFilter::File,
+    FileTypeFilter::Directory,
+    FileTypeFilter::Symlink,
+    FileTypeFilter::Pipe,
+    FileTypeFilter::CharDevice,
+    FileTypeFilter::BlockDevice,
+    FileTypeFilter::Socket,
+    FileTypeFilter::Unknown,
+    FileTypeFilter::Executable,
+    FileTypeFilter::Empty,
+];
+
 impl FileTypeFilter {
     /**
      Converts the file type filter to its corresponding byte representation
@@ -51,6 +68,7 @@ impl FileTypeFilter {
      - `b'u'` for unknown file types
      - `b'x'` for executable files
      - `b'e'` for empty files
+     - `b'm'` for [`Multiple`](Self::Multiple) filters
 
      # Examples
      ```
@@ -75,6 +93,116 @@ impl FileTypeFilter {
             Self::Unknown => b'u',
             Self::Executable => b'x',
             Self::Empty => b'e',
+            Self::Multiple(_) => b'm',
+        }
+    }
+
+    /**
+     Returns the filter's bit, or bits for [`Multiple`](Self::Multiple), so a set of filters is one `u16`
+
+     # Examples
+     ```
+     # use fdf::filters::FileTypeFilter;
+     let either = FileTypeFilter::File.mask() | FileTypeFilter::Symlink.mask();
+     assert_eq!(FileTypeFilter::any_of([FileTypeFilter::File, FileTypeFilter::Symlink]), Some(FileTypeFilter::Multiple(either)));
+     ```
+    */
+    #[must_use]
+    pub const fn mask(self) -> u16 {
+        match self {
+            Self::File => 1 << 0,
+            Self::Directory => 1 << 1,
+            Self::Symlink => 1 << 2,
+            Self::Pipe => 1 << 3,
+            Self::CharDevice => 1 << 4,
+            Self::BlockDevice => 1 << 5,
+            Self::Socket => 1 << 6,
+            Self::Unknown => 1 << 7,
+            Self::Executable => 1 << 8,
+            Self::Empty => 1 << 9,
+            Self::Multiple(mask) => mask,
+        }
+    }
+
+    /**
+     Combines filters so that an entry matching any of them matches, eg files or symlinks
+
+     Returns `None` when there are no filters (ie no filtering), and the filter itself when there's only one.
+
+     # Examples
+     ```
+     # use fdf::filters::FileTypeFilter;
+     assert_eq!(FileTypeFilter::any_of([]), None);
+     assert_eq!(FileTypeFilter::any_of([FileTypeFilter::File, FileTypeFilter::File]), Some(FileTypeFilter::File));
+     assert!(matches!(
+         FileTypeFilter::any_of([FileTypeFilter::File, FileTypeFilter::Directory]),
+         Some(FileTypeFilter::Multiple(_))
+     ));
+     ```
+    */
+    pub fn any_of<I: IntoIterator<Item = Self>>(filters: I) -> Option<Self> {
+        let mask = filters
+            .into_iter()
+            .fold(0, |mask, filter| mask | filter.mask());
+        match mask {
+            0 => None,
+            _ if mask.is_power_of_two() => SINGLE_FILTERS
+                .into_iter()
+                .find(|filter| filter.mask() == mask),
+            _ => Some(Self::Multiple(mask)),
+        }
+    }
+
+    /// The filter matching entries of `file_type`, as read from the directory
+    pub(crate) const fn of_type(file_type: FileType) -> Self {
+        match file_type {
+            FileType::RegularFile => Self::File,
+            FileType::Directory => Self::Directory,
+            FileType::Symlink => Self::Symlink,
+            FileType::Pipe => Self::Pipe,
+            FileType::CharDevice => Self::CharDevice,
+            FileType::BlockDevice => Self::BlockDevice,
+            FileType::Socket => Self::Socket,
+            FileType::Unknown => Self::Unknown,
+        }
+    }
+
+    /// Parses a type by name or letter (eg `f`, `file` or `regular`), ignoring case, as accepted by `--type`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "d" | "dir" | "hardlink" | "directory" => Some(Self::Directory),
+            "u" | "unknown" => Some(Self::Unknown),
+            "l" | "symlink" | "link" => Some(Self::Symlink),
+            "f" | "file" | "regular" => Some(Self::File),
+            "p" | "pipe" | "fifo" => Some(Self::Pipe),
+            "c" | "char" | "chardev" | "chardevice" => Some(Self::CharDevice),
+            "b" | "block" | "blockdev" | "blockdevice" => Some(Self::BlockDevice),
+            "s" | "socket" | "sock" => Some(Self::Socket),
+            "e" | "empty" => Some(Self::Empty),
+            "x" | "exec" | "executable" => Some(Self::Executable),
+            _ => None,
+        }
+    }
+
+    /// Checks the entry against the filter, a `stat` is only needed for the executable and empty filters
+    pub(crate) fn matches_at(self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        match self {
+            Self::File => entry.is_regular_file(),
+            Self::Directory => entry.is_dir(),
+            Self::Symlink => entry.is_symlink(),
+            Self::Pipe => entry.is_pipe(),
+            Self::CharDevice => entry.is_char_device(),
+            Self::BlockDevice => entry.is_block_device(),
+            Self::Socket => entry.is_socket(),
+            Self::Unknown => entry.is_unknown(),
+            Self::Executable => entry.is_executable_at(opt_fd),
+            Self::Empty => entry.is_empty_at(opt_fd),
+            // The type bit is free, only then are the filters needing a `stat` tried
+            Self::Multiple(mask) => {
+                mask & Self::of_type(entry.file_type).mask() != 0
+                    || mask & Self::Executable.mask() != 0 && entry.is_executable_at(opt_fd)
+                    || mask & Self::Empty.mask() != 0 && entry.is_empty_at(opt_fd)
+            }
         }
     }
 
@@ -156,18 +284,9 @@ impl TypedValueParser for FileTypeFilterParser {
             .to_str()
             .ok_or_else(|| Error::new(ErrorKind::InvalidUtf8).with_cmd(cmd))?;
 
-        match value_str.to_lowercase().as_str() {
-            "d" | "dir" | "hardlink" | "directory" => Ok(FileTypeFilter::Directory),
-            "u" | "unknown" => Ok(FileTypeFilter::Unknown),
-            "l" | "symlink" | "link" => Ok(FileTypeFilter::Symlink),
-            "f" | "file" | "regular" => Ok(FileTypeFilter::File),
-            "p" | "pipe" | "fifo" => Ok(FileTypeFilter::Pipe),
-            "c" | "char" | "chardev" | "chardevice" => Ok(FileTypeFilter::CharDevice),
-            "b" | "block" | "blockdev" | "blockdevice" => Ok(FileTypeFilter::BlockDevice),
-            "s" | "socket" | "sock" => Ok(FileTypeFilter::Socket),
-            "e" | "empty" => Ok(FileTypeFilter::Empty),
-            "x" | "exec" | "executable" => Ok(FileTypeFilter::Executable),
-            _ => {
+        match FileTypeFilter::from_name(value_str) {
+            Some(filter) => Ok(filter),
+            None => {
                 let mut error = Error::new(ErrorKind::InvalidValue).with_cmd(cmd);
 
                 error.insert(
@@ -0,0 +1,136 @@
+/// How much of a file [`sniff_mime`] needs to see, enough for every signature below (the tar one is furthest in)
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+/// `(offset, magic bytes, MIME type)`, checked in order so more specific signatures come first
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    // Images
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"II*\0", "image/tiff"),
+    (0, b"MM\0*", "image/tiff"),
+    (0, b"\0\0\x01\0", "image/vnd.microsoft.icon"),
+    (0, b"8BPS", "image/vnd.adobe.photoshop"),
+    // Audio and video
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"MThd", "audio/midi"),
+    (0, b"\x1a\x45\xdf\xa3", "video/x-matroska"),
+    // Documents and archives
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"%!PS", "application/postscript"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"\x1f\x8b", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xfd7zXZ\0", "application/x-xz"),
+    (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (0, b"Rar!\x1a\x07", "application/vnd.rar"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"SQLite format 3\0", "application/vnd.sqlite3"),
+    // Executables and fonts
+    (0, b"\x7fELF", "application/x-executable"),
+    (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+    (0, b"\xca\xfe\xba\xbe", "application/java-vm"),
+    (0, b"\0asm", "application/wasm"),
+    (0, b"wOFF", "font/woff"),
+    (0, b"wOF2", "font/woff2"),
+    (0, b"OTTO", "font/otf"),
+    (0, b"\0\x01\0\0\0", "font/ttf"),
+];
+
+/**
+ Guesses the MIME type of a file from (the start of) its contents, like `file --mime-type` but far smaller.
+
+ Only the first [`SNIFF_LEN`] bytes are looked at. Known binary signatures are checked first, then markup,
+ then anything that looks like text (valid UTF-8 without NUL bytes) is `text/plain`. Everything else is
+ `application/octet-stream`, and an empty file is `inode/x-empty`.
+
+ ```
+ use fdf::filters::sniff_mime;
+
+ assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+ assert_eq!(sniff_mime(b"<!DOCTYPE html><html></html>"), "text/html");
+ assert_eq!(sniff_mime("plain old text, caf\u{e9}\n".as_bytes()), "text/plain");
+ assert_eq!(sniff_mime(b"\x00\x01\x02\x03"), "application/octet-stream");
+ assert_eq!(sniff_mime(b""), "inode/x-empty");
+ ```
+*/
+#[must_use]
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn sniff_mime(contents: &[u8]) -> &'static str {
+    let head = &contents[..contents.len().min(SNIFF_LEN)];
+    if head.is_empty() {
+        return "inode/x-empty";
+    }
+
+    // RIFF and ISO media containers name their format after a fixed header
+    match (head.get(..4), head.get(4..8), head.get(8..12)) {
+        (Some(b"RIFF"), _, Some(b"WEBP")) => return "image/webp",
+        (Some(b"RIFF"), _, Some(b"WAVE")) => return "audio/wav",
+        (Some(b"RIFF"), _, Some(b"AVI ")) => return "video/x-msvideo",
+        (_, Some(b"ftyp"), Some(b"qt  ")) => return "video/quicktime",
+        (_, Some(b"ftyp"), Some(b"heic" | b"heix" | b"mif1")) => return "image/heic",
+        (_, Some(b"ftyp"), Some(b"avif")) => return "image/avif",
+        (_, Some(b"ftyp"), Some(b"M4A ")) => return "audio/mp4",
+        (_, Some(b"ftyp"), _) => return "video/mp4",
+        _ => {}
+    }
+
+    if let Some(&(_, _, mime)) = SIGNATURES
+        .iter()
+        .find(|&&(offset, magic, _)| head.get(offset..offset + magic.len()) == Some(magic))
+    {
+        return mime;
+    }
+
+    if !looks_like_text(head) {
+        return "application/octet-stream";
+    }
+
+    let text = head
+        .strip_prefix(b"\xef\xbb\xbf")
+        .unwrap_or(head)
+        .trim_ascii_start();
+    let starts_with = |prefix: &[u8]| {
+        text.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    };
+
+    if starts_with(b"<!doctype html") || starts_with(b"<html") {
+        "text/html"
+    } else if starts_with(b"<svg") {
+        "image/svg+xml"
+    } else if starts_with(b"<?xml") {
+        // An SVG usually opens with an XML declaration, so look a little further for its root element
+        if memchr::memmem::find(text, b"<svg").is_some() {
+            "image/svg+xml"
+        } else {
+            "text/xml"
+        }
+    } else if starts_with(b"#!") {
+        "text/x-script"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Returns true if `head` has no NUL bytes and is UTF-8 (or UTF-16 with a BOM), allowing a character cut off at the end
+fn looks_like_text(head: &[u8]) -> bool {
+    if head.starts_with(b"\xff\xfe") || head.starts_with(b"\xfe\xff") {
+        return true;
+    }
+    if memchr::memchr(0, head).is_some() {
+        return false;
+    }
+
+    match core::str::from_utf8(head) {
+        Ok(_) => true,
+        // `error_len` is `None` when the input just ends part way through a character
+        Err(error) => error.error_len().is_none(),
+    }
+}
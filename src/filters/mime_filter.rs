@@ -0,0 +1,179 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_inline_in_public_items)]
+use super::content_filter::open_regular_file;
+use super::magic::{SNIFF_LEN, sniff_mime};
+use crate::fs::{DirEntry, FileDes};
+use clap::{
+    Arg, Command, Error,
+    builder::TypedValueParser,
+    error::{ContextKind, ContextValue, ErrorKind},
+};
+use core::fmt;
+use std::ffi::OsStr;
+use std::os::fd::AsRawFd as _;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum ParseMimeError {
+    Empty,
+    MissingSubtype,
+}
+
+impl fmt::Display for ParseMimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Empty => write!(f, "empty MIME type"),
+            Self::MissingSubtype => write!(f, "expected TYPE/SUBTYPE, eg 'image/png' or 'image/*'"),
+        }
+    }
+}
+
+impl core::error::Error for ParseMimeError {}
+
+/// A filter on a file's MIME type, as guessed from its first few KB by [`sniff_mime`].
+///
+/// Patterns are `type/subtype`, where either half may be `*`, and several can be separated by commas.
+/// Matching is case insensitive. Only regular files (or symlinks to them) have a MIME type.
+///
+/// # Examples
+///
+/// ```
+/// use fdf::filters::MimeFilter;
+///
+/// let filter = MimeFilter::from_string("image/*,application/pdf").unwrap();
+/// assert!(filter.matches_mime("image/png"));
+/// assert!(filter.matches_mime("application/pdf"));
+/// assert!(!filter.matches_mime("text/plain"));
+///
+/// // Sniffed from the contents rather than guessed from the extension
+/// let manifest = fdf::fs::DirEntry::new(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+/// assert!(MimeFilter::from_string("text/plain").unwrap().matches(&manifest));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MimeFilter {
+    /// `(type, subtype)` pairs in lowercase, `*` matching anything
+    patterns: Box<[(Box<str>, Box<str>)]>,
+}
+
+impl MimeFilter {
+    /**
+     Parses a comma separated list of `type/subtype` patterns and returns a `MimeFilter`
+
+     # Errors
+
+     Returns `ParseMimeError` in the following cases:
+     - `ParseMimeError::Empty` if the input (or one of its patterns) is empty
+     - `ParseMimeError::MissingSubtype` if a pattern has no `/`, or nothing either side of it
+    */
+    pub fn from_string(s: &str) -> Result<Self, ParseMimeError> {
+        let patterns = s
+            .split(',')
+            .map(|pattern| {
+                let pattern = pattern.trim();
+                if pattern.is_empty() {
+                    return Err(ParseMimeError::Empty);
+                }
+                match pattern.split_once('/') {
+                    Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() => Ok((
+                        kind.to_ascii_lowercase().into_boxed_str(),
+                        subtype.to_ascii_lowercase().into_boxed_str(),
+                    )),
+                    _ => Err(ParseMimeError::MissingSubtype),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { patterns })
+    }
+
+    /// Checks a MIME type (such as one returned by [`sniff_mime`]) against the filter
+    #[must_use]
+    pub fn matches_mime(&self, mime: &str) -> bool {
+        let (kind, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+        let part_matches =
+            |pattern: &str, part: &str| pattern == "*" || pattern.eq_ignore_ascii_case(part);
+
+        self.patterns.iter().any(|(pattern_kind, pattern_subtype)| {
+            part_matches(pattern_kind, kind) && part_matches(pattern_subtype, subtype)
+        })
+    }
+
+    /// Returns true if `entry` is a regular file (or a symlink to one) whose sniffed MIME type matches
+    #[inline]
+    #[must_use]
+    pub fn matches(&self, entry: &DirEntry) -> bool {
+        self.matches_at(entry, None)
+    }
+
+    /// Like [`matches`](Self::matches), opening the file relative to `opt_fd` when provided
+    pub(crate) fn matches_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        let Some((file, _)) = open_regular_file(entry, opt_fd) else {
+            return false;
+        };
+
+        let mut head = [0u8; SNIFF_LEN];
+        let mut filled = 0;
+        // A read can come up short, so keep going until the buffer is full or the file ends
+        while filled < head.len() {
+            // SAFETY: the descriptor is open and the rest of the buffer is valid for writes
+            let read = unsafe {
+                libc::read(
+                    file.as_raw_fd(),
+                    head[filled..].as_mut_ptr().cast(),
+                    head.len() - filled,
+                )
+            };
+            match usize::try_from(read) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(_)
+                    if std::io::Error::last_os_error().kind()
+                        == std::io::ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+
+        self.matches_mime(sniff_mime(&head[..filled]))
+    }
+}
+
+/// A Custom parser that provides helpful error messages and suggestions for MIME types
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_structs)]
+pub struct MimeFilterParser;
+
+impl TypedValueParser for MimeFilterParser {
+    type Value = MimeFilter;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        _arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let value_str = value
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+
+        MimeFilter::from_string(value_str).map_err(|err| {
+            let mut error = Error::new(ErrorKind::InvalidValue).with_cmd(cmd);
+
+            error.insert(
+                ContextKind::InvalidValue,
+                ContextValue::String(format!("{err}")),
+            );
+
+            error.insert(
+                ContextKind::SuggestedValue,
+                ContextValue::Strings(vec![
+                    "image/*".into(),
+                    "text/plain".into(),
+                    "application/pdf".into(),
+                    "audio/*,video/*".into(),
+                ]),
+            );
+
+            error
+        })
+    }
+}
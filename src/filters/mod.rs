@@ -1,7 +1,19 @@
+mod content_filter;
+mod expr;
 mod file_type_filter;
+mod magic;
+mod mime_filter;
+mod perm_filter;
 mod size_filter;
 mod time_filter;
 
+pub use content_filter::ContentFilter;
+#[cfg(any(feature = "xxhash", feature = "blake3"))]
+pub(crate) use content_filter::open_regular_file;
+pub use expr::{Filter, FilterParser, ParseExprError};
 pub use file_type_filter::{FileTypeFilter, FileTypeFilterParser};
-pub use size_filter::{SizeFilter, SizeFilterParser};
-pub use time_filter::{TimeFilter, TimeFilterParser};
+pub use magic::{SNIFF_LEN, sniff_mime};
+pub use mime_filter::{MimeFilter, MimeFilterParser, ParseMimeError};
+pub use perm_filter::{PermFilter, PermFilterParser};
+pub use size_filter::{ParseSizeError, SizeFilter, SizeFilterParser, parse_byte_size};
+pub use time_filter::{ParseTimeError, TimeFilter, TimeFilterParser, parse_duration};
@@ -0,0 +1,226 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_inline_in_public_items)]
+use clap::{
+    Arg, Command, Error,
+    builder::TypedValueParser,
+    error::{ContextKind, ContextValue, ErrorKind},
+};
+use core::fmt;
+use std::ffi::OsStr;
+
+/// The permission bits (including setuid, setgid and sticky) of a mode
+const PERMISSION_BITS: u32 = 0o7777;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum ParsePermError {
+    Empty,
+    InvalidOctal,
+    InvalidSymbolic,
+}
+
+impl fmt::Display for ParsePermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Empty => write!(f, "empty permission string"),
+            Self::InvalidOctal => write!(f, "invalid octal mode"),
+            Self::InvalidSymbolic => write!(f, "invalid symbolic mode"),
+        }
+    }
+}
+
+impl core::error::Error for ParsePermError {}
+
+/**
+ A filter for permission bits, following the semantics of GNU find's `-perm`.
+
+ # Examples
+
+ ```
+ use fdf::filters::PermFilter;
+
+ // Exactly rw-r--r--
+ let filter = PermFilter::from_string("644").unwrap();
+ assert!(filter.matches_mode(0o100644));
+ assert!(!filter.matches_mode(0o100664));
+
+ // Writable by its owner, whatever the other bits are
+ let filter = PermFilter::from_string("-u+w").unwrap();
+ assert!(filter.matches_mode(0o100600));
+ assert!(!filter.matches_mode(0o100444));
+
+ // Executable by anyone
+ let filter = PermFilter::from_string("/111").unwrap();
+ assert!(filter.matches_mode(0o100744));
+ assert!(!filter.matches_mode(0o100644));
+ ```
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum PermFilter {
+    /// The permission bits must be exactly these
+    Exact(u32),
+    /// Every one of these bits must be set
+    All(u32),
+    /// At least one of these bits must be set (an empty set matches everything)
+    Any(u32),
+}
+
+impl PermFilter {
+    /**
+     Parses a permission string and returns a `PermFilter`
+
+     # Errors
+
+     Returns `ParsePermError` in the following cases:
+     - `ParsePermError::Empty` if the input string is empty
+     - `ParsePermError::InvalidOctal` if an octal mode has non octal digits or bits beyond `7777`
+     - `ParsePermError::InvalidSymbolic` if a symbolic mode can't be parsed
+
+     # Format
+
+     The expected format is: `[-|/]?<mode>`, where the mode is either octal (`644`) or symbolic (`u+w,g=r`) as in `chmod`
+     - `-` prefix: every bit in the mode must be set
+     - `/` prefix: any bit in the mode must be set
+     - No prefix: the permission bits must be exactly the mode
+
+     Symbolic modes are applied to an empty mode, so `u=rw,go=r` is the same as `644`.
+    */
+    pub fn from_string(s: &str) -> Result<Self, ParsePermError> {
+        let s = s.trim();
+        let (constructor, mode): (fn(u32) -> Self, &str) = if let Some(mode) = s.strip_prefix('-') {
+            (Self::All, mode)
+        } else if let Some(mode) = s.strip_prefix('/') {
+            (Self::Any, mode)
+        } else {
+            (Self::Exact, s)
+        };
+
+        if mode.is_empty() {
+            return Err(ParsePermError::Empty);
+        }
+
+        if mode.bytes().all(|byte| byte.is_ascii_digit()) {
+            return u32::from_str_radix(mode, 8)
+                .ok()
+                .filter(|bits| *bits & !PERMISSION_BITS == 0)
+                .map(constructor)
+                .ok_or(ParsePermError::InvalidOctal);
+        }
+
+        Self::parse_symbolic(mode)
+            .map(constructor)
+            .ok_or(ParsePermError::InvalidSymbolic)
+    }
+
+    /// Parses comma separated `[ugoa]*[+-=][rwxXst]*` clauses, applied in order to an empty mode
+    fn parse_symbolic(mode: &str) -> Option<u32> {
+        let mut bits = 0;
+        for clause in mode.split(',') {
+            let operator_index = clause.find(['+', '-', '='])?;
+            let (who, rest) = clause.split_at(operator_index);
+
+            let who_mask = if who.is_empty() {
+                0o7777
+            } else {
+                who.chars().try_fold(0, |mask, c| match c {
+                    'u' => Some(mask | 0o4700),
+                    'g' => Some(mask | 0o2070),
+                    'o' => Some(mask | 0o1007),
+                    'a' => Some(mask | 0o7777),
+                    _ => None,
+                })?
+            };
+
+            // `u+r-w` style chains of operators within one clause
+            let mut remaining = rest;
+            while let Some(operator) = remaining.chars().next() {
+                let perms_end = remaining[1..]
+                    .find(['+', '-', '='])
+                    .map_or(remaining.len(), |pos| pos + 1);
+                let perm_mask = remaining[1..perms_end]
+                    .chars()
+                    .try_fold(0, |mask, c| match c {
+                        'r' => Some(mask | 0o444),
+                        'w' => Some(mask | 0o222),
+                        'x' | 'X' => Some(mask | 0o111),
+                        's' => Some(mask | 0o6000),
+                        't' => Some(mask | 0o1000),
+                        _ => None,
+                    })?;
+
+                let affected = who_mask & perm_mask;
+                match operator {
+                    '+' => bits |= affected,
+                    '-' => bits &= !affected,
+                    // Only the classes named by `who` are reset
+                    _ => bits = (bits & !who_mask) | affected,
+                }
+                remaining = &remaining[perms_end..];
+            }
+        }
+        Some(bits)
+    }
+
+    /// Checks a `st_mode` against the filter, the file type bits are ignored
+    #[must_use]
+    pub const fn matches_mode(&self, mode: u32) -> bool {
+        let mode = mode & PERMISSION_BITS;
+        match *self {
+            Self::Exact(bits) => mode == bits,
+            Self::All(bits) => mode & bits == bits,
+            Self::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+/// A Custom parser that provides helpful error messages and suggestions for permission modes
+#[derive(Clone, Debug)]
+#[allow(clippy::exhaustive_structs)]
+pub struct PermFilterParser;
+
+impl TypedValueParser for PermFilterParser {
+    type Value = PermFilter;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        _arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let value_str = value
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+
+        PermFilter::from_string(value_str).map_err(|err| {
+            let mut error = Error::new(ErrorKind::InvalidValue).with_cmd(cmd);
+
+            error.insert(
+                ContextKind::InvalidValue,
+                ContextValue::String(format!("{err}")),
+            );
+
+            error.insert(
+                ContextKind::SuggestedValue,
+                ContextValue::Strings(vec![
+                    "644".into(),   // exactly rw-r--r--
+                    "-u+w".into(),  // writable by the owner
+                    "/111".into(),  // executable by anyone
+                    "-4000".into(), // setuid
+                ]),
+            );
+
+            error.insert(
+                ContextKind::Usage,
+                ContextValue::Strings(vec![
+                    "Prefixes:".into(),
+                    "  -MODE  - every bit in MODE is set".into(),
+                    "  /MODE  - any bit in MODE is set".into(),
+                    "   MODE  - the permissions are exactly MODE (default)".into(),
+                ]),
+            );
+
+            error
+        })
+    }
+}
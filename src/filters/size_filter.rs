@@ -177,6 +177,36 @@ impl SizeFilter {
     }
 }
 
+/**
+ Parses a plain size such as `4096`, `10mi` or `1gb` into bytes, as taken by `--max-content-size` and the other
+ options which take an amount rather than a filter.
+
+ The units are the same as [`SizeFilter`]'s, but there's no `+`/`-` prefix.
+
+ ```
+ use fdf::filters::parse_byte_size;
+
+ assert_eq!(parse_byte_size("10mi"), Ok(10 * 1024 * 1024));
+ assert_eq!(parse_byte_size("500"), Ok(500));
+ assert!(parse_byte_size("+1m").is_err());
+ ```
+
+ # Errors
+ Returns `ParseSizeError::Empty` for an empty string, `ParseSizeError::InvalidUnit` for an unknown unit, or
+ `ParseSizeError::InvalidNumber` if the number is malformed (including a sign) or the size doesn't fit in a `u64`.
+*/
+pub fn parse_byte_size(s: &str) -> Result<u64, ParseSizeError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseSizeError::Empty);
+    }
+    let (quantity, unit) = SizeFilter::parse_size_parts(s).ok_or(ParseSizeError::InvalidNumber)?;
+    let multiplier = SizeFilter::unit_multiplier(&unit).ok_or(ParseSizeError::InvalidUnit)?;
+    quantity
+        .checked_mul(multiplier)
+        .ok_or(ParseSizeError::InvalidNumber)
+}
+
 /// A Custom parser that provides helpful error messages and suggestions for file sizes
 #[derive(Clone, Debug)]
 #[allow(clippy::exhaustive_structs)]
@@ -4,7 +4,11 @@ use clap::{
     error::{ContextKind, ContextValue, ErrorKind},
 };
 use core::time::Duration;
-use std::{ffi::OsStr, fmt, time::SystemTime};
+use std::{
+    ffi::OsStr,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(clippy::exhaustive_enums)]
@@ -30,6 +34,56 @@ impl fmt::Display for ParseTimeError {
 
 impl core::error::Error for ParseTimeError {}
 
+/**
+ Parses a duration such as `5s`, `30min` or `2d`, as taken by the time filters and `--timeout`.
+
+ The units are `ms`, `s`, `m`/`min`, `h`, `d`, `w` and `y` (365 days), along with their long forms (eg `seconds`).
+
+ ```
+ use fdf::filters::parse_duration;
+ use std::time::Duration;
+
+ assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+ assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+ assert!(parse_duration("5").is_err());
+ ```
+
+ # Errors
+
+ Returns `ParseTimeError::InvalidNumber` if it doesn't start with a number, or `ParseTimeError::InvalidUnit` if the
+ unit isn't one of the above.
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn parse_duration(s: &str) -> Result<Duration, ParseTimeError> {
+    let s = s.trim().to_lowercase();
+
+    // Find where digits end
+    let digit_end = s
+        .chars()
+        .position(|c| !c.is_ascii_digit())
+        .ok_or(ParseTimeError::InvalidUnit)?;
+
+    let (num_str, unit_str) = s.split_at(digit_end);
+    let quantity: u64 = num_str.parse().map_err(|_| ParseTimeError::InvalidNumber)?;
+
+    let seconds = |per: u64| {
+        quantity
+            .checked_mul(per)
+            .map(Duration::from_secs)
+            .ok_or(ParseTimeError::InvalidNumber)
+    };
+    match unit_str.trim() {
+        "ms" | "millis" | "millisecond" | "milliseconds" => Ok(Duration::from_millis(quantity)),
+        "s" | "sec" | "second" | "seconds" => seconds(1),
+        "m" | "min" | "minute" | "minutes" => seconds(60),
+        "h" | "hour" | "hours" => seconds(3600),
+        "d" | "day" | "days" => seconds(86400),
+        "w" | "week" | "weeks" => seconds(604_800),
+        "y" | "year" | "years" => seconds(31_536_000),
+        _ => Err(ParseTimeError::InvalidUnit),
+    }
+}
+
 /**
  A filter for file modification times.
 
@@ -66,12 +120,22 @@ impl TimeFilter {
 
      # Format
 
-     The expected format is: `[+|-]<number><unit>` or for between: `<number><unit>..<number><unit>`
-     - `-` prefix: files modified within the last X time (After/newer than)
-     - `+` prefix: files modified more than X time ago (Before/older than)
+     The expected format is: `[+|-]<time>` or for between: `<time>..<time>`, where a time is either a duration
+     (`<number><unit>`, relative to now) or an absolute timestamp
+     - `-` prefix: files modified within the last X time (After/newer than), or after a timestamp
+     - `+` prefix: files modified more than X time ago (Before/older than), or before a timestamp
      - `..` separator: between two times (e.g., "2d..1d" = files modified between 2 days and 1 day ago)
      - Supported units: s (seconds), m (minutes), h (hours), d (days), w (weeks), y (years)
 
+     # Timestamps
+
+     - `2024-01-01`: a date, meaning midnight at the start of it
+     - `2024-01-01T10:00:00` or `2024-01-01 10:00` (seconds optional): a date and time
+     - `2024-01-01T10:00:00Z` or `2024-01-01T10:00:00+02:00`: an RFC 3339 timestamp, with an explicit offset
+
+     Timestamps without an offset are taken as UTC, not the local timezone, so results don't depend on the
+     machine (or `TZ`) the search runs on. Give an offset to mean a local time.
+
      # Examples
 
      ```
@@ -85,6 +149,14 @@ impl TimeFilter {
 
      // Files modified between 2 days and 1 day ago
      let filter = TimeFilter::from_string("2d..1d").unwrap();
+
+     // Files modified during January 2024 (UTC)
+     let filter = TimeFilter::from_string("2024-01-01..2024-02-01").unwrap();
+     assert!(matches!(filter, TimeFilter::Between(..)));
+
+     // Files modified before 10am in Paris
+     let filter = TimeFilter::from_string("+2024-01-01T10:00:00+01:00").unwrap();
+     assert_eq!(filter, TimeFilter::from_string("+2024-01-01T09:00:00Z").unwrap());
      ```
 
      # Errors
@@ -96,6 +168,99 @@ impl TimeFilter {
         Self::parse_args(s).ok_or(ParseTimeError::InvalidFormat)
     }
 
+    /**
+     Creates a filter for files modified after the given point in time (`--changed-within`).
+
+     The point is either a duration relative to now (eg `2d`) or an absolute timestamp (eg `2024-01-01` or
+     `2024-01-01T10:00:00Z`), see [`from_string`](Self::from_string) for the formats and timezone handling.
+
+     ```
+     use fdf::filters::TimeFilter;
+
+     assert!(matches!(TimeFilter::changed_within("2d"), Ok(TimeFilter::After(_))));
+     assert!(matches!(TimeFilter::changed_within("2024-01-01"), Ok(TimeFilter::After(_))));
+     ```
+
+     # Errors
+
+     Returns `ParseTimeError::InvalidFormat` if the string is neither a duration nor a timestamp.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn changed_within(s: &str) -> Result<Self, ParseTimeError> {
+        Self::parse_time_point(s).map(Self::After)
+    }
+
+    /**
+     Creates a filter for files modified before the given point in time (`--changed-before`).
+
+     Accepts the same formats as [`changed_within`](Self::changed_within).
+
+     ```
+     use fdf::filters::TimeFilter;
+     use std::time::{Duration, UNIX_EPOCH};
+
+     let filter = TimeFilter::changed_before("2024-01-01").unwrap();
+     assert_eq!(filter, TimeFilter::Before(UNIX_EPOCH + Duration::from_secs(1_704_067_200)));
+     ```
+
+     # Errors
+
+     Returns `ParseTimeError::InvalidFormat` if the string is neither a duration nor a timestamp.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn changed_before(s: &str) -> Result<Self, ParseTimeError> {
+        Self::parse_time_point(s).map(Self::Before)
+    }
+
+    fn parse_time_point(s: &str) -> Result<SystemTime, ParseTimeError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseTimeError::Empty);
+        }
+
+        if let Some(time) = Self::parse_relative_time(s) {
+            return Ok(time);
+        }
+
+        Self::parse_absolute_time(s)
+    }
+
+    /// Parses a timestamp, see [`from_string`](Self::from_string) for the accepted formats
+    fn parse_absolute_time(s: &str) -> Result<SystemTime, ParseTimeError> {
+        /// Dates and times without an offset, taken as UTC
+        const NAIVE_FORMATS: [&str; 4] = [
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M:%S%.f",
+            "%Y-%m-%dT%H:%M",
+            "%Y-%m-%d %H:%M",
+        ];
+
+        let time = chrono::DateTime::parse_from_rfc3339(s)
+            .map(|time| time.to_utc())
+            .or_else(|_| {
+                NAIVE_FORMATS
+                    .iter()
+                    .find_map(|format| chrono::NaiveDateTime::parse_from_str(s, format).ok())
+                    .or_else(|| {
+                        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                            .ok()
+                            .map(|date| date.and_time(chrono::NaiveTime::MIN))
+                    })
+                    .map(|time| time.and_utc())
+                    .ok_or(ParseTimeError::InvalidFormat)
+            })?;
+
+        let seconds = time.timestamp();
+        let nanos = Duration::from_nanos(u64::from(time.timestamp_subsec_nanos()));
+        u64::try_from(seconds)
+            .map_or_else(
+                |_| UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs())),
+                |secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)),
+            )
+            .and_then(|time| time.checked_add(nanos))
+            .ok_or(ParseTimeError::InvalidTimestamp)
+    }
+
     fn parse_args(start: &str) -> Option<Self> {
         let s = start.trim();
         if s.is_empty() {
@@ -104,8 +269,8 @@ impl TimeFilter {
 
         // Check for between format (contains "..")
         if let Some((before_str, after_str)) = s.split_once("..") {
-            let before_time = Self::parse_relative_time(before_str.trim())?;
-            let after_time = Self::parse_relative_time(after_str.trim())?;
+            let before_time = Self::parse_time_point(before_str).ok()?;
+            let after_time = Self::parse_time_point(after_str).ok()?;
 
             // Ensure before_time is actually before after_time
             let (older, newer) = if before_time > after_time {
@@ -124,7 +289,7 @@ impl TimeFilter {
             .or_else(|| s.strip_prefix('-').map(|stripped| ("-", stripped)))
             .unwrap_or(("", s));
 
-        let time = Self::parse_relative_time(remaining)?;
+        let time = Self::parse_time_point(remaining).ok()?;
 
         match prefix {
             "+" => Some(Self::Before(time)),     // Older than X time ago
@@ -134,25 +299,55 @@ impl TimeFilter {
     }
 
     fn parse_relative_time(start_str: &str) -> Option<SystemTime> {
-        let s = start_str.trim().to_lowercase();
-
-        // Find where digits end
-        let digit_end = s.chars().position(|c| !c.is_ascii_digit())?;
-
-        let (num_str, unit_str) = s.split_at(digit_end);
-        let quantity: u64 = num_str.parse().ok()?;
-
-        let duration = match unit_str.trim() {
-            "s" | "sec" | "second" | "seconds" => Duration::from_secs(quantity),
-            "m" | "min" | "minute" | "minutes" => Duration::from_secs(quantity * 60),
-            "h" | "hour" | "hours" => Duration::from_secs(quantity * 3600),
-            "d" | "day" | "days" => Duration::from_secs(quantity * 86400),
-            "w" | "week" | "weeks" => Duration::from_secs(quantity * 604_800),
-            "y" | "year" | "years" => Duration::from_secs(quantity * 31_536_000),
-            _ => return None,
+        SystemTime::now().checked_sub(parse_duration(start_str).ok()?)
+    }
+
+    /**
+     Combines two filters into one matching the times both match, eg `--changed-within 1w --changed-before 1d`
+     becomes a window from a week ago to a day ago. Disjoint filters give a window matching nothing.
+
+     ```
+     use fdf::filters::TimeFilter;
+     use std::time::{Duration, UNIX_EPOCH};
+
+     let week = UNIX_EPOCH + Duration::from_secs(7 * 86_400);
+     let day = UNIX_EPOCH + Duration::from_secs(13 * 86_400);
+     let window = TimeFilter::After(week).intersect(TimeFilter::Before(day));
+     assert_eq!(window, TimeFilter::Between(week, day));
+     assert!(window.matches_time(UNIX_EPOCH + Duration::from_secs(10 * 86_400)));
+     assert!(!window.matches_time(UNIX_EPOCH + Duration::from_secs(14 * 86_400)));
+
+     // The narrower bound wins
+     assert_eq!(TimeFilter::After(week).intersect(TimeFilter::After(day)), TimeFilter::After(day));
+     ```
+    */
+    #[must_use]
+    #[inline]
+    pub fn intersect(self, other: Self) -> Self {
+        let (lower, upper) = self.bounds();
+        let (other_lower, other_upper) = other.bounds();
+        let lower = lower.max(other_lower); // None is less than any time
+        let upper = match (upper, other_upper) {
+            (Some(upper), Some(other_upper)) => Some(upper.min(other_upper)),
+            (upper, other_upper) => upper.or(other_upper),
         };
 
-        SystemTime::now().checked_sub(duration)
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => Self::Between(lower, upper),
+            (Some(lower), None) => Self::After(lower),
+            (None, Some(upper)) => Self::Before(upper),
+            // Every filter has at least one bound
+            (None, None) => self,
+        }
+    }
+
+    /// The (exclusive) earliest and latest times matched, if bounded
+    const fn bounds(self) -> (Option<SystemTime>, Option<SystemTime>) {
+        match self {
+            Self::Before(cutoff) => (None, Some(cutoff)),
+            Self::After(cutoff) => (Some(cutoff), None),
+            Self::Between(newer, older) => (Some(newer), Some(older)),
+        }
     }
 
     #[must_use]
@@ -215,6 +410,8 @@ impl TypedValueParser for TimeFilterParser {
                         "+2d".into(),    // modified more than 2 days ago
                         "+1w".into(),    // modified more than 1 week ago
                         "1d..2h".into(), // modified between 1 day and 2 hours ago
+                        "+2024-01-01".into(),
+                        "2024-01-01T10:00:00Z..2024-01-02".into(),
                     ]),
                 );
 
@@ -240,6 +437,8 @@ impl TypedValueParser for TimeFilterParser {
                         "d, day, days".into(),
                         "w, week, weeks".into(),
                         "y, year, years".into(),
+                        "or a timestamp: YYYY-MM-DD[THH:MM[:SS]][Z|+HH:MM] (UTC without an offset)"
+                            .into(),
                     ]),
                 );
 
@@ -257,6 +456,9 @@ impl TypedValueParser for TimeFilterParser {
                 PossibleValue::new("+2d").help("modified more than 2 days ago"),
                 PossibleValue::new("+1w").help("modified more than 1 week ago"),
                 PossibleValue::new("1d..2h").help("modified between 1 day and 2 hours ago"),
+                PossibleValue::new("+2024-01-01").help("modified before 2024 (UTC)"),
+                PossibleValue::new("2024-01-01T10:00:00Z..2024-01-02")
+                    .help("modified between two timestamps"),
             ]
             .into_iter(),
         ))
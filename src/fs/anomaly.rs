@@ -0,0 +1,84 @@
+use crate::fs::{
+    DirEntry, FileType,
+    limits::{name_max, path_max},
+};
+use core::fmt;
+
+/**
+ A filesystem hygiene problem found by [`DirEntry::anomalies`].
+
+ These are all legal, but tend to break scripts and other tools (and occasionally indicate corruption).
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Anomaly {
+    /// The path (with its null terminator) doesn't fit in `PATH_MAX`, so it can only be opened relative to a parent
+    PathTooLong(usize),
+    /// The file name is longer than `NAME_MAX` (of the root filesystem, usually 255 bytes)
+    NameTooLong(usize),
+    /// The file name contains ASCII control characters (eg newlines or escape sequences)
+    ControlCharacters,
+    /// The file name is not valid UTF-8
+    InvalidUtf8,
+    /// The type reported by the directory entry (`d_type`) disagrees with `lstat`
+    TypeMismatch {
+        /// The type reported while reading the directory
+        reported: FileType,
+        /// The type reported by `lstat`
+        actual: FileType,
+    },
+}
+
+impl fmt::Display for Anomaly {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::PathTooLong(len) => {
+                write!(f, "path length {len} exceeds PATH_MAX ({})", path_max())
+            }
+            Self::NameTooLong(len) => write!(f, "name length {len} exceeds {} bytes", name_max()),
+            Self::ControlCharacters => write!(f, "name contains control characters"),
+            Self::InvalidUtf8 => write!(f, "name is not valid UTF-8"),
+            Self::TypeMismatch { reported, actual } => {
+                write!(
+                    f,
+                    "directory entry type {reported} disagrees with lstat ({actual})"
+                )
+            }
+        }
+    }
+}
+
+pub(crate) fn find_anomalies(entry: &DirEntry) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+    let name = entry.file_name();
+
+    // `len` excludes the null terminator
+    if entry.len() >= path_max() {
+        anomalies.push(Anomaly::PathTooLong(entry.len()));
+    }
+    if name.len() > name_max() {
+        anomalies.push(Anomaly::NameTooLong(name.len()));
+    }
+    if name.iter().any(u8::is_ascii_control) {
+        anomalies.push(Anomaly::ControlCharacters);
+    }
+    if core::str::from_utf8(name).is_err() {
+        anomalies.push(Anomaly::InvalidUtf8);
+    }
+
+    // An unknown d_type isn't a disagreement, the filesystem just doesn't report one
+    if entry.file_type() != FileType::Unknown
+        && let Ok(statted) = entry.get_lstat()
+    {
+        let actual = FileType::from(statted);
+        if actual != entry.file_type() {
+            anomalies.push(Anomaly::TypeMismatch {
+                reported: entry.file_type(),
+                actual,
+            });
+        }
+    }
+
+    anomalies
+}
@@ -2,6 +2,17 @@ use core::marker::Copy;
 use core::mem::MaybeUninit;
 use core::ops::{Index, IndexMut};
 use core::slice::SliceIndex;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+use std::os::fd::AsRawFd as _;
 mod sealed {
     /// Sealed trait pattern to restrict `ValueType` implementation to i8 and u8 only
     pub trait Sealed {}
@@ -118,6 +129,16 @@ where
     }
 }
 
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
 impl<T> Default for AlignedBuffer<T, { crate::fs::types::BUFFER_SIZE }>
 where
     T: ValueType
@@ -209,7 +230,9 @@ where
     ))]
     pub fn getdents(&mut self, fd: &crate::fs::FileDes) -> isize {
         // SAFETY: we're passing a valid buffer
-        unsafe { crate::util::getdents64(fd.0, self.as_mut_ptr().cast(), Self::BUFFER_SIZE) }
+        unsafe {
+            crate::util::getdents64(fd.as_raw_fd(), self.as_mut_ptr().cast(), Self::BUFFER_SIZE)
+        }
     }
 
     /// Executes the `getdirentries64` system call
@@ -239,7 +262,7 @@ where
         // SAFETY: we're passing a valid buffer and valid base pointer
         unsafe {
             crate::util::getdirentries64(
-                fd.0,
+                fd.as_raw_fd(),
                 self.as_mut_ptr().cast(),
                 Self::BUFFER_SIZE,
                 core::ptr::from_mut(basep),
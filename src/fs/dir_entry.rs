@@ -97,7 +97,7 @@ const AT_EACCESS: core::ffi::c_int = 0; // android being weird, again....
 #[cfg(not(target_os = "android"))]
 use libc::AT_EACCESS;
 
-use std::{ffi::OsStr, os::unix::ffi::OsStrExt as _, path::Path};
+use std::{ffi::OsStr, os::fd::AsRawFd as _, os::unix::ffi::OsStrExt as _, path::Path};
 
 //TODO, test #[align(64)] to check for presence of false sharing/perf improvements
 // If so, add extra relevant metadata to the struct...
@@ -174,7 +174,11 @@ pub struct DirEntry {
     ///
     /// `None` means not computed yet, `Some(bool)` means cached result.
     pub(crate) is_traversible_cache: Cell<Option<bool>>, //1byte
-} //38 bytes, rounded to 40
+    /// The device ID of the file (following symlinks), see [`DirEntry::dev`]
+    ///
+    /// `None` means not statted yet.
+    pub(crate) device: Cell<Option<u64>>, //16 bytes
+} //54 bytes, rounded to 56
 
 // TODO add extra metadata from stat calls if available to avoid doing, ie like st_mode etc.
 
@@ -201,11 +205,12 @@ impl<'drnt> From<&'drnt DirEntry> for &'drnt CStr {
     }
 }
 
+/// Shows the path converted lossily, see [`DirEntry::display_with`] for other options
 impl fmt::Display for DirEntry {
     // TODO: I might need to change this to show other metadata.
     #[allow(clippy::missing_inline_in_public_items)]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string_lossy())
+        fmt::Display::fmt(&self.display_with(crate::fs::DisplayOptions::new()), f)
     }
 }
 
@@ -251,10 +256,29 @@ impl fmt::Debug for DirEntry {
             .field("file_name_index", &self.file_name_index)
             .field("inode", &self.inode)
             .field("traversible_cache", &self.is_traversible_cache)
+            .field("device", &self.device)
             .finish()
     }
 }
 
+/// Serialises the cheap, already known fields. Paths which aren't valid UTF-8 are converted lossily.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DirEntry {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+        let mut state = serializer.serialize_struct("DirEntry", 4)?;
+        state.serialize_field("path", &self.to_string_lossy())?;
+        state.serialize_field("file_type", &self.file_type)?;
+        state.serialize_field("depth", &self.depth)?;
+        state.serialize_field("inode", &self.inode)?;
+        state.end()
+    }
+}
+
 impl DirEntry {
     /**
     Checks if the entry is an executable file.
@@ -299,7 +323,7 @@ impl DirEntry {
             && unsafe {
                 opt_fd.map_or_else(
                     || faccessat(AT_FDCWD, self.as_ptr(), X_OK, AT_EACCESS) == 0,
-                    |fd| faccessat(fd.0, self.file_name_ptr(), X_OK, AT_EACCESS) == 0,
+                    |fd| faccessat(fd.as_raw_fd(), self.file_name_ptr(), X_OK, AT_EACCESS) == 0,
                 )
             }
     }
@@ -363,6 +387,9 @@ impl DirEntry {
         // Unfortunately, because the error returned is just 'PERMISSION DENIED' (find the appropriate libc errno for it)
         // We can't just 'retry' certain files, ie ones in /proc
 
+        #[cfg(feature = "fault-injection")]
+        crate::fs::fault::before_open(self)?;
+
         // SAFETY: the pointer is null terminated
         let fd = unsafe { libc::open(self.as_ptr(), FLAGS) };
 
@@ -370,28 +397,34 @@ impl DirEntry {
             return_os_error!()
         }
 
-        Ok(FileDes(fd))
+        // SAFETY: the descriptor was just opened, and only the returned value closes it
+        Ok(unsafe { FileDes::from_raw(fd) })
     }
 
-    /// Opens a child directory by name relative to an already-open parent directory fd.
+    /// Opens this directory by name relative to its already-open parent directory fd.
     ///
     /// Uses `openat(2)` to avoid a full path resolution — the kernel resolves the name
     /// relative to `parent_fd` directly.  The same flags as [`Self::open`] are used.
     #[inline]
-    pub(crate) fn open_at(parent_fd: i32, child_name: &core::ffi::CStr) -> Result<FileDes> {
+    pub(crate) fn open_at(&self, parent_fd: i32) -> Result<FileDes> {
         const FLAGS: i32 = libc::O_CLOEXEC | libc::O_DIRECTORY | libc::O_NONBLOCK | libc::O_RDONLY;
-        // SAFETY: child_name is null-terminated; parent_fd is a valid open directory fd.
-        let fd = unsafe { libc::openat(parent_fd, child_name.as_ptr(), FLAGS) };
+
+        #[cfg(feature = "fault-injection")]
+        crate::fs::fault::before_open(self)?;
+
+        // SAFETY: the file name is null-terminated; parent_fd is a valid open directory fd.
+        let fd = unsafe { libc::openat(parent_fd, self.file_name_cstr().as_ptr(), FLAGS) };
         if fd < 0 {
             return_os_error!()
         }
-        Ok(FileDes(fd))
+        // SAFETY: the descriptor was just opened, and only the returned value closes it
+        Ok(unsafe { FileDes::from_raw(fd) })
     }
 
     /// Returns a [`ReadDir`] iterator backed by a pre-opened fd, avoiding a second `open()` call.
     #[inline]
     #[allow(unused)] // for esoteric platforms
-    pub(crate) fn readdir_from_fd(&self, fd: FileDes) -> ReadDir {
+    pub(crate) fn readdir_from_fd(&self, fd: FileDes) -> Result<ReadDir> {
         ReadDir::from_fd(fd, self)
     }
 
@@ -418,6 +451,40 @@ impl DirEntry {
         String::from_utf8_lossy(self)
     }
 
+    /**
+    Returns an adapter showing the path with `options`: quoted, truncated to a width, or with invalid UTF-8 and
+    control characters escaped rather than replaced. See [`DisplayOptions`](crate::fs::DisplayOptions).
+
+    ```
+    use fdf::fs::{DirEntry, DisplayOptions, NameEncoding, QuoteStyle};
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt as _};
+
+    let dir = std::env::temp_dir().join("fdf_display_with_doctest");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(OsStr::from_bytes(b"bad\xffname\n"));
+    std::fs::write(&path, "").unwrap();
+
+    let entry = DirEntry::new(&path).unwrap();
+    let options = DisplayOptions::new()
+        .quote(QuoteStyle::Double)
+        .encoding(NameEncoding::Escaped);
+    let shown = entry.display_with(options).to_string();
+    assert!(shown.ends_with("/bad\\xffname\\n\""));
+    std::fs::remove_dir_all(&dir).unwrap();
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub const fn display_with(
+        &self,
+        options: crate::fs::DisplayOptions,
+    ) -> crate::fs::DisplayWith<'_> {
+        crate::fs::DisplayWith {
+            entry: self,
+            options,
+        }
+    }
+
     /**
     Returns the underlying bytes as a UTF-8 string slice if valid.
     # Errors
@@ -524,29 +591,84 @@ impl DirEntry {
         // (This code is verbose but it's an essential operation so it makes sense to optimise it)
         match self.file_type {
             FileType::RegularFile => self.file_size().is_ok_and(|size| size == 0),
-            FileType::Directory => {
-                #[cfg(any(
-                    target_os = "linux",
-                    target_os = "android",
-                    target_os = "netbsd",
-                    target_os = "solaris",
-                    target_os = "illumos"
-                ))]
-                let result = self.is_empty_getdents();
-                #[cfg(not(any(
-                    target_os = "linux",
-                    target_os = "android",
-                    target_os = "netbsd",
-                    target_os = "solaris",
-                    target_os = "illumos"
-                )))]
-                let result = self.is_empty_posix(); //Avoid allocating a buffer here or any other ops.
-                result
-            }
+            FileType::Directory => self.is_empty_dir(),
             _ => false,
         }
     }
 
+    /**
+    Returns true if the entry is a directory (or a symlink to one) holding nothing but `.` and `..`.
+
+    On Linux, Android, NetBSD, Solaris and illumos this is a single `getdents` call into a stack buffer,
+    elsewhere it falls back to `readdir`. Returns false for anything else, or if the directory can't be opened.
+
+    # Examples
+    ```
+    use fdf::fs::DirEntry;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join("fdf_is_empty_dir_doctest");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    assert!(DirEntry::new(&dir).unwrap().is_empty_dir());
+
+    fs::write(dir.join("file"), b"").unwrap();
+    assert!(!DirEntry::new(&dir).unwrap().is_empty_dir());
+    // An empty file isn't an empty directory
+    assert!(!DirEntry::new(dir.join("file")).unwrap().is_empty_dir());
+    fs::remove_dir_all(&dir).unwrap();
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub fn is_empty_dir(&self) -> bool {
+        if !self.is_traversible() {
+            return false;
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "netbsd",
+            target_os = "solaris",
+            target_os = "illumos"
+        ))]
+        let result = self.is_empty_getdents();
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "netbsd",
+            target_os = "solaris",
+            target_os = "illumos"
+        )))]
+        let result = self.is_empty_posix();
+        result
+    }
+
+    /**
+    Checks if the entry is empty as [`is_empty`](Self::is_empty) does, but only where that needs no heap
+    allocation, returning `None` otherwise.
+
+    Regular files are checked by their size. Directories are checked with a stack buffer on the platforms where
+    [`is_empty_dir`](Self::is_empty_dir) uses `getdents`, elsewhere `readdir` allocates its stream so they give
+    `None`, as does every other type.
+    */
+    #[inline]
+    #[must_use]
+    pub fn is_empty_cheap(&self) -> Option<bool> {
+        match self.file_type {
+            FileType::RegularFile => Some(self.file_size().is_ok_and(|size| size == 0)),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "netbsd",
+                target_os = "solaris",
+                target_os = "illumos"
+            ))]
+            FileType::Directory => Some(self.is_empty_getdents()),
+            _ => None,
+        }
+    }
+
     #[inline]
     #[must_use]
     pub(crate) fn is_empty_at(&self, opt_fd: Option<&FileDes>) -> bool {
@@ -561,7 +683,7 @@ impl DirEntry {
             FileType::Directory => opt_fd.map_or_else(
                 || self.is_empty(),
                 |parent_fd| {
-                    Self::open_at(parent_fd.0, self.file_name_cstr())
+                    self.open_at(parent_fd.as_raw_fd())
                         .ok()
                         .and_then(|dir_fd| read_direntries_from_fd!(self, dir_fd).ok())
                         .is_some_and(|mut entries| entries.next().is_none())
@@ -586,8 +708,8 @@ impl DirEntry {
         use libc::closedir;
 
         debug_assert!(
-            self.is_dir(),
-            "checking the only entries to this are directories"
+            self.is_dir() || self.is_symlink(),
+            "checking the only entries to this are directories (or symlinks to them)"
         );
 
         // SAFETY: guaranteed null terminated path
@@ -632,10 +754,9 @@ impl DirEntry {
         if let Ok(fd) = dirfd {
             let mut syscall_buffer = AlignedBuffer::<u8, BUF_SIZE>::new();
             // SAFETY: guaranteed open, valid ptr etc.
-            let dents = unsafe { getdents64(fd.0, syscall_buffer.as_mut_ptr().cast(), BUF_SIZE) };
-
-            // SAFETY: Closed only once confirmed open
-            unsafe { libc::close(fd.0) };
+            let dents =
+                unsafe { getdents64(fd.as_raw_fd(), syscall_buffer.as_mut_ptr().cast(), BUF_SIZE) };
+            // The descriptor is closed as `fd` goes out of scope
             // if empty, then only 2 entries expected, . and .., this means only 64 or below (or neg if errors, who cares.)
             return dents <= 2 * MINIMUM_DIRENT_SIZE;
         }
@@ -938,11 +1059,15 @@ impl DirEntry {
         self.get_realpath(|full_path| {
             let file_name_index = full_path.to_bytes().file_name_index();
 
-            let (file_type, ino) = if self.is_symlink() {
+            let (file_type, ino, device) = if self.is_symlink() {
                 let statted = self.get_stat()?; // only call stat if it's a symlink, because I don't deduplicate normal files, this works well for symlinks
-                (FileType::from_stat(&statted), access_stat!(statted, st_ino))
+                (
+                    FileType::from_stat(&statted),
+                    access_stat!(statted, st_ino),
+                    Some(access_stat!(statted, st_dev)),
+                )
             } else {
-                (self.file_type(), self.ino())
+                (self.file_type(), self.ino(), self.device.get())
             };
 
             Ok(Self {
@@ -952,9 +1077,59 @@ impl DirEntry {
                 depth: self.depth,
                 file_name_index,
                 is_traversible_cache: Cell::new(Some(file_type == FileType::Directory)),
+                device: Cell::new(device),
             })
         })
     }
+    /**
+    Follows a symlink chain one hop at a time, returning every path visited after this entry.
+
+    Relative targets are joined onto the directory of the link they came from (without normalising `..`).
+    At most `max_hops` hops are followed (see [`MAX_SYMLINK_HOPS`](crate::fs::MAX_SYMLINK_HOPS)), so for a
+    loop or an over long chain the last path returned is still a symlink. A dangling link ends the chain on
+    its missing target. Entries which aren't symlinks return an empty chain.
+
+    # Errors
+    Returns an error if a link in the chain can't be read.
+
+    # Examples
+    ```
+    use fdf::fs::{DirEntry, MAX_SYMLINK_HOPS};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    let dir = std::env::temp_dir().join("fdf_resolve_chain_doctest");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("target"), "").unwrap();
+    symlink("target", dir.join("middle")).unwrap();
+    symlink(dir.join("middle"), dir.join("start")).unwrap();
+
+    let chain = DirEntry::new(dir.join("start")).unwrap().resolve_chain(MAX_SYMLINK_HOPS).unwrap();
+    assert_eq!(chain, vec![dir.join("middle"), dir.join("target")]);
+    fs::remove_dir_all(&dir).unwrap();
+    ```
+    */
+    #[inline]
+    pub fn resolve_chain(&self, max_hops: usize) -> Result<Vec<std::path::PathBuf>> {
+        let mut chain = Vec::new();
+        let mut current = std::path::PathBuf::from(self.as_os_str());
+
+        while chain.len() < max_hops
+            && std::fs::symlink_metadata(&current).is_ok_and(|meta| meta.file_type().is_symlink())
+        {
+            let target = std::fs::read_link(&current)?;
+            let next = match current.parent() {
+                Some(parent) if target.is_relative() => parent.join(target),
+                _ => target,
+            };
+            chain.push(next.clone());
+            current = next;
+        }
+
+        Ok(chain)
+    }
+
     /**
     Returns the parent path as byte slice, or None if at root.
 
@@ -1055,6 +1230,105 @@ impl DirEntry {
         unsafe { access(self.as_ptr(), F_OK) == 0 }
     }
 
+    /**
+    Returns the filesystem hygiene problems with this entry, see [`Anomaly`](crate::fs::Anomaly).
+
+    Checks for over long paths and names, control characters or invalid UTF-8 in the name, and a `d_type`
+    which disagrees with `lstat` (this costs an `lstat` call per entry).
+
+    ```
+    use fdf::fs::{Anomaly, DirEntry};
+    use std::fs;
+
+    let dir = std::env::temp_dir().join("fdf_anomalies_doctest");
+    fs::create_dir_all(&dir).unwrap();
+    let odd = dir.join("line\nbreak");
+    fs::write(&odd, "").unwrap();
+
+    let entry = DirEntry::new(&odd).unwrap();
+    assert_eq!(entry.anomalies(), vec![Anomaly::ControlCharacters]);
+    fs::remove_dir_all(&dir).unwrap();
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub fn anomalies(&self) -> Vec<crate::fs::Anomaly> {
+        crate::fs::anomaly::find_anomalies(self)
+    }
+
+    /**
+    Checks if this entry is on a pseudo filesystem whose contents the kernel generates, eg `/proc`, `/sys` or `/dev`.
+
+    This asks `statfs` for the filesystem's type (following symlinks), see
+    [`FinderBuilder::skip_pseudo_fs`](crate::walk::FinderBuilder::skip_pseudo_fs). Returns `false` if the entry
+    cannot be statted, and on platforms other than Linux, Android, macOS, FreeBSD and OpenBSD.
+
+    # Examples
+    ```
+    use fdf::fs::DirEntry;
+
+    assert!(!DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap().is_on_pseudo_fs());
+    # #[cfg(target_os = "linux")]
+    # if std::path::Path::new("/proc/self").exists() {
+    #     assert!(DirEntry::new("/proc").unwrap().is_on_pseudo_fs());
+    # }
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub fn is_on_pseudo_fs(&self) -> bool {
+        crate::fs::mounts::is_pseudo_fs(self)
+    }
+
+    /**
+    Checks if this entry is a mount point (the root of a mounted filesystem), following symlinks.
+
+    On Linux this uses `statx`'s `STATX_ATTR_MOUNT_ROOT` attribute, which also detects bind mounts
+    of the same filesystem. Elsewhere (or on kernels older than 5.8) it falls back to comparing the
+    device ID with the parent directory's, which cannot detect same-device bind mounts.
+
+    The filesystem root (`/`) is always considered a mount point. Returns `false` if the entry cannot be statted.
+    */
+    #[inline]
+    #[must_use]
+    pub fn is_mount_point(&self) -> bool {
+        #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+        {
+            let mut statx_buf = core::mem::MaybeUninit::<libc::statx>::uninit();
+            // SAFETY: the path is null terminated and the buffer is valid for writes
+            let ret = unsafe {
+                libc::statx(
+                    libc::AT_FDCWD,
+                    self.as_ptr(),
+                    libc::AT_NO_AUTOMOUNT,
+                    libc::STATX_TYPE,
+                    statx_buf.as_mut_ptr(),
+                )
+            };
+            if ret == 0 {
+                // SAFETY: statx succeeded so the buffer is initialised
+                let statted = unsafe { statx_buf.assume_init() };
+                const MOUNT_ROOT: u64 = libc::STATX_ATTR_MOUNT_ROOT as u64;
+                if statted.stx_attributes_mask & MOUNT_ROOT != 0 {
+                    return statted.stx_attributes & MOUNT_ROOT != 0;
+                }
+            }
+            // Kernel too old to report the attribute (or statx unsupported), fall through
+        }
+
+        let Ok(device) = self.dev() else {
+            return false;
+        };
+
+        self.parent().is_none_or(|parent| {
+            let parent_path = if parent.is_empty() { b"." } else { parent };
+            std::fs::metadata(OsStr::from_bytes(parent_path)).is_ok_and(|meta| {
+                use std::os::unix::fs::MetadataExt as _;
+                meta.dev() != device
+            })
+        })
+    }
+
     /**
     Gets file metadata using lstatat for a file relative to a directory file descriptor.
 
@@ -1072,7 +1346,12 @@ impl DirEntry {
     */
     #[inline]
     pub fn get_lstatat(&self, fd: &FileDes) -> Result<stat> {
-        stat_syscall!(fstatat, fd.0, self.file_name_ptr(), AT_SYMLINK_NOFOLLOW)
+        stat_syscall!(
+            fstatat,
+            fd.as_raw_fd(),
+            self.file_name_ptr(),
+            AT_SYMLINK_NOFOLLOW
+        )
     }
 
     /**
@@ -1130,6 +1409,43 @@ impl DirEntry {
         stat_syscall!(stat, self.as_ptr())
     }
 
+    /**
+    Returns the device ID of the filesystem holding the file (`st_dev`), following symlinks.
+
+    The file is only statted the first time, the result is cached on the entry. During a traversal with
+    [`same_filesystem`](crate::walk::FinderBuilder::same_filesystem) (or following symlinks) directories
+    are statted anyway, so theirs is usually known already.
+
+    # Examples
+    ```
+    use fdf::fs::DirEntry;
+    use std::os::unix::fs::MetadataExt as _;
+
+    let entry = DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+    let expected = std::fs::metadata(env!("CARGO_MANIFEST_DIR")).unwrap().dev();
+    assert_eq!(entry.dev().unwrap(), expected);
+    ```
+
+    # Errors
+    Returns `DirEntryError::IOError` if the entry can't be statted
+    */
+    #[inline]
+    pub fn dev(&self) -> Result<u64> {
+        if let Some(device) = self.device.get() {
+            return Ok(device);
+        }
+        self.get_stat_caching_device()
+            .map(|statted| access_stat!(statted, st_dev))
+    }
+
+    /// As [`get_stat`](Self::get_stat), remembering the device ID for [`dev`](Self::dev)
+    #[inline]
+    pub(crate) fn get_stat_caching_device(&self) -> Result<stat> {
+        let statted = self.get_stat()?;
+        self.device.set(Some(access_stat!(statted, st_dev)));
+        Ok(statted)
+    }
+
     /**
     Gets file metadata using statat for a file relative to a directory file descriptor.
 
@@ -1148,7 +1464,72 @@ impl DirEntry {
     */
     #[inline]
     pub fn get_statat(&self, fd: &FileDes) -> Result<stat> {
-        stat_syscall!(fstatat, fd.0, self.file_name_ptr(), 0)
+        stat_syscall!(fstatat, fd.as_raw_fd(), self.file_name_ptr(), 0)
+    }
+
+    /**
+    Gets selected file metadata without following symlinks, using the Linux `statx` system call.
+
+    `mask` is a combination of `libc::STATX_*` flags naming the fields wanted (eg `STATX_SIZE | STATX_MTIME`),
+    filesystems which have to work for a field (eg network filesystems) can skip the ones not asked for.
+    `stx_mask` of the result says which fields were actually filled in, which may be more or less than requested,
+    in particular the birth time (`STATX_BTIME`) isn't available on every filesystem.
+
+    On kernels older than 4.11 this falls back to `lstat`, which fills in the basic fields (`STATX_BASIC_STATS`).
+
+    # Examples
+    ```
+    use fdf::fs::DirEntry;
+
+    let entry = DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+    let statted = entry.get_statx(libc::STATX_SIZE | libc::STATX_MTIME).unwrap();
+    assert!(statted.stx_mask & libc::STATX_MTIME != 0);
+    assert_eq!(statted.stx_size, entry.get_lstat().unwrap().st_size as u64);
+    ```
+
+    # Errors
+    Returns `DirEntryError::IOError` if the stat operation fails
+    */
+    #[inline]
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+    pub fn get_statx(&self, mask: u32) -> Result<libc::statx> {
+        Self::statx_syscall(libc::AT_FDCWD, self.as_ptr(), mask)
+    }
+
+    /// Like [`get_statx`](Self::get_statx), relative to the directory `fd` this entry was read from
+    #[inline]
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+    pub(crate) fn get_statxat(&self, fd: &FileDes, mask: u32) -> Result<libc::statx> {
+        Self::statx_syscall(fd.as_raw_fd(), self.file_name_ptr(), mask)
+    }
+
+    #[inline]
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+    fn statx_syscall(dirfd: i32, path: *const c_char, mask: u32) -> Result<libc::statx> {
+        let mut statx_buf = core::mem::MaybeUninit::<libc::statx>::uninit();
+        // SAFETY: the path is null terminated and the buffer is valid for writes
+        let res = unsafe {
+            libc::statx(
+                dirfd,
+                path,
+                AT_SYMLINK_NOFOLLOW | libc::AT_NO_AUTOMOUNT,
+                mask,
+                statx_buf.as_mut_ptr(),
+            )
+        };
+
+        if res == 0 {
+            // SAFETY: statx succeeded so the buffer is initialised
+            return Ok(unsafe { statx_buf.assume_init() });
+        }
+
+        let error = std::io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ENOSYS) {
+            return Err(error.into());
+        }
+        // Kernel too old for statx
+        let statted: Result<stat> = stat_syscall!(fstatat, dirfd, path, AT_SYMLINK_NOFOLLOW);
+        statted.map(|statted| statx_from_stat(&statted))
     }
 
     /// Cost free conversion to bytes (because it is already is bytes)
@@ -1458,6 +1839,51 @@ impl DirEntry {
         })
     }
 
+    /**
+     Returns the file name without its extension.
+
+     This follows the same rules as [`extension`](Self::extension), so whenever there is an extension
+     the file name is exactly the stem, a dot and the extension. Note that this means a leading dot counts,
+     the stem of `.bashrc` is empty.
+
+     # Examples
+
+     ```
+     use fdf::fs::DirEntry;
+
+     let tmp = std::env::temp_dir();
+     let file_test = tmp.join("archive.tar.gz");
+     std::fs::File::create(&file_test).unwrap();
+
+     let entry = DirEntry::new(&file_test).unwrap();
+     assert_eq!(entry.file_stem(), b"archive.tar");
+     assert_eq!(entry.extension(), Some(b"gz".as_ref()));
+     std::fs::remove_file(&file_test).unwrap();
+     ```
+    */
+    #[inline]
+    #[must_use]
+    pub fn file_stem(&self) -> &[u8] {
+        crate::util::file_stem(self.file_name())
+    }
+
+    /**
+    Attaches a value computed for this entry, see [`EntryWith`](crate::fs::EntryWith).
+
+    ```
+    use fdf::fs::DirEntry;
+
+    let entry = DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap().with("root");
+    assert_eq!(*entry.data(), "root");
+    assert!(entry.is_dir());
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub const fn with<T>(self, data: T) -> crate::fs::EntryWith<T> {
+        crate::fs::EntryWith::new(self, data)
+    }
+
     /**
     Creates a new [`DirEntry`] from the given path.
 
@@ -1535,6 +1961,7 @@ impl DirEntry {
             depth: 0,
             file_name_index,
             is_traversible_cache: Cell::new(None), //no need to check(we'd need to call stat instead!)
+            device: Cell::new(None),
         })
     }
 
@@ -1587,19 +2014,138 @@ impl DirEntry {
 
     /// Returns last modification time in UTC, resolving relative to `opt_fd` when provided.
     #[inline]
-    #[expect(
+    #[allow(
         clippy::cast_sign_loss,
         clippy::cast_possible_truncation,
         reason = "needs to be in u32 for chrono"
     )]
     pub(crate) fn modified_time_at(&self, opt_fd: Option<&FileDes>) -> Result<DateTime<Utc>> {
-        let statted = opt_fd.map_or_else(|| self.get_lstat(), |fd| self.get_lstatat(fd))?;
+        #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+        {
+            let statted = opt_fd.map_or_else(
+                || self.get_statx(libc::STATX_MTIME),
+                |fd| self.get_statxat(fd, libc::STATX_MTIME),
+            )?;
+            DateTime::from_timestamp(statted.stx_mtime.tv_sec, statted.stx_mtime.tv_nsec)
+                .ok_or(DirEntryError::TimeError)
+        }
 
-        DateTime::from_timestamp(
-            access_stat!(statted, st_mtime),
-            access_stat!(statted, st_mtimensec),
-        )
-        .ok_or(DirEntryError::TimeError)
+        #[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "android")))]
+        {
+            let statted = opt_fd.map_or_else(|| self.get_lstat(), |fd| self.get_lstatat(fd))?;
+
+            DateTime::from_timestamp(
+                access_stat!(statted, st_mtime),
+                access_stat!(statted, st_mtimensec),
+            )
+            .ok_or(DirEntryError::TimeError)
+        }
+    }
+
+    /**
+    Returns the last access time in UTC, without following symlinks.
+
+    Many systems only update access times lazily (eg Linux's default `relatime` mount option),
+    so this is best treated as approximate.
+
+    # Errors
+    Returns an error if the entry can't be statted or the timestamp can't be represented.
+    */
+    #[inline]
+    pub fn accessed_time(&self) -> Result<DateTime<Utc>> {
+        self.accessed_time_at(None)
+    }
+
+    /**
+    Returns the creation (birth) time in UTC, without following symlinks.
+
+    This uses `statx` on Linux and `st_birthtime` on macOS and the BSDs which have it.
+
+    # Errors
+    Returns an error if the entry can't be statted, or with [`io::ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported)
+    if the platform or filesystem doesn't record creation times.
+    */
+    #[inline]
+    pub fn created_time(&self) -> Result<DateTime<Utc>> {
+        self.created_time_at(None)
+    }
+
+    /// Returns the last access time in UTC, resolving relative to `opt_fd` when provided.
+    #[inline]
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "needs to be in u32 for chrono"
+    )]
+    pub(crate) fn accessed_time_at(&self, opt_fd: Option<&FileDes>) -> Result<DateTime<Utc>> {
+        #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+        {
+            let statted = opt_fd.map_or_else(
+                || self.get_statx(libc::STATX_ATIME),
+                |fd| self.get_statxat(fd, libc::STATX_ATIME),
+            )?;
+            DateTime::from_timestamp(statted.stx_atime.tv_sec, statted.stx_atime.tv_nsec)
+                .ok_or(DirEntryError::TimeError)
+        }
+
+        #[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "android")))]
+        {
+            let statted = opt_fd.map_or_else(|| self.get_lstat(), |fd| self.get_lstatat(fd))?;
+
+            DateTime::from_timestamp(
+                access_stat!(statted, st_atime),
+                access_stat!(statted, st_atimensec),
+            )
+            .ok_or(DirEntryError::TimeError)
+        }
+    }
+
+    /// Returns the creation time in UTC, resolving relative to `opt_fd` when provided.
+    #[inline]
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "needs to be in u32 for chrono"
+    )]
+    pub(crate) fn created_time_at(&self, opt_fd: Option<&FileDes>) -> Result<DateTime<Utc>> {
+        #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+        {
+            let statted = opt_fd.map_or_else(
+                || self.get_statx(libc::STATX_BTIME),
+                |fd| self.get_statxat(fd, libc::STATX_BTIME),
+            )?;
+            if statted.stx_mask & libc::STATX_BTIME == 0 {
+                // The filesystem (or the lstat fallback) has no birth time
+                return Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into());
+            }
+            DateTime::from_timestamp(statted.stx_btime.tv_sec, statted.stx_btime.tv_nsec)
+                .ok_or(DirEntryError::TimeError)
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "netbsd"))]
+        {
+            let statted = opt_fd.map_or_else(|| self.get_lstat(), |fd| self.get_lstatat(fd))?;
+
+            #[cfg(target_os = "netbsd")]
+            let nanos = statted.st_birthtimensec;
+            #[cfg(not(target_os = "netbsd"))]
+            let nanos = statted.st_birthtime_nsec;
+
+            DateTime::from_timestamp(statted.st_birthtime as _, nanos as _)
+                .ok_or(DirEntryError::TimeError)
+        }
+
+        #[cfg(not(any(
+            all(target_os = "linux", target_env = "gnu"),
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        )))]
+        {
+            let _ = opt_fd;
+            Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+        }
     }
 
     /**
@@ -1723,4 +2269,63 @@ impl DirEntry {
     pub fn getdents(&self) -> Result<crate::fs::GetDents> {
         crate::fs::GetDents::new(self)
     }
+
+    /// Like [`getdents`](Self::getdents) but with a kernel buffer of `buffer_size` bytes,
+    /// used by the `Finder` when a custom buffer size has been configured.
+    #[inline]
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "macos",
+        target_os = "freebsd"
+    ))]
+    pub(crate) fn getdents_with_buffer_size(
+        &self,
+        buffer_size: usize,
+    ) -> Result<crate::fs::GetDents> {
+        crate::fs::GetDents::with_buffer_size(self, buffer_size)
+    }
+}
+
+/// Fills a `statx` from a `stat`, for kernels without `statx`
+#[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::useless_conversion,
+    clippy::unnecessary_cast,
+    reason = "the field widths differ between stat and statx (and between architectures)"
+)]
+pub(crate) fn statx_from_stat(statted: &stat) -> libc::statx {
+    let timestamp = |sec: i64, nsec: i64| {
+        // SAFETY: statx_timestamp is plain integers, for which all zeroes is valid
+        let mut timestamp: libc::statx_timestamp = unsafe { core::mem::zeroed() };
+        timestamp.tv_sec = sec;
+        timestamp.tv_nsec = nsec as u32;
+        timestamp
+    };
+
+    // SAFETY: statx is plain integers, for which all zeroes is valid
+    let mut converted: libc::statx = unsafe { core::mem::zeroed() };
+    converted.stx_mask = libc::STATX_BASIC_STATS;
+    converted.stx_blksize = statted.st_blksize as u32;
+    converted.stx_nlink = statted.st_nlink as u32;
+    converted.stx_uid = statted.st_uid;
+    converted.stx_gid = statted.st_gid;
+    converted.stx_mode = statted.st_mode as u16;
+    converted.stx_ino = statted.st_ino as u64;
+    converted.stx_size = statted.st_size as u64;
+    converted.stx_blocks = statted.st_blocks as u64;
+    converted.stx_atime = timestamp(statted.st_atime.into(), statted.st_atime_nsec.into());
+    converted.stx_mtime = timestamp(statted.st_mtime.into(), statted.st_mtime_nsec.into());
+    converted.stx_ctime = timestamp(statted.st_ctime.into(), statted.st_ctime_nsec.into());
+    converted.stx_rdev_major = libc::major(statted.st_rdev);
+    converted.stx_rdev_minor = libc::minor(statted.st_rdev);
+    converted.stx_dev_major = libc::major(statted.st_dev);
+    converted.stx_dev_minor = libc::minor(statted.st_dev);
+    converted
 }
@@ -0,0 +1,102 @@
+/*!
+ Bounds checks for the `dirent64` records returned by `getdents`/`getdirentries`.
+
+ [`GetDents`](crate::fs::GetDents) trusts the kernel for the layout of the records within a batch, these checks
+ make sure a malformed record (eg from a buggy FUSE or network filesystem) ends the batch instead of sending the
+ pointer arithmetic out of bounds. [`parse_dirents`] runs them over arbitrary bytes for fuzzing.
+*/
+
+use crate::{Unique, dirent64};
+use core::mem::offset_of;
+
+/// Offset from the start of a record to its name
+const NAME_OFFSET: usize = offset_of!(dirent64, d_name);
+
+/**
+ Returns the length of the record at `offset` if it lies within the first `len` bytes of `buffer` and is well formed:
+ 8 byte aligned, long enough to hold its header and a terminated name (which must fit in it on systems with
+ `d_namlen`).
+
+ # Safety
+ `buffer` must be aligned to 8 bytes and valid for reads of `len` bytes.
+*/
+#[inline]
+pub(crate) unsafe fn checked_reclen(buffer: *const u8, offset: usize, len: usize) -> Option<usize> {
+    if !offset.is_multiple_of(8) || len.checked_sub(offset)? <= NAME_OFFSET {
+        return None;
+    }
+    // SAFETY: the header lies within the buffer (checked above) and is aligned, as the buffer and offset are
+    let drnt = unsafe { Unique::new_unchecked(buffer.add(offset).cast::<dirent64>().cast_mut()) };
+    let reclen = drnt.d_reclen();
+    let fits = reclen > NAME_OFFSET && reclen.is_multiple_of(8) && reclen <= len - offset;
+    #[cfg(has_d_namlen)]
+    let fits = fits && drnt.d_namlen() < reclen - NAME_OFFSET;
+    fits.then_some(reclen)
+}
+
+/// A record read by [`parse_dirents`]
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedDirent {
+    pub inode: u64,
+    pub d_type: u8,
+    pub name: Box<[u8]>,
+}
+
+/**
+ Parses `buffer` as a batch of `dirent64` records, as returned by `getdents`, checking every bound along the way.
+
+ This is the entry point for fuzzing the record parsing (see `fuzz/` in the repository). Returns the offset of
+ the first malformed record, including any whose name isn't laid out the way the kernel does it: terminated, and
+ padded out to the next multiple of 8 bytes and no further (which the fast name length relies on).
+
+ # Panics
+ If the fast name length of a well formed record disagrees with a plain `strlen`, which is the bug being looked for.
+*/
+#[doc(hidden)]
+#[allow(clippy::missing_inline_in_public_items, clippy::missing_errors_doc)]
+pub fn parse_dirents(buffer: &[u8]) -> Result<Vec<ParsedDirent>, usize> {
+    // The kernel's buffers are aligned to 8 bytes, arbitrary input isn't
+    let mut aligned = vec![0u64; buffer.len().div_ceil(8)];
+    // SAFETY: `aligned` holds at least `buffer.len()` bytes, and is a separate allocation
+    unsafe {
+        buffer
+            .as_ptr()
+            .copy_to_nonoverlapping(aligned.as_mut_ptr().cast(), buffer.len());
+    }
+    let base: *const u8 = aligned.as_ptr().cast();
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < buffer.len() {
+        // SAFETY: `base` is aligned and valid for reads of `buffer.len()` bytes
+        let reclen = unsafe { checked_reclen(base, offset, buffer.len()) }.ok_or(offset)?;
+        let record = &buffer[offset..offset + reclen];
+        let name_len = record[NAME_OFFSET..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(offset)?;
+        if (NAME_OFFSET + name_len + 1).next_multiple_of(8) != reclen {
+            return Err(offset);
+        }
+
+        // SAFETY: the record was checked to lie within the buffer
+        let drnt = unsafe { Unique::new_unchecked(base.add(offset).cast::<dirent64>().cast_mut()) };
+        #[cfg(has_d_namlen)]
+        if drnt.d_namlen() != name_len {
+            return Err(offset);
+        }
+        assert_eq!(
+            drnt.name_length(),
+            name_len,
+            "fast name length disagrees at offset {offset}"
+        );
+        records.push(ParsedDirent {
+            inode: drnt.d_ino(),
+            d_type: drnt.d_type(),
+            name: drnt.d_name_slice().into(),
+        });
+        offset += reclen;
+    }
+    Ok(records)
+}
@@ -0,0 +1,171 @@
+use crate::fs::DirEntry;
+use core::fmt;
+
+/// How [`DisplayOptions`] quotes a path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuoteStyle {
+    /// The path as is
+    #[default]
+    None,
+    /// Wrapped in double quotes, like the CLI's `--quoted`
+    Double,
+}
+
+/// How [`DisplayOptions`] writes bytes which can't be shown as they are
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NameEncoding {
+    /// Invalid UTF-8 is replaced with `U+FFFD`, as by [`DirEntry::to_string_lossy`]
+    #[default]
+    Lossy,
+    /// As [`Lossy`](Self::Lossy), with control characters replaced by `?` like the CLI does on a terminal
+    Replaced,
+    /**
+     Invalid UTF-8 and control characters are written as escapes (`\xff`, `\n`, `\x1b`), so nothing is lost or
+     hidden. Backslashes are doubled, as are double quotes when quoting, so the original bytes can be recovered.
+    */
+    Escaped,
+}
+
+/**
+ Options for showing a [`DirEntry`] through [`DirEntry::display_with`].
+
+ The defaults match the plain `Display` implementation: the lossily converted path, unquoted and untruncated.
+
+ ```
+ use fdf::fs::{DirEntry, DisplayOptions, NameEncoding, QuoteStyle};
+
+ let entry = DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+ assert_eq!(
+     entry.display_with(DisplayOptions::new()).to_string(),
+     entry.to_string()
+ );
+
+ let options = DisplayOptions::new()
+     .quote(QuoteStyle::Double)
+     .encoding(NameEncoding::Escaped)
+     .max_width(Some(12));
+ let shown = entry.display_with(options).to_string();
+ assert!(shown.starts_with("\"…") && shown.ends_with('"'));
+ assert_eq!(shown.chars().count(), 12);
+ ```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DisplayOptions {
+    quote: QuoteStyle,
+    encoding: NameEncoding,
+    max_width: Option<usize>,
+}
+
+impl DisplayOptions {
+    /// The default options, see [`DisplayOptions`]
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            quote: QuoteStyle::None,
+            encoding: NameEncoding::Lossy,
+            max_width: None,
+        }
+    }
+
+    /// Set how the path is quoted, defaults to [`QuoteStyle::None`]
+    #[inline]
+    #[must_use]
+    pub const fn quote(mut self, quote: QuoteStyle) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Set how bytes which can't be shown as they are get written, defaults to [`NameEncoding::Lossy`]
+    #[inline]
+    #[must_use]
+    pub const fn encoding(mut self, encoding: NameEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /**
+    Set the most characters shown (including any quotes), defaults to unlimited
+
+    A longer path loses its start rather than its end, which is replaced by `…`, so the file name stays visible.
+    Characters are counted as `char`s, not terminal columns.
+    */
+    #[inline]
+    #[must_use]
+    pub const fn max_width(mut self, max_width: Option<usize>) -> Self {
+        self.max_width = max_width;
+        self
+    }
+}
+
+/// Shows a [`DirEntry`] with [`DisplayOptions`], returned by [`DirEntry::display_with`]
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayWith<'entry> {
+    pub(crate) entry: &'entry DirEntry,
+    pub(crate) options: DisplayOptions,
+}
+
+impl DisplayWith<'_> {
+    /// Writes the path, without quotes or truncation
+    fn write_path<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        let bytes = self.entry.as_bytes();
+        match self.options.encoding {
+            NameEncoding::Lossy => out.write_str(&String::from_utf8_lossy(bytes)),
+            NameEncoding::Replaced => String::from_utf8_lossy(bytes)
+                .chars()
+                .try_for_each(|ch| out.write_char(if ch.is_control() { '?' } else { ch })),
+            NameEncoding::Escaped => {
+                for chunk in bytes.utf8_chunks() {
+                    for ch in chunk.valid().chars() {
+                        match ch {
+                            '\\' => out.write_str("\\\\")?,
+                            '"' if self.options.quote == QuoteStyle::Double => {
+                                out.write_str("\\\"")?
+                            }
+                            '\n' => out.write_str("\\n")?,
+                            '\t' => out.write_str("\\t")?,
+                            '\r' => out.write_str("\\r")?,
+                            _ if ch.is_control() => write!(out, "\\x{:02x}", u32::from(ch))?,
+                            _ => out.write_char(ch)?,
+                        }
+                    }
+                    chunk
+                        .invalid()
+                        .iter()
+                        .try_for_each(|byte| write!(out, "\\x{byte:02x}"))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for DisplayWith<'_> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quote = match self.options.quote {
+            QuoteStyle::None => "",
+            QuoteStyle::Double => "\"",
+        };
+        let mut path = String::with_capacity(self.entry.len() + 2 * quote.len());
+        self.write_path(&mut path)?;
+
+        if let Some(max_width) = self.options.max_width {
+            let available = max_width.saturating_sub(2 * quote.len());
+            let length = path.chars().count();
+            if length > available {
+                // Keep the end, with the ellipsis taking the place of a character
+                let kept = available.saturating_sub(1);
+                let start = path
+                    .char_indices()
+                    .nth(length - kept)
+                    .map_or(path.len(), |(index, _)| index);
+                let ellipsis = if available == 0 { "" } else { "…" };
+                return f.pad(&[quote, ellipsis, &path[start..], quote].concat());
+            }
+        }
+        f.pad(&[quote, &path, quote].concat())
+    }
+}
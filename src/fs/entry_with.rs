@@ -0,0 +1,134 @@
+use crate::fs::DirEntry;
+
+/**
+ A [`DirEntry`] carrying a value computed for it, such as a hash, a score or a matched span.
+
+ This saves keeping a side table keyed by path when a pipeline computes something per entry.
+ The entry's methods are available directly through `Deref`.
+
+ ```
+ use fdf::fs::EntryWith;
+ use fdf::walk::Finder;
+
+ let mut sized: Vec<EntryWith<u64>> = Finder::init(env!("CARGO_MANIFEST_DIR"))
+     .build()
+     .unwrap()
+     .traverse()
+     .unwrap()
+     .filter(|entry| entry.is_regular_file())
+     .map(|entry| {
+         let size = entry.file_size().unwrap_or(0);
+         entry.with(size)
+     })
+     .collect();
+
+ sized.sort_by_key(|entry| core::cmp::Reverse(*entry.data()));
+ assert!(sized[0].data() >= sized[sized.len() - 1].data());
+ assert!(!sized[0].file_name().is_empty());
+ ```
+*/
+#[derive(Clone, Debug)]
+pub struct EntryWith<T> {
+    entry: DirEntry,
+    data: T,
+}
+
+impl<T> EntryWith<T> {
+    /// Attaches `data` to `entry`, see also [`DirEntry::with`]
+    #[inline]
+    #[must_use]
+    pub const fn new(entry: DirEntry, data: T) -> Self {
+        Self { entry, data }
+    }
+
+    /// Returns the entry
+    #[inline]
+    #[must_use]
+    pub const fn entry(&self) -> &DirEntry {
+        &self.entry
+    }
+
+    /// Returns the attached data
+    #[inline]
+    #[must_use]
+    pub const fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Returns the attached data mutably, eg to accumulate a score over several passes
+    #[inline]
+    #[must_use]
+    pub const fn data_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+
+    /// Replaces the attached data with the result of `map`, keeping the entry
+    #[inline]
+    #[must_use]
+    pub fn map<U, F: FnOnce(T) -> U>(self, map: F) -> EntryWith<U> {
+        EntryWith {
+            entry: self.entry,
+            data: map(self.data),
+        }
+    }
+
+    /// Splits into the entry and its data
+    #[inline]
+    #[must_use]
+    pub fn into_parts(self) -> (DirEntry, T) {
+        (self.entry, self.data)
+    }
+
+    /// Drops the attached data, returning the entry
+    #[inline]
+    #[must_use]
+    pub fn into_entry(self) -> DirEntry {
+        self.entry
+    }
+
+    /// Drops the entry, returning the attached data
+    #[inline]
+    #[must_use]
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
+
+impl<T> core::ops::Deref for EntryWith<T> {
+    type Target = DirEntry;
+
+    #[inline]
+    fn deref(&self) -> &DirEntry {
+        &self.entry
+    }
+}
+
+impl<T> AsRef<DirEntry> for EntryWith<T> {
+    #[inline]
+    fn as_ref(&self) -> &DirEntry {
+        &self.entry
+    }
+}
+
+impl<T> From<EntryWith<T>> for DirEntry {
+    #[inline]
+    fn from(entry: EntryWith<T>) -> Self {
+        entry.entry
+    }
+}
+
+/// Serialises as the entry's fields (see [`DirEntry`]'s implementation) alongside `data`
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for EntryWith<T> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+        let mut state = serializer.serialize_struct("EntryWith", 5)?;
+        state.serialize_field("path", &self.entry.to_string_lossy())?;
+        state.serialize_field("file_type", &self.entry.file_type)?;
+        state.serialize_field("depth", &self.entry.depth)?;
+        state.serialize_field("inode", &self.entry.inode)?;
+        state.serialize_field("data", &self.data)?;
+        state.end()
+    }
+}
@@ -0,0 +1,182 @@
+/*!
+ Injected syscall failures for testing, enabled by the `fault-injection` feature. Never enable it in a build
+ meant for real use.
+
+ Faults are scoped to a directory, so tests running in parallel (or the rest of the filesystem) are unaffected,
+ and only apply while the [`FaultGuard`] returned by [`Faults::install`] is alive. They make the rarely taken
+ paths deterministic: permission errors part way through a walk, and directories which take many
+ `getdents`/`getdirentries` calls to read (as seen on CIFS and some ZFS datasets), exercising the end of stream
+ handling.
+
+ # Examples
+ ```
+ use fdf::{fs::fault::Faults, walk::Finder};
+ use std::num::NonZeroUsize;
+
+ let root = env!("CARGO_MANIFEST_DIR");
+ let _guard = Faults::new(root).deny_open_every(NonZeroUsize::MIN).install();
+
+ // Every directory below the root fails to open with EACCES, so only the root's own entries are found
+ let finder = Finder::init(root).collect_errors(true).build().unwrap();
+ let found = finder.traverse().unwrap().filter(|entry| entry.depth() > 1).count();
+ assert_eq!(found, 0);
+ ```
+*/
+
+use crate::fs::{DirEntry, Result};
+use core::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use std::{
+    os::unix::ffi::OsStrExt as _,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// Every installed set of faults, checked by each directory open and read
+static INSTALLED: RwLock<Vec<Arc<Faults>>> = RwLock::new(Vec::new());
+
+/// The failures injected into the directories below a root, see the [module documentation](self)
+#[derive(Debug)]
+pub struct Faults {
+    root: Box<[u8]>,
+    deny_open_every: Option<NonZeroUsize>,
+    read_size: Option<usize>,
+    /// Directories below the root opened so far
+    opens: AtomicUsize,
+    /// Opens failed so far
+    denied: AtomicUsize,
+}
+
+impl Faults {
+    /// No faults yet, applying to the directories below `root` (but never `root` itself, so a walk from it starts)
+    #[inline]
+    #[must_use]
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().as_os_str().as_bytes().into(),
+            deny_open_every: None,
+            read_size: None,
+            opens: AtomicUsize::new(0),
+            denied: AtomicUsize::new(0),
+        }
+    }
+
+    /// Fail every `n`th directory opened with `EACCES`, counting opens in whatever order the walk makes them
+    #[inline]
+    #[must_use]
+    pub const fn deny_open_every(mut self, n: NonZeroUsize) -> Self {
+        self.deny_open_every = Some(n);
+        self
+    }
+
+    /**
+    Read directories `bytes` at a time (rounded up to hold at least one entry), so each takes many short reads
+    to get through.
+
+    This only applies to the `getdents`/`getdirentries` backends, `readdir` is left alone.
+    */
+    #[inline]
+    #[must_use]
+    pub const fn short_reads(mut self, bytes: usize) -> Self {
+        self.read_size = Some(bytes);
+        self
+    }
+
+    /// Starts injecting the faults, until the guard is dropped
+    #[inline]
+    #[must_use = "the faults are removed as soon as the guard is dropped"]
+    pub fn install(self) -> FaultGuard {
+        let faults = Arc::new(self);
+        INSTALLED
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::clone(&faults));
+        FaultGuard(faults)
+    }
+
+    /// Returns true if `dir` is strictly below the root
+    fn applies_to(&self, dir: &DirEntry) -> bool {
+        dir.as_bytes()
+            .strip_prefix(&*self.root)
+            .and_then(|rest| {
+                rest.strip_prefix(b"/")
+                    .or_else(|| self.root.ends_with(b"/").then_some(rest))
+            })
+            .is_some_and(|rest| !rest.is_empty())
+    }
+}
+
+/// Keeps a set of [`Faults`] installed, removing them when dropped
+#[derive(Debug)]
+pub struct FaultGuard(Arc<Faults>);
+
+impl FaultGuard {
+    /// The number of directory opens failed so far
+    #[inline]
+    #[must_use]
+    pub fn denied(&self) -> usize {
+        self.0.denied.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for FaultGuard {
+    #[inline]
+    fn drop(&mut self) {
+        INSTALLED
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|faults| !Arc::ptr_eq(faults, &self.0));
+    }
+}
+
+/// Calls `f` with each installed set of faults applying to `dir`
+fn for_each_applying(dir: &DirEntry, mut f: impl FnMut(&Faults)) {
+    INSTALLED
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|faults| faults.applies_to(dir))
+        .for_each(|faults| f(faults));
+}
+
+/// Fails with `EACCES` if an installed set of faults denies this open of `dir`
+pub(crate) fn before_open(dir: &DirEntry) -> Result<()> {
+    let mut deny = false;
+    for_each_applying(dir, |faults| {
+        let opened = faults.opens.fetch_add(1, Ordering::Relaxed) + 1;
+        if faults
+            .deny_open_every
+            .is_some_and(|n| opened.is_multiple_of(n.get()))
+        {
+            faults.denied.fetch_add(1, Ordering::Relaxed);
+            deny = true;
+        }
+    });
+    if deny {
+        return Err(std::io::Error::from_raw_os_error(libc::EACCES).into());
+    }
+    Ok(())
+}
+
+/// The kernel buffer size to read `dir` with, shrunk if an installed set of faults asks for short reads
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+pub(crate) fn read_size(dir: &DirEntry, buffer_size: usize) -> usize {
+    let mut size = buffer_size;
+    for_each_applying(dir, |faults| {
+        if let Some(bytes) = faults.read_size {
+            size = size.min(bytes.max(crate::fs::MIN_BUFFER_SIZE).next_multiple_of(8));
+        }
+    });
+    size
+}
@@ -4,7 +4,7 @@ use libc::{
     AT_SYMLINK_FOLLOW, AT_SYMLINK_NOFOLLOW, DT_BLK, DT_CHR, DT_DIR, DT_FIFO, DT_LNK, DT_REG,
     DT_SOCK, DT_UNKNOWN, S_IFMT, fstatat, mode_t,
 };
-use std::{os::unix::fs::FileTypeExt as _, path::Path};
+use std::{os::fd::AsRawFd as _, os::unix::fs::FileTypeExt as _, path::Path};
 
 /**
 Represents the type of a file in the filesystem
@@ -116,7 +116,13 @@ impl FileType {
     #[inline]
     #[must_use]
     pub fn from_fd_no_follow(fd: &FileDes, filename: &CStr) -> Self {
-        stat_syscall!(fstatat, fd.0, filename.as_ptr(), AT_SYMLINK_NOFOLLOW, DTYPE)
+        stat_syscall!(
+            fstatat,
+            fd.as_raw_fd(),
+            filename.as_ptr(),
+            AT_SYMLINK_NOFOLLOW,
+            DTYPE
+        )
     }
 
     /**
@@ -142,7 +148,13 @@ impl FileType {
     #[inline]
     #[must_use]
     pub fn from_fd_follow(fd: &FileDes, filename: &CStr) -> Self {
-        stat_syscall!(fstatat, fd.0, filename.as_ptr(), AT_SYMLINK_FOLLOW, DTYPE)
+        stat_syscall!(
+            fstatat,
+            fd.as_raw_fd(),
+            filename.as_ptr(),
+            AT_SYMLINK_FOLLOW,
+            DTYPE
+        )
     }
 
     /// Returns true if this represents a directory  (cost free check)
@@ -268,6 +280,40 @@ impl FileType {
     }
 }
 
+impl FileType {
+    /**
+    Returns a short machine readable name for the file type, as used by the JSON output
+
+    ```
+    use fdf::fs::FileType;
+    assert_eq!(FileType::RegularFile.as_str(), "regular_file");
+    assert_eq!(FileType::Directory.as_str(), "directory");
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match *self {
+            Self::BlockDevice => "block_device",
+            Self::CharDevice => "char_device",
+            Self::Directory => "directory",
+            Self::Pipe => "pipe",
+            Self::Symlink => "symlink",
+            Self::RegularFile => "regular_file",
+            Self::Socket => "socket",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for FileType {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 impl core::fmt::Display for FileType {
     #[allow(clippy::missing_inline_in_public_items)]
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
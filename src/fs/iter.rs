@@ -1,3 +1,4 @@
+use crate::fs::{DirEntry, FileDes, FileType, Result};
 #[cfg(any(
     target_os = "macos",
     target_os = "linux",
@@ -8,19 +9,22 @@
     target_os = "illumos",
     target_os = "solaris"
 ))]
-use crate::fs::types::SyscallBuffer;
-use crate::fs::{DirEntry, FileDes, FileType, Result};
+use crate::fs::{
+    dirent::checked_reclen,
+    types::{BUFFER_SIZE, MIN_BUFFER_SIZE, SyscallBuffer},
+};
 use crate::{Unique, dirent64, readdir64};
 use core::cell::Cell;
 use core::ffi::CStr;
 use core::mem::MaybeUninit;
 use core::ptr::NonNull;
 use libc::{AT_SYMLINK_NOFOLLOW, DIR, closedir, fstatat};
+use std::os::fd::AsRawFd as _;
 /**
  POSIX-compliant directory iterator using libc's readdir
 
  This iterator traverses directory entries using the standard POSIX directory
- reading API. It automatically skips "." and ".." entries and provides
+ reading API. It skips "." and ".." entries (unless `include_dot_entries` is set) and provides
  a safe Rust interface over the underlying C library functions.
 
 */
@@ -36,6 +40,8 @@ pub struct ReadDir {
     pub(crate) parent_depth: u32,
     /// The file descriptor of this directory, for use in calls like openat/statat etc.
     pub(crate) fd: FileDes,
+    /// Whether the iterator yields the "." and ".." entries, see `include_dot_entries`
+    pub(crate) include_dot_entries: bool,
 }
 
 impl ReadDir {
@@ -77,51 +83,51 @@ impl ReadDir {
 
     #[inline]
     pub(crate) fn new(dir_path: &DirEntry) -> Result<Self> {
-        let fd = dir_path.open()?; //read file descriptor
-        let (path_buffer, file_name_index) = Self::init_from_path(dir_path);
-        // Mutate the buffer to contain the full path, then add a null terminator and record the new length
-        // We use this length to index to get the filename (store full path -> index to get filename)
-
-        // SAFETY: the fd was opened  with `O_DIRECTORY`, this  is guaranteed to be valid.
-        let dir = unsafe { NonNull::new_unchecked(libc::fdopendir(fd.0)) };
-        debug_assert!(fd.is_open(), "We expect it to be open");
-
-        Ok(Self {
-            dir,
-            path_buffer,
-            file_name_index,
-            parent_depth: dir_path.depth, //inherit depth
-            fd,
-        })
+        Self::from_fd(dir_path.open()?, dir_path)
     }
 
     /// Constructs a `ReadDir` from a pre-opened file descriptor, skipping the `open()` call.
     ///
     /// Used when the caller already holds an fd obtained via `openat`, avoiding a second
     /// full-path resolution for the child directory.
+    ///
+    /// The `DIR` stream is given its own duplicate of `fd`, as `closedir` closes the descriptor it owns
+    /// and clones of [`dirfd`](Self::dirfd) may outlive the iterator.
     #[inline]
-    pub(crate) fn from_fd(fd: FileDes, dir_path: &DirEntry) -> Self {
-        let (path_buffer, file_name_index) = Self::init_from_path(dir_path);
-        // SAFETY: caller provides a valid directory fd; fdopendir takes ownership.
-        let dir = unsafe { NonNull::new_unchecked(libc::fdopendir(fd.0)) };
+    pub(crate) fn from_fd(fd: FileDes, dir_path: &DirEntry) -> Result<Self> {
         debug_assert!(fd.is_open(), "We expect it to be open");
-        Self {
+        // SAFETY: `fd` is an open directory
+        let stream_fd = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0) };
+        if stream_fd < 0 {
+            return_os_error!()
+        }
+        // SAFETY: `stream_fd` is an open directory, which the stream takes ownership of
+        let Some(dir) = NonNull::new(unsafe { libc::fdopendir(stream_fd) }) else {
+            let error = std::io::Error::last_os_error();
+            // SAFETY: fdopendir failed, so the duplicate is still ours to close
+            unsafe { libc::close(stream_fd) };
+            return Err(error.into());
+        };
+        // Mutate the buffer to contain the full path, then add a null terminator and record the new length
+        // We use this length to index to get the filename (store full path -> index to get filename)
+        let (path_buffer, file_name_index) = Self::init_from_path(dir_path);
+
+        Ok(Self {
             dir,
             path_buffer,
             file_name_index,
-            parent_depth: dir_path.depth,
+            parent_depth: dir_path.depth, //inherit depth
             fd,
-        }
+            include_dot_entries: false,
+        })
     }
 }
 
 impl Drop for ReadDir {
     /**
-    Closes the directory file descriptor to prevent resource leaks.
-
+    Closes the directory stream (and its duplicate of the descriptor) to prevent resource leaks.
 
-    File descriptors are limited system resources, so proper cleanup
-    is essential.
+    The descriptor returned by [`dirfd`](Self::dirfd) is closed separately, once its last clone is dropped.
     */
     #[inline]
     fn drop(&mut self) {
@@ -143,7 +149,7 @@ impl Drop for ReadDir {
     }
     // Basically fdsan shouts about a different object owning the fd, so we close via closedir.
     // This is because it's UB to close via file descriptor according to GNU docs, if that file descriptor
-    // was obtained from the `dirfd()`
+    // was obtained from the `dirfd()`, hence the stream having a duplicate of its own
 }
 
 /**
@@ -182,6 +188,7 @@ pub(crate) trait DirentConstructor {
             depth: self.parent_depth() + 1,
             file_name_index: self.file_index(),
             is_traversible_cache: Cell::new(None), // Lazy cache for traversal checks
+            device: Cell::new(None),
         }
     }
 
@@ -252,7 +259,7 @@ pub(crate) trait DirentConstructor {
         let file_type: FileType = match FileType::from_dtype(drnt.d_type()) {
             FileType::Unknown => stat_syscall!(
                 fstatat,
-                self.file_descriptor().0, //borrow before mutably borrowing the path buffer
+                self.file_descriptor().as_raw_fd(), //borrow before mutably borrowing the path buffer
                 d_name.cast(), //cast into i8 (depending on architecture, pointers are either i8/u8)
                 AT_SYMLINK_NOFOLLOW, // dont follow, to keep same semantics as readdir/getdents
                 DTYPE
@@ -263,7 +270,7 @@ pub(crate) trait DirentConstructor {
         #[cfg(not(has_d_type))] // Have to make a syscall on these systems alas
         let file_type = stat_syscall!(
             fstatat,
-            self.file_descriptor().0, //borrow before mutably borrowing the path buffer
+            self.file_descriptor().as_raw_fd(), //borrow before mutably borrowing the path buffer
             d_name.cast(), //cast into i8 (depending on architecture, pointers are either i8/u8)
             AT_SYMLINK_NOFOLLOW, // dont follow, to keep same semantics as readdir/getdents
             DTYPE
@@ -314,12 +321,16 @@ High-throughput directory iterator backed by `getdents` or `getdirentries`,
     target_os = "macos"
 ))]
 pub struct GetDents {
-    /// File descriptor of the open directory, wrapped in a `New Type`, does not implement Drop(maydo at later point),
-    /// The iterator closes the file descriptor upon this struct beying dropped.
+    /// File descriptor of the open directory, closed once the iterator and every clone of [`dirfd`](Self::dirfd)
+    /// have been dropped
     pub(crate) fd: FileDes,
     /// Kernel buffer for batch reading directory entries via system call I/O
-    /// typically using the best calculated  buffer sizes, optimised for typical directory traversal (derived from syscall tracing)
-    pub(crate) syscall_buffer: SyscallBuffer,
+    /// Defaults to the best calculated buffer size for the platform (derived from syscall tracing),
+    /// but can be overridden at runtime via [`FinderBuilder::getdents_buffer_size`](crate::walk::FinderBuilder::getdents_buffer_size)
+    /// Owned (freed on drop) and never smaller than [`Self::BUFFER_SIZE`], so it can always be viewed as a [`SyscallBuffer`]
+    pub(crate) syscall_buffer: Unique<[MaybeUninit<u64>]>,
+    /// Number of bytes of `syscall_buffer` handed to the kernel on each read
+    pub(crate) buffer_size: usize,
     /// buffer for constructing full entry paths
     /// Reused for each entry to avoid repeated memory allocation (only constructed once per dir)
     pub(crate) path_buffer: Vec<MaybeUninit<u8>>,
@@ -337,6 +348,8 @@ pub struct GetDents {
     pub(crate) remaining_bytes: usize,
     /// A marker for when the `FileDes` can give no more entries
     pub(crate) end_of_stream: bool,
+    /// Whether the iterator yields the "." and ".." entries, see `include_dot_entries`
+    pub(crate) include_dot_entries: bool,
     #[cfg(any(target_os = "freebsd", target_os = "macos"))] // TODO add dragonflyBSD here eventually
     /// The base pointer for the getdirentries call
     pub(crate) base_pointer: i64,
@@ -355,23 +368,33 @@ pub struct GetDents {
 impl GetDents {
     #[inline]
     #[rustfmt::skip]
-    /// Convenience function for pointer arithmetic on the buffer
-    pub(crate) const unsafe fn get_next_pointer(&mut self) -> Unique<dirent64> {
+    /// Convenience function for pointer arithmetic on the buffer, returns `None` (ending the stream) for a malformed record
+    pub(crate) unsafe fn get_next_pointer(&mut self) -> Option<Unique<dirent64>> {
         debug_assert!(
             self.offset.is_multiple_of(8),
             "offset should always be multiple of 8"
         );
-        // SAFETY:  internal use only, the `offset` parameter is always within bounds of the buffer.
+        // SAFETY: the buffer is aligned, and the kernel wrote its first `remaining_bytes`
+        let Some(reclen) = (unsafe {
+            checked_reclen(self.syscall_buffer.as_ptr().cast(), self.offset, self.remaining_bytes)
+        }) else {
+            // Nothing after a bad record can be trusted, so give up on the rest of the directory
+            self.end_of_stream = true;
+            self.remaining_bytes = 0;
+            return None;
+        };
+        // SAFETY: the record was checked to lie within the buffer
         let drnt = unsafe {
             Unique::new_unchecked(self.syscall_buffer.as_ptr()
+                    .cast::<u64>()
                     .byte_add(self.offset)
                     .cast::<dirent64>(),
             )
         };
         // increment the offset by the size of the dirent structure (reclen=size of dirent struct in bytes)
-        self.offset += drnt.d_reclen();
+        self.offset += reclen;
 
-        drnt
+        Some(drnt)
     }
 
     #[inline]
@@ -390,9 +413,40 @@ impl GetDents {
     so low-level callers can inspect buffer sizing or reuse it for diagnostics.
     The buffer remains owned by the iterator and is mutated whenever a new batch
     of directory entries is fetched.
+
+    When a [`buffer_size`](Self::buffer_size) other than [`Self::BUFFER_SIZE`] is in use, this is only the start of
+    the allocation (or extends past the part the kernel writes to), see [`kernel_buffer`](Self::kernel_buffer).
     */
     pub const fn syscall_buffer(&mut self) -> &mut SyscallBuffer {
-        &mut self.syscall_buffer
+        // SAFETY: the allocation is 8 byte aligned, at least `BUFFER_SIZE` bytes and owned by `self`
+        unsafe {
+            self.syscall_buffer
+                .as_non_null_ptr()
+                .cast::<SyscallBuffer>()
+                .as_mut()
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the part of the kernel I/O buffer actually passed to each read, [`buffer_size`](Self::buffer_size) bytes long.
+    pub const fn kernel_buffer(&mut self) -> &mut [MaybeUninit<u64>] {
+        // SAFETY: the allocation holds at least `buffer_size` bytes and is owned by `self`
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                self.syscall_buffer.as_non_null_ptr().cast().as_ptr(),
+                self.buffer_size / size_of::<u64>(),
+            )
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    /// Returns the size (in bytes) of the kernel buffer used by this iterator.
+    ///
+    /// This is [`Self::BUFFER_SIZE`] (rounded up to a whole number of pages) unless overridden via [`FinderBuilder::getdents_buffer_size`](crate::walk::FinderBuilder::getdents_buffer_size)
+    pub const fn buffer_size(&self) -> usize {
+        self.buffer_size
     }
 
     /// Convenience function to safe verbosity(+safety)
@@ -402,8 +456,7 @@ impl GetDents {
     #[must_use]
     #[cfg(has_eof_trick)]
     pub(crate) const fn syscall_buffer_ptr(&mut self) -> NonNull<u64> {
-        // SAFETY: never null
-        unsafe { NonNull::new_unchecked(self.syscall_buffer().as_mut_ptr()) }
+        self.syscall_buffer.as_non_null_ptr().cast()
     }
 
     #[inline]
@@ -425,16 +478,23 @@ impl GetDents {
      code is responsible for consuming the buffer and handling end-of-stream.
     */
     pub fn getdents(&mut self) -> isize {
+        let buffer_size = self.buffer_size();
+        let buffer = self.kernel_buffer().as_mut_ptr().cast();
         #[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
         {
-            self.syscall_buffer.getdents(&self.fd)
+            // SAFETY: we're passing a valid buffer of `buffer_size` bytes
+            unsafe { crate::util::getdents64(self.fd.as_raw_fd(), buffer, buffer_size) }
         }
         #[cfg(any(target_os = "macos", target_os = "freebsd"))]
         {
             //SAFETY: passing a valid buffer to an open file descriptor and base pointer
             unsafe {
-                self.syscall_buffer
-                    .getdirentries64(&self.fd, &mut self.base_pointer)
+                crate::util::getdirentries64(
+                    self.fd.as_raw_fd(),
+                    buffer,
+                    buffer_size,
+                    core::ptr::from_mut(&mut self.base_pointer),
+                )
             }
         }
     }
@@ -453,9 +513,13 @@ impl GetDents {
         self.end_of_stream
     }
 
-    /// A constant representing the maximum size of the internal Stack based buffer on this platform
+    /// A constant representing the default size of the internal buffer on this platform
     /// Differs per platform and in debug/release! Do not rely on this except if you're doing pointer arithmetic.
-    pub const BUFFER_SIZE: usize = SyscallBuffer::BUFFER_SIZE;
+    /// The size actually in use is returned by [`Self::buffer_size`].
+    pub const BUFFER_SIZE: usize = BUFFER_SIZE;
+
+    /// The smallest buffer size accepted, large enough to hold a single maximally sized dirent.
+    pub const MIN_BUFFER_SIZE: usize = MIN_BUFFER_SIZE;
 
     #[inline]
     #[allow(clippy::missing_assert_message)] // for cleaner code.
@@ -478,7 +542,7 @@ impl GetDents {
          Alignment of 8 => Alignment of 4 guaranteed invariant. */
         // SAFETY: see above
         let last_four_bytes: &mut MaybeUninit<u32> = unsafe {
-            self.syscall_buffer_ptr().byte_add(Self::BUFFER_SIZE - 4)
+            self.syscall_buffer_ptr().byte_add(self.buffer_size() - 4)
             .cast().as_mut()
         };
 
@@ -571,30 +635,34 @@ impl GetDents {
         }
         // We have data in buffer, get next entry
         // SAFETY: the buffer is not empty and therefore has remaining bytes to be read and is properly aligned
-        let drnt = unsafe { self.get_next_pointer() };
-
-        Some(drnt)
+        unsafe { self.get_next_pointer() }
     }
 
     #[inline]
     pub(crate) fn new(dir: &DirEntry) -> Result<Self> {
-        let fd = dir.open()?; //getting the file descriptor
-        debug_assert!(fd.is_open(), "We expect it to always be open");
+        Self::with_buffer_size(dir, crate::fs::limits::default_buffer_size())
+    }
 
-        let (path_buffer, file_name_index) = Self::init_from_path(dir);
+    /// Opens `dir` with a kernel buffer of `buffer_size` bytes.
+    ///
+    /// `buffer_size` must be a multiple of 8 and at least [`Self::MIN_BUFFER_SIZE`],
+    /// this is validated by [`FinderBuilder::build`](crate::walk::FinderBuilder::build).
+    #[inline]
+    pub(crate) fn with_buffer_size(dir: &DirEntry, buffer_size: usize) -> Result<Self> {
+        let fd = dir.open()?; //getting the file descriptor
+        Ok(Self::from_fd_with_buffer_size(fd, dir, buffer_size))
+    }
 
-        Ok(Self {
-            fd,
-            syscall_buffer: SyscallBuffer::new(),
-            path_buffer,
-            file_name_index,
-            parent_depth: dir.depth,
-            offset: 0,
-            remaining_bytes: 0,
-            end_of_stream: false,
-            #[cfg(any(target_os = "macos", target_os = "freebsd"))]
-            base_pointer: 0,
-        })
+    /// Allocates room for `buffer_size` bytes, and at least a whole [`SyscallBuffer`] for [`Self::syscall_buffer`]
+    #[inline]
+    fn allocate_buffer(buffer_size: usize) -> Unique<[MaybeUninit<u64>]> {
+        debug_assert!(
+            buffer_size.is_multiple_of(8) && buffer_size >= MIN_BUFFER_SIZE,
+            "buffer size should be validated before reaching here"
+        );
+        let buffer =
+            Box::<[u64]>::new_uninit_slice(buffer_size.max(BUFFER_SIZE) / size_of::<u64>());
+        Unique::from(Box::leak(buffer))
     }
 
     /// Constructs a `GetDents` from a pre-opened file descriptor, skipping the `open()` call.
@@ -604,17 +672,31 @@ impl GetDents {
     /// Used internally only due to non-enforceable invariants
     #[inline]
     pub(crate) fn from_fd(fd: FileDes, dir: &DirEntry) -> Self {
+        Self::from_fd_with_buffer_size(fd, dir, crate::fs::limits::default_buffer_size())
+    }
+
+    /// Like [`from_fd`](Self::from_fd) with a kernel buffer of `buffer_size` bytes, see [`with_buffer_size`](Self::with_buffer_size)
+    #[inline]
+    pub(crate) fn from_fd_with_buffer_size(
+        fd: FileDes,
+        dir: &DirEntry,
+        buffer_size: usize,
+    ) -> Self {
         debug_assert!(fd.is_open(), "We expect it to always be open");
+        #[cfg(feature = "fault-injection")]
+        let buffer_size = crate::fs::fault::read_size(dir, buffer_size);
         let (path_buffer, file_name_index) = Self::init_from_path(dir);
         Self {
             fd,
-            syscall_buffer: SyscallBuffer::new(),
+            syscall_buffer: Self::allocate_buffer(buffer_size),
+            buffer_size,
             path_buffer,
             file_name_index,
             parent_depth: dir.depth,
             offset: 0,
             remaining_bytes: 0,
             end_of_stream: false,
+            include_dot_entries: false,
             #[cfg(any(target_os = "macos", target_os = "freebsd"))]
             base_pointer: 0,
         }
@@ -633,25 +715,17 @@ impl GetDents {
 ))]
 impl Drop for GetDents {
     /**
-      Drops the iterator, closing the file descriptor.
-      we need to close the file descriptor when the iterator is dropped to avoid resource leaks.
+      Frees the kernel buffer, and checks nothing else closed the descriptor while the iterator held it, in debug builds.
+      The descriptor itself is closed by [`FileDes`] once its last clone is dropped.
     */
     #[inline]
     fn drop(&mut self) {
         debug_assert!(
             self.fd.is_open(),
-            "We expect the file descriptor to be open before closing"
+            "We expect the file descriptor to be open until it's dropped"
         );
-        // SAFETY: only closing HERE
-        #[cfg(not(debug_assertions))]
-        unsafe {
-            libc::close(self.fd.0)
-        };
-        // SAFETY: As above
-        #[cfg(debug_assertions)]
-        unsafe {
-            assert!(libc::close(self.fd.0) == 0, "fd was not closed in getdents")
-        }
+        // SAFETY: the buffer was leaked from a `Box` in `allocate_buffer` and nothing else frees it
+        drop(unsafe { Box::from_raw(self.syscall_buffer.as_non_null_ptr().as_ptr()) });
     }
 }
 
@@ -664,8 +738,10 @@ macro_rules! impl_iterator_public_methods {
             #[inline]
             fn next(&mut self) -> Option<Self::Item> {
                 while let Some(drnt) = self.get_next_entry() {
-                    skip_dot_or_dot_dot_entries!(drnt.as_ptr(), continue);
-                    // this just skips dot entries in a really efficient manner(avoids strlen) by checking dtype first on most OS'es
+                    if !self.include_dot_entries {
+                        skip_dot_or_dot_dot_entries!(drnt.as_ptr(), continue);
+                        // this just skips dot entries in a really efficient manner(avoids strlen) by checking dtype first on most OS'es
+                    }
                     return Some(self.construct_direntry(drnt));
                 }
                 None // signal end
@@ -676,7 +752,8 @@ macro_rules! impl_iterator_public_methods {
             /**
             Returns the file descriptor for this directory.
 
-            Useful for operations that need the raw directory FD.
+            Useful for operations that need the raw directory FD. Clone it to keep the directory open after the
+            iterator has been dropped.
 
             */
             #[inline]
@@ -685,6 +762,19 @@ macro_rules! impl_iterator_public_methods {
                 &self.fd
             }
 
+            /**
+            Sets whether the "." and ".." entries are yielded, they are skipped by default.
+
+            Useful for callers that want to inspect the directory itself through its entries, eg measuring link counts.
+            The yielded paths are `dir/.` and `dir/..`, with a depth one greater than the directory.
+            */
+            #[inline]
+            #[must_use]
+            pub const fn include_dot_entries(mut self, include: bool) -> Self {
+                self.include_dot_entries = include;
+                self
+            }
+
             #[inline]
             /**
              Constructs a `DirEntry` from a directory entry pointer.
@@ -0,0 +1,102 @@
+/*!
+ Limits of the machine actually running fdf, derived lazily with `sysconf`/`pathconf`.
+
+ Deriving these when fdf is built gets them wrong for a binary built on one machine and run on another (or cross
+ compiled), eg a kernel with 16KiB pages. Each is queried once on first use, falling back to a compile time value
+ if the query fails. Sizes which shape types (eg [`SyscallBuffer`](crate::fs::SyscallBuffer)) stay constants.
+*/
+
+use core::ffi::c_int;
+use core::num::NonZeroUsize;
+use std::sync::OnceLock;
+
+/// Used if `pathconf(_PC_NAME_MAX)` fails, the limit of practically every filesystem (POSIX only guarantees 14!)
+const FALLBACK_NAME_MAX: usize = 255;
+/// Used if `pathconf(_PC_PATH_MAX)` fails
+#[cfg(not(target_os = "hurd"))]
+const FALLBACK_PATH_MAX: usize = libc::PATH_MAX as _;
+/// The Hurd has no limit on the length of a path
+#[cfg(target_os = "hurd")]
+const FALLBACK_PATH_MAX: usize = usize::MAX;
+/// Used if `sysconf(_SC_PAGESIZE)` fails
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+const FALLBACK_PAGE_SIZE: usize = 4096;
+
+/// Queries a `pathconf` limit of the root filesystem, `None` if it fails or there's no limit
+fn root_pathconf(name: c_int) -> Option<usize> {
+    // SAFETY: the path is NUL terminated
+    let limit = unsafe { libc::pathconf(c"/".as_ptr(), name) };
+    usize::try_from(limit).ok().filter(|&limit| limit != 0)
+}
+
+/// Longest file name (in bytes) the root filesystem accepts
+pub(crate) fn name_max() -> usize {
+    static NAME_MAX: OnceLock<usize> = OnceLock::new();
+    *NAME_MAX.get_or_init(|| root_pathconf(libc::_PC_NAME_MAX).unwrap_or(FALLBACK_NAME_MAX))
+}
+
+/// Longest path (in bytes, including the null terminator) the path based syscalls accept
+pub(crate) fn path_max() -> usize {
+    static PATH_MAX: OnceLock<usize> = OnceLock::new();
+    *PATH_MAX.get_or_init(|| root_pathconf(libc::_PC_PATH_MAX).unwrap_or(FALLBACK_PATH_MAX))
+}
+
+/// Size of a page of memory
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+pub(crate) fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE.get_or_init(|| {
+        // SAFETY: trivially safe
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        usize::try_from(size)
+            .ok()
+            .filter(|size| size.is_power_of_two())
+            .unwrap_or(FALLBACK_PAGE_SIZE)
+    })
+}
+
+/// Number of threads the process can run in parallel, at least one
+///
+/// Cached, as [`std::thread::available_parallelism`] reads the cgroup limits every time on Linux.
+pub(crate) fn cpu_count() -> NonZeroUsize {
+    static CPU_COUNT: OnceLock<NonZeroUsize> = OnceLock::new();
+    *CPU_COUNT.get_or_init(|| std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+}
+
+/**
+ Default size (in bytes) of the kernel buffer for `getdents`/`getdirentries`.
+
+ The platform's [`BUFFER_SIZE`](crate::fs::BUFFER_SIZE), rounded up to a whole number of pages: each directory
+ read allocates its buffer, and an allocation this large is served in pages anyway.
+*/
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+pub(crate) fn default_buffer_size() -> usize {
+    crate::fs::BUFFER_SIZE.next_multiple_of(page_size())
+}
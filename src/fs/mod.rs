@@ -1,11 +1,51 @@
+mod anomaly;
 mod buffer;
 mod dir_entry;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+mod dirent;
+mod display;
+mod entry_with;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 mod file_type;
 mod iter;
+pub(crate) mod limits;
+pub mod mounts;
+mod portable;
+mod realpath_cache;
 mod types;
 
+pub use anomaly::Anomaly;
 pub use buffer::{AlignedBuffer, ValueType};
 pub use dir_entry::DirEntry;
+#[cfg(all(
+    test,
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "android")
+))]
+pub(crate) use dir_entry::statx_from_stat;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+#[doc(hidden)]
+pub use dirent::{ParsedDirent, parse_dirents};
+pub use display::{DisplayOptions, DisplayWith, NameEncoding, QuoteStyle};
+pub use entry_with::EntryWith;
 pub use file_type::FileType;
 #[cfg(any(
     target_os = "linux",
@@ -19,7 +59,9 @@ pub use file_type::FileType;
 ))]
 pub use iter::GetDents;
 pub use iter::ReadDir;
-pub use types::{FileDes, Result};
+pub(crate) use portable::PortableReadDir;
+pub use realpath_cache::RealpathCache;
+pub use types::{FileDes, MAX_SYMLINK_HOPS, Result};
 
 #[cfg(any(
     target_os = "linux",
@@ -31,4 +73,4 @@ pub use types::{FileDes, Result};
     target_os = "macos",
     target_os = "freebsd"
 ))]
-pub use types::SyscallBuffer;
+pub use types::{BUFFER_SIZE, MIN_BUFFER_SIZE, SyscallBuffer};
@@ -0,0 +1,290 @@
+/*!
+ Information about mounted filesystems.
+
+ On Linux/Android this is read from `/proc/self/mountinfo`, on macOS, FreeBSD and OpenBSD from `getmntinfo`.
+ Other platforms currently report no mounts.
+*/
+
+use crate::fs::DirEntry;
+use std::io;
+use std::path::PathBuf;
+
+/**
+ Filesystem types whose contents are generated by the kernel rather than stored anywhere, eg `/proc` and `/sys`.
+
+ Walking them is slow, can block (eg `/proc/kcore`, `/sys/kernel/debug`) and only finds files describing the
+ running system. `tmpfs` isn't among them, it holds real files.
+*/
+pub const VIRTUAL_FS_TYPES: &[&str] = &[
+    // Linux
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "pstore",
+    "bpf",
+    "configfs",
+    "fusectl",
+    "mqueue",
+    "hugetlbfs",
+    "binfmt_misc",
+    "efivarfs",
+    "selinuxfs",
+    "rpc_pipefs",
+    "nsfs",
+    // The BSDs and macOS
+    "devfs",
+    "procfs",
+    "fdescfs",
+    "linprocfs",
+    "linsysfs",
+    "kernfs",
+    "ptyfs",
+];
+
+/// A mounted filesystem
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MountPoint {
+    /// The device ID of the mounted filesystem, as found in `st_dev`
+    pub device: u64,
+    /// Where the filesystem is mounted
+    pub path: PathBuf,
+    /// The filesystem type, eg `ext4`, `apfs` or `proc`
+    pub fs_type: Box<str>,
+}
+
+impl MountPoint {
+    /// Returns true if the filesystem is one of the [`VIRTUAL_FS_TYPES`]
+    #[inline]
+    #[must_use]
+    pub fn is_virtual(&self) -> bool {
+        VIRTUAL_FS_TYPES.contains(&&*self.fs_type)
+    }
+}
+
+/**
+ Returns every mounted filesystem, in the order the kernel lists them (parents before children).
+
+ Bind mounts appear as additional entries sharing a device ID.
+
+ # Errors
+ Returns an error if the mount table can't be read.
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn mount_points() -> io::Result<Vec<MountPoint>> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        Ok(parse_mountinfo(&std::fs::read("/proc/self/mountinfo")?))
+    }
+    #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+    {
+        mount_info()
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    )))]
+    {
+        Ok(Vec::new())
+    }
+}
+
+/**
+ Returns the device IDs of the mounted [virtual](MountPoint::is_virtual) filesystems, sorted.
+
+ # Errors
+ Returns an error if the mount table can't be read.
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn virtual_devices() -> io::Result<Vec<u64>> {
+    let mut devices: Vec<u64> = mount_points()?
+        .into_iter()
+        .filter(MountPoint::is_virtual)
+        .map(|mount| mount.device)
+        .collect();
+    devices.sort_unstable();
+    devices.dedup();
+    Ok(devices)
+}
+
+/// Pseudo filesystem magic numbers (`f_type`) reported by Linux's `statfs`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // the magic numbers are all 32 bit
+const PSEUDO_FS_MAGICS: &[u32] = &[
+    libc::PROC_SUPER_MAGIC as u32,
+    libc::SYSFS_MAGIC as u32,
+    libc::DEVPTS_SUPER_MAGIC as u32,
+    libc::CGROUP_SUPER_MAGIC as u32,
+    libc::CGROUP2_SUPER_MAGIC as u32,
+    libc::DEBUGFS_MAGIC as u32,
+    libc::TRACEFS_MAGIC as u32,
+    libc::SECURITYFS_MAGIC as u32,
+    libc::SELINUX_MAGIC as u32,
+    libc::BPF_FS_MAGIC as u32,
+    libc::NSFS_MAGIC as u32,
+    libc::HUGETLBFS_MAGIC as u32,
+];
+
+/**
+ Returns true if `dir` is on a pseudo filesystem, eg `/proc`, `/sys` or `/dev`, judged by `statfs`.
+
+ On Linux this is the filesystem's magic number, except that `devtmpfs` reports itself as `tmpfs`, so a `tmpfs`
+ directory counts if the mount table lists its device as `devtmpfs`. On macOS, FreeBSD and OpenBSD it's the
+ filesystem's type name, checked against [`VIRTUAL_FS_TYPES`]. Returns false if `dir` can't be `statfs`ed.
+*/
+pub(crate) fn is_pseudo_fs(dir: &DirEntry) -> bool {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut buf = core::mem::MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: the path is NUL terminated and the buffer is valid for writes
+        if unsafe { libc::statfs(dir.as_ptr(), buf.as_mut_ptr()) } != 0 {
+            return false;
+        }
+        // SAFETY: `statfs` succeeded, so it filled in the buffer
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // see above
+        let magic = unsafe { buf.assume_init() }.f_type as u32;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        if magic == libc::TMPFS_MAGIC as u32 {
+            static DEVTMPFS: std::sync::OnceLock<Box<[u64]>> = std::sync::OnceLock::new();
+            let devices = DEVTMPFS.get_or_init(|| {
+                mount_points()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|mount| &*mount.fs_type == "devtmpfs")
+                    .map(|mount| mount.device)
+                    .collect()
+            });
+            return !devices.is_empty() && dir.dev().is_ok_and(|device| devices.contains(&device));
+        }
+        PSEUDO_FS_MAGICS.contains(&magic)
+    }
+    #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+    {
+        let mut buf = core::mem::MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: the path is NUL terminated and the buffer is valid for writes
+        if unsafe { libc::statfs(dir.as_ptr(), buf.as_mut_ptr()) } != 0 {
+            return false;
+        }
+        // SAFETY: `statfs` succeeded, so it filled in the buffer
+        let buf = unsafe { buf.assume_init() };
+        // SAFETY: the kernel NUL terminates the name
+        let fs_type = unsafe { core::ffi::CStr::from_ptr(buf.f_fstypename.as_ptr()) };
+        fs_type
+            .to_str()
+            .is_ok_and(|fs_type| VIRTUAL_FS_TYPES.contains(&fs_type))
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "openbsd"
+    )))]
+    {
+        let _ = dir;
+        false
+    }
+}
+
+/// Returns the path of the first mount (ie not a bind mount) of `device`, if known
+#[must_use]
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn mount_point_of(mounts: &[MountPoint], device: u64) -> Option<&MountPoint> {
+    mounts.iter().find(|mount| mount.device == device)
+}
+
+/// Parses the mountinfo format, see `proc_pid_mountinfo(5)`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn parse_mountinfo(contents: &[u8]) -> Vec<MountPoint> {
+    use std::os::unix::ffi::OsStringExt as _;
+
+    contents
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| {
+            // mount ID, parent ID, major:minor, root, mount point, optional fields..., -, type, ...
+            let mut fields = line.split(|&byte| byte == b' ');
+            let device = fields.nth(2)?;
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.skip_while(|&field| field != b"-").nth(1)?;
+
+            let (major, minor) = core::str::from_utf8(device).ok()?.split_once(':')?;
+            let device = libc::makedev(major.parse().ok()?, minor.parse().ok()?);
+
+            Some(MountPoint {
+                #[allow(clippy::useless_conversion)] // dev_t isn't u64 everywhere
+                device: u64::from(device),
+                path: PathBuf::from(std::ffi::OsString::from_vec(unescape_octal(mount_point))),
+                fs_type: String::from_utf8_lossy(&unescape_octal(fs_type)).into(),
+            })
+        })
+        .collect()
+}
+
+/// Decodes the `\040` style escapes used for spaces, tabs, newlines and backslashes in mount paths
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unescape_octal(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut index = 0;
+    while let Some(&byte) = field.get(index) {
+        let escaped = field
+            .get(index + 1..index + 4)
+            .filter(|digits| byte == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)))
+            .map(|digits| digits.iter().fold(0u8, |acc, d| (acc << 3) | (d - b'0')));
+
+        if let Some(decoded) = escaped {
+            out.push(decoded);
+            index += 4;
+        } else {
+            out.push(byte);
+            index += 1;
+        }
+    }
+    out
+}
+
+/// Reads the mount table with `getmntinfo`, statting each mount point for its device ID
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn mount_info() -> io::Result<Vec<MountPoint>> {
+    use core::ffi::CStr;
+    use std::os::unix::{ffi::OsStrExt as _, fs::MetadataExt as _};
+
+    let mut table: *mut libc::statfs = core::ptr::null_mut();
+    // SAFETY: `table` is valid for writes, libc owns the buffer it points it to
+    let count = unsafe { libc::getmntinfo(&raw mut table, libc::MNT_NOWAIT) };
+    let Ok(count @ 1..) = usize::try_from(count) else {
+        return Err(io::Error::last_os_error());
+    };
+    // SAFETY: `getmntinfo` filled in `count` entries
+    let table = unsafe { core::slice::from_raw_parts(table, count) };
+
+    Ok(table
+        .iter()
+        .filter_map(|mount| {
+            // SAFETY: both names are NUL terminated by the kernel
+            let (path, fs_type) = unsafe {
+                (
+                    CStr::from_ptr(mount.f_mntonname.as_ptr()),
+                    CStr::from_ptr(mount.f_fstypename.as_ptr()),
+                )
+            };
+            let path = PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes()));
+            // A mount which can't be statted (eg a stale network mount) can't be matched to entries anyway
+            let device = std::fs::metadata(&path).ok()?.dev();
+            Some(MountPoint {
+                #[allow(clippy::useless_conversion)] // dev_t isn't u64 everywhere
+                device: u64::from(device),
+                path,
+                fs_type: fs_type.to_string_lossy().into(),
+            })
+        })
+        .collect())
+}
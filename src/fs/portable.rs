@@ -0,0 +1,93 @@
+/*!
+ The strictly POSIX directory reader behind [`Backend::Portable`](crate::walk::Backend::Portable).
+
+ Directories are opened with `opendir` by their full path and read with plain `readdir`, and every entry is
+ `lstat`ed for its type and inode, as `d_type` isn't part of POSIX. No raw syscalls, descriptor tricks or
+ platform specific symbols are involved, so it works wherever libc does, only slower.
+*/
+
+use crate::fs::{DirEntry, FileType, Result};
+use core::{cell::Cell, ffi::CStr, mem::MaybeUninit, ptr::NonNull};
+use std::ffi::CString;
+
+/// Reads a directory with `opendir`/`readdir`, `lstat`ing each entry
+#[derive(Debug)]
+pub(crate) struct PortableReadDir {
+    dir: NonNull<libc::DIR>,
+    /// The directory's path and a trailing slash, each entry's name is appended to it
+    base: Vec<u8>,
+    parent_depth: u32,
+}
+
+impl PortableReadDir {
+    /// Opens `dir` by its full path
+    pub(crate) fn new(dir: &DirEntry) -> Result<Self> {
+        #[cfg(feature = "fault-injection")]
+        crate::fs::fault::before_open(dir)?;
+
+        // SAFETY: the path is NUL terminated
+        let Some(stream) = NonNull::new(unsafe { libc::opendir(dir.as_ptr()) }) else {
+            return_os_error!()
+        };
+        let mut base = dir.as_bytes().to_vec();
+        if !base.ends_with(b"/") {
+            base.push(b'/');
+        }
+        Ok(Self {
+            dir: stream,
+            base,
+            parent_depth: dir.depth,
+        })
+    }
+}
+
+impl Iterator for PortableReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<DirEntry> {
+        loop {
+            // SAFETY: the stream is open until the iterator is dropped
+            let drnt = NonNull::new(unsafe { libc::readdir(self.dir.as_ptr()) })?;
+            // SAFETY: `readdir` returned a valid entry, whose name is NUL terminated
+            let name = unsafe { CStr::from_ptr((*drnt.as_ptr()).d_name.as_ptr()) }.to_bytes();
+            if name == b"." || name == b".." {
+                continue;
+            }
+
+            let mut path = Vec::with_capacity(self.base.len() + name.len() + 1);
+            path.extend_from_slice(&self.base);
+            path.extend_from_slice(name);
+            // SAFETY: a name never contains a NUL
+            let path = unsafe { CString::from_vec_unchecked(path) };
+
+            let mut stat = MaybeUninit::<libc::stat>::uninit();
+            // SAFETY: the path is NUL terminated and `stat` is valid for writes
+            let (file_type, inode): (FileType, u64) =
+                if unsafe { libc::lstat(path.as_ptr(), stat.as_mut_ptr()) } == 0 {
+                    // SAFETY: `lstat` succeeded, so it filled in `stat`
+                    let stat = unsafe { stat.assume_init() };
+                    (FileType::from(stat), access_stat!(stat, st_ino))
+                } else {
+                    // Removed since it was listed, it's still reported like `readdir` would
+                    (FileType::Unknown, 0)
+                };
+
+            return Some(DirEntry {
+                path: path.into_boxed_c_str(),
+                file_type,
+                inode,
+                depth: self.parent_depth + 1,
+                file_name_index: self.base.len(),
+                is_traversible_cache: Cell::new(None),
+                device: Cell::new(None),
+            });
+        }
+    }
+}
+
+impl Drop for PortableReadDir {
+    fn drop(&mut self) {
+        // SAFETY: the stream is only closed here
+        unsafe { libc::closedir(self.dir.as_ptr()) };
+    }
+}
@@ -0,0 +1,144 @@
+use crate::fs::{DirEntry, Result};
+use core::cell::Cell;
+use dashmap::DashMap;
+use std::ffi::{CStr, CString};
+use std::sync::Arc;
+
+/**
+ Canonicalises entries like [`DirEntry::to_full_path`], resolving each parent directory only once.
+
+ `realpath` walks and `lstat`s every component of a path, so calling it for every result of a large search
+ repeats the same work for every child of a directory. This cache resolves each distinct parent directory
+ once, and derives the full path of its (non symlink) children by concatenation. Symlinks are still resolved
+ individually since their targets can be anywhere.
+
+ The cache is shared safely between threads. Resolved directories are assumed not to move while it's in use,
+ [`clear`](Self::clear) it if they might have.
+
+ ```
+ use fdf::fs::{DirEntry, RealpathCache};
+ use std::fs;
+ use std::os::unix::fs::symlink;
+
+ let dir = std::env::temp_dir().join("fdf_realpath_cache_doctest");
+ let _ = fs::remove_dir_all(&dir);
+ fs::create_dir_all(dir.join("real")).unwrap();
+ fs::write(dir.join("real/a.txt"), "").unwrap();
+ fs::write(dir.join("real/b.txt"), "").unwrap();
+ symlink(dir.join("real"), dir.join("alias")).unwrap();
+
+ let cache = RealpathCache::new();
+ let real = fs::canonicalize(dir.join("real")).unwrap();
+ for name in ["a.txt", "b.txt"] {
+     let entry = DirEntry::new(dir.join("alias").join(name)).unwrap();
+     let resolved = cache.to_full_path(&entry).unwrap();
+     assert_eq!(resolved.as_path(), real.join(name));
+     assert_eq!(resolved.file_name(), name.as_bytes());
+ }
+ // Both files shared the one resolved parent
+ assert_eq!(cache.len(), 1);
+ fs::remove_dir_all(&dir).unwrap();
+ ```
+*/
+#[derive(Debug, Default)]
+pub struct RealpathCache {
+    /// Parent directory as it appears in entries, to its canonical path
+    dirs: DashMap<Box<[u8]>, Arc<[u8]>>,
+}
+
+impl RealpathCache {
+    /// Creates an empty cache
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     Returns the canonical form of `entry`, the same as [`DirEntry::to_full_path`] would.
+
+     # Errors
+     Returns an error if the entry (or its parent directory) can't be resolved, see [`DirEntry::to_full_path`].
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn to_full_path(&self, entry: &DirEntry) -> Result<DirEntry> {
+        let path = entry.as_bytes();
+        let name = entry.file_name();
+        // Symlinks resolve elsewhere, and `.`/`..` (or a bare name with no parent) don't name a child of the prefix
+        if entry.is_symlink() || entry.file_name_index == 0 || matches!(name, b"." | b".." | b"") {
+            return entry.to_full_path();
+        }
+
+        let parent = &path[..entry.file_name_index];
+        let parent = if parent.len() > 1 {
+            parent.strip_suffix(b"/").unwrap_or(parent)
+        } else {
+            parent
+        };
+        let resolved_parent = match self.dirs.get(parent) {
+            Some(resolved) => Arc::clone(&resolved),
+            None => {
+                let resolved = realpath(parent)?;
+                self.dirs.insert(parent.into(), Arc::clone(&resolved));
+                resolved
+            }
+        };
+
+        let mut full_path = Vec::with_capacity(resolved_parent.len() + name.len() + 2);
+        full_path.extend_from_slice(&resolved_parent);
+        if full_path.last() != Some(&b'/') {
+            full_path.push(b'/');
+        }
+        let file_name_index = full_path.len();
+        full_path.extend_from_slice(name);
+
+        Ok(DirEntry {
+            path: CString::new(full_path)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?
+                .into_boxed_c_str(),
+            file_type: entry.file_type,
+            inode: entry.inode,
+            depth: entry.depth,
+            file_name_index,
+            is_traversible_cache: Cell::new(entry.is_traversible_cache.get()),
+            device: Cell::new(entry.device.get()),
+        })
+    }
+
+    /// Returns the number of directories resolved so far
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// Returns true if no directories have been resolved yet
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    /// Forgets every resolved directory
+    #[inline]
+    pub fn clear(&self) {
+        self.dirs.clear();
+    }
+}
+
+/// Canonicalises a directory path with `realpath`
+fn realpath(dir: &[u8]) -> Result<Arc<[u8]>> {
+    let dir =
+        CString::new(dir).map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+    // SAFETY: the path is null terminated, and realpath allocates the result when given a null buffer
+    let ptr = unsafe { libc::realpath(dir.as_ptr(), core::ptr::null_mut()) };
+    if ptr.is_null() {
+        return_os_error!()
+    }
+
+    // SAFETY: realpath succeeded so ptr is a valid null terminated string
+    let resolved = Arc::from(unsafe { CStr::from_ptr(ptr) }.to_bytes());
+    // SAFETY: ptr was allocated by realpath and isn't used after this
+    unsafe { libc::free(ptr.cast()) };
+    Ok(resolved)
+}
@@ -1,8 +1,13 @@
 use crate::DirEntryError;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::sync::Arc;
 
 ///Generic result type for directory entry operations
 pub type Result<T> = core::result::Result<T, DirEntryError>;
 
+/// Default number of hops [`DirEntry::resolve_chain`](crate::fs::DirEntry::resolve_chain) follows, matching Linux's `MAXSYMLINKS`
+pub const MAX_SYMLINK_HOPS: usize = 40;
+
 /// A buffer used to  hold the bytes sent from the OS for `getdents`/`getdirentries` calls
 #[cfg(any(
     target_os = "linux",
@@ -19,17 +24,67 @@ pub type Result<T> = core::result::Result<T, DirEntryError>;
 /// An aligned(to 8 bytes) stack allocated buffer of [`MaybeUninit`]
 pub type SyscallBuffer = crate::fs::AlignedBuffer<u64, { BUFFER_SIZE / size_of::<u64>() }>;
 
-/// A safe abstraction around file descriptors for internal IO
-#[derive(Debug)]
+/**
+ An open file descriptor, closed once the last clone is dropped.
+
+ Clones share the one descriptor (behind an `Arc`) rather than duplicating it, so a clone taken from an
+ iterator's `dirfd()` stays open after the iterator itself is dropped, and the number can't be reused by
+ another open in the meantime.
+
+ ```
+ use fdf::fs::DirEntry;
+
+ let dir = DirEntry::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+ let entries = dir.readdir().unwrap();
+ let fd = entries.dirfd().clone();
+ drop(entries);
+
+ // Still ours to use
+ assert!(fd.is_open());
+ let manifest = dir.readdir().unwrap().find(|entry| entry.file_name() == b"Cargo.toml").unwrap();
+ assert!(manifest.get_lstatat(&fd).is_ok());
+ ```
+*/
+#[derive(Debug, Clone)]
 #[repr(transparent)]
-pub struct FileDes(pub(crate) i32);
+pub struct FileDes(Arc<OwnedDescriptor>);
+
+/// The descriptor shared by every clone of a [`FileDes`], closed on drop
+#[derive(Debug)]
+struct OwnedDescriptor(i32);
+
+impl Drop for OwnedDescriptor {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the descriptor is owned by this value alone, so it's open and closed exactly once
+        let closed = unsafe { libc::close(self.0) };
+        // EBADF here means something else closed it from under us, which is a bug
+        debug_assert!(
+            closed == 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::EBADF),
+            "file descriptor {} was closed by someone else",
+            self.0
+        );
+    }
+}
 
 impl FileDes {
+    /**
+     Takes ownership of an open descriptor, which is closed once the last clone is dropped.
+
+     # Safety
+     `fd` must be open, and nothing else may close it.
+    */
+    #[inline]
+    pub(crate) unsafe fn from_raw(fd: i32) -> Self {
+        debug_assert!(fd >= 0, "expected an open file descriptor, got {fd}");
+        Self(Arc::new(OwnedDescriptor(fd)))
+    }
+
     /// Returns a borrowed reference to the underlying file descriptor.
     #[must_use]
     #[inline]
-    pub const fn as_borrowed_fd(&self) -> &i32 {
-        &self.0
+    pub fn as_borrowed_fd(&self) -> &i32 {
+        &self.0.0
     }
 
     /// Checks if the file descriptor is currently open
@@ -40,7 +95,7 @@ impl FileDes {
         // Use fcntl with F_GETFD to check if the file descriptor is valid
         // If it returns -1 with errno EBADF, the fd is closed
         //SAFETY:  Always safe
-        unsafe { libc::fcntl(self.0, libc::F_GETFD) != -1 }
+        unsafe { libc::fcntl(self.as_raw_fd(), libc::F_GETFD) != -1 }
     }
 
     /**
@@ -53,8 +108,46 @@ impl FileDes {
     pub fn is_closed(&self) -> bool {
         !self.is_open()
     }
+
+    /// Returns true if no other clone shares the descriptor, so dropping this one closes it
+    #[must_use]
+    #[inline]
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+impl AsRawFd for FileDes {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.0
+    }
 }
 
+impl AsFd for FileDes {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: the descriptor stays open for as long as `self` (or any clone) exists
+        unsafe { BorrowedFd::borrow_raw(self.0.0) }
+    }
+}
+
+/// The smallest buffer size accepted for `getdents`/`getdirentries` calls.
+///
+/// This is the size of a maximally sized `dirent64` (rounded up to 8 byte alignment),
+/// any smaller and the kernel may be unable to return a single entry (`EINVAL`).
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "solaris",
+    target_os = "illumos"
+))]
+pub const MIN_BUFFER_SIZE: usize = size_of::<crate::dirent64>().next_multiple_of(8);
+
 #[cfg(all(target_os = "android", not(debug_assertions)))]
 pub const BUFFER_SIZE: usize = 4200;
 
@@ -95,6 +95,7 @@ compile_error!("This application is not supported on Windows (yet)");
 
 // Re-exports
 pub use chrono;
+pub use crossbeam_channel;
 pub use libc;
 
 #[macro_use]
@@ -112,8 +113,15 @@ mod error;
 pub use crate::util::Unique;
 pub use error::{DirEntryError, FilesystemIOError, SearchConfigError, TraversalError};
 mod config;
-pub use config::SearchConfig;
+pub use config::{Preset, SearchConfig};
+#[cfg(any(feature = "xxhash", feature = "blake3"))]
+pub mod dedupe;
+pub mod exec;
 pub mod filters;
 pub mod fs;
+mod list;
+pub use list::{ListOptions, list_dir};
+pub mod rules;
+pub mod serve;
 pub mod util;
 pub mod walk;
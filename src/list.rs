@@ -0,0 +1,132 @@
+use crate::fs::{DirEntry, Result};
+use core::num::NonZeroU32;
+use std::ffi::OsStr;
+
+/**
+ Options for [`list_dir`], a builder in the style of [`FinderBuilder`](crate::walk::FinderBuilder).
+
+ The defaults list the directory's own entries (depth 1), hidden ones included, in the order they're read.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ListOptions {
+    max_depth: NonZeroU32,
+    keep_hidden: bool,
+    sort: bool,
+}
+
+impl Default for ListOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListOptions {
+    /// Creates the default options
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_depth: NonZeroU32::MIN,
+            keep_hidden: true,
+            sort: false,
+        }
+    }
+
+    /// Set how many levels to list, defaults to 1 (only the directory's own entries)
+    #[inline]
+    #[must_use]
+    pub const fn max_depth(mut self, max_depth: NonZeroU32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set whether to list hidden entries (and descend into hidden directories), defaults to true
+    #[inline]
+    #[must_use]
+    pub const fn keep_hidden(mut self, keep_hidden: bool) -> Self {
+        self.keep_hidden = keep_hidden;
+        self
+    }
+
+    /// Set whether to sort the entries by path, defaults to false (the order the filesystem returns them in)
+    #[inline]
+    #[must_use]
+    pub const fn sort(mut self, sort: bool) -> Self {
+        self.sort = sort;
+        self
+    }
+}
+
+/**
+ Lists a directory on the calling thread, for when spinning up a [`Finder`](crate::walk::Finder) is overkill.
+
+ Entries are read in batches with `getdents` (or the platform's equivalent) where available, the same as a
+ full traversal, without any worker threads or channels. With a [`max_depth`](ListOptions::max_depth) above 1,
+ subdirectories are listed too (breadth first, symlinks aren't followed), and a subdirectory which can't be read is
+ skipped rather than failing the whole listing.
+
+ # Errors
+ Returns an error if `path` can't be opened as a directory.
+
+ # Examples
+ ```
+ use fdf::{ListOptions, list_dir};
+
+ let entries = list_dir(env!("CARGO_MANIFEST_DIR"), ListOptions::new().sort(true)).unwrap();
+ assert!(entries.iter().any(|entry| entry.file_name() == b"Cargo.toml"));
+ assert!(entries.iter().all(|entry| entry.depth() == 1));
+ assert!(entries.windows(2).all(|pair| pair[0].as_bytes() <= pair[1].as_bytes()));
+ ```
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn list_dir<P: AsRef<OsStr>>(path: P, options: ListOptions) -> Result<Vec<DirEntry>> {
+    let root = DirEntry::new(path)?;
+    let mut entries = Vec::new();
+    read_children(&root, options, &mut entries)?;
+
+    // Entries are appended as they're found, so walking the list by index visits each new subdirectory in turn
+    let mut index = 0;
+    while options.max_depth.get() > 1 && index < entries.len() {
+        if entries[index].is_dir() && entries[index].depth < options.max_depth.get() {
+            let dir = entries[index].clone();
+            // Unreadable subdirectories (eg permission denied) are skipped, as in a traversal
+            let _ = read_children(&dir, options, &mut entries);
+        }
+        index += 1;
+    }
+
+    if options.sort {
+        entries.sort_unstable_by(|left, right| left.as_bytes().cmp(right.as_bytes()));
+    }
+    Ok(entries)
+}
+
+/// Appends the entries of `dir` which the options keep
+fn read_children(dir: &DirEntry, options: ListOptions, entries: &mut Vec<DirEntry>) -> Result<()> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "macos",
+        target_os = "freebsd"
+    ))]
+    let children = dir.getdents()?;
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "macos",
+        target_os = "freebsd"
+    )))]
+    let children = dir.readdir()?;
+
+    entries.extend(children.filter(|entry| options.keep_hidden || !entry.is_hidden()));
+    Ok(())
+}
@@ -111,13 +111,86 @@ macro_rules! access_stat {
             $stat_struct.st_mtimensec as _
         } // Why did they do such a specific change
 
-        #[cfg(not(target_os = "netbsd"))]
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_mtim.tv_nsec as _
+        }
+
+        #[cfg(not(any(target_os = "netbsd", target_os = "hurd")))]
         {
             $stat_struct.st_mtime_nsec as _
         }
     }};
 
-    ($stat_struct:expr, st_mtime) => {{ $stat_struct.st_mtime as _ }};
+    ($stat_struct:expr, st_mtime) => {{
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_mtim.tv_sec as _
+        } // The Hurd only has the POSIX 2008 timespecs
+
+        #[cfg(not(target_os = "hurd"))]
+        {
+            $stat_struct.st_mtime as _
+        }
+    }};
+
+    ($stat_struct:expr, st_ctimensec) => {{
+        #[cfg(target_os = "netbsd")]
+        {
+            $stat_struct.st_ctimensec as _
+        }
+
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_ctim.tv_nsec as _
+        }
+
+        #[cfg(not(any(target_os = "netbsd", target_os = "hurd")))]
+        {
+            $stat_struct.st_ctime_nsec as _
+        }
+    }};
+
+    ($stat_struct:expr, st_ctime) => {{
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_ctim.tv_sec as _
+        }
+
+        #[cfg(not(target_os = "hurd"))]
+        {
+            $stat_struct.st_ctime as _
+        }
+    }};
+
+    ($stat_struct:expr, st_atimensec) => {{
+        #[cfg(target_os = "netbsd")]
+        {
+            $stat_struct.st_atimensec as _
+        }
+
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_atim.tv_nsec as _
+        }
+
+        #[cfg(not(any(target_os = "netbsd", target_os = "hurd")))]
+        {
+            $stat_struct.st_atime_nsec as _
+        }
+    }};
+
+    ($stat_struct:expr, st_atime) => {{
+        #[cfg(target_os = "hurd")]
+        {
+            $stat_struct.st_atim.tv_sec as _
+        }
+
+        #[cfg(not(target_os = "hurd"))]
+        {
+            $stat_struct.st_atime as _
+        }
+    }};
 
     // Inode number, normalised to u64 for compatibility
     ($stat_struct:expr, st_ino) => {{
@@ -151,6 +224,9 @@ macro_rules! access_stat {
  - Linux/Android/OpenBSD/NetBSD/Illumos/Solaris: `getdents`
  - macOS/FreeBSD: `getdirentries`
  - Other supported Unix targets: `readdir`
+
+ The two argument form additionally takes a runtime buffer size (in bytes) for the
+ `getdents`/`getdirentries` backends, which is ignored by `readdir`.
 */
 macro_rules! read_direntries {
     ($dir:expr) => {{
@@ -185,6 +261,38 @@ macro_rules! read_direntries {
             $dir.readdir()
         }
     }};
+    ($dir:expr, $buffer_size:expr) => {{
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        {
+            $dir.getdents_with_buffer_size($buffer_size)
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        )))]
+        {
+            let _ = $buffer_size; // Buffer size only applies to getdents/getdirentries
+            $dir.readdir()
+        }
+    }};
 }
 
 /**
@@ -195,8 +303,47 @@ macro_rules! read_direntries {
 
  - Linux/Android/OpenBSD/NetBSD/Illumos/Solaris/macOS/FreeBSD: `getdents_from_fd`
  - Other supported Unix targets: `readdir_from_fd`
+
+ As with [`read_direntries!`], a buffer size may be given which only applies to `getdents`/`getdirentries`.
+ Both forms return a `Result`, as `readdir` needs a duplicate descriptor for its stream.
 */
 macro_rules! read_direntries_from_fd {
+    ($dir:expr, $fd:expr, $buffer_size:expr) => {{
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        {
+            Ok::<_, $crate::DirEntryError>($crate::fs::GetDents::from_fd_with_buffer_size(
+                $fd,
+                &$dir,
+                $buffer_size,
+            ))
+        }
+
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        )))]
+        {
+            let _ = $buffer_size; // Buffer size only applies to getdents/getdirentries
+            $dir.readdir_from_fd($fd)
+        }
+    }};
     ($dir:expr, $fd:expr) => {{
         #[cfg(any(
             target_os = "linux",
@@ -225,7 +372,7 @@ macro_rules! read_direntries_from_fd {
             target_os = "freebsd"
         )))]
         {
-            Ok::<_, $crate::DirEntryError>($dir.readdir_from_fd($fd))
+            $dir.readdir_from_fd($fd)
         }
     }};
 }
@@ -1,17 +1,39 @@
-use clap::{ArgAction, CommandFactory as _, Parser, ValueHint, value_parser};
+use clap::{ArgAction, CommandFactory as _, Parser, ValueEnum, ValueHint, value_parser};
 use clap_complete::aot::{Shell, generate};
 use core::num::NonZeroUsize;
-use fdf::filters::{FileTypeFilterParser, SizeFilterParser, TimeFilterParser};
-use fdf::walk::Finder;
+use core::time::Duration;
+#[cfg(any(feature = "xxhash", feature = "blake3"))]
+use fdf::dedupe::{DuplicateGroup, HashAlgo};
+use fdf::exec::{CommandTemplate, ExecMode};
+use fdf::filters::{
+    FileTypeFilterParser, FilterParser, MimeFilterParser, PermFilterParser, SizeFilterParser,
+    TimeFilterParser, parse_byte_size, parse_duration,
+};
+use fdf::rules::Rules;
+use fdf::util::{
+    ColourMode, ErrorFormat, OutputFormat, PrinterBuilder, SizeFormat, format_size, write_errors,
+};
+use fdf::walk::{
+    Backend, CancellationToken, DEFAULT_SORT_MEMORY, DepthLimit, Finder, PathIndex, ResultOrder,
+    SampleSize, SortedEntries, TraversalStats, WatchEvent,
+};
 use fdf::{
-    SearchConfigError, TraversalError,
-    filters::{FileTypeFilter, SizeFilter, TimeFilter},
+    Preset, SearchConfigError, TraversalError,
+    filters::{
+        ContentFilter, FileTypeFilter, Filter, MimeFilter, PermFilter, SizeFilter, TimeFilter,
+    },
+    fs::{DirEntry, RealpathCache},
 };
 use std::env;
 use std::ffi::OsString;
-use std::io::{self, stdout};
-use std::os::unix::ffi::{OsStrExt as _, OsStringExt as _};
-use std::process::Command;
+use std::io::{self, Write as _, stdout};
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{
+    Arc, Mutex, OnceLock, PoisonError,
+    atomic::{AtomicBool, Ordering},
+};
 
 #[cfg(all(
     any(target_os = "linux", target_os = "android", target_os = "macos"),
@@ -32,12 +54,12 @@ struct Args {
     pattern: Option<String>,
     #[arg(
         value_name = "PATH",
-        help = format!("Path to search (defaults to current working directory)"),
+        help = format!("Paths to search, several are searched in one traversal (defaults to current working directory)"),
         value_hint=ValueHint::DirPath,
         required=false,
         index=2
     )]
-    directory: Option<OsString>,
+    directory: Vec<OsString>,
     #[arg(
         short = 'H',
         long = "hidden",
@@ -52,6 +74,14 @@ struct Args {
         default_value_t = false
     )]
     sort: bool,
+    #[arg(
+        long = "sort-memory",
+        value_name = "SIZE",
+        value_parser = parse_byte_size,
+        requires = "sort",
+        help = "Write sorted runs to temporary files once the results take more than SIZE, eg '1gi' (defaults to 256mi)"
+    )]
+    sort_memory: Option<u64>,
     #[arg(
         short = 's',
         long = "case-sensitive",
@@ -62,20 +92,29 @@ struct Args {
     #[arg(
         short = 'e',
         long = "extension",
-        help = "filters based on extension, eg --extension .txt or -E txt",
-        long_help = "An example command would be `fdf -HI -e  c '^str' / "
+        action = ArgAction::Append,
+        help = "filters based on extension, eg --extension .txt or -e txt, repeat to match any of several (eg -e rs -e toml)",
+        long_help = "An example command would be `fdf -HI -e  c '^str' /`\nRepeat to match any of several extensions, eg `fdf -e rs -e toml`"
     )]
-    extension: Option<String>,
+    extension: Vec<String>,
     #[arg(
         short = 'j',
         long = "threads",
         help = "Number of threads to use, defaults to available threads available on your computer"
     )]
     thread_num: Option<NonZeroUsize>,
+    #[arg(
+        long = "backend",
+        value_name = "BACKEND",
+        help = "How directories are read: native (the fastest way the platform offers) or portable (only opendir/readdir/lstat, slower), defaults to native (portable on unusual platforms)"
+    )]
+    backend: Option<BackendArg>,
     #[arg(
         short = 'a',
         long = "absolute-path",
-        help = "Starts with the directory entered being resolved to full"
+        help = "Starts with the directory entered being resolved to full",
+        long_help = "Starts with the directory entered being resolved to full.\n\
+                     With --follow, results found through symlinked directories are resolved to their real paths too."
     )]
     absolute_path: bool,
 
@@ -83,16 +122,39 @@ struct Args {
         short = 'L',
         long = "follow",
         default_value_t = false,
-        help = "Include symlinks in traversal,defaults to false"
+        help = "Include symlinks in traversal,defaults to false",
+        long_help = "Include symlinks in traversal,defaults to false.\n\
+                     Without --depth, directories below a depth of 256 aren't read (with a warning), in case of link farms which look endless."
     )]
     follow_symlinks: bool,
+    #[arg(
+        long = "color",
+        alias = "colour",
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "When to colour output: auto (only to a terminal), always or never",
+        long_help = "When to colour output: auto (only to a terminal), always or never.\n\
+                     With auto, NO_COLOR (or NO_COLOUR) disables colour, CLICOLOR_FORCE enables it even when piped,\n\
+                     and CLICOLOR=0 or TERM=dumb disable it.\n\
+                     Colours are taken from LS_COLORS where it's set, falling back to the built in scheme."
+    )]
+    colour: ColourMode,
     #[arg(
         long = "nocolour",
         alias = "nocolor",
         default_value_t = false,
-        help = "Disable colouring output when sending to terminal"
+        hide = true,
+        conflicts_with = "colour",
+        help = "Disable colouring output, the same as --color=never"
     )]
     no_colour: bool,
+    #[arg(
+        long = "hyperlink",
+        default_value_t = false,
+        conflicts_with_all = ["print0", "format"],
+        help = "Make printed paths clickable in terminals supporting OSC 8 hyperlinks (eg kitty, iTerm2, wezterm)"
+    )]
+    hyperlink: bool,
     #[arg(
         short = 'g',
         long = "glob",
@@ -108,6 +170,53 @@ struct Args {
         help = "Retrieves the first eg 10 results, 'fdf  -n 10 '.cache' /"
     )]
     top_n: Option<usize>,
+    #[arg(
+        long = "max-total-size",
+        value_name = "SIZE",
+        value_parser = parse_byte_size,
+        help = "Stop once the matched files add up to SIZE, eg '1gi', without going over it"
+    )]
+    max_total_size: Option<u64>,
+    #[arg(
+        long = "timeout",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = "Stop searching after DURATION, eg '5s' or '500ms', printing what was found so far and exiting with status 2"
+    )]
+    timeout: Option<Duration>,
+    #[arg(
+        long = "sample",
+        value_name = "RATIO|COUNT",
+        help = "Emit a uniform random sample of the results, eg '--sample 10%' or '--sample 100'",
+        long_help = "Emit a uniform random sample of the results.\n\
+                     A ratio (0.1 or 10%) keeps each result with that probability, results still stream as they're found.\n\
+                     A count (100) keeps exactly that many results (or all of them, if there are fewer), printed once the traversal ends."
+    )]
+    sample: Option<SampleSize>,
+    #[arg(
+        long = "order",
+        value_name = "ORDER",
+        conflicts_with_all = ["sort", "shuffle", "sample", "cached"],
+        help = "Print the results in a deterministic depth first (dfs) or breadth first (bfs) order",
+        long_help = "Print the results in a deterministic order, the same whatever the number of threads.\n\
+                     dfs: each directory followed by its contents. bfs: level by level.\n\
+                     Siblings are ordered by name. Directories are still read in parallel and results stream as soon as\n\
+                     everything before them has been read, which is much cheaper than --sort."
+    )]
+    order: Option<OrderArg>,
+    #[arg(
+        long = "shuffle",
+        conflicts_with = "sort",
+        help = "Print the results in a random order",
+        default_value_t = false
+    )]
+    shuffle: bool,
+    #[arg(
+        long = "seed",
+        value_name = "N",
+        help = "Seed the randomness of --sample and --shuffle, so runs are reproducible"
+    )]
+    seed: Option<u64>,
     #[arg(
         short = 'd',
         long = "depth",
@@ -115,13 +224,22 @@ struct Args {
         help = "Retrieves only traverse to x depth"
     )]
     depth: Option<u32>,
+    #[arg(
+        long = "min-depth",
+        value_name = "NUM",
+        help = "Only show results at least NUM levels deep, the search root's own entries are depth 1"
+    )]
+    min_depth: Option<u32>,
 
     #[arg(
         short = 'p',
         long = "full-path",
         required = false,
         default_value_t = false,
-        help = "Use a full path for regex matching, default to false"
+        help = "Use a full path for regex matching, default to false",
+        long_help = "Match the pattern against the path relative to the search root (eg 'src/main.rs') rather than the file name.\n\
+                     With --glob, '*' stays within a directory and '**' as a whole component matches any number of directories,\n\
+                     eg 'src/**/*.rs'. A pattern containing a '/' always matches the full path."
     )]
     full_path: bool,
 
@@ -137,11 +255,13 @@ struct Args {
 
     #[arg(
         long = "show-errors",
-        required = false,
-        default_value_t = false,
-        help = "Show errors when traversing"
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        help = "Show errors when traversing, as text or json (one object per line with path, errno, operation and message).\nThe exit status is 0 if anything was found, 1 if nothing was and 2 if anything failed (errors shown or not)"
     )]
-    show_errors: bool,
+    show_errors: Option<ErrorFormat>,
     #[arg(
         long = "same-file-system",
         alias="one-file-system", //alias for fd for easier use
@@ -150,6 +270,159 @@ struct Args {
         help = "Only traverse the same filesystem as the starting directory"
     )]
     same_file_system: bool,
+    #[arg(
+        long = "skip-mounts",
+        required = false,
+        default_value_t = false,
+        help = "Do not descend into mount points, including bind mounts (independent of --follow)"
+    )]
+    skip_mounts: bool,
+    #[arg(
+        long = "skip-virtual-fs",
+        required = false,
+        default_value_t = false,
+        help = "Do not descend into virtual filesystems such as /proc and /sys, recognised by their type in the mount table"
+    )]
+    skip_virtual_fs: bool,
+    #[arg(
+        long = "skip-pseudo",
+        required = false,
+        default_value_t = false,
+        conflicts_with = "no_skip_pseudo",
+        help = "Do not descend into pseudo filesystems such as /proc, /sys and /dev, detected with statfs (the default when searching /)"
+    )]
+    skip_pseudo: bool,
+    #[arg(
+        long = "no-skip-pseudo",
+        required = false,
+        default_value_t = false,
+        help = "Descend into pseudo filesystems even when searching /"
+    )]
+    no_skip_pseudo: bool,
+    #[arg(
+        long = "list-mounts",
+        required = false,
+        default_value_t = false,
+        help = "Print the mounted filesystems as 'path<TAB>type' (virtual ones marked with a trailing '*') and exit"
+    )]
+    list_mounts: bool,
+    #[arg(
+        long = "prune",
+        required = false,
+        default_value_t = false,
+        help = "Do not descend into directories which match, eg find every node_modules without listing their contents"
+    )]
+    prune: bool,
+    #[arg(
+        long = "find-anomalies",
+        default_value_t = false,
+        conflicts_with_all = ["exec", "exec_batch", "format"],
+        help = "Report entries with over long paths/names, control characters or invalid UTF-8 in their name, or a d_type that disagrees with lstat"
+    )]
+    find_anomalies: bool,
+    #[arg(
+        long = "rules",
+        value_name = "FILE",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "serve", "daemon", "group_by", "show_hardlinks", "duplicates"],
+        help = "Label each result with the first matching rule of FILE, lines of '\"glob\" = \"label\"', printed as 'label<TAB>path'"
+    )]
+    rules: Option<PathBuf>,
+    #[arg(
+        long = "label",
+        value_name = "LABEL",
+        requires = "rules",
+        action = ArgAction::Append,
+        help = "Only show results labelled LABEL by --rules, can be repeated"
+    )]
+    label: Vec<String>,
+    #[arg(
+        long = "resolve",
+        default_value_t = false,
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies"],
+        help = "Print the full chain of every symlink, eg 'a -> b -> c'"
+    )]
+    resolve: bool,
+    #[arg(
+        long = "serve",
+        value_name = "SOCKET",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "list", "list_human", "summary", "path_separator", "print0"],
+        help = "Stream results to the first client of a Unix socket at SOCKET, as u32 big-endian length-prefixed paths"
+    )]
+    serve: Option<PathBuf>,
+    #[arg(
+        long = "daemon",
+        value_name = "SOCKET",
+        value_hint = ValueHint::FilePath,
+        conflicts_with_all = ["serve", "exec", "exec_batch", "format", "find_anomalies", "resolve", "list", "list_human", "summary", "path_separator", "print0"],
+        help = "Index the results once, then answer regex queries from clients of a Unix socket at SOCKET until killed",
+        long_help = "Index the results once, then answer regex queries from clients of a Unix socket at SOCKET until killed.\nQueries and results are u32 big-endian length-prefixed records, each query is answered by the matching paths then an empty record.\nThe other filters (pattern, extension, type etc) decide what is indexed, --refresh keeps the index up to date.\nClients are answered concurrently (up to 64 at once), a client idle for a minute is disconnected."
+    )]
+    daemon: Option<PathBuf>,
+    #[arg(
+        long = "refresh",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        requires = "daemon",
+        help = "With --daemon, walk again every DURATION (eg '10m'), answering later queries from the new results"
+    )]
+    refresh: Option<Duration>,
+    #[arg(
+        long = "group-by",
+        value_name = "KEY",
+        value_enum,
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "print0"],
+        help = "Group results under a header, 'filesystem' groups by device with the mount point as the header"
+    )]
+    group_by: Option<GroupBy>,
+    #[arg(
+        long = "show-hardlinks",
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "print0", "group_by", "shuffle", "sample"],
+        help = "Group files which are hard links to the same file under a header, '-n' limits the number of groups",
+        default_value_t = false
+    )]
+    show_hardlinks: bool,
+    #[arg(
+        long = "duplicates",
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "print0", "group_by", "shuffle", "sample", "show_hardlinks"],
+        help = "Group regular files with identical contents under a header, largest first, '-n' limits the number of groups",
+        long_help = "Group regular files with identical contents under a header, largest first.\n\
+                     Files are compared by size, then by a hash of their contents (only files sharing a size are read).\n\
+                     Empty files are skipped, '-n' limits the number of groups.",
+        default_value_t = false
+    )]
+    duplicates: bool,
+    #[arg(
+        long = "stats",
+        conflicts_with = "daemon",
+        help = "After the search, print the number of results of each type, their total size, the deepest path, the largest file and how many directories couldn't be read",
+        long_help = "After the search, print the number of results of each type, their total size (of regular files),\n\
+                     the deepest path, the largest file and how many directories couldn't be read.\n\
+                     The report goes to stderr, so the results can still be piped.",
+        default_value_t = false
+    )]
+    stats: bool,
+    #[arg(
+        long = "cached",
+        conflicts_with_all = ["shuffle", "sample", "show_hardlinks", "duplicates", "watch"],
+        help = "Answer from an index of the directory listings kept under ~/.cache/fdf, only reading directories which changed since",
+        long_help = "Answer from an index of the directory listings kept under $XDG_CACHE_HOME/fdf (or ~/.cache/fdf), one per root.\n\
+                     Each directory is only read again if its modification time changed since it was indexed, the index is then updated.\n\
+                     The search runs on a single thread, results come in the order of a depth first walk.",
+        default_value_t = false
+    )]
+    cached: bool,
+    #[arg(
+        long = "watch",
+        conflicts_with_all = ["exec", "exec_batch", "format", "find_anomalies", "resolve", "serve", "daemon", "rules", "group_by", "show_hardlinks", "duplicates", "stats", "list", "list_human", "summary", "sort", "shuffle", "sample"],
+        help = "Watch the searched directories, printing each matching change as 'created|modified|deleted<TAB>path' until interrupted",
+        long_help = "Watch the searched directories, printing each matching change as 'created|modified|deleted<TAB>path' until interrupted.\n\
+                     The existing results aren't printed, only changes made once the search is done. New directories are watched as they appear.\n\
+                     Ignore files aren't consulted for changes, and deleted entries are only matched on their path (Linux/Android only).",
+        default_value_t = false
+    )]
+    watch: bool,
     #[arg(
         short = '0',
         long = "print0",
@@ -157,14 +430,14 @@ struct Args {
         required = false,
         default_value_t = false,
         help = "Makes all output null terminated",
-        long_help = "Makes all output null terminated as opposed to newline terminated only applies to non-coloured output and redirected(useful for xargs)"
+        long_help = "Makes all output null terminated as opposed to newline terminated, disabling colours (useful for `xargs -0`)"
     )]
     print0: bool,
     #[arg(
         short = 'I',
         long = "no-ignore",
         default_value_t = false,
-        help = "Do not respect .gitignore rules during traversal"
+        help = "Do not respect .gitignore/.ignore/.fdignore rules during traversal"
     )]
     no_ignore: bool,
     #[arg(
@@ -173,6 +446,65 @@ struct Args {
         help = "Strip the leading './' from results when searching the current directory"
     )]
     strip_cwd_prefix: bool,
+    #[arg(
+        long = "strip-prefix",
+        default_value_t = false,
+        conflicts_with_all = ["exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "Print results relative to the search root, eg 'lib.rs' rather than 'src/lib.rs' when searching 'src'"
+    )]
+    strip_prefix: bool,
+    #[arg(
+        long = "relative-to",
+        value_name = "DIR",
+        value_hint = ValueHint::DirPath,
+        conflicts_with_all = ["strip_prefix", "exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "Print results relative to DIR, eg '../src/lib.rs' from 'benches' when searching 'src'"
+    )]
+    relative_to: Option<OsString>,
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        default_value = "plain",
+        conflicts_with_all = ["quoted", "print0"],
+        help = "Output format: plain, json (a single array) or ndjson (one object per line)"
+    )]
+    format: OutputFormat,
+    #[arg(
+        short = 'l',
+        long = "list",
+        default_value_t = false,
+        conflicts_with_all = ["format", "quoted", "exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "Use a long listing format like 'ls -l' (permissions, links, owner, group, size and modification time)"
+    )]
+    list: bool,
+    #[arg(
+        long = "list-human",
+        default_value_t = false,
+        conflicts_with_all = ["format", "quoted", "exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "As --list, with human readable sizes (eg 1.5K, 23M), the same as --list --size-format binary"
+    )]
+    list_human: bool,
+    #[arg(
+        long = "size-format",
+        value_name = "FORMAT",
+        help = "How sizes are printed by --list, --summary and --stats: bytes (the default), si (eg 1.5k, powers of 1000) or binary (eg 1.5K, powers of 1024)"
+    )]
+    size_format: Option<SizeFormat>,
+    #[arg(
+        long = "path-separator",
+        value_name = "SEPARATOR",
+        conflicts_with_all = ["format", "exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "Print SEPARATOR instead of '/' between path components (eg '\\' for Windows tools over WSL)"
+    )]
+    path_separator: Option<OsString>,
+    #[arg(
+        long = "summary",
+        default_value_t = false,
+        conflicts_with_all = ["format", "exec", "exec_batch", "group_by", "find_anomalies", "resolve"],
+        help = "Finish the output with the number of entries and their cumulative size",
+        long_help = "Finish the output with the number of entries and their cumulative size, with --list the listing also starts with 'total N' (in 1K blocks) like 'ls -l'"
+    )]
+    summary: bool,
     #[arg(
         short = 'Q',
         long = "quoted",
@@ -181,15 +513,27 @@ struct Args {
     )]
     quoted: bool,
     #[arg(
+        short = 'x',
         long = "exec",
         value_name = "CMD",
         num_args = 1..,
         allow_hyphen_values = true,
-        conflicts_with_all = ["generate", "quoted", "print0", "no_colour"],
-        help = "Execute a command once per search result",
-        long_help = "Execute a command once per search result.\nUse '{}' to insert the matched path into an argument; if '{}' is omitted, the path is appended as the final argument. This option should be the final CLI flag.\nExample: 'fdf 'junk.files' 'test_directory' -HI --exec rm -rf ' , delete all files meeting the criteria"
+        conflicts_with_all = ["generate", "quoted", "print0", "colour", "no_colour", "exec_batch"],
+        help = "Execute a command once per search result, in parallel",
+        long_help = "Execute a command once per search result, in parallel.\nPlaceholders: '{}' path, '{.}' path without extension, '{/}' file name, '{/.}' file name without extension, '{//}' parent directory.\nIf no placeholder is given, the path is appended as the final argument. This option should be the final CLI flag.\nExample: 'fdf 'junk.files' 'test_directory' -HI --exec rm -rf ' , delete all files meeting the criteria"
     )]
     exec: Option<Vec<OsString>>,
+    #[arg(
+        short = 'X',
+        long = "exec-batch",
+        value_name = "CMD",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        conflicts_with_all = ["generate", "quoted", "print0", "colour", "no_colour"],
+        help = "Execute a command once with all search results as arguments",
+        long_help = "Execute a command once with all search results as arguments (split into several runs if they exceed the argument size limit).\nSupports the same placeholders as --exec, an argument containing one is repeated for every result.\nThis option should be the final CLI flag.\nExample: 'fdf -e rs -X wc -l'"
+    )]
+    exec_batch: Option<Vec<OsString>>,
     #[arg(
         long = "ignore",
         value_name = "PATTERN",
@@ -197,6 +541,15 @@ struct Args {
         help = "Ignore paths that match this regex pattern (repeatable)"
     )]
     ignore: Vec<String>,
+    #[arg(
+        short = 'E',
+        long = "exclude",
+        value_name = "GLOB",
+        action = ArgAction::Append,
+        help = "Exclude files and directories matching this glob, excluded directories are not traversed (repeatable)",
+        long_help = "Exclude files and directories matching this glob (repeatable), excluded directories are not traversed.\nUses .gitignore syntax relative to the search root, eg '--exclude node_modules --exclude \"*.o\"'"
+    )]
+    exclude: Vec<String>,
     #[arg(
         long = "ignoreg",
         value_name = "GLOB",
@@ -219,6 +572,35 @@ struct Args {
         help = "Add additional required search patterns, all of which must be matched.\nMultiple additional patterns can be specified. The patterns are regular expressions, unless '--glob' or '--fixed-strings' is used."
     )]
     and_opt: Vec<String>,
+    #[arg(
+        long = "not",
+        value_name = "pattern",
+        action = ArgAction::Append,
+        help = "Exclude results matching this pattern, eg `fdf '\\.rs$' --not test` (repeatable).\nDirectories matching it are still searched, see '--exclude' to skip them. The patterns are regular expressions, unless '--glob' or '--fixed-strings' is used."
+    )]
+    not: Vec<String>,
+    #[arg(
+        long = "pattern-file",
+        value_name = "path",
+        action = ArgAction::Append,
+        value_hint = ValueHint::FilePath,
+        help = "Read patterns from a file, one per line, of which any must match (repeatable)",
+        long_help = "Read patterns from a file, one per line, of which any must match (repeatable).\n\
+                     Blank lines and lines starting with '#' are skipped. The patterns are regular expressions (globs with '--glob'),\n\
+                     compiled into a single set, and apply on top of the search pattern and '--and' patterns."
+    )]
+    pattern_file: Vec<OsString>,
+    #[arg(
+        long = "preset",
+        value_name = "NAME",
+        action = ArgAction::Append,
+        help = "Match a curated pattern list, artifacts or secrets (repeatable)",
+        long_help = "Match a curated pattern list, of which any pattern must match (repeatable).\n\
+                     artifacts: dependency and build directories (node_modules, target), compiled objects and core dumps\n\
+                     secrets:   private keys (id_rsa, *.pem), .env files and credential files\n\
+                     Presets combine with '--pattern-file' lists, and apply on top of the search pattern."
+    )]
+    preset: Vec<Preset>,
     /// Filter by file size
     ///
     /// PREFIXES:
@@ -252,6 +634,30 @@ struct Args {
     verbatim_doc_comment
 )]
     size: Option<SizeFilter>,
+    #[arg(
+        long = "mime",
+        value_name = "TYPE/SUBTYPE",
+        value_parser = MimeFilterParser,
+        help = "Only show regular files of a MIME type sniffed from their contents, eg 'image/*' or 'text/plain,application/pdf'"
+    )]
+    mime: Option<MimeFilter>,
+    #[arg(
+        long = "search-content",
+        value_name = "PATTERN",
+        help = "Only show regular files whose contents match a regex (binary files are skipped)",
+        long_help = "Only show regular files whose contents match a regex.\n\
+                     The regex is case sensitive unless it starts with (?i), and ^/$ match at line boundaries.\n\
+                     Binary files (those with a NUL byte in the first 8KiB) and files over --max-content-size never match."
+    )]
+    search_content: Option<String>,
+    #[arg(
+        long = "max-content-size",
+        value_name = "SIZE",
+        value_parser = parse_byte_size,
+        requires = "search_content",
+        help = "Skip files larger than SIZE when searching contents, eg '10mi' (defaults to 64mi)"
+    )]
+    max_content_size: Option<u64>,
     /// Filter by file modification time
     ///
     /// PREFIXES:
@@ -262,6 +668,9 @@ struct Args {
     /// TIME RANGE:
     ///   TIME..TIME   Find files modified between two times
     ///
+    /// TIMESTAMPS (UTC unless an offset is given):
+    ///   2024-01-01, 2024-01-01T10:00[:00], 2024-01-01T10:00:00Z, 2024-01-01T10:00:00+02:00
+    ///
     /// UNITS:
     ///   s, sec, second, seconds     - Seconds
     ///   m, min, minute, minutes     - Minutes
@@ -275,6 +684,7 @@ struct Args {
     ///   --time +2d        Files modified more than 2 days ago
     ///   --time 1d..2h     Files modified between 1 day and 2 hours ago
     ///   --time -30m       Files modified within the last 30 minutes
+    ///   --time +2024-01-01  Files modified before 2024
     #[arg(
     long = "time-modified",
     short = 'T',
@@ -285,16 +695,81 @@ struct Args {
     verbatim_doc_comment
 )]
     time: Option<TimeFilter>,
+    #[arg(
+        long = "changed-within",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_within,
+        help = "Only show files modified within TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)"
+    )]
+    changed_within: Option<TimeFilter>,
+    #[arg(
+        long = "changed-before",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_before,
+        help = "Only show files modified before TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)",
+        long_help = "Only show files modified before TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given).\n\
+                     Combined with --changed-within (or --time-modified), only files modified in the window between them are shown.\n\
+                     The same goes for the --created-* and --accessed-* pairs."
+    )]
+    changed_before: Option<TimeFilter>,
+    #[arg(
+        long = "created-within",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_within,
+        help = "Only show files created within TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)"
+    )]
+    created_within: Option<TimeFilter>,
+    #[arg(
+        long = "created-before",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_before,
+        help = "Only show files created before TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)"
+    )]
+    created_before: Option<TimeFilter>,
+    #[arg(
+        long = "accessed-within",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_within,
+        help = "Only show files accessed within TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)"
+    )]
+    accessed_within: Option<TimeFilter>,
+    #[arg(
+        long = "accessed-before",
+        value_name = "TIME",
+        value_parser = TimeFilter::changed_before,
+        help = "Only show files accessed before TIME, either a duration (eg 2d, 30min) or a timestamp (eg 2024-01-01 or 2024-01-01T10:00:00Z, UTC unless an offset is given)"
+    )]
+    accessed_before: Option<TimeFilter>,
+    #[arg(
+        long = "perm",
+        value_name = "MODE",
+        allow_hyphen_values = true,
+        value_parser = PermFilterParser,
+        help = "Filter by permission bits like find: MODE exactly, -MODE all bits set, /MODE any bit set (octal or symbolic, eg 644, -u+w, /111)"
+    )]
+    perm: Option<PermFilter>,
 
+    #[arg(
+        long = "expr",
+        value_name = "EXPR",
+        value_parser = FilterParser,
+        help = "Only show entries matching a filter expression, eg '(name:*.log & size:+1M) | type:socket'",
+        long_help = "Only show entries matching a filter expression, eg '(name:*.log & size:+1M) | type:socket'.\n\
+                     Tests are key:value, with keys name (a glob), regex, path (a glob on the path below the root), ext,\n\
+                     size, type, mtime and perm taking the same values as the options of the same name.\n\
+                     Combine them with & (and), | (or) and ! (not), grouping with parentheses. Quote values containing spaces or &|()."
+    )]
+    expr: Option<Filter>,
     #[arg(
     short = 't',
     long = "type",
     required = false,
+    action = ArgAction::Append,
     value_parser = FileTypeFilterParser,
-    help = "Filter by file type",
+    help = "Filter by file type, repeat to match any of several (eg -t f -t l)",
 
 )]
-    type_of: Option<FileTypeFilter>,
+    type_of: Vec<FileTypeFilter>,
     #[arg(
     long = "generate",
     action = ArgAction::Set,
@@ -310,178 +785,839 @@ struct Args {
     generate: Option<Shell>,
 }
 
-fn main() -> Result<(), SearchConfigError> {
-    let args = Args::parse();
+/// Keys accepted by `--group-by`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// Bucket results by the device they live on (`st_dev`)
+    Filesystem,
+}
+
+/// Orders accepted by `--order`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OrderArg {
+    /// Depth first, each directory followed by its contents
+    Dfs,
+    /// Breadth first, level by level
+    Bfs,
+}
+
+/// Backends accepted by `--backend`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BackendArg {
+    /// `getdents`/`getdirentries` where available, trusting `d_type`
+    Native,
+    /// Only `opendir`, `readdir` and `lstat`
+    Portable,
+}
+
+/// Cancelled by the first Ctrl-C, see [`cancel_on_interrupt`]
+static INTERRUPT: OnceLock<CancellationToken> = OnceLock::new();
+
+/// Exit status after being interrupted, as the shell reports for SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 128 + libc::SIGINT;
+/// Exit status when the search found nothing (as with `fd` and `grep`)
+const NO_MATCHES_EXIT_CODE: u8 = 1;
+/// Exit status when anything failed, including directories which couldn't be read
+const ERROR_EXIT_CODE: u8 = 2;
+
+/// Set once the search finds anything, for the exit status
+static FOUND: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if let Some(token) = INTERRUPT.get() {
+        token.cancel();
+    }
+    restore_default_interrupt();
+}
+
+/**
+ Makes the first Ctrl-C stop the traversal cleanly, so whatever was found so far is still printed
+ (and any commands already running finish). A second Ctrl-C kills the process as usual.
+*/
+fn cancel_on_interrupt() -> CancellationToken {
+    let token = INTERRUPT.get_or_init(CancellationToken::new).clone();
+    // SAFETY: the handler only does an atomic store and calls `signal`, both async-signal-safe
+    unsafe { libc::signal(libc::SIGINT, on_interrupt as libc::sighandler_t) };
+    token
+}
+
+fn restore_default_interrupt() {
+    // SAFETY: restoring the default disposition is always sound
+    unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+}
 
+fn main() -> ExitCode {
+    let interrupt = cancel_on_interrupt();
+    let result = run(Args::parse(), interrupt.clone());
+    if interrupt.is_cancelled() {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    result.unwrap_or_else(|error| {
+        eprintln!("Error: {error:?}");
+        ExitCode::from(ERROR_EXIT_CODE)
+    })
+}
+
+fn run(args: Args, interrupt: CancellationToken) -> Result<ExitCode, SearchConfigError> {
     if let Some(generator) = args.generate {
         let mut cmd = Args::command();
         let bin_name = cmd.get_name().to_owned();
         cmd.set_bin_name("fdf");
 
         generate(generator, &mut cmd, bin_name, &mut stdout());
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.list_mounts {
+        return list_mounts();
     }
 
-    let path: OsString = args.directory.unwrap_or_else(|| ".".into());
+    let paths: Vec<OsString> = if args.directory.is_empty() {
+        vec![".".into()]
+    } else {
+        args.directory
+    };
+    if paths.len() > 1
+        && (args.cached || args.rules.is_some() || args.strip_prefix || args.relative_to.is_some())
+    {
+        return Err(SearchConfigError::IOError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--cached, --rules, --strip-prefix and --relative-to take a single PATH",
+        )));
+    }
     // Only strip `./` when the root is actually `.` or `./`; that is the only case
     // where every emitted path is guaranteed to carry that prefix (safety invariant).
-    let root_is_cwd = matches!(path.as_bytes(), b"." | b"./");
+    let root_is_cwd = paths
+        .iter()
+        .all(|path| matches!(path.as_bytes(), b"." | b"./"));
     let strip_cwd_prefix = args.strip_cwd_prefix && root_is_cwd;
 
-    let finder = Finder::init(&path)
-        .pattern(args.pattern.unwrap_or_else(String::new)) //empty string
+    let sort_memory = args.sort_memory.map_or(DEFAULT_SORT_MEMORY, |max| {
+        usize::try_from(max).unwrap_or(usize::MAX)
+    });
+
+    let sample_count = match args.sample {
+        Some(SampleSize::Count(count)) => Some(count),
+        _ => None,
+    };
+
+    let order = if args.cached {
+        Order::Cached
+    } else if let Some(count) = sample_count {
+        Order::Sample(count)
+    } else if args.shuffle {
+        Order::Shuffle
+    } else if let Some(order) = args.order {
+        Order::Ordered(match order {
+            OrderArg::Dfs => ResultOrder::DepthFirst,
+            OrderArg::Bfs => ResultOrder::BreadthFirst,
+        })
+    } else {
+        Order::Walk
+    };
+    // Sorting, shuffling, count samples, label filters, hard link and duplicate groups need every result before they can pick the first N
+    let collects_all = args.sort
+        || !matches!(order, Order::Walk | Order::Ordered(_))
+        || !args.label.is_empty()
+        || args.show_hardlinks
+        || args.duplicates;
+
+    let content_filter = args
+        .search_content
+        .as_deref()
+        .map(|pattern| {
+            let filter = ContentFilter::new(pattern)?;
+            Ok::<_, SearchConfigError>(match args.max_content_size {
+                None => filter,
+                Some(max) => filter.max_size(max),
+            })
+        })
+        .transpose()?;
+
+    // The root is already canonical, so only paths found through followed symlinks need resolving
+    let canonicalise = args.absolute_path && args.follow_symlinks;
+
+    let builder = Finder::init_multi(&paths)
+        .pattern(args.pattern.unwrap_or_default()) //empty string
         .and_patterns(args.and_opt)
+        .not_patterns(args.not)
+        .pattern_files(args.pattern_file)
+        .presets(args.preset)
         .keep_hidden(!args.hidden)
         .case_insensitive(args.case_insensitive)
         .fixed_string(args.fixed_string)
         .canonicalise_root(args.absolute_path)
         .file_name_only(!args.full_path)
-        .extension(args.extension.unwrap_or_else(String::new))
+        .extensions(args.extension)
         .max_depth(args.depth)
+        .min_depth(args.min_depth)
         .follow_symlinks(args.follow_symlinks)
         .filter_by_size(args.size)
-        .filter_by_time(args.time)
-        .type_filter(args.type_of)
-        .collect_errors(args.show_errors)
+        .filter_by_time(time_window([
+            args.time,
+            args.changed_within,
+            args.changed_before,
+        ]))
+        .filter_by_created(time_window([args.created_within, args.created_before]))
+        .filter_by_accessed(time_window([args.accessed_within, args.accessed_before]))
+        .filter_by_permissions(args.perm)
+        .type_filter(FileTypeFilter::any_of(args.type_of))
+        .filter_by_mime(args.mime)
+        .filter_expr(args.expr)
+        .search_content(content_filter)
+        // Always collected (it costs nothing until a directory can't be read), as they decide the exit status
+        .collect_errors(true)
+        .collect_stats(args.stats)
         .use_glob(args.glob)
         .same_filesystem(args.same_file_system)
+        .follow_mounts(!args.skip_mounts)
+        .skip_virtual_fs(args.skip_virtual_fs)
+        .prune(args.prune)
         .respect_gitignore(!args.no_ignore)
         .ignore_patterns(args.ignore)
         .ignore_glob_patterns(args.ignoreg)
+        .exclude_patterns(args.exclude)
         .ignore_files(args.ignore_file)
         .thread_count(args.thread_num)
-        .build()?;
+        .backend(match args.backend {
+            Some(BackendArg::Native) => Backend::Native,
+            Some(BackendArg::Portable) => Backend::Portable,
+            None => Backend::default(),
+        })
+        .sample_ratio(match args.sample {
+            Some(SampleSize::Ratio(ratio)) => Some(ratio),
+            _ => None,
+        })
+        .seed(args.seed)
+        .max_results(args.top_n.filter(|_| !collects_all))
+        .max_total_size(args.max_total_size)
+        .timeout(args.timeout)
+        .cancellation_token(interrupt);
+    // Left to the builder unless asked for either way, it skips them when searching /
+    let finder = match (args.skip_pseudo, args.no_skip_pseudo) {
+        (true, _) => builder.skip_pseudo_fs(true),
+        (_, true) => builder.skip_pseudo_fs(false),
+        _ => builder,
+    }
+    .build()?;
 
     let errors = finder.error_store();
+    let relative_to = match (&args.relative_to, args.strip_prefix) {
+        (Some(base), _) => Some(finder.relative_output(base)?),
+        (None, true) => Some(finder.relative_output(finder.root_dir())?),
+        (None, false) => None,
+    };
+    let size_format = args.size_format.unwrap_or(if args.list_human {
+        SizeFormat::Binary
+    } else {
+        SizeFormat::Bytes
+    });
+    // Reports once the search is over, whichever way the output is produced
+    let _stats_report = finder.stats().map(|stats| StatsReport(stats, size_format));
+    let _depth_warning = finder.follow_depth_limit().map(DepthLimitWarning);
+
+    if args.resolve {
+        print_resolved(results(finder, order, false)?.take(args.top_n.unwrap_or(usize::MAX)))?;
 
-    if let Some(exec) = args.exec.as_deref() {
-        run_exec_search(
-            finder.traverse()?,
-            exec,
-            args.sort,
-            args.top_n,
-            strip_cwd_prefix,
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+
+    if let Some(socket) = args.serve {
+        // The walk only starts once a client is connected, so it sees every result
+        let server = fdf::serve::SocketServer::bind(socket)?;
+        let stream = server.accept()?;
+        fdf::serve::stream_records(
+            stream,
+            results(finder, order, canonicalise)?.take(args.top_n.unwrap_or(usize::MAX)),
         )?;
 
-        if args.show_errors {
-            print_collected_errors(errors.as_deref());
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+
+    if let Some(socket) = args.daemon {
+        // Bind first, so a bad socket path fails before a potentially long traversal
+        let server = fdf::serve::SocketServer::bind(socket)?;
+        let index = Arc::new(fdf::serve::SharedIndex::new(fdf::serve::Index::new(
+            results(finder.clone(), order, canonicalise)?,
+        )));
+        // Nothing to stop cleanly any more, the daemon runs until it's killed
+        restore_default_interrupt();
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        if let Some(interval) = args.refresh {
+            let index = Arc::clone(&index);
+            std::thread::spawn(move || {
+                loop {
+                    std::thread::sleep(interval);
+                    // A walk which can't start (eg the root was removed) keeps the previous results
+                    if let Ok(results) = results(finder.clone(), order, canonicalise) {
+                        index.replace(fdf::serve::Index::new(results));
+                    }
+                }
+            });
+        }
+
+        fdf::serve::serve_queries(&server, &index)?;
+        return Ok(status);
+    }
+
+    if args.watch {
+        let events = finder.watch()?;
+        // Only the initial search can fail to read directories
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        print_watch_events(events, if args.print0 { b'\0' } else { b'\n' })?;
+        return Ok(status);
+    }
+
+    #[cfg(any(feature = "xxhash", feature = "blake3"))]
+    if args.duplicates {
+        let mut groups = finder.find_duplicates(HashAlgo::default())?;
+        groups.truncate(args.top_n.unwrap_or(usize::MAX));
+        FOUND.store(!groups.is_empty(), Ordering::Relaxed);
+        print_duplicates(&groups, canonicalise)?;
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+    #[cfg(not(any(feature = "xxhash", feature = "blake3")))]
+    if args.duplicates {
+        return Err(SearchConfigError::IOError(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--duplicates needs fdf built with the xxhash or blake3 feature",
+        )));
+    }
+
+    if args.show_hardlinks {
+        let mut groups = finder.group_by_inode()?;
+        groups.truncate(args.top_n.unwrap_or(usize::MAX));
+        FOUND.store(!groups.is_empty(), Ordering::Relaxed);
+        print_hardlinks(&groups, canonicalise)?;
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+
+    if args.group_by == Some(GroupBy::Filesystem) {
+        let limit = args.top_n.unwrap_or(usize::MAX);
+        let collected = if args.sort {
+            let mut sorted =
+                SortedEntries::new(results(finder, order, canonicalise)?, sort_memory)?;
+            let collected: Vec<_> = sorted.by_ref().take(limit).collect();
+            if let Some(error) = sorted.take_error() {
+                return Err(error.into());
+            }
+            collected
+        } else {
+            results(finder, order, canonicalise)?.take(limit).collect()
+        };
+        print_grouped_by_filesystem(collected)?;
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+
+    if let Some(rules_file) = args.rules {
+        let rules = Rules::load(rules_file, finder.root_dir())?;
+        if let Some(unknown) = args.label.iter().find(|label| !rules.has_label(label)) {
+            return Err(SearchConfigError::IOError(io::Error::other(format!(
+                "no rule gives the label '{unknown}'"
+            ))));
+        }
+
+        let mut results = results(finder, order, canonicalise)?;
+        let mut sorted = args
+            .sort
+            .then(|| SortedEntries::new(results.by_ref(), sort_memory))
+            .transpose()?;
+        let labelled: &mut dyn Iterator<Item = DirEntry> = match sorted.as_mut() {
+            Some(sorted) => sorted,
+            None => &mut results,
+        };
+        print_labelled(
+            labelled,
+            &rules,
+            &args.label,
+            args.top_n.unwrap_or(usize::MAX),
+            if args.print0 { b'\0' } else { b'\n' },
+        )?;
+        if let Some(error) = sorted.as_mut().and_then(SortedEntries::take_error) {
+            return Err(error.into());
         }
 
-        return Ok(());
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
     }
 
-    finder
-        .build_printer()?
+    if args.find_anomalies {
+        print_anomalies(
+            results(finder, order, canonicalise)?.take(args.top_n.unwrap_or(usize::MAX)),
+        )?;
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        return Ok(status);
+    }
+
+    let exec = args
+        .exec
+        .map(|command| (command, ExecMode::PerResult))
+        .or_else(|| args.exec_batch.map(|command| (command, ExecMode::Batch)));
+
+    if let Some((command, mode)) = exec {
+        let template = CommandTemplate::new(command)?.strip_leading_dot_slash(strip_cwd_prefix);
+
+        // Ordered results are run one at a time too, so the commands keep the order
+        let summary = if collects_all || matches!(order, Order::Ordered(_)) {
+            let limit = args.top_n.unwrap_or(usize::MAX);
+            let results = results(finder, order, canonicalise)?;
+            // A single thread keeps the commands in order
+            if args.sort {
+                let mut sorted = SortedEntries::new(results, sort_memory)?;
+                let summary =
+                    template.execute(sorted.by_ref().take(limit), mode, NonZeroUsize::MIN)?;
+                if let Some(error) = sorted.take_error() {
+                    return Err(error.into());
+                }
+                summary
+            } else {
+                template.execute(results.take(limit), mode, NonZeroUsize::MIN)?
+            }
+        } else {
+            let summary = finder.for_each_exec(&template, mode)?;
+            FOUND.store(summary.spawned != 0, Ordering::Relaxed);
+            summary
+        };
+
+        let status = report_errors(errors.as_deref(), args.show_errors);
+
+        if !summary.success() {
+            return Err(SearchConfigError::IOError(io::Error::other(format!(
+                "{} of {} commands exited unsuccessfully",
+                summary.failed, summary.spawned
+            ))));
+        }
+
+        return Ok(status);
+    }
+
+    PrinterBuilder::new(results(finder, order, canonicalise)?)
         .limit(args.top_n)
         .sort(args.sort)
+        .sort_memory(sort_memory)
         .null_terminated(args.print0)
+        .colour(args.colour)
         .nocolour(args.no_colour)
+        .hyperlinks(args.hyperlink)
         .quoted(args.quoted)
+        .format(args.format)
+        .summary(args.summary)
+        .long_format(args.list || args.list_human)
+        .size_format(size_format)
+        .path_separator(
+            args.path_separator
+                .map(|separator| separator.as_bytes().into()),
+        )
         .strip_leading_dot_slash(strip_cwd_prefix)
-        .print_errors(args.show_errors)
+        .relative_to(relative_to)
         .print()?;
 
-    Ok(())
+    Ok(report_errors(errors.as_deref(), args.show_errors))
+}
+/// Combines the given time filters, so a `--*-within` and a `--*-before` give a window between them
+fn time_window<const N: usize>(filters: [Option<TimeFilter>; N]) -> Option<TimeFilter> {
+    filters.into_iter().flatten().reduce(TimeFilter::intersect)
+}
+
+/// Which results to emit, and in what order
+#[derive(Clone, Copy)]
+enum Order {
+    /// Every result, as they're found
+    Walk,
+    /// Every result, in a deterministic order
+    Ordered(ResultOrder),
+    /// Every result, shuffled
+    Shuffle,
+    /// A random sample of this many results
+    Sample(NonZeroUsize),
+    /// Every result, answered from the index of the root
+    Cached,
+}
+
+/// The results of a traversal, either streamed (as found or in order) or collected up front
+enum Results<W, O, C> {
+    Walk(W),
+    Ordered(O),
+    Collected(C),
+}
+
+impl<W, O, C> Iterator for Results<W, O, C>
+where
+    W: Iterator<Item = DirEntry>,
+    O: Iterator<Item = DirEntry>,
+    C: Iterator<Item = DirEntry>,
+{
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<DirEntry> {
+        match self {
+            Self::Walk(walk) => walk.next(),
+            Self::Ordered(ordered) => ordered.next(),
+            Self::Collected(collected) => collected.next(),
+        }
+    }
+}
+
+/// Starts the search in the requested order, canonicalising each result when `canonicalise` is set
+fn results(
+    finder: Finder,
+    order: Order,
+    canonicalise: bool,
+) -> Result<impl Iterator<Item = DirEntry>, SearchConfigError> {
+    let results = match order {
+        Order::Walk => Results::Walk(finder.traverse()?),
+        Order::Ordered(order) => Results::Ordered(finder.traverse_ordered(order)?),
+        Order::Shuffle => Results::Collected(finder.shuffle()?.into_iter()),
+        Order::Sample(count) => Results::Collected(finder.sample(count)?.into_iter()),
+        Order::Cached => Results::Collected(cached_results(finder)?.into_iter()),
+    };
+
+    // Each directory is resolved once, results which can't be resolved (eg dangling symlinks) are kept as found
+    let cache = canonicalise.then(RealpathCache::new);
+    Ok(results.map(move |entry| {
+        FOUND.store(true, Ordering::Relaxed);
+        match &cache {
+            Some(cache) => cache.to_full_path(&entry).unwrap_or(entry),
+            None => entry,
+        }
+    }))
+}
+
+/// Answers from the saved index of the root, updating it with the directories which changed
+fn cached_results(finder: Finder) -> Result<Vec<DirEntry>, SearchConfigError> {
+    let mut index = PathIndex::load(finder.root_dir())?;
+    let found = finder.query_index(&mut index)?;
+    // Failing to save only costs the next search the reads the index would have saved
+    let _ = index.save();
+    Ok(found)
 }
+
 #[allow(clippy::print_stderr)] // CLI opt
-fn print_collected_errors(errors: Option<&std::sync::Mutex<Vec<TraversalError>>>) {
-    if let Some(errors_arc) = errors
-        && let Ok(error_vec) = errors_arc.lock()
-    {
-        for error in error_vec.iter() {
-            eprintln!("{error}");
+/// Writes the [`TraversalStats`] to stderr when dropped
+struct StatsReport(Arc<TraversalStats>, SizeFormat);
+
+impl Drop for StatsReport {
+    fn drop(&mut self) {
+        let (stats, size_format) = (&self.0, self.1);
+        let mut writer = io::BufWriter::new(io::stderr().lock());
+        let mut report = || -> io::Result<()> {
+            for (file_type, count) in stats.counts().filter(|&(_, count)| count != 0) {
+                writeln!(writer, "{file_type}: {count}")?;
+            }
+            writeln!(writer, "Total: {}", stats.total())?;
+            let total_bytes = stats.total_bytes();
+            if size_format == SizeFormat::Bytes {
+                writeln!(writer, "Total size: {total_bytes} bytes")?;
+            } else {
+                writeln!(
+                    writer,
+                    "Total size: {}",
+                    format_size(total_bytes, size_format)
+                )?;
+            }
+            if let Some(deepest) = stats.deepest() {
+                write!(writer, "Deepest (depth {}): ", deepest.depth())?;
+                writer.write_all(deepest.as_bytes())?;
+                writeln!(writer)?;
+            }
+            if let Some((largest, size)) = stats.largest() {
+                if size_format == SizeFormat::Bytes {
+                    write!(writer, "Largest ({size} bytes): ")?;
+                } else {
+                    write!(writer, "Largest ({}): ", format_size(size, size_format))?;
+                }
+                writer.write_all(largest.as_bytes())?;
+                writeln!(writer)?;
+            }
+            writeln!(writer, "Errors: {}", stats.errors())?;
+            writer.flush()
+        };
+        // Nothing left to report a failure to
+        let _ = report();
+    }
+}
+
+#[allow(clippy::print_stderr)] // CLI opt
+/// Warns on stderr when dropped if the depth limit for following symlinks cut the search short
+struct DepthLimitWarning(Arc<DepthLimit>);
+
+impl Drop for DepthLimitWarning {
+    fn drop(&mut self) {
+        if self.0.was_hit() {
+            eprintln!(
+                "Warning: stopped following symlinks at depth {}, pass --depth to search deeper",
+                self.0.limit()
+            );
         }
     }
 }
 
-fn run_exec_search<I>(
+/// Prints the collected errors in `format` (if given), and returns the exit status of the search
+fn report_errors(
+    errors: Option<&Mutex<Vec<TraversalError>>>,
+    format: Option<ErrorFormat>,
+) -> ExitCode {
+    let errors = errors.map(|errors| errors.lock().unwrap_or_else(PoisonError::into_inner));
+    let failed = errors.as_ref().is_some_and(|errors| !errors.is_empty());
+    match (errors, format) {
+        (Some(errors), Some(format)) => {
+            let _ = write_errors(&mut io::stderr().lock(), &errors, format);
+        }
+        // Otherwise the results being incomplete would go unnoticed
+        (Some(errors), None) if errors.iter().any(TraversalError::is_timed_out) => {
+            eprintln!("Warning: timed out, the results are incomplete");
+        }
+        _ => {}
+    }
+
+    if failed {
+        ExitCode::from(ERROR_EXIT_CODE)
+    } else if FOUND.load(Ordering::Relaxed) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(NO_MATCHES_EXIT_CODE)
+    }
+}
+
+/// Writes `label<TAB>path` for every result (the label is empty if no rule matches), keeping only `labels` if any are given
+fn print_labelled<I>(
     paths: I,
-    exec: &[OsString],
-    sort: bool,
-    limit: Option<usize>,
-    strip_leading_dot_slash: bool,
-) -> Result<(), SearchConfigError>
+    rules: &Rules,
+    labels: &[String],
+    limit: usize,
+    terminator: u8,
+) -> io::Result<()>
 where
-    I: Iterator<Item = fdf::fs::DirEntry>,
+    I: Iterator<Item = DirEntry>,
 {
-    let new_limit = limit.unwrap_or(usize::MAX);
-
-    if sort {
-        let mut collected: Vec<_> = paths.collect();
-        collected.sort_by(|left, right| left.as_bytes().cmp(right.as_bytes()));
+    let mut writer = io::BufWriter::new(stdout().lock());
+    let labelled = paths
+        .map(|entry| (rules.classify(&entry).unwrap_or_default().to_owned(), entry))
+        .filter(|(label, _)| labels.is_empty() || labels.iter().any(|wanted| wanted == label))
+        .take(limit);
 
-        for path in collected.into_iter().take(new_limit) {
-            execute_for_path(exec, &path, strip_leading_dot_slash)?;
-        }
-    } else {
-        for path in paths.take(new_limit) {
-            execute_for_path(exec, &path, strip_leading_dot_slash)?;
-        }
+    for (label, entry) in labelled {
+        writer.write_all(label.as_bytes())?;
+        writer.write_all(b"\t")?;
+        writer.write_all(entry.as_bytes())?;
+        writer.write_all(&[terminator])?;
     }
+    writer.flush()
+}
 
+/// Prints each change as it arrives, until the watch stops (eg on interrupt)
+fn print_watch_events(
+    events: fdf::crossbeam_channel::Receiver<WatchEvent>,
+    terminator: u8,
+) -> io::Result<()> {
+    let mut writer = stdout().lock();
+    for event in events {
+        writer.write_all(event.kind().as_bytes())?;
+        writer.write_all(b"\t")?;
+        writer.write_all(event.entry().as_bytes())?;
+        writer.write_all(&[terminator])?;
+        writer.flush()?;
+    }
     Ok(())
 }
-#[allow(clippy::indexing_slicing)]
-fn execute_for_path(
-    exec: &[OsString],
-    path: &fdf::fs::DirEntry,
-    strip_leading_dot_slash: bool,
-) -> Result<(), SearchConfigError> {
-    let argv = build_exec_argv(exec, displayed_path_bytes(path, strip_leading_dot_slash));
-    let status = Command::new(&argv[0]).args(&argv[1..]).status()?;
-
-    if status.success() {
-        return Ok(());
-    }
 
-    let command_name = argv[0].as_os_str().to_string_lossy();
-    Err(SearchConfigError::IOError(io::Error::other(format!(
-        "command '{command_name}' exited with status {status}"
-    ))))
+/// Writes `path: anomaly, anomaly` for every entry with hygiene problems, paths are written as raw bytes
+fn print_anomalies<I>(paths: I) -> io::Result<()>
+where
+    I: Iterator<Item = fdf::fs::DirEntry>,
+{
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for entry in paths {
+        let anomalies = entry.anomalies();
+        if anomalies.is_empty() {
+            continue;
+        }
+        writer.write_all(entry.as_bytes())?;
+        for (index, anomaly) in anomalies.iter().enumerate() {
+            let separator = if index == 0 { ": " } else { ", " };
+            write!(writer, "{separator}{anomaly}")?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
 }
 
-fn displayed_path_bytes(path: &fdf::fs::DirEntry, strip_leading_dot_slash: bool) -> &[u8] {
-    let start = usize::from(strip_leading_dot_slash) * 2;
-    // SAFETY: `strip_leading_dot_slash` is only enabled when the root is `.` or `./`,
-    // so every emitted path is guaranteed to start with `./`.
-    unsafe { path.get_unchecked(start..) }
+/// Writes `path<TAB>type` for every mounted filesystem, marking the virtual ones, paths are written as raw bytes
+fn list_mounts() -> Result<ExitCode, SearchConfigError> {
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for mount in fdf::fs::mounts::mount_points()? {
+        writer.write_all(mount.path.as_os_str().as_bytes())?;
+        writer.write_all(b"\t")?;
+        writer.write_all(mount.fs_type.as_bytes())?;
+        if mount.is_virtual() {
+            writer.write_all(b"*")?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(ExitCode::SUCCESS)
 }
 
-fn build_exec_argv(exec: &[OsString], path: &[u8]) -> Vec<OsString> {
-    let mut argv = Vec::with_capacity(exec.len() + 1);
-    let mut replaced_placeholder = false;
+/// Writes the results bucketed by device, each under a `mount point:` header, paths are written as raw bytes
+fn print_grouped_by_filesystem(paths: Vec<fdf::fs::DirEntry>) -> io::Result<()> {
+    use std::collections::BTreeMap;
 
-    for arg in exec {
-        let (replaced, did_replace) = replace_exec_placeholder(arg, path);
-        replaced_placeholder |= did_replace;
-        argv.push(replaced);
-    }
+    // A missing mount table (eg /proc isn't mounted) just means falling back to the device numbers
+    let mounts = fdf::fs::mounts::mount_points().unwrap_or_default();
 
-    if !replaced_placeholder {
-        argv.push(OsString::from_vec(path.to_vec()));
+    // Entries which can't be statted (eg removed mid traversal) are grouped under `None`
+    let mut groups: BTreeMap<Option<u64>, Vec<fdf::fs::DirEntry>> = BTreeMap::new();
+    for entry in paths {
+        #[allow(clippy::useless_conversion)] // dev_t isn't u64 everywhere
+        let device = entry
+            .get_lstat()
+            .ok()
+            .map(|statted| u64::from(statted.st_dev));
+        groups.entry(device).or_default().push(entry);
     }
 
-    argv
+    let mut groups: Vec<_> = groups
+        .into_iter()
+        .map(|(device, entries)| {
+            let header = match device {
+                Some(device) => fdf::fs::mounts::mount_point_of(&mounts, device).map_or_else(
+                    || {
+                        format!(
+                            "device {}:{}",
+                            libc::major(device as _),
+                            libc::minor(device as _)
+                        )
+                        .into_bytes()
+                    },
+                    |mount| mount.path.as_os_str().as_bytes().to_vec(),
+                ),
+                None => b"unknown device".to_vec(),
+            };
+            (header, entries)
+        })
+        .collect();
+    groups.sort_by(|left, right| left.0.cmp(&right.0));
+
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for (index, (header, entries)) in groups.iter().enumerate() {
+        if index != 0 {
+            writer.write_all(b"\n")?;
+        }
+        writer.write_all(header)?;
+        writer.write_all(b":\n")?;
+        for entry in entries {
+            writer.write_all(entry.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    writer.flush()
 }
-#[allow(clippy::indexing_slicing)]
-fn replace_exec_placeholder(arg: &OsString, path: &[u8]) -> (OsString, bool) {
-    let bytes = arg.as_os_str().as_bytes();
-    let mut index = 0;
-    let mut replaced = false;
-    let mut output = Vec::with_capacity(bytes.len().saturating_add(path.len()));
-
-    while let Some(relative_pos) = bytes[index..].windows(2).position(|window| window == b"{}") {
-        let absolute_pos = index + relative_pos;
-        output.extend_from_slice(&bytes[index..absolute_pos]);
-        output.extend_from_slice(path);
-        index = absolute_pos + 2;
-        replaced = true;
+
+/// Writes each group of identical files under a `count files of size bytes (hash):` header, paths are written as raw bytes
+#[cfg(any(feature = "xxhash", feature = "blake3"))]
+fn print_duplicates(groups: &[DuplicateGroup], canonicalise: bool) -> io::Result<()> {
+    let cache = canonicalise.then(RealpathCache::new);
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for (index, group) in groups.iter().enumerate() {
+        if index != 0 {
+            writer.write_all(b"\n")?;
+        }
+        writeln!(
+            writer,
+            "{} files of {} bytes ({:016x}):",
+            group.entries.len(),
+            group.size,
+            group.hash
+        )?;
+        for entry in &group.entries {
+            match cache
+                .as_ref()
+                .and_then(|cache| cache.to_full_path(entry).ok())
+            {
+                Some(resolved) => writer.write_all(resolved.as_bytes())?,
+                None => writer.write_all(entry.as_bytes())?,
+            }
+            writer.write_all(b"\n")?;
+        }
     }
+    writer.flush()
+}
 
-    if !replaced {
-        return (arg.clone(), false);
+/// Writes each group of hard links under an `inode on device (found of links):` header, paths are written as raw bytes
+fn print_hardlinks(groups: &[fdf::walk::InodeGroup], canonicalise: bool) -> io::Result<()> {
+    let cache = canonicalise.then(RealpathCache::new);
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for (index, group) in groups.iter().enumerate() {
+        if index != 0 {
+            writer.write_all(b"\n")?;
+        }
+        writeln!(
+            writer,
+            "inode {} on device {}:{} ({} of {} links):",
+            group.inode,
+            libc::major(group.device as _),
+            libc::minor(group.device as _),
+            group.entries.len(),
+            group.links
+        )?;
+        for entry in &group.entries {
+            match cache
+                .as_ref()
+                .and_then(|cache| cache.to_full_path(entry).ok())
+            {
+                Some(resolved) => writer.write_all(resolved.as_bytes())?,
+                None => writer.write_all(entry.as_bytes())?,
+            }
+            writer.write_all(b"\n")?;
+        }
     }
+    writer.flush()
+}
 
-    output.extend_from_slice(&bytes[index..]);
-    (OsString::from_vec(output), true)
+/// Writes every path, followed by each hop of its symlink chain, paths are written as raw bytes
+fn print_resolved<I>(paths: I) -> io::Result<()>
+where
+    I: Iterator<Item = fdf::fs::DirEntry>,
+{
+    let mut writer = io::BufWriter::new(stdout().lock());
+    for entry in paths {
+        writer.write_all(entry.as_bytes())?;
+        if entry.is_symlink() {
+            match entry.resolve_chain(fdf::fs::MAX_SYMLINK_HOPS) {
+                Ok(chain) => {
+                    for hop in &chain {
+                        writer.write_all(b" -> ")?;
+                        writer.write_all(hop.as_os_str().as_bytes())?;
+                    }
+                    if chain.len() == fdf::fs::MAX_SYMLINK_HOPS
+                        && chain.last().is_some_and(|last| last.is_symlink())
+                    {
+                        writer.write_all(b" -> ... (too many levels of symbolic links)")?;
+                    }
+                }
+                Err(error) => write!(writer, " -> ({error})")?,
+            }
+        }
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
 }
@@ -0,0 +1,209 @@
+//! Classifying search results with an ordered list of glob rules, the library side of `fdf --rules`.
+//!
+//! A rules file maps glob patterns to labels, one per line, and the first rule matching an entry gives its label:
+//!
+//! ```toml
+//! # Earlier rules take priority
+//! "target/**" = "build"
+//! "*.rs" = "source"
+//! "*.{png,jpg}" = "image"
+//! ```
+//!
+//! This is a subset of TOML: quoted (or bare) keys holding `.gitignore` style globs, string values holding labels,
+//! comments, and optionally a `[rules]` table header. As in `.gitignore`, a pattern without a slash matches the
+//! file name anywhere in the tree while one with a slash is relative to the search root.
+//! Negated (`!`) patterns aren't supported. The patterns are compiled into a single glob set, so classifying an
+//! entry takes one pass however many rules there are.
+//!
+//! ```
+//! use fdf::fs::DirEntry;
+//! use fdf::rules::Rules;
+//!
+//! let root = env!("CARGO_MANIFEST_DIR");
+//! let rules = Rules::parse("'src/**' = 'code'\n'*.toml' = 'config'\n", root).unwrap();
+//!
+//! let manifest = DirEntry::new(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+//! assert_eq!(rules.classify(&manifest), Some("config"));
+//! let main = DirEntry::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/main.rs")).unwrap();
+//! assert_eq!(rules.classify(&main), Some("code"));
+//! ```
+
+use crate::SearchConfigError;
+use crate::fs::DirEntry;
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An ordered list of glob rules, see the [module documentation](self)
+#[derive(Debug)]
+pub struct Rules {
+    /// Every rule's pattern compiled into one glob set, so classifying an entry is a single pass
+    /// however many rules there are. Each glob records its rule's index as its origin.
+    matcher: Gitignore,
+    labels: Vec<Box<str>>,
+}
+
+impl Rules {
+    /**
+     Creates rules from `(pattern, label)` pairs in priority order, with slashed patterns relative to `root`.
+
+     # Errors
+     Returns [`SearchConfigError::IOError`] if a pattern isn't a valid glob, or is negated (starts with `!`).
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new<I, P, L>(rules: I, root: impl AsRef<Path>) -> Result<Self, SearchConfigError>
+    where
+        I: IntoIterator<Item = (P, L)>,
+        P: AsRef<str>,
+        L: Into<Box<str>>,
+    {
+        let (patterns, labels): (Vec<P>, Vec<Box<str>>) = rules
+            .into_iter()
+            .map(|(pattern, label)| (pattern, label.into()))
+            .unzip();
+
+        let invalid = |pattern: &str, reason: &dyn core::fmt::Display| {
+            SearchConfigError::IOError(io::Error::other(format!(
+                "invalid rule pattern '{pattern}': {reason}"
+            )))
+        };
+
+        // The last matching glob wins in a glob set (as in `.gitignore`), so add them in reverse
+        let mut builder = GitignoreBuilder::new(root);
+        for (index, pattern) in patterns.iter().enumerate().rev() {
+            let pattern = pattern.as_ref();
+            if pattern.starts_with('!') {
+                return Err(invalid(pattern, &"negated patterns aren't supported"));
+            }
+            builder
+                .add_line(Some(PathBuf::from(index.to_string())), pattern)
+                .map_err(|error| invalid(pattern, &error))?;
+        }
+        let matcher = builder
+            .build()
+            .map_err(|error| SearchConfigError::IOError(io::Error::other(error.to_string())))?;
+
+        Ok(Self { matcher, labels })
+    }
+
+    /**
+     Parses a rules file's contents, see the [module documentation](self) for the format.
+
+     # Errors
+     Returns [`SearchConfigError::IOError`] naming the line of the first malformed rule, or if a pattern isn't a valid glob.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn parse(text: &str, root: impl AsRef<Path>) -> Result<Self, SearchConfigError> {
+        let mut rules = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line == "[rules]" {
+                continue;
+            }
+
+            let rule = parse_rule(line).ok_or_else(|| {
+                SearchConfigError::IOError(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "rules line {}: expected `\"pattern\" = \"label\"`, found `{line}`",
+                        index + 1
+                    ),
+                ))
+            })?;
+            rules.push(rule);
+        }
+
+        Self::new(rules, root)
+    }
+
+    /**
+     Reads and parses a rules file, see [`parse`](Self::parse).
+
+     # Errors
+     Returns [`SearchConfigError::IOError`] if the file can't be read or isn't valid.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn load(file: impl AsRef<Path>, root: impl AsRef<Path>) -> Result<Self, SearchConfigError> {
+        Self::parse(&std::fs::read_to_string(file)?, root)
+    }
+
+    /// Returns the label of the first rule matching `entry`, if any
+    #[inline]
+    #[must_use]
+    pub fn classify(&self, entry: &DirEntry) -> Option<&str> {
+        let glob = match self.matcher.matched(entry.as_path(), entry.is_dir()) {
+            Match::Ignore(glob) => glob,
+            Match::None | Match::Whitelist(_) => return None,
+        };
+        let index: usize = glob.from()?.to_str()?.parse().ok()?;
+        self.labels.get(index).map(|label| &**label)
+    }
+
+    /// Returns true if any rule gives `label`
+    #[inline]
+    #[must_use]
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|rule_label| &**rule_label == label)
+    }
+
+    /// Returns the number of rules
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns true if there are no rules, so nothing is ever labelled
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+/// Parses a `key = value` line, returning `None` if it's malformed
+fn parse_rule(line: &str) -> Option<(String, String)> {
+    let (pattern, rest) = parse_key(line)?;
+    let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+    let (label, rest) = parse_string(rest)?;
+    let rest = rest.trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some((pattern, label))
+}
+
+/// Parses a quoted or bare key, returning it along with the rest of the line
+fn parse_key(input: &str) -> Option<(String, &str)> {
+    if input.starts_with(['"', '\'']) {
+        return parse_string(input);
+    }
+
+    let end = input
+        .find(|chr: char| !(chr.is_ascii_alphanumeric() || chr == '_' || chr == '-'))
+        .unwrap_or(input.len());
+    (end != 0).then(|| (input[..end].to_owned(), &input[end..]))
+}
+
+/// Parses a basic (`"..."`, with escapes) or literal (`'...'`) string, returning it along with the rest of the line
+fn parse_string(input: &str) -> Option<(String, &str)> {
+    if let Some(literal) = input.strip_prefix('\'') {
+        let end = literal.find('\'')?;
+        return Some((literal[..end].to_owned(), &literal[end + 1..]));
+    }
+
+    let basic = input.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((index, chr)) = chars.next() {
+        match chr {
+            '"' => return Some((value, &basic[index + 1..])),
+            '\\' => value.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                escaped @ ('"' | '\\') => escaped,
+                _ => return None,
+            }),
+            _ => value.push(chr),
+        }
+    }
+    None
+}
@@ -0,0 +1,358 @@
+/*!
+ Streaming search results over a Unix domain socket, the library side of `fdf --serve`.
+
+ Results are written as length-prefixed records, a `u32` big-endian byte count followed by the raw path
+ (no terminator, not necessarily UTF-8), in the order the walker finds them.
+ The end of the results is the end of the stream. No record may be longer than [`MAX_RECORD_LEN`].
+
+ [`serve_queries`] (`fdf --daemon`) instead keeps the results of a traversal in a [`SharedIndex`] and answers
+ repeated queries: each query is a record holding a regex, answered by the matching paths as records
+ followed by an empty record. A client can send any number of queries on one connection, up to
+ [`MAX_CLIENTS`] clients are answered at once, and one idle for [`CLIENT_TIMEOUT`] is disconnected.
+ The index is replaced with [`SharedIndex::replace`], eg by `fdf --daemon --refresh` walking again periodically.
+
+ ```
+ use fdf::serve::{read_record, write_record};
+
+ let mut buffer = Vec::new();
+ write_record(&mut buffer, b"./src/main.rs").unwrap();
+ assert_eq!(&buffer[..4], &13u32.to_be_bytes());
+
+ let mut reader = buffer.as_slice();
+ assert_eq!(read_record(&mut reader).unwrap().as_deref(), Some(&b"./src/main.rs"[..]));
+ assert_eq!(read_record(&mut reader).unwrap(), None);
+ ```
+*/
+
+use crate::fs::DirEntry;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use regex::bytes::Regex;
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::os::unix::fs::FileTypeExt as _;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+
+/**
+ The longest record (in bytes) [`read_record`] accepts or [`write_record`] writes.
+
+ Far longer than any path the kernel accepts (`PATH_MAX` is 4096 on Linux) or any sensible query, it stops a
+ client making the reader allocate up to 4GiB from a single length header.
+*/
+pub const MAX_RECORD_LEN: usize = 1 << 16;
+
+/// How long [`serve_queries`] waits for a client to send a query (or take its answer) before disconnecting it
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Most clients [`serve_queries`] answers at once, any more are disconnected as soon as they connect
+pub const MAX_CLIENTS: usize = 64;
+
+/**
+ A listening Unix domain socket, the socket file is removed when this is dropped.
+
+ Binding replaces a stale socket left behind by a previous run, but never any other kind of file.
+*/
+#[derive(Debug)]
+pub struct SocketServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl SocketServer {
+    /**
+     Binds a new socket at `path`.
+
+     # Errors
+     Returns an error if `path` exists and isn't a socket, or the socket can't be bound.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            Err(_) => {}
+        }
+
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /**
+     Waits for the next client to connect.
+
+     # Errors
+     Returns an error if accepting the connection fails.
+    */
+    #[inline]
+    pub fn accept(&self) -> io::Result<UnixStream> {
+        self.listener.accept().map(|(stream, _)| stream)
+    }
+
+    /// The path the socket is bound to
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SocketServer {
+    #[inline]
+    fn drop(&mut self) {
+        // Nothing useful can be done if it's already gone
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/**
+ Writes a single length-prefixed record.
+
+ # Errors
+ Returns an error if the record is longer than [`MAX_RECORD_LEN`] or the write fails.
+*/
+#[inline]
+pub fn write_record<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len())
+        .ok()
+        .filter(|&len| len as usize <= MAX_RECORD_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "record is too long"))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)
+}
+
+/**
+ Reads a single length-prefixed record, returning `None` at a clean end of stream.
+
+ # Errors
+ Returns an error if the stream ends part way through a record or the read fails, and an
+ [`InvalidData`](io::ErrorKind::InvalidData) error (before reading or allocating anything more) if the length
+ header is over [`MAX_RECORD_LEN`].
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match reader.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => filled += read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_RECORD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("record of {len} bytes is over the limit of {MAX_RECORD_LEN}"),
+        ));
+    }
+    let mut record = vec![0; len];
+    reader.read_exact(&mut record)?;
+    Ok(Some(record))
+}
+
+/**
+ Writes every entry's path as a record, returning how many were written.
+
+ # Errors
+ Returns an error if a write fails, eg the client disconnected.
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn stream_records<W, I>(writer: W, entries: I) -> io::Result<usize>
+where
+    W: Write,
+    I: IntoIterator<Item = DirEntry>,
+{
+    let mut writer = BufWriter::new(writer);
+    let mut count = 0;
+    for entry in entries {
+        write_record(&mut writer, entry.as_bytes())?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/**
+ The results of a traversal kept in memory, so repeated queries don't walk the filesystem again.
+
+ The index is a snapshot, it doesn't notice files created or removed after it was built.
+ Keep it current by building a new one and swapping it into a [`SharedIndex`].
+*/
+#[derive(Debug, Default)]
+pub struct Index {
+    /// Each path with the index its file name starts at, kept as plain bytes (unlike a [`DirEntry`]) so the
+    /// index can be shared between threads
+    entries: Vec<(Box<[u8]>, usize)>,
+}
+
+impl Index {
+    /// Builds an index from the results of a traversal
+    #[inline]
+    pub fn new<I: IntoIterator<Item = DirEntry>>(entries: I) -> Self {
+        Self {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.as_bytes().into(), entry.file_name_index()))
+                .collect(),
+        }
+    }
+
+    /// The number of indexed entries
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if nothing was indexed
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /**
+     Returns the paths matching `pattern`, an empty pattern matches everything.
+
+     As on the command line, the pattern is matched against the file name unless it contains a `/`,
+     in which case it's matched against the full path.
+
+     # Errors
+     Returns an error if `pattern` isn't a valid regex.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn query<'index>(
+        &'index self,
+        pattern: &str,
+    ) -> Result<impl Iterator<Item = &'index [u8]>, regex::Error> {
+        let regex = (!pattern.is_empty())
+            .then(|| Regex::new(pattern))
+            .transpose()?;
+        let full_path = pattern.contains('/');
+
+        Ok(self
+            .entries
+            .iter()
+            .filter(move |(path, file_name_index)| {
+                regex.as_ref().is_none_or(|regex| {
+                    regex.is_match(if full_path {
+                        path
+                    } else {
+                        &path[*file_name_index..]
+                    })
+                })
+            })
+            .map(|(path, _)| &**path))
+    }
+}
+
+/**
+ An [`Index`] shared by the clients of [`serve_queries`], which can be replaced while they're being answered.
+
+ Each query is answered from the index current when it arrives, so a replacement only affects later queries.
+*/
+#[derive(Debug, Default)]
+pub struct SharedIndex {
+    current: RwLock<Arc<Index>>,
+}
+
+impl SharedIndex {
+    /// Shares `index`
+    #[inline]
+    #[must_use]
+    pub fn new(index: Index) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(index)),
+        }
+    }
+
+    /// The index queries are currently answered from
+    #[inline]
+    #[must_use]
+    pub fn current(&self) -> Arc<Index> {
+        // Only ever holds a complete index, so a panic elsewhere can't have left it half written
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Answers later queries from `index`, eg the results of walking again
+    #[inline]
+    pub fn replace(&self, index: Index) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(index);
+    }
+}
+
+impl From<Index> for SharedIndex {
+    #[inline]
+    fn from(index: Index) -> Self {
+        Self::new(index)
+    }
+}
+
+/**
+ Answers queries against `index` from every client of `server`, forever.
+
+ Each client is answered on its own thread, up to [`MAX_CLIENTS`] at once, and is disconnected once it leaves
+ a read or write waiting for [`CLIENT_TIMEOUT`], so a stalled client can't hold up the others.
+ A query which isn't valid UTF-8 or a valid regex is answered with no results.
+ A client disconnecting (even mid reply) only ends that client's session.
+
+ # Errors
+ Returns an error only if accepting a connection fails, once every client being answered is done.
+*/
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn serve_queries(server: &SocketServer, index: &SharedIndex) -> io::Result<()> {
+    let clients = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        loop {
+            let stream = server.accept()?;
+            // Dropping the stream disconnects a client which can't be answered
+            if clients.load(Ordering::Acquire) >= MAX_CLIENTS
+                || stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+
+            clients.fetch_add(1, Ordering::AcqRel);
+            let clients = &clients;
+            scope.spawn(move || {
+                // The client's problem, not the daemon's
+                let _ = answer_queries(&stream, index);
+                clients.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+    })
+}
+
+/// Answers every query from one client until it closes the connection
+fn answer_queries(stream: &UnixStream, index: &SharedIndex) -> io::Result<()> {
+    let mut reader = io::BufReader::new(stream);
+    let mut writer = BufWriter::new(stream);
+
+    while let Some(query) = read_record(&mut reader)? {
+        let index = index.current();
+        let matches = core::str::from_utf8(&query)
+            .ok()
+            .and_then(|pattern| index.query(pattern).ok());
+        for path in matches.into_iter().flatten() {
+            write_record(&mut writer, path)?;
+        }
+        write_record(&mut writer, b"")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -10,6 +10,8 @@ const RANDOM_SEED: u64 = 4269;
 mod tests {
     #![allow(dead_code)]
     #![allow(unused_imports)]
+    #![allow(clippy::useless_transmute, clippy::unnecessary_cast)]
+    #![allow(clippy::useless_conversion, clippy::needless_borrows_for_generic_args)]
     use super::*;
     use crate::filters::{SizeFilter, TimeFilter};
     use crate::fs::{DirEntry, FileType};
@@ -183,6 +185,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_swar_zero_byte_helpers_match_scalar() {
+        use crate::util::swar::{
+            contains_zero_byte_u64, find_last_zero_byte_u64, find_last_zero_byte_u64_scalar,
+            find_zero_byte_u64, find_zero_byte_u64_scalar,
+        };
+
+        let mut rng = StdRng::seed_from_u64(RANDOM_SEED);
+        let mut words = generate_random_u64_arrays(10_000, DETERMINISTIC);
+        // Bias towards 0x00/0x01/0x80/0xFF bytes, these are the cases which break naive SWAR (borrows/carries)
+        for bytes in words.iter_mut().step_by(2) {
+            for byte in bytes.iter_mut() {
+                *byte = [0x00, 0x01, 0x80, 0xFF, *byte][rng.random_range(0..5)];
+            }
+        }
+        words.extend([[0; 8], [0xFF; 8], [1; 8], [0, 1, 1, 1, 1, 1, 1, 1]]);
+
+        for bytes in words {
+            let word = u64::from_ne_bytes(bytes);
+            assert_eq!(
+                find_zero_byte_u64(word),
+                find_zero_byte_u64_scalar(word),
+                "first zero byte mismatch for {bytes:?}"
+            );
+            assert_eq!(
+                find_last_zero_byte_u64(word),
+                find_last_zero_byte_u64_scalar(word),
+                "last zero byte mismatch for {bytes:?}"
+            );
+            assert_eq!(contains_zero_byte_u64(word), bytes.contains(&0));
+        }
+    }
+
     #[test]
     fn check_filenames() {
         let temp_dir = std::env::temp_dir();
@@ -812,6 +847,36 @@ mod tests {
         let _ = fs::remove_dir_all(&tdir);
     }
 
+    #[test]
+    fn test_is_empty_dir_and_cheap() {
+        let temp_dir = temp_dir().join("is_empty_dir_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("empty")).unwrap();
+        fs::create_dir_all(temp_dir.join("full")).unwrap();
+        fs::write(temp_dir.join("full/file"), b"data").unwrap();
+        File::create(temp_dir.join("empty.txt")).unwrap();
+        std::os::unix::fs::symlink(temp_dir.join("empty"), temp_dir.join("link")).unwrap();
+
+        let entry = |name: &str| DirEntry::new(temp_dir.join(name)).unwrap();
+        assert!(entry("empty").is_empty_dir());
+        assert!(!entry("full").is_empty_dir());
+        assert!(!entry("empty.txt").is_empty_dir());
+        // Symlinks are followed, unlike is_empty
+        assert!(entry("link").is_empty_dir());
+        assert!(!entry("link").is_empty());
+
+        assert_eq!(entry("empty.txt").is_empty_cheap(), Some(true));
+        assert_eq!(entry("full/file").is_empty_cheap(), Some(false));
+        assert_eq!(entry("link").is_empty_cheap(), None);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            assert_eq!(entry("empty").is_empty_cheap(), Some(true));
+            assert_eq!(entry("full").is_empty_cheap(), Some(false));
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
     #[test]
     fn test_dirname() {
         let temp_dir = std::env::temp_dir();
@@ -937,6 +1002,24 @@ mod tests {
         let _ = fs::remove_dir_all(dir);
     }
 
+    #[test]
+    fn test_parse_byte_size() {
+        use crate::filters::{ParseSizeError, parse_byte_size};
+
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("10mi"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_byte_size(" 1GB "), Ok(1_000_000_000));
+        // Sizes are amounts here, not filters, so there's no prefix
+        assert_eq!(parse_byte_size("+1m"), Err(ParseSizeError::InvalidNumber));
+        assert_eq!(parse_byte_size("-1m"), Err(ParseSizeError::InvalidNumber));
+        assert_eq!(parse_byte_size("1x"), Err(ParseSizeError::InvalidUnit));
+        assert_eq!(parse_byte_size(""), Err(ParseSizeError::Empty));
+        assert_eq!(
+            parse_byte_size("99999999ti"),
+            Err(ParseSizeError::InvalidNumber)
+        );
+    }
+
     #[test]
     fn test_size_filter_from_string() {
         assert_eq!(SizeFilter::from_string("100"), Ok(SizeFilter::Equals(100)));
@@ -1850,4 +1933,4758 @@ mod tests {
 
         fs::remove_dir_all(&temp_dir).unwrap();
     }
+
+    #[test]
+    fn test_getdents_buffer_size() {
+        let temp_dir = temp_dir().join("getdents_buffer_size_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("nested")).unwrap();
+
+        // Enough entries to need several syscalls with the smallest buffer
+        for i in 0..64 {
+            fs::write(temp_dir.join(format!("file_{i:02}.txt")), "").unwrap();
+            fs::write(
+                temp_dir.join("nested").join(format!("inner_{i:02}.txt")),
+                "",
+            )
+            .unwrap();
+        }
+
+        let collect_with = |size: Option<usize>| {
+            let builder = Finder::init(&temp_dir).pattern(".");
+            let builder = match size {
+                Some(size) => builder.getdents_buffer_size(size),
+                None => builder,
+            };
+            let mut names: Vec<Vec<u8>> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let default_names = collect_with(None);
+        assert_eq!(default_names.len(), 129);
+        assert_eq!(
+            collect_with(Some(crate::fs::MIN_BUFFER_SIZE)),
+            default_names
+        );
+        assert_eq!(
+            collect_with(Some(crate::fs::MIN_BUFFER_SIZE + 3)),
+            default_names
+        );
+        assert_eq!(collect_with(Some(1 << 20)), default_names);
+
+        let too_small = Finder::init(&temp_dir)
+            .getdents_buffer_size(crate::fs::MIN_BUFFER_SIZE - 1)
+            .build();
+        assert!(matches!(
+            too_small,
+            Err(crate::SearchConfigError::InvalidBufferSize(_))
+        ));
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        {
+            // A smaller buffer is what the kernel sees, the `SyscallBuffer` view stays whole
+            let mut small = DirEntry::new(&temp_dir)
+                .unwrap()
+                .getdents_with_buffer_size(crate::fs::MIN_BUFFER_SIZE)
+                .unwrap();
+            assert_eq!(small.buffer_size(), crate::fs::MIN_BUFFER_SIZE);
+            assert_eq!(
+                size_of_val(small.kernel_buffer()),
+                crate::fs::MIN_BUFFER_SIZE
+            );
+            assert_eq!(size_of_val(small.syscall_buffer()), crate::fs::BUFFER_SIZE);
+            assert_eq!(small.count(), 65);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_runtime_limits() {
+        use crate::fs::limits::{cpu_count, default_buffer_size, name_max, page_size, path_max};
+
+        // SAFETY: trivially safe
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        assert_eq!(page_size(), usize::try_from(page).unwrap());
+        let buffer_size = default_buffer_size();
+        assert!(buffer_size >= crate::fs::BUFFER_SIZE);
+        assert!(buffer_size.is_multiple_of(page_size()));
+        assert_eq!(
+            Finder::init(".").build().unwrap().getdents_buffer_size,
+            buffer_size
+        );
+
+        // POSIX minimums, every real filesystem is well above them
+        assert!(name_max() >= 14);
+        assert!(path_max() >= 256);
+        assert_eq!(
+            cpu_count(),
+            std::thread::available_parallelism().unwrap_or(core::num::NonZeroUsize::MIN)
+        );
+    }
+
+    #[test]
+    fn test_postorder_emits_parents_after_descendants() {
+        use crate::walk::TraversalStrategy;
+
+        let temp_dir = temp_dir().join("postorder_traversal_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        // A few levels of fan out so several threads end up working on the same subtree
+        for a in 0..4 {
+            for b in 0..4 {
+                let leaf = temp_dir
+                    .join(format!("a{a}"))
+                    .join(format!("b{b}"))
+                    .join("c");
+                fs::create_dir_all(&leaf).unwrap();
+                for f in 0..8 {
+                    fs::write(leaf.join(format!("file{f}")), "").unwrap();
+                }
+                fs::write(leaf.parent().unwrap().join("sibling"), "").unwrap();
+            }
+        }
+
+        let collect = |strategy| -> Vec<Vec<u8>> {
+            Finder::init(&temp_dir)
+                .strategy(strategy)
+                .thread_count(core::num::NonZeroUsize::new(8))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.to_vec())
+                .collect()
+        };
+
+        let postorder = collect(TraversalStrategy::PostOrder);
+        let mut depth_first = collect(TraversalStrategy::DepthFirst);
+
+        for (index, path) in postorder.iter().enumerate() {
+            let mut prefix = path.clone();
+            prefix.push(b'/');
+            for later in &postorder[index + 1..] {
+                assert!(
+                    !later.starts_with(&prefix),
+                    "{} was emitted after its ancestor {}",
+                    String::from_utf8_lossy(later),
+                    String::from_utf8_lossy(path)
+                );
+            }
+        }
+
+        let mut sorted_postorder = postorder.clone();
+        sorted_postorder.sort();
+        depth_first.sort();
+        assert_eq!(sorted_postorder, depth_first);
+        // 4 `a` dirs + 16 `b` dirs (each with a sibling file) + 16 `c` dirs with 8 files each
+        assert_eq!(postorder.len(), 4 + 16 * 2 + 16 * 9);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dot_ignore_and_fdignore_files() {
+        let temp_dir = temp_dir().join("dot_ignore_fdignore_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+
+        // Not a git repository, so `.gitignore` must not apply but `.ignore`/`.fdignore` do
+        fs::write(temp_dir.join(".gitignore"), "*.rs\n").unwrap();
+        fs::write(temp_dir.join(".ignore"), "*.log\nbuild/\n").unwrap();
+        fs::write(temp_dir.join("sub").join(".fdignore"), "!keep.log\n*.tmp\n").unwrap();
+
+        fs::write(temp_dir.join("main.rs"), "").unwrap();
+        fs::write(temp_dir.join("drop.log"), "").unwrap();
+        fs::create_dir_all(temp_dir.join("build")).unwrap();
+        fs::write(temp_dir.join("build").join("artifact"), "").unwrap();
+        fs::write(temp_dir.join("sub").join("keep.log"), "").unwrap();
+        fs::write(temp_dir.join("sub").join("drop.tmp"), "").unwrap();
+
+        let collect = |respect| {
+            let mut names: Vec<Vec<u8>> = Finder::init(&temp_dir)
+                .respect_gitignore(respect)
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .filter(|entry| entry.is_regular_file())
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            collect(true),
+            vec![b"keep.log".to_vec(), b"main.rs".to_vec()]
+        );
+        assert_eq!(collect(false).len(), 5);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_results_is_exact_across_threads() {
+        let temp_dir = temp_dir().join("max_results_exact_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+
+        for d in 0..16 {
+            let sub = temp_dir.join(format!("dir{d}"));
+            fs::create_dir_all(&sub).unwrap();
+            for f in 0..64 {
+                fs::write(sub.join(format!("file{f}")), "").unwrap();
+            }
+        }
+
+        for limit in [0, 1, 37, 300, 10_000] {
+            let count = Finder::init(&temp_dir)
+                .max_results(Some(limit))
+                .thread_count(core::num::NonZeroUsize::new(8))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .count();
+            // 16 directories + 1024 files in total
+            assert_eq!(count, limit.min(16 + 16 * 64), "limit {limit}");
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_early_termination_stops_reading_queued_directories() {
+        use crate::walk::CancellationToken;
+        use core::num::NonZeroUsize;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        let temp_dir = temp_dir().join("early_termination_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for d in 0..100 {
+            let sub = temp_dir.join(format!("dir{d}"));
+            fs::create_dir_all(&sub).unwrap();
+            for f in 0..3 {
+                fs::write(sub.join(format!("file{f}")), "").unwrap();
+            }
+        }
+
+        // Every directory below the root is offered to the prune closure just before it's read
+        let walk = |max_results: Option<usize>, cancel_after: Option<usize>| {
+            let reads = Arc::new(AtomicUsize::new(0));
+            let token = CancellationToken::new();
+            let counter = Arc::clone(&reads);
+            let cancel = token.clone();
+            let found = Finder::init(&temp_dir)
+                .pattern("^file")
+                .max_results(max_results)
+                .thread_count(NonZeroUsize::new(1))
+                .cancellation_token(token)
+                .prune_dirs(move |_| {
+                    let read = counter.fetch_add(1, Ordering::Relaxed) + 1;
+                    if cancel_after.is_some_and(|after| read >= after) {
+                        cancel.cancel();
+                    }
+                    false
+                })
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .count();
+            (found, reads.load(Ordering::Relaxed))
+        };
+
+        assert_eq!(walk(None, None), (300, 100));
+
+        // The first directory read fills the limit, the 99 still queued are dropped unread
+        let (found, reads) = walk(Some(2), None);
+        assert_eq!(found, 2);
+        assert!(reads <= 2, "{reads} directories read");
+
+        // Likewise once cancelled, whatever is queued is dropped
+        let (found, reads) = walk(None, Some(5));
+        assert!(found <= 5 * 3, "{found} results");
+        assert!(reads <= 6, "{reads} directories read");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_mount_point_and_follow_mounts() {
+        let temp_dir = temp_dir().join("follow_mounts_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a").join("b")).unwrap();
+        fs::write(temp_dir.join("a").join("b").join("file"), "").unwrap();
+
+        assert!(DirEntry::new("/").unwrap().is_mount_point());
+        assert!(!DirEntry::new(temp_dir.join("a")).unwrap().is_mount_point());
+
+        // No mounts in a plain temp tree, so skipping them must not change the results
+        let count = |follow| {
+            Finder::init(&temp_dir)
+                .follow_mounts(follow)
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .count()
+        };
+        assert_eq!(count(false), 3);
+        assert_eq!(count(true), 3);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dev_is_cached_and_matches_metadata() {
+        use std::os::unix::fs::MetadataExt as _;
+        let temp_dir = temp_dir().join("dev_cache_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let entry = DirEntry::new(&temp_dir).unwrap();
+        assert_eq!(entry.device.get(), None);
+        let expected = fs::metadata(&temp_dir).unwrap().dev();
+        assert_eq!(entry.dev().unwrap(), expected);
+        assert_eq!(entry.device.get(), Some(expected));
+        // Served from the cache, even once the directory is gone
+        fs::remove_dir_all(&temp_dir).unwrap();
+        assert_eq!(entry.dev().unwrap(), expected);
+
+        assert!(DirEntry::new("/").unwrap().dev().is_ok());
+    }
+
+    #[test]
+    fn test_same_filesystem_stays_on_root_device() {
+        use std::os::unix::fs::MetadataExt as _;
+        let root_device = fs::metadata("/").unwrap().dev();
+        for same_filesystem in [false, true] {
+            let finder = Finder::init("/")
+                .same_filesystem(same_filesystem)
+                .build()
+                .unwrap();
+            assert_eq!(finder.root_device().unwrap(), root_device);
+        }
+
+        // Only meaningful where /proc (or /dev) is a filesystem of its own, as is usual on Linux
+        let Some(mounted) = ["/proc", "/dev", "/sys"].into_iter().find(|path| {
+            fs::metadata(path).is_ok_and(|meta| meta.is_dir() && meta.dev() != root_device)
+        }) else {
+            return;
+        };
+        let results: Vec<DirEntry> = Finder::init("/")
+            .same_filesystem(true)
+            .keep_hidden(false)
+            .max_depth(Some(2))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .collect();
+
+        // Nothing below the other filesystem is read
+        assert!(!results.is_empty());
+        let prefix = format!("{mounted}/");
+        assert!(
+            !results
+                .iter()
+                .any(|entry| entry.as_bytes().starts_with(prefix.as_bytes()))
+        );
+        // The directories carry the device the check already statted
+        for entry in results.iter().filter(|entry| entry.is_dir()) {
+            assert_eq!(entry.device.get(), Some(root_device), "{entry:?}");
+        }
+    }
+
+    #[test]
+    fn test_exec_placeholders_and_summary() {
+        use crate::exec::{CommandTemplate, ExecMode};
+
+        let template =
+            CommandTemplate::new(["echo", "{}", "{.}", "{/}", "{//}", "x{/}.bak"]).unwrap();
+        assert_eq!(
+            template.argv(b"./dir/archive.tar.gz"),
+            [
+                "echo",
+                "./dir/archive.tar.gz",
+                "./dir/archive.tar",
+                "archive.tar.gz",
+                "./dir",
+                "xarchive.tar.gz.bak"
+            ]
+        );
+        // Hidden files have no extension and bare names have `.` as their parent
+        assert_eq!(
+            template.argv(b".bashrc"),
+            ["echo", ".bashrc", ".bashrc", ".bashrc", ".", "x.bashrc.bak"]
+        );
+
+        let appended = CommandTemplate::new(["ls", "-l"])
+            .unwrap()
+            .strip_leading_dot_slash(true);
+        assert_eq!(appended.argv(b"./a"), ["ls", "-l", "a"]);
+        assert_eq!(
+            appended.batch_argv(&[b"./a", b"./b"]),
+            ["ls", "-l", "a", "b"]
+        );
+        assert!(CommandTemplate::new(Vec::<String>::new()).is_err());
+
+        let temp_dir = temp_dir().join("exec_summary_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        for f in 0..5 {
+            fs::write(temp_dir.join(format!("file{f}")), "").unwrap();
+        }
+
+        let run = |command: &str, mode| {
+            Finder::init(&temp_dir)
+                .build()
+                .unwrap()
+                .for_each_exec(&CommandTemplate::new([command]).unwrap(), mode)
+                .unwrap()
+        };
+
+        let ok = run("true", ExecMode::PerResult);
+        assert_eq!((ok.spawned, ok.failed, ok.exit_code()), (5, 0, 0));
+        let failed = run("false", ExecMode::PerResult);
+        assert_eq!(
+            (failed.spawned, failed.failed, failed.exit_code()),
+            (5, 5, 1)
+        );
+        let batched = run("true", ExecMode::Batch);
+        assert_eq!((batched.spawned, batched.failed), (1, 0));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverse_projected_fields() {
+        use crate::walk::Fields;
+
+        let temp_dir = temp_dir().join("projection_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        fs::write(temp_dir.join("a"), "12345").unwrap();
+        fs::write(temp_dir.join("sub").join("b"), "123").unwrap();
+
+        let project = |fields| {
+            Finder::init(&temp_dir)
+                .project(fields)
+                .build()
+                .unwrap()
+                .traverse_projected()
+                .unwrap()
+                .collect::<Vec<_>>()
+        };
+
+        let counted = project(Fields::NONE);
+        assert_eq!(counted.len(), 3);
+        assert!(counted.iter().all(|entry| entry.as_bytes().is_none()
+            && entry.size().is_none()
+            && entry.modified().is_none()));
+
+        let sized = project(Fields::SIZE);
+        let file_total: u64 = sized
+            .iter()
+            .filter(|entry| entry.file_type().is_regular_file())
+            .filter_map(|entry| entry.size())
+            .sum();
+        assert_eq!(file_total, 8);
+        assert!(sized.iter().all(|entry| entry.as_bytes().is_none()));
+
+        let mut paths: Vec<_> = project(Fields::PATH | Fields::MODIFIED)
+            .into_iter()
+            .filter(|entry| entry.depth() == 2)
+            .map(|entry| {
+                assert!(entry.modified().is_some());
+                entry.as_bytes().unwrap().to_vec()
+            })
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                temp_dir
+                    .join("sub")
+                    .join("b")
+                    .as_os_str()
+                    .as_bytes()
+                    .to_vec()
+            ]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_output_formats() {
+        use crate::util::{OutputFormat, write_json};
+
+        let temp_dir = temp_dir().join("json_output_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let tricky = temp_dir.join("quote\"back\\slash\nline\u{1}");
+        fs::write(&tricky, "").unwrap();
+
+        let render = |format| {
+            let mut out = Vec::new();
+            write_json(&mut out, [DirEntry::new(&tricky).unwrap()], format, None).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let prefix = temp_dir.to_str().unwrap();
+        let object = format!(
+            r#"{{"path":"{prefix}/quote\"back\\slash\nline\u0001","type":"regular_file","depth":0}}"#
+        );
+        assert_eq!(render(OutputFormat::Ndjson), format!("{object}\n"));
+        assert_eq!(render(OutputFormat::Json), format!("[\n{object}\n]\n"));
+        assert_eq!("ndjson".parse::<OutputFormat>(), Ok(OutputFormat::Ndjson));
+        assert!("xml".parse::<OutputFormat>().is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_extensions_summary() {
+        use crate::walk::ExtensionSummary;
+
+        let temp_dir = temp_dir().join("extensions_summary_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src.d")).unwrap();
+        fs::write(temp_dir.join("src.d/main.rs"), b"fn main() {}").unwrap();
+        fs::write(temp_dir.join("lib.rs"), b"//").unwrap();
+        fs::write(temp_dir.join("build.py"), b"").unwrap();
+        fs::write(temp_dir.join("Makefile"), b"all:").unwrap();
+
+        let summary = Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .extensions_summary()
+            .unwrap();
+        let expected = |count, bytes| ExtensionSummary { count, bytes };
+        // Directories and files without an extension aren't counted
+        assert_eq!(
+            summary.into_iter().collect::<Vec<_>>(),
+            [
+                (b"py"[..].into(), expected(1, 0)),
+                (b"rs"[..].into(), expected(2, 14)),
+            ]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_errors_formats() {
+        use crate::TraversalError;
+        use crate::util::{ErrorFormat, write_errors};
+
+        let errors = [
+            TraversalError {
+                dir: DirEntry::new("/").unwrap(),
+                error: std::io::Error::from_raw_os_error(libc::EACCES).into(),
+            },
+            TraversalError {
+                dir: DirEntry::new("/").unwrap(),
+                error: crate::DirEntryError::TimeError,
+            },
+        ];
+        let render = |format| {
+            let mut out = Vec::new();
+            write_errors(&mut out, &errors, format).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let json = render(ErrorFormat::Json);
+        let lines: Vec<_> = json.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!(
+            r#"{{"path":"/","errno":{},"operation":"read_dir","message":"I/O error: Permission denied"#,
+            libc::EACCES
+        )));
+        assert_eq!(
+            lines[1],
+            r#"{"path":"/","errno":null,"operation":"read_dir","message":"Invalid time conversion"}"#
+        );
+        assert_eq!(
+            render(ErrorFormat::Text),
+            format!("{}\n{}\n", errors[0], errors[1])
+        );
+        assert_eq!("json".parse::<ErrorFormat>(), Ok(ErrorFormat::Json));
+        assert!("yaml".parse::<ErrorFormat>().is_err());
+    }
+
+    #[test]
+    fn test_literal_pattern_fast_path() {
+        use crate::config::PathMatcher;
+
+        let matcher = |pattern: &str, fixed, case_insensitive| {
+            Finder::init("/")
+                .pattern(pattern)
+                .fixed_string(fixed)
+                .case_insensitive(case_insensitive)
+                .build()
+                .unwrap()
+                .search_config
+                .regex_match
+                .clone()
+                .unwrap()
+        };
+
+        // Plain literals (including `regex::escape`d ones) skip regex compilation
+        let literal = matcher("foo-bar", false, false);
+        assert!(matches!(literal, PathMatcher::Literal(_)));
+        assert!(literal.is_match(b"xfoo-barx") && !literal.is_match(b"foo_bar"));
+
+        let fixed = matcher("a.b(c)", true, false);
+        assert!(matches!(fixed, PathMatcher::Literal(_)));
+        assert!(fixed.is_match(b"za.b(c)") && !fixed.is_match(b"axb(c)"));
+
+        // `.` keeps its regex meaning, and only non ASCII case insensitive letters need the regex engine
+        let dotted = matcher("main.rs", false, false);
+        assert!(matches!(dotted, PathMatcher::Regex(_)));
+        assert!(dotted.is_match(b"main_rs"));
+        assert!(matches!(
+            matcher("caf\u{e9}", false, true),
+            PathMatcher::Regex(_)
+        ));
+        let folded = matcher("Foo-Bar", false, true);
+        assert!(matches!(folded, PathMatcher::AsciiCaseInsensitive(_)));
+        assert!(folded.is_match(b"/x/FOO-bar.rs") && folded.is_match(b"ffoo-barr"));
+        assert!(!folded.is_match(b"foo_bar") && !folded.is_match(b"foo-ba"));
+        assert!(matches!(
+            matcher("123", false, true),
+            PathMatcher::Literal(_)
+        ));
+        assert!(matches!(
+            matcher(r"\d", false, false),
+            PathMatcher::Regex(_)
+        ));
+
+        // The default config is case insensitive, so plain names should still skip the regex engine
+        let default = Finder::init("/")
+            .pattern("readme")
+            .build()
+            .unwrap()
+            .search_config
+            .regex_match
+            .clone()
+            .unwrap();
+        assert!(matches!(default, PathMatcher::AsciiCaseInsensitive(_)));
+        assert!(default.is_match(b"README.md"));
+    }
+
+    #[test]
+    fn test_find_anomalies() {
+        use crate::fs::Anomaly;
+
+        let temp_dir = temp_dir().join("anomalies_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("clean.txt"), "").unwrap();
+        fs::write(temp_dir.join(OsStr::from_bytes(b"tab\tand\xff")), "").unwrap();
+
+        let mut found: Vec<(Vec<u8>, Vec<Anomaly>)> = Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .map(|entry| (entry.file_name().to_vec(), entry.anomalies()))
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            found,
+            vec![
+                (b"clean.txt".to_vec(), vec![]),
+                (
+                    b"tab\tand\xff".to_vec(),
+                    vec![Anomaly::ControlCharacters, Anomaly::InvalidUtf8]
+                ),
+            ]
+        );
+        assert_eq!(
+            Anomaly::TypeMismatch {
+                reported: FileType::RegularFile,
+                actual: FileType::Directory
+            }
+            .to_string(),
+            "directory entry type Regular file disagrees with lstat (Directory)"
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_chain_loops_and_dangling() {
+        let temp_dir = temp_dir().join("resolve_chain_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        symlink("loop_b", temp_dir.join("loop_a")).unwrap();
+        symlink("loop_a", temp_dir.join("loop_b")).unwrap();
+        symlink("missing", temp_dir.join("dangling")).unwrap();
+        fs::write(temp_dir.join("plain"), "").unwrap();
+
+        let chain = |name: &str, hops| {
+            DirEntry::new(temp_dir.join(name))
+                .unwrap()
+                .resolve_chain(hops)
+                .unwrap()
+        };
+
+        let looped = chain("loop_a", 5);
+        assert_eq!(looped.len(), 5);
+        assert!(looped.last().unwrap().is_symlink());
+        assert_eq!(chain("dangling", 5), vec![temp_dir.join("missing")]);
+        assert!(chain("plain", 5).is_empty());
+        assert!(chain("loop_a", 0).is_empty());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_changed_within_before_with_size_filter() {
+        let temp_dir = temp_dir().join("changed_within_before_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let old_time = FileTime::from_unix_time(1_500_000_000, 0); // 2017
+        for (name, contents, old) in [
+            ("old_big", "0123456789", true),
+            ("old_small", "", true),
+            ("new_big", "0123456789", false),
+        ] {
+            let path = temp_dir.join(name);
+            fs::write(&path, contents).unwrap();
+            if old {
+                set_file_times(&path, old_time, old_time).unwrap();
+            }
+        }
+
+        let names = |time: TimeFilter| {
+            let mut found: Vec<Vec<u8>> = Finder::init(&temp_dir)
+                .filter_by_time(Some(time))
+                .filter_by_size(Some(SizeFilter::Min(5)))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            found.sort();
+            found
+        };
+
+        assert_eq!(
+            names(TimeFilter::changed_before("2020-01-01").unwrap()),
+            vec![b"old_big".to_vec()]
+        );
+        assert_eq!(
+            names(TimeFilter::changed_within("1d").unwrap()),
+            vec![b"new_big".to_vec()]
+        );
+        assert!(TimeFilter::changed_before("2020-13-01").is_err());
+        assert!(TimeFilter::changed_within("").is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_parse_mountinfo_and_mount_point_of() {
+        use crate::fs::mounts::{mount_point_of, parse_mountinfo};
+
+        let mountinfo = b"22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw\n\
+            40 22 0:35 / /mnt/with\\040space rw shared:2 - tmpfs tmpfs rw\n\
+            41 22 259:2 /srv /bind rw shared:1 - ext4 /dev/nvme0n1p2 rw\n\
+            garbage line\n";
+        let mounts = parse_mountinfo(mountinfo);
+
+        assert_eq!(mounts.len(), 3, "the malformed line should be skipped");
+        assert_eq!(mounts[1].path, Path::new("/mnt/with space"));
+        assert_eq!(mounts[1].device, libc::makedev(0, 35) as u64);
+        assert_eq!(&*mounts[0].fs_type, "ext4");
+        assert_eq!(&*mounts[1].fs_type, "tmpfs");
+        assert!(!mounts[1].is_virtual());
+        let proc = parse_mountinfo(b"25 22 0:23 / /proc rw,nosuid - proc proc rw\n");
+        assert_eq!(proc[0].path, Path::new("/proc"));
+        assert!(proc[0].is_virtual());
+
+        // The bind mount shares a device with `/`, the original mount wins
+        let root = mount_point_of(&mounts, libc::makedev(259, 2) as u64).unwrap();
+        assert_eq!(root.path, Path::new("/"));
+        assert!(mount_point_of(&mounts, libc::makedev(1, 1) as u64).is_none());
+
+        // The real table always has the root mounted
+        let real = crate::fs::mounts::mount_points().unwrap();
+        let root_dev = fs::symlink_metadata("/").unwrap();
+        assert!(mount_point_of(&real, std::os::unix::fs::MetadataExt::dev(&root_dev)).is_some());
+    }
+
+    #[test]
+    fn test_skip_virtual_fs() {
+        use std::os::unix::fs::MetadataExt as _;
+        let virtual_devices = crate::fs::mounts::virtual_devices().unwrap_or_default();
+        let walk = |skip| -> Vec<DirEntry> {
+            Finder::init("/")
+                .skip_virtual_fs(skip)
+                .keep_hidden(false)
+                .max_depth(Some(2))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .collect()
+        };
+
+        let skipped = walk(true);
+        // The mount points are still reported, only their contents are skipped
+        for mounted in skipped.iter().filter(|entry| {
+            entry.is_dir()
+                && fs::metadata(entry.as_path())
+                    .is_ok_and(|meta| virtual_devices.binary_search(&meta.dev()).is_ok())
+        }) {
+            let prefix = [mounted.as_bytes(), b"/"].concat();
+            assert!(
+                !skipped
+                    .iter()
+                    .any(|entry| entry.as_bytes().starts_with(&prefix)),
+                "{mounted:?} was read"
+            );
+        }
+        if virtual_devices.is_empty() {
+            assert_eq!(skipped.len(), walk(false).len());
+        }
+
+        // Searching within a virtual filesystem still reads it
+        let proc_is_virtual = fs::metadata("/proc")
+            .is_ok_and(|meta| virtual_devices.binary_search(&meta.dev()).is_ok());
+        if proc_is_virtual {
+            let count = Finder::init("/proc")
+                .skip_virtual_fs(true)
+                .max_depth(Some(1))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .count();
+            assert!(count > 0);
+        }
+    }
+
+    #[test]
+    fn test_skip_pseudo_fs_from_root() {
+        let temp_dir = temp_dir().join("skip_pseudo_fs_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Only on by default when searching the whole system
+        assert!(Finder::init("/").build().unwrap().skip_pseudo_fs);
+        assert!(!Finder::init(&temp_dir).build().unwrap().skip_pseudo_fs);
+        assert!(
+            !Finder::init("/")
+                .skip_pseudo_fs(false)
+                .build()
+                .unwrap()
+                .skip_pseudo_fs
+        );
+        assert!(!DirEntry::new(&temp_dir).unwrap().is_on_pseudo_fs());
+        fs::remove_dir_all(&temp_dir).unwrap();
+
+        if !DirEntry::new("/proc").is_ok_and(|proc| proc.is_on_pseudo_fs()) {
+            return;
+        }
+        let below_proc = |finder: crate::walk::FinderBuilder| {
+            finder
+                .keep_hidden(false)
+                .max_depth(Some(2))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .filter(|entry| entry.as_bytes().starts_with(b"/proc/"))
+                .count()
+        };
+        // /proc itself is still reported, just not read
+        assert_eq!(below_proc(Finder::init("/")), 0);
+        assert!(below_proc(Finder::init("/").skip_pseudo_fs(false)) > 0);
+        // Searching within it reads it as usual
+        assert!(below_proc(Finder::init("/proc")) > 0);
+    }
+
+    #[test]
+    fn test_display_with_options() {
+        use crate::fs::{DisplayOptions, NameEncoding, QuoteStyle};
+
+        let temp_dir = temp_dir().join("display_with_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join(OsStr::from_bytes(b"a\\b\"c\x1b\xfe.txt"));
+        fs::write(&path, "").unwrap();
+        let entry = DirEntry::new(&path).unwrap();
+        let dir = temp_dir.to_str().unwrap();
+
+        let show = |options: DisplayOptions| entry.display_with(options).to_string();
+        assert_eq!(show(DisplayOptions::new()), entry.to_string());
+        assert_eq!(
+            show(DisplayOptions::new()),
+            format!("{dir}/a\\b\"c\x1b\u{fffd}.txt")
+        );
+        assert_eq!(
+            show(DisplayOptions::new().encoding(NameEncoding::Replaced)),
+            format!("{dir}/a\\b\"c?\u{fffd}.txt")
+        );
+        assert_eq!(
+            show(DisplayOptions::new().encoding(NameEncoding::Escaped)),
+            format!(r#"{dir}/a\\b"c\x1b\xfe.txt"#)
+        );
+        assert_eq!(
+            show(
+                DisplayOptions::new()
+                    .encoding(NameEncoding::Escaped)
+                    .quote(QuoteStyle::Double)
+            ),
+            format!(r#""{dir}/a\\b\"c\x1b\xfe.txt""#)
+        );
+
+        // Truncated from the start, the quotes count towards the width
+        let short = DisplayOptions::new().encoding(NameEncoding::Escaped);
+        assert_eq!(show(short.max_width(Some(9))), r"…\xfe.txt");
+        assert_eq!(
+            show(short.max_width(Some(11)).quote(QuoteStyle::Double)),
+            r#""…\xfe.txt""#
+        );
+        assert_eq!(show(short.max_width(Some(1))), "…");
+        assert_eq!(show(short.max_width(Some(0))), "");
+        let long = show(short.max_width(Some(10_000)));
+        assert_eq!(long, show(short));
+        // Padding from the format string still applies
+        assert_eq!(
+            format!("{:>12}", entry.display_with(short.max_width(Some(9)))),
+            r"   …\xfe.txt"
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_dot_entries() {
+        let temp_dir = temp_dir().join("include_dot_entries_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("child")).unwrap();
+        File::create(temp_dir.join("file.txt")).unwrap();
+
+        let entry = DirEntry::new(&temp_dir).unwrap();
+
+        let names = |entries: Vec<DirEntry>| {
+            let mut names: Vec<_> = entries.iter().map(|e| e.file_name().to_vec()).collect();
+            names.sort();
+            names
+        };
+        let expected_with_dots: Vec<Vec<u8>> = vec![
+            b".".to_vec(),
+            b"..".to_vec(),
+            b"child".to_vec(),
+            b"file.txt".to_vec(),
+        ];
+
+        assert_eq!(names(entry.readdir().unwrap().collect()).len(), 2);
+        let with_dots: Vec<_> = entry.readdir().unwrap().include_dot_entries(true).collect();
+        assert_eq!(names(with_dots), expected_with_dots);
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            assert_eq!(names(entry.getdents().unwrap().collect()).len(), 2);
+            let with_dots: Vec<_> = entry
+                .getdents()
+                .unwrap()
+                .include_dot_entries(true)
+                .collect();
+
+            // `dir/.` is the directory itself
+            let dot = with_dots.iter().find(|e| e.file_name() == b".").unwrap();
+            assert!(dot.is_dir());
+            assert_eq!(dot.depth(), entry.depth() + 1);
+            assert_eq!(
+                dot.get_lstat().unwrap().st_ino,
+                entry.get_lstat().unwrap().st_ino
+            );
+            assert_eq!(names(with_dots), expected_with_dots);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_perm_filter_parsing_and_traversal() {
+        use crate::filters::{FileTypeFilter, PermFilter};
+
+        assert_eq!(PermFilter::from_string("644"), Ok(PermFilter::Exact(0o644)));
+        assert_eq!(PermFilter::from_string("-u+w"), Ok(PermFilter::All(0o200)));
+        assert_eq!(PermFilter::from_string("/a+x"), Ok(PermFilter::Any(0o111)));
+        assert_eq!(
+            PermFilter::from_string("u=rwx,go=rx"),
+            Ok(PermFilter::Exact(0o755))
+        );
+        assert_eq!(PermFilter::from_string("+t"), Ok(PermFilter::Exact(0o1000)));
+        assert_eq!(
+            PermFilter::from_string("u+rw-r"),
+            Ok(PermFilter::Exact(0o200))
+        );
+        assert!(PermFilter::from_string("").is_err());
+        assert!(PermFilter::from_string("-").is_err());
+        assert!(PermFilter::from_string("888").is_err());
+        assert!(PermFilter::from_string("17777").is_err());
+        assert!(PermFilter::from_string("q+w").is_err());
+        assert!(PermFilter::from_string("u+z").is_err());
+
+        // File type bits never take part
+        assert!(PermFilter::Exact(0o755).matches_mode(0o040_755));
+        assert!(PermFilter::Any(0).matches_mode(0));
+        assert!(!PermFilter::All(0o4000).matches_mode(0o755));
+
+        let temp_dir = temp_dir().join("perm_filter_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        for (name, mode) in [("a", 0o644), ("b", 0o600), ("c", 0o755)] {
+            let path = temp_dir.join(name);
+            File::create(&path).unwrap().write_all(b"data").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let search = |perm: &str, with_other_filters: bool| {
+            let mut names: Vec<_> = Finder::init(&temp_dir)
+                .type_filter(Some(FileTypeFilter::File))
+                .filter_by_permissions(Some(PermFilter::from_string(perm).unwrap()))
+                // Exercises the path where one lstat is shared between every filter
+                .filter_by_size(with_other_filters.then_some(SizeFilter::Min(1)))
+                .filter_by_time(with_other_filters.then(|| TimeFilter::from_string("-1d").unwrap()))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            names.sort();
+            names
+        };
+
+        for with_other_filters in [false, true] {
+            assert_eq!(search("644", with_other_filters), vec![b"a".to_vec()]);
+            assert_eq!(search("/111", with_other_filters), vec![b"c".to_vec()]);
+            assert_eq!(
+                search("-g+r", with_other_filters),
+                vec![b"a".to_vec(), b"c".to_vec()]
+            );
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_exclude_patterns_prune_subtrees() {
+        let temp_dir = temp_dir().join("exclude_patterns_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(temp_dir.join("src/nested")).unwrap();
+        fs::create_dir_all(temp_dir.join("build")).unwrap();
+        for file in [
+            "a.o",
+            "src/b.o",
+            "src/main.c",
+            "src/nested/build",
+            "node_modules/pkg/x.js",
+            "build/out",
+        ] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+
+        let search = |patterns: &[&str]| {
+            let mut paths: Vec<_> = Finder::init(&temp_dir)
+                .exclude_patterns(patterns.iter().map(|&p| p.to_owned()).collect())
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| {
+                    entry
+                        .as_path()
+                        .strip_prefix(&temp_dir)
+                        .unwrap()
+                        .to_path_buf()
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(
+            search(&["node_modules", "*.o", "build/"]),
+            vec![
+                PathBuf::from("src"),
+                PathBuf::from("src/main.c"),
+                PathBuf::from("src/nested"),
+                // A file named `build` doesn't match the directory only pattern
+                PathBuf::from("src/nested/build"),
+            ]
+        );
+
+        // Patterns containing a slash are anchored to the root
+        assert!(!search(&["src/*.o"]).contains(&PathBuf::from("src/b.o")));
+        assert!(search(&["src/*.o"]).contains(&PathBuf::from("a.o")));
+
+        // The excluded directory must not be opened, so an unreadable one produces no error
+        fs::set_permissions(
+            temp_dir.join("node_modules"),
+            fs::Permissions::from_mode(0o000),
+        )
+        .unwrap();
+        let finder = Finder::init(&temp_dir)
+            .exclude_patterns(vec!["node_modules".into()])
+            .collect_errors(true)
+            .build()
+            .unwrap();
+        let errors = finder.error_store();
+        assert_eq!(finder.traverse().unwrap().count(), 11 - 3);
+        assert!(errors.unwrap().lock().unwrap().is_empty());
+        fs::set_permissions(
+            temp_dir.join("node_modules"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverse_with_errors_yields_unreadable_dirs() {
+        let temp_dir = temp_dir().join("traverse_with_errors_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("no_read")).unwrap();
+        File::create(temp_dir.join("readable.txt")).unwrap();
+        fs::set_permissions(temp_dir.join("no_read"), fs::Permissions::from_mode(0o000)).unwrap();
+
+        let (mut found, mut errors) = (Vec::new(), Vec::new());
+        for result in Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse_with_errors()
+            .unwrap()
+        {
+            match result {
+                Ok(entry) => found.push(entry.file_name().to_vec()),
+                Err(error) => errors.push(error),
+            }
+        }
+        fs::set_permissions(temp_dir.join("no_read"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        found.sort();
+        assert_eq!(found, [&b"no_read"[..], b"readable.txt"]);
+        // Permissions don't stop root reading the directory
+        if unsafe { libc::geteuid() } == 0 {
+            assert!(errors.is_empty());
+        } else {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].path().file_name(), b"no_read");
+            assert_eq!(errors[0].raw_os_error(), Some(libc::EACCES));
+        }
+
+        // Codes are kept even by the variants which drop the io::Error
+        let error = crate::DirEntryError::from(std::io::Error::from_raw_os_error(libc::ENOENT));
+        assert_eq!(error.raw_os_error(), Some(libc::ENOENT));
+        assert_eq!(crate::DirEntryError::TimeError.raw_os_error(), None);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_printer_summary_footer() {
+        use crate::util::{SizeFormat, Summary};
+
+        let temp_dir = temp_dir().join("printer_summary_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("dir")).unwrap();
+        fs::write(temp_dir.join("dir/a"), b"12345").unwrap();
+        fs::write(temp_dir.join("b"), b"123").unwrap();
+
+        let mut summary = Summary::default();
+        Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .for_each(|entry| summary.add(&entry));
+
+        // The directory is counted but its size isn't
+        assert_eq!(summary.entries, 3);
+        assert_eq!(summary.bytes, 8);
+
+        let mut footer = Vec::new();
+        summary
+            .write(&mut footer, false, SizeFormat::Bytes)
+            .unwrap();
+        assert_eq!(footer, b"3 entries, 8 bytes\n");
+
+        let mut footer = Vec::new();
+        Summary {
+            entries: 1,
+            bytes: 0,
+        }
+        .write(&mut footer, true, SizeFormat::Bytes)
+        .unwrap();
+        assert_eq!(footer, b"1 entry, 0 bytes\0");
+
+        let mut footer = Vec::new();
+        Summary {
+            entries: 2,
+            bytes: 1_500_000,
+        }
+        .write(&mut footer, false, SizeFormat::Si)
+        .unwrap();
+        assert_eq!(footer, b"2 entries, 1.5M\n");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_long_format_listing() {
+        use crate::util::{OutputContext, SizeFormat, format_size, long_format};
+
+        assert_eq!(format_size(999, SizeFormat::Binary), "999");
+        assert_eq!(format_size(1536, SizeFormat::Binary), "1.5K");
+        assert_eq!(format_size(23 * 1024 * 1024, SizeFormat::Binary), "23M");
+
+        let temp_dir = temp_dir().join("long_format_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("dir")).unwrap();
+        fs::write(temp_dir.join("file"), vec![0u8; 1536]).unwrap();
+        fs::set_permissions(temp_dir.join("file"), fs::Permissions::from_mode(0o4754)).unwrap();
+        fs::set_permissions(temp_dir.join("dir"), fs::Permissions::from_mode(0o1750)).unwrap();
+        symlink("file", temp_dir.join("link")).unwrap();
+
+        let mut entries: Vec<_> = Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .collect();
+        entries.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+
+        let mut out = Vec::new();
+        long_format(
+            &mut out,
+            entries,
+            SizeFormat::Binary,
+            true,
+            &OutputContext::default(),
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+
+        assert_eq!(lines.len(), 5, "total, 3 entries and the footer: {out}");
+        assert!(lines[0].starts_with("total "));
+        assert!(lines[1].starts_with("drwxr-x--T "), "{}", lines[1]);
+        assert!(lines[2].starts_with("-rwsr-xr-- "), "{}", lines[2]);
+        assert!(lines[2].contains(" 1.5K "), "{}", lines[2]);
+        assert!(lines[3].starts_with("lrwxrwxrwx "), "{}", lines[3]);
+        assert!(lines[3].ends_with("link -> file"), "{}", lines[3]);
+        assert_eq!(lines[4], "3 entries, 1.5K");
+
+        // Columns are aligned, so the paths all start at the same offset
+        let path_offsets: Vec<_> = lines[1..4]
+            .iter()
+            .map(|line| line.find(temp_dir.to_str().unwrap()).unwrap())
+            .collect();
+        assert!(path_offsets.windows(2).all(|pair| pair[0] == pair[1]));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_path_separator_rewrites_output() {
+        use crate::util::{OutputContext, SizeFormat, long_format, write_nocolour};
+
+        let temp_dir = temp_dir().join("path_separator_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("dir")).unwrap();
+        fs::write(temp_dir.join("dir/file"), b"").unwrap();
+
+        let dir = DirEntry::new(temp_dir.join("dir")).unwrap();
+        let file = DirEntry::new(temp_dir.join("dir/file")).unwrap();
+        let windows_path = |path: &Path| path.to_str().unwrap().replace('/', "\\");
+
+        let mut out = Vec::new();
+        let context = OutputContext {
+            quoted: true,
+            separator: Some(Box::from(&b"\\"[..])),
+            ..OutputContext::default()
+        };
+        write_nocolour(&mut out, [dir, file], &context).unwrap();
+        let expected = format!(
+            "\"{}\\\"\n\"{}\"\n",
+            windows_path(&temp_dir.join("dir")),
+            windows_path(&temp_dir.join("dir/file"))
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        // Multi byte separators work too
+        let file = DirEntry::new(temp_dir.join("dir/file")).unwrap();
+        let mut out = Vec::new();
+        let context = OutputContext {
+            null_terminated: true,
+            separator: Some(Box::from(&b"::"[..])),
+            ..OutputContext::default()
+        };
+        long_format(&mut out, vec![file], SizeFormat::Bytes, false, &context).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(&format!(
+            "{}\0",
+            temp_dir.join("dir/file").to_str().unwrap().replace('/', "::")
+        )));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_serve_streams_length_prefixed_records() {
+        use crate::serve::{SocketServer, read_record, stream_records};
+        use std::os::unix::net::UnixStream;
+
+        let temp_dir = temp_dir().join("serve_records_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("tree/sub")).unwrap();
+        fs::write(temp_dir.join("tree/sub/with\nnewline"), b"").unwrap();
+        fs::write(temp_dir.join("tree/plain"), b"").unwrap();
+
+        // Binding over a regular file is refused, a stale socket is replaced
+        let socket = temp_dir.join("fdf.sock");
+        fs::write(&socket, b"").unwrap();
+        assert!(SocketServer::bind(&socket).is_err());
+        fs::remove_file(&socket).unwrap();
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+
+        let server = SocketServer::bind(&socket).unwrap();
+        let root = temp_dir.join("tree");
+        let writer = std::thread::spawn(move || {
+            let stream = server.accept().unwrap();
+            let entries = Finder::init(&root).build().unwrap().traverse().unwrap();
+            stream_records(stream, entries).unwrap()
+        });
+
+        let mut client = UnixStream::connect(&socket).unwrap();
+        let mut received = Vec::new();
+        while let Some(record) = read_record(&mut client).unwrap() {
+            received.push(record);
+        }
+        received.sort();
+
+        assert_eq!(writer.join().unwrap(), 3);
+        let prefix = temp_dir.join("tree");
+        let expected: Vec<Vec<u8>> = ["plain", "sub", "sub/with\nnewline"]
+            .iter()
+            .map(|name| prefix.join(name).as_os_str().as_bytes().to_vec())
+            .collect();
+        assert_eq!(received, expected);
+        assert!(
+            !socket.exists(),
+            "the socket is removed once the server is dropped"
+        );
+
+        // A truncated record is an error, not a silent end of stream
+        let mut truncated: &[u8] = &[0, 0, 0, 5, b'a'];
+        assert!(read_record(&mut truncated).is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_record_rejects_oversized_length() {
+        use crate::serve::{MAX_RECORD_LEN, read_record, write_record};
+
+        // A lone 4GiB header is refused without waiting for (or allocating) the body
+        let mut huge: &[u8] = &u32::MAX.to_be_bytes();
+        let error = read_record(&mut huge).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let over = u32::try_from(MAX_RECORD_LEN + 1).unwrap().to_be_bytes();
+        let mut just_over: &[u8] = &over;
+        assert_eq!(
+            read_record(&mut just_over).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        // The limit itself is fine, both ways
+        let longest = vec![b'a'; MAX_RECORD_LEN];
+        let mut buffer = Vec::new();
+        write_record(&mut buffer, &longest).unwrap();
+        assert_eq!(
+            read_record(&mut buffer.as_slice()).unwrap().as_deref(),
+            Some(longest.as_slice())
+        );
+        assert_eq!(
+            write_record(&mut Vec::new(), &[b'a'; MAX_RECORD_LEN + 1])
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_daemon_answers_repeated_queries() {
+        use crate::serve::{
+            Index, SharedIndex, SocketServer, read_record, serve_queries, write_record,
+        };
+        use std::os::unix::net::UnixStream;
+
+        let temp_dir = temp_dir().join("daemon_queries_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("tree/src")).unwrap();
+        for file in ["tree/src/main.rs", "tree/src/lib.rs", "tree/README.md"] {
+            fs::write(temp_dir.join(file), b"").unwrap();
+        }
+
+        let index = Index::new(
+            Finder::init(temp_dir.join("tree"))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap(),
+        );
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.query(r"\.rs$").unwrap().count(), 2);
+        // A slash in the pattern matches against the full path rather than the name
+        assert_eq!(index.query("src/").unwrap().count(), 2);
+        assert!(index.query("(").is_err());
+
+        let socket = temp_dir.join("daemon.sock");
+        let server = SocketServer::bind(&socket).unwrap();
+        let shared = std::sync::Arc::new(SharedIndex::new(index));
+        let serving = std::sync::Arc::clone(&shared);
+        // Never returns, the thread ends with the test binary
+        std::thread::spawn(move || serve_queries(&server, &serving));
+
+        let ask = |client: &mut UnixStream, query: &[u8]| {
+            write_record(client, query).unwrap();
+            let mut names = Vec::new();
+            while let Some(record) = read_record(client).unwrap() {
+                if record.is_empty() {
+                    break;
+                }
+                names.push(
+                    Path::new(OsStr::from_bytes(&record))
+                        .file_name()
+                        .unwrap()
+                        .to_owned(),
+                );
+            }
+            names.sort();
+            names
+        };
+
+        let mut client = UnixStream::connect(&socket).unwrap();
+        assert_eq!(ask(&mut client, br"\.rs$"), ["lib.rs", "main.rs"]);
+        assert_eq!(ask(&mut client, b"README"), ["README.md"]);
+        assert_eq!(
+            ask(&mut client, b""),
+            ["README.md", "lib.rs", "main.rs", "src"]
+        );
+        // Invalid patterns get an empty answer, and the session carries on
+        assert!(ask(&mut client, b"(").is_empty());
+        assert_eq!(ask(&mut client, b"main"), ["main.rs"]);
+
+        // A client which connects and never sends anything doesn't hold up the others
+        let _idle = UnixStream::connect(&socket).unwrap();
+        let mut second = UnixStream::connect(&socket).unwrap();
+        assert_eq!(ask(&mut second, b"lib"), ["lib.rs"]);
+        assert_eq!(ask(&mut client, b"lib"), ["lib.rs"]);
+
+        // A replaced index answers later queries, on connections already open too
+        fs::write(temp_dir.join("tree/src/new.rs"), b"").unwrap();
+        shared.replace(Index::new(
+            Finder::init(temp_dir.join("tree"))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap(),
+        ));
+        assert_eq!(shared.current().len(), 5);
+        assert_eq!(ask(&mut second, br"\.rs$"), ["lib.rs", "main.rs", "new.rs"]);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_traverse_streaming_bounded_batches() {
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("traverse_streaming_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..5 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..20 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+
+        let finder = || {
+            Finder::init(&temp_dir)
+                .thread_count(NonZeroUsize::new(2))
+                .build()
+                .unwrap()
+        };
+        let batch_size = NonZeroUsize::new(7).unwrap();
+
+        let receiver = finder().traverse_streaming(batch_size, 1).unwrap();
+        let mut total = 0;
+        for batch in receiver {
+            assert!(!batch.is_empty() && batch.len() <= 7, "{}", batch.len());
+            total += batch.len();
+        }
+        assert_eq!(total, 105);
+
+        // A rendezvous channel still delivers everything to a slow consumer
+        let receiver = finder().traverse_streaming(batch_size, 0).unwrap();
+        let mut total = 0;
+        while let Ok(batch) = receiver.recv() {
+            std::thread::sleep(Duration::from_millis(1));
+            total += batch.len();
+        }
+        assert_eq!(total, 105);
+
+        // Dropping the receiver early stops the workers rather than blocking forever
+        let receiver = finder().traverse_streaming(batch_size, 0).unwrap();
+        assert!(receiver.recv().is_ok());
+        drop(receiver);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_flush_policies_with_slow_consumer() {
+        use crate::walk::{Fields, FlushPolicy};
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("flush_policy_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..5 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..20 {
+                fs::write(sub.join(format!("file{file}")), b"spill").unwrap();
+            }
+        }
+        let finder = |policy| {
+            Finder::init(&temp_dir)
+                .flush_policy(policy)
+                .build()
+                .unwrap()
+        };
+        let mut expected: Vec<_> = finder(FlushPolicy::Block)
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.to_vec())
+            .collect();
+        expected.sort();
+        assert_eq!(expected.len(), 105);
+
+        // Let the walk finish before reading anything, so almost every batch has to be spilled
+        let receiver = finder(FlushPolicy::SpillToDisk)
+            .traverse_streaming(NonZeroUsize::new(2).unwrap(), 1)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let mut spilled: Vec<_> = receiver
+            .into_iter()
+            .flatten()
+            .map(|entry| {
+                assert_eq!(
+                    entry.file_name(),
+                    DirEntry::new(entry.as_os_str()).unwrap().file_name()
+                );
+                entry.to_vec()
+            })
+            .collect();
+        spilled.sort();
+        assert_eq!(spilled, expected);
+
+        // Projected records round trip through the spill file too
+        let total: u64 = Finder::init(&temp_dir)
+            .flush_policy(FlushPolicy::SpillToDisk)
+            .project(Fields::SIZE)
+            .build()
+            .unwrap()
+            .traverse_projected()
+            .unwrap()
+            .filter_map(|entry| entry.size().filter(|_| entry.file_type().is_regular_file()))
+            .sum();
+        assert_eq!(total, 100 * 5);
+
+        // Evicting never blocks the walk, so a consumer that shows up late sees at most what fits in the channel
+        let receiver = finder(FlushPolicy::DropOldest)
+            .traverse_streaming(NonZeroUsize::new(2).unwrap(), 3)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let kept: usize = receiver.into_iter().map(|batch| batch.len()).sum();
+        assert!(kept > 0 && kept <= 3 * 2, "{kept}");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_sorted_entries_spill_and_merge() {
+        use crate::walk::SortedEntries;
+
+        let temp_dir = temp_dir().join("sorted_entries_spill_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..5 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..20 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+
+        let traverse = || Finder::init(&temp_dir).build().unwrap().traverse().unwrap();
+        let mut expected: Vec<_> = traverse().map(|entry| entry.as_bytes().to_vec()).collect();
+        expected.sort();
+        assert_eq!(expected.len(), 105);
+
+        // Everything fits, so nothing is written out
+        let sorted = SortedEntries::new(traverse(), usize::MAX).unwrap();
+        assert_eq!(sorted.spilled_runs(), 0);
+        let paths: Vec<_> = sorted.map(|entry| entry.as_bytes().to_vec()).collect();
+        assert_eq!(paths, expected);
+
+        // Roughly ten entries at a time, so the merge has many runs to interleave
+        let limit = 10 * (size_of::<DirEntry>() + temp_dir.as_os_str().len() + 12);
+        let mut sorted = SortedEntries::new(traverse(), limit).unwrap();
+        assert!(sorted.spilled_runs() >= 5, "{}", sorted.spilled_runs());
+        let paths: Vec<_> = sorted
+            .by_ref()
+            .map(|entry| entry.as_bytes().to_vec())
+            .collect();
+        assert!(sorted.take_error().is_none());
+        assert_eq!(paths, expected);
+
+        // Every entry in a run of its own
+        let sorted = SortedEntries::new(traverse(), 0).unwrap();
+        assert_eq!(sorted.spilled_runs(), 105);
+        let paths: Vec<_> = sorted.map(|entry| entry.as_bytes().to_vec()).collect();
+        assert_eq!(paths, expected);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_multi_dedups_overlapping_roots() {
+        use crate::walk::ResultOrder;
+
+        let temp_dir = temp_dir().join("init_multi_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for root in ["a", "b"] {
+            let sub = temp_dir.join(root).join("sub");
+            fs::create_dir_all(&sub).unwrap();
+            File::create(temp_dir.join(root).join("top")).unwrap();
+            File::create(sub.join("deep")).unwrap();
+        }
+        let (a, b) = (temp_dir.join("a"), temp_dir.join("b"));
+
+        // A repeat and a directory inside another root are searched as part of it
+        let finder = || {
+            Finder::init_multi([&a, &b, &a.join("sub"), &temp_dir.join("a/")]).max_depth(Some(2))
+        };
+        let built = finder().build().unwrap();
+        let roots: Vec<_> = built.roots().map(OsStr::to_os_string).collect();
+        assert_eq!(roots, [a.as_os_str(), b.as_os_str()]);
+
+        let relative = |entries: Vec<DirEntry>| {
+            let mut found: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    let path = entry.as_path().strip_prefix(&temp_dir).unwrap();
+                    (path.to_string_lossy().into_owned(), entry.depth())
+                })
+                .collect();
+            found.sort();
+            found
+        };
+        let expected = [
+            ("a/sub".to_owned(), 1),
+            ("a/sub/deep".to_owned(), 2),
+            ("a/top".to_owned(), 1),
+            ("b/sub".to_owned(), 1),
+            ("b/sub/deep".to_owned(), 2),
+            ("b/top".to_owned(), 1),
+        ];
+        assert_eq!(relative(built.traverse().unwrap().collect()), expected);
+
+        // Depths (and so depth limits) count from each root
+        let shallow = finder().max_depth(Some(1)).build().unwrap();
+        let found = relative(shallow.traverse().unwrap().collect());
+        assert_eq!(
+            found,
+            [
+                expected[0].clone(),
+                expected[2].clone(),
+                expected[3].clone(),
+                expected[5].clone()
+            ]
+        );
+
+        // Full path patterns and excludes see the path below whichever root an entry is under
+        let full_path = finder().pattern("^sub/").build().unwrap();
+        let found = relative(full_path.traverse().unwrap().collect());
+        assert_eq!(found, [expected[1].clone(), expected[4].clone()]);
+        let excluded = finder()
+            .exclude_patterns(vec!["/sub".into()])
+            .build()
+            .unwrap();
+        let found = relative(excluded.traverse().unwrap().collect());
+        assert_eq!(found, [expected[2].clone(), expected[5].clone()]);
+
+        // Ordered traversals walk the roots in the order given
+        let ordered: Vec<_> = finder()
+            .build()
+            .unwrap()
+            .traverse_ordered(ResultOrder::DepthFirst)
+            .unwrap()
+            .collect();
+        let first_b = ordered
+            .iter()
+            .position(|entry| entry.as_path().starts_with(&b))
+            .unwrap();
+        assert_eq!(first_b, 3);
+        assert_eq!(relative(ordered), expected);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_visit_skip_subtree_and_stop() {
+        use crate::walk::ControlFlow;
+        use core::num::NonZeroUsize;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        let temp_dir = temp_dir().join("visit_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in ["keep/nested", "skip/nested"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+        }
+        for file in ["keep/a", "keep/nested/b", "skip/c", "skip/nested/d", "e"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+
+        let visited = std::sync::Mutex::new(Vec::new());
+        Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .visit(|entry| {
+                visited
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(entry.file_name()).into_owned());
+                if entry.file_name() == b"skip" {
+                    ControlFlow::SkipSubtree
+                } else {
+                    ControlFlow::Continue
+                }
+            })
+            .unwrap();
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort();
+        // The skipped directory itself is still visited, just not its contents
+        assert_eq!(visited, ["a", "b", "e", "keep", "nested", "skip"]);
+
+        // With visit_all, directories which don't match are still visited before they're read, so they can be pruned too
+        let visited = std::sync::Mutex::new(Vec::new());
+        Finder::init(&temp_dir)
+            .pattern("^[a-e]$")
+            .max_results(Some(3))
+            .build()
+            .unwrap()
+            .visit_all(|entry| {
+                visited
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(entry.file_name()).into_owned());
+                if entry.file_name() == b"skip" {
+                    ControlFlow::SkipSubtree
+                } else {
+                    ControlFlow::Continue
+                }
+            })
+            .unwrap();
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort();
+        // Only the matching files count towards the limit of 3
+        assert_eq!(visited, ["a", "b", "e", "keep", "nested", "skip"]);
+
+        let calls = AtomicUsize::new(0);
+        Finder::init(&temp_dir)
+            .thread_count(NonZeroUsize::new(1))
+            .build()
+            .unwrap()
+            .visit(|_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                ControlFlow::Stop
+            })
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let calls = AtomicUsize::new(0);
+        Finder::init(&temp_dir)
+            .max_results(Some(3))
+            .build()
+            .unwrap()
+            .visit(|_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                ControlFlow::Continue
+            })
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancellation_token_stops_traversal() {
+        use crate::walk::CancellationToken;
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("cancellation_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..20 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..100 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+        let total = 20 * 100 + 20;
+
+        let token = CancellationToken::new();
+        let mut results = Finder::init(&temp_dir)
+            .thread_count(NonZeroUsize::new(1))
+            .cancellation_token(token.clone())
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap();
+        assert!(results.next().is_some());
+        token.cancel();
+        // Only what was already in the channel is still delivered
+        assert!(results.count() < total - 1);
+
+        // Workers blocked on a full channel give up too, rather than waiting for a consumer that's gone quiet
+        let token = CancellationToken::new();
+        let receiver = Finder::init(&temp_dir)
+            .cancellation_token(token.clone())
+            .build()
+            .unwrap()
+            .traverse_streaming(NonZeroUsize::new(1).unwrap(), 0)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        token.cancel();
+        let started = SystemTime::now();
+        assert!(receiver.iter().count() < total);
+        assert!(started.elapsed().unwrap() < Duration::from_secs(5));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_traverse_async_stream() {
+        use core::pin::Pin;
+        use futures_core::Stream;
+        use std::future::poll_fn;
+
+        let temp_dir = temp_dir().join("traverse_async_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..10 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..50 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+        let finder = || Finder::init(&temp_dir).build().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut stream = finder().traverse_async().unwrap();
+            let mut count = 0;
+            while poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                .await
+                .is_some()
+            {
+                count += 1;
+            }
+            assert_eq!(count, finder().traverse().unwrap().count());
+
+            // Cancelling from async code ends the stream early without blocking
+            let mut stream = finder().traverse_async().unwrap();
+            assert!(
+                poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                    .await
+                    .is_some()
+            );
+            stream.cancel();
+            let mut rest = 0;
+            while poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+                .await
+                .is_some()
+            {
+                rest += 1;
+            }
+            assert!(rest < count);
+        });
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_stem_matches_extension_rules() {
+        use crate::exec::CommandTemplate;
+
+        let temp_dir = temp_dir().join("file_stem_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        for (name, stem) in [
+            ("archive.tar.gz", "archive.tar"),
+            ("README", "README"),
+            ("trailing.", "trailing."),
+            (".bashrc", ""),
+            ("a.b", "a"),
+        ] {
+            let path = temp_dir.join(name);
+            File::create(&path).unwrap();
+            let entry = DirEntry::new(&path).unwrap();
+            assert_eq!(entry.file_stem(), stem.as_bytes(), "{name}");
+
+            // The stem, a dot and the extension always make up the whole name
+            if let Some(extension) = entry.extension() {
+                assert_eq!(
+                    [entry.file_stem(), b".", extension].concat(),
+                    entry.file_name()
+                );
+            } else {
+                assert_eq!(entry.file_stem(), entry.file_name());
+            }
+        }
+
+        let template = CommandTemplate::new(["mv", "{}", "{//}/{/.}.bak"]).unwrap();
+        assert_eq!(
+            template.argv(b"./dir/archive.tar.gz"),
+            ["mv", "./dir/archive.tar.gz", "./dir/archive.tar.bak"]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_stateful_filters() {
+        use crate::filters::FileTypeFilter;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use std::collections::HashSet;
+
+        let temp_dir = temp_dir().join("stateful_filter_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..4 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..25 {
+                File::create(sub.join(format!("file{file}.txt"))).unwrap();
+            }
+        }
+
+        // Shared state: a counter incremented concurrently, which only sees entries passing the other filters
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&calls);
+        let results = Finder::init(&temp_dir)
+            .extension("txt")
+            .filter_shared(move |_| counted.fetch_add(1, Ordering::Relaxed).is_multiple_of(2))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(calls.load(Ordering::Relaxed), 100);
+        assert_eq!(results, 50);
+
+        // Mutable state: deduplicating names across directories, with no synchronisation in the closure itself
+        let mut names = HashSet::new();
+        let mut unique: Vec<_> = Finder::init(&temp_dir)
+            .type_filter(Some(FileTypeFilter::File))
+            .filter_mut(move |entry| names.insert(entry.file_name().to_vec()))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.file_name().to_vec())
+            .collect();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 25);
+
+        // Rejecting directories doesn't stop them being descended into
+        let files = Finder::init(&temp_dir)
+            .filter_mut(|entry| !entry.is_dir())
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(files, 100);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_sampling_ratio_and_count() {
+        use crate::filters::FileTypeFilter;
+        use crate::walk::SampleSize;
+        use core::num::NonZeroUsize;
+        use std::collections::HashSet;
+
+        let temp_dir = temp_dir().join("sample_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..10 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..100 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+        let files = || Finder::init(&temp_dir).type_filter(Some(FileTypeFilter::File));
+
+        // A count sample returns that many distinct entries, or all of them when there are fewer
+        let sample = files()
+            .build()
+            .unwrap()
+            .sample(NonZeroUsize::new(50).unwrap())
+            .unwrap();
+        let distinct: HashSet<_> = sample
+            .iter()
+            .map(|entry| entry.as_bytes().to_vec())
+            .collect();
+        assert_eq!(sample.len(), 50);
+        assert_eq!(distinct.len(), 50);
+
+        let everything = files()
+            .build()
+            .unwrap()
+            .sample(NonZeroUsize::new(5000).unwrap())
+            .unwrap();
+        assert_eq!(everything.len(), 1000);
+
+        // A ratio sample keeps roughly that share of the results
+        let kept = files()
+            .sample_ratio(Some(0.25))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert!((150..=350).contains(&kept), "kept {kept} of 1000");
+
+        let all = files()
+            .sample_ratio(Some(1.0))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(all, 1000);
+
+        assert_eq!("50%".parse(), Ok(SampleSize::Ratio(0.5)));
+        assert!("0%".parse::<SampleSize>().is_err());
+        assert!("abc".parse::<SampleSize>().is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_seeded_sample_and_shuffle_are_reproducible() {
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("seeded_shuffle_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..8 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..50 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+        let paths = |entries: Vec<DirEntry>| -> Vec<Vec<u8>> {
+            entries
+                .into_iter()
+                .map(|entry| entry.as_bytes().to_vec())
+                .collect()
+        };
+        let finder = |seed, threads| {
+            Finder::init(&temp_dir)
+                .seed(Some(seed))
+                .thread_count(NonZeroUsize::new(threads))
+                .build()
+                .unwrap()
+        };
+
+        // The same seed gives the same order and sample, however many threads aggregate the results
+        let shuffled = paths(finder(7, 1).shuffle().unwrap());
+        assert_eq!(shuffled, paths(finder(7, 4).shuffle().unwrap()));
+        assert_ne!(shuffled, paths(finder(8, 4).shuffle().unwrap()));
+
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_ne!(shuffled, sorted);
+        let mut walked: Vec<_> = finder(7, 4)
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.as_bytes().to_vec())
+            .collect();
+        walked.sort();
+        assert_eq!(sorted, walked);
+
+        let count = NonZeroUsize::new(20).unwrap();
+        let sample = paths(finder(7, 1).sample(count).unwrap());
+        assert_eq!(sample, paths(finder(7, 4).sample(count).unwrap()));
+        assert_eq!(finder(7, 1).seed(), 7);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverses_paths_longer_than_path_max() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt as _;
+
+        let temp_dir = temp_dir().join("long_path_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Build the tree one level at a time relative to its parent, as the full path is too long to use
+        let name = CString::new("d".repeat(200)).unwrap();
+        let levels = 30; // ~6000 bytes, beyond Linux's 4096 byte PATH_MAX
+        let root = CString::new(temp_dir.as_os_str().as_bytes()).unwrap();
+        // SAFETY: valid null terminated paths, every descriptor opened here is closed
+        unsafe {
+            let mut fd = libc::open(root.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY);
+            assert!(fd >= 0);
+            for _ in 0..levels {
+                assert_eq!(libc::mkdirat(fd, name.as_ptr(), 0o755), 0);
+                let child = libc::openat(fd, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY);
+                libc::close(fd);
+                assert!(child >= 0);
+                fd = child;
+            }
+            let file = CString::new("deepest.txt").unwrap();
+            let file_fd = libc::openat(fd, file.as_ptr(), libc::O_CREAT | libc::O_WRONLY, 0o644);
+            assert!(file_fd >= 0);
+            libc::close(file_fd);
+            libc::close(fd);
+        }
+
+        let finder = Finder::init(&temp_dir)
+            .pattern("deepest")
+            .collect_errors(true)
+            .build()
+            .unwrap();
+        let errors = finder.error_store();
+        let results: Vec<_> = finder.traverse().unwrap().collect();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].len() > 4096);
+        assert_eq!(results[0].depth(), levels + 1);
+        assert_eq!(results[0].file_name(), b"deepest.txt");
+        assert!(errors.unwrap().lock().unwrap().is_empty());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_entry_with_attached_data() {
+        use crate::filters::FileTypeFilter;
+        use crate::fs::EntryWith;
+
+        let temp_dir = temp_dir().join("entry_with_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        for (name, len) in [("small", 1), ("medium", 10), ("large", 100)] {
+            fs::write(temp_dir.join(name), vec![b'x'; len]).unwrap();
+        }
+
+        let mut scored: Vec<EntryWith<u64>> = Finder::init(&temp_dir)
+            .type_filter(Some(FileTypeFilter::File))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .map(|entry| {
+                let size = entry.file_size().unwrap();
+                entry.with(size)
+            })
+            .collect();
+        scored.sort_by_key(|entry| *entry.data());
+
+        let names: Vec<_> = scored.iter().map(|entry| entry.file_name()).collect();
+        assert_eq!(names, [b"small".as_ref(), b"medium", b"large"]);
+
+        let mut largest = scored.pop().unwrap().map(|size| size * 2);
+        *largest.data_mut() += 1;
+        let (entry, data) = largest.into_parts();
+        assert_eq!(entry.file_name(), b"large");
+        assert_eq!(data, 201);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(all(target_os = "linux", target_env = "gnu"), target_os = "android"))]
+    fn test_statx_matches_lstat() {
+        use crate::fs::statx_from_stat;
+
+        let temp_dir = temp_dir().join("statx_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let file = temp_dir.join("file");
+        fs::write(&file, b"twelve bytes").unwrap();
+        std::os::unix::fs::symlink(&file, temp_dir.join("link")).unwrap();
+
+        for name in ["file", "link"] {
+            let entry = DirEntry::new(temp_dir.join(name)).unwrap();
+            let statted = entry.get_lstat().unwrap();
+            let statxed = entry
+                .get_statx(libc::STATX_SIZE | libc::STATX_MTIME)
+                .unwrap();
+
+            // Symlinks aren't followed, so the link's own size is reported
+            assert!(statxed.stx_mask & libc::STATX_SIZE != 0);
+            assert_eq!(statxed.stx_size, statted.st_size as u64);
+            assert_eq!(statxed.stx_mtime.tv_sec, statted.st_mtime);
+
+            // The fallback for kernels without statx gives the same basic fields
+            let converted = statx_from_stat(&statted);
+            assert_eq!(converted.stx_size, statxed.stx_size);
+            assert_eq!(converted.stx_ino, statted.st_ino);
+            assert_eq!(converted.stx_mtime.tv_sec, statxed.stx_mtime.tv_sec);
+            assert_eq!(converted.stx_mtime.tv_nsec, statxed.stx_mtime.tv_nsec);
+            assert_eq!(u32::from(converted.stx_mode), statted.st_mode);
+        }
+        assert_eq!(
+            DirEntry::new(&file)
+                .unwrap()
+                .get_statx(libc::STATX_SIZE)
+                .unwrap()
+                .stx_size,
+            12
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_created_and_accessed_time_filters() {
+        use crate::filters::TimeFilter;
+        use std::time::{Duration, SystemTime};
+
+        let temp_dir = temp_dir().join("created_accessed_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let old = temp_dir.join("old_access");
+        let new = temp_dir.join("new_access");
+        File::create(&old).unwrap();
+        File::create(&new).unwrap();
+
+        // Backdate one file's access time by a week
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_times(fs::FileTimes::new().set_accessed(week_ago))
+            .unwrap();
+
+        let accessed = DirEntry::new(&old).unwrap().accessed_time().unwrap();
+        assert!(
+            (accessed.timestamp() - chrono::DateTime::<chrono::Utc>::from(week_ago).timestamp())
+                .abs()
+                <= 1
+        );
+
+        let names = |builder: crate::walk::FinderBuilder| -> Vec<Vec<u8>> {
+            let mut names: Vec<_> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let recent = TimeFilter::changed_within("1d").unwrap();
+        let stale = TimeFilter::changed_before("1d").unwrap();
+        assert_eq!(
+            names(Finder::init(&temp_dir).filter_by_accessed(Some(recent))),
+            [b"new_access".to_vec()]
+        );
+        assert_eq!(
+            names(Finder::init(&temp_dir).filter_by_accessed(Some(stale))),
+            [b"old_access".to_vec()]
+        );
+
+        // Creation times aren't recorded everywhere, when they are both files were just created
+        if DirEntry::new(&new).unwrap().created_time().is_ok() {
+            assert_eq!(
+                names(Finder::init(&temp_dir).filter_by_created(Some(recent))),
+                [b"new_access".to_vec(), b"old_access".to_vec()]
+            );
+            assert!(names(Finder::init(&temp_dir).filter_by_created(Some(stale))).is_empty());
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_rules_first_match_classification() {
+        use crate::rules::Rules;
+
+        let temp_dir = temp_dir().join("rules_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("target/debug")).unwrap();
+        fs::create_dir_all(temp_dir.join("src")).unwrap();
+        for file in [
+            "target/debug/main.rs",
+            "src/main.rs",
+            "README.md",
+            "image.png",
+        ] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+
+        let rules = Rules::parse(
+            "[rules]\n\
+             # Earlier rules win\n\
+             \"target/**\" = \"build\"\n\
+             '*.rs' = \"source\" # trailing comment\n\
+             md = 'never'\n\
+             \"*.{md,txt}\" = \"docs\"\n",
+            &temp_dir,
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 4);
+        assert!(rules.has_label("docs") && !rules.has_label("image"));
+
+        let mut labelled: Vec<_> = Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .map(|entry| {
+                let label = rules.classify(&entry).map(str::to_owned);
+                (entry.file_name().to_vec(), entry.depth(), label)
+            })
+            .collect();
+        labelled.sort();
+
+        let label_of = |name: &[u8], depth: usize| {
+            labelled
+                .iter()
+                .find(|(entry_name, entry_depth, _)| entry_name == name && *entry_depth == depth)
+                .and_then(|(_, _, label)| label.as_deref())
+        };
+        assert_eq!(label_of(b"main.rs", 3), Some("build"));
+        assert_eq!(label_of(b"main.rs", 2), Some("source"));
+        assert_eq!(label_of(b"README.md", 1), Some("docs"));
+        assert_eq!(label_of(b"image.png", 1), None);
+
+        assert!(Rules::parse("\"*.rs\" \"source\"", &temp_dir).is_err());
+        assert!(Rules::parse("\"*.rs\" = \"unterminated", &temp_dir).is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_content_filter() {
+        use crate::filters::ContentFilter;
+
+        let temp_dir = temp_dir().join("search_content_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("nested/needle_dir")).unwrap();
+        fs::write(temp_dir.join("match.txt"), "first line\nneedle here\n").unwrap();
+        fs::write(temp_dir.join("nested/deep.txt"), "a needle\n").unwrap();
+        fs::write(temp_dir.join("miss.txt"), "nothing to see\n").unwrap();
+        fs::write(temp_dir.join("binary.bin"), b"\0\x01needle").unwrap();
+        fs::write(temp_dir.join("large.txt"), "needle ".repeat(1000)).unwrap();
+        File::create(temp_dir.join("empty.txt")).unwrap();
+        symlink(temp_dir.join("match.txt"), temp_dir.join("link.txt")).unwrap();
+
+        let search = |filter: ContentFilter| {
+            let mut names: Vec<_> = Finder::init(&temp_dir)
+                .search_content(Some(filter))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let filter = ContentFilter::new("^needle").unwrap();
+        assert_eq!(
+            search(filter.clone()),
+            ["large.txt", "link.txt", "match.txt"]
+        );
+        assert_eq!(search(filter.max_size(100)), ["link.txt", "match.txt"]);
+        assert_eq!(
+            search(ContentFilter::new("needle").unwrap()),
+            ["deep.txt", "large.txt", "link.txt", "match.txt"]
+        );
+        // An empty file is still searched, so it matches a pattern matching the empty input
+        assert_eq!(search(ContentFilter::new(r"\A\z").unwrap()), ["empty.txt"]);
+        assert!(ContentFilter::new("(unclosed").is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_many_rules_keep_first_match_priority() {
+        use crate::rules::Rules;
+
+        let temp_dir = temp_dir().join("many_rules_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("logs")).unwrap();
+        for file in ["file7.txt", "file42.txt", "logs/file7.txt", "other.md"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+
+        // Overlapping rules, where only the order decides which label wins
+        let mut rules = vec![("logs/**".to_owned(), "logs".to_owned())];
+        rules.extend((0..60).map(|n| (format!("file{n}.txt"), format!("file{n}"))));
+        rules.push(("*.txt".to_owned(), "text".to_owned()));
+        rules.push(("file7.txt".to_owned(), "shadowed".to_owned()));
+        let rules = Rules::new(rules, &temp_dir).unwrap();
+        assert_eq!(rules.len(), 63);
+        assert!(rules.has_label("shadowed"));
+
+        let label_of = |file: &str| {
+            let entry = DirEntry::new(temp_dir.join(file)).unwrap();
+            rules.classify(&entry).map(str::to_owned)
+        };
+        assert_eq!(label_of("file7.txt").as_deref(), Some("file7"));
+        assert_eq!(label_of("file42.txt").as_deref(), Some("file42"));
+        assert_eq!(label_of("logs/file7.txt").as_deref(), Some("logs"));
+        assert_eq!(label_of("other.md"), None);
+
+        assert!(Rules::new([("!*.txt", "negated")], &temp_dir).is_err());
+        assert!(Rules::new([("a{b", "unclosed")], &temp_dir).is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_mime_filter_sniffs_contents() {
+        use crate::filters::{MimeFilter, sniff_mime};
+
+        let temp_dir = temp_dir().join("mime_filter_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("photos.d")).unwrap();
+        // Extensions deliberately misleading, only the contents count
+        fs::write(temp_dir.join("image.txt"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(temp_dir.join("photo.bin"), b"\xff\xd8\xff\xe0\0\x10JFIF\0").unwrap();
+        fs::write(temp_dir.join("notes.png"), "just some notes\n").unwrap();
+        fs::write(temp_dir.join("doc.dat"), b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n").unwrap();
+        fs::write(
+            temp_dir.join("page.htm"),
+            "\n  <!doctype HTML>\n<html></html>",
+        )
+        .unwrap();
+        let mut tar = vec![0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        fs::write(temp_dir.join("archive"), &tar).unwrap();
+        File::create(temp_dir.join("empty")).unwrap();
+
+        let search = |pattern: &str| {
+            let mut names: Vec<_> = Finder::init(&temp_dir)
+                .filter_by_mime(Some(MimeFilter::from_string(pattern).unwrap()))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(search("image/*"), ["image.txt", "photo.bin"]);
+        assert_eq!(search("Text/Plain"), ["notes.png"]);
+        assert_eq!(
+            search("application/pdf, text/html"),
+            ["doc.dat", "page.htm"]
+        );
+        assert_eq!(search("*/x-tar"), ["archive"]);
+        assert_eq!(search("inode/x-empty"), ["empty"]);
+        // Directories have no contents to sniff
+        assert!(search("*/*").iter().all(|name| name != "photos.d"));
+
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypmp42"), "video/mp4");
+        assert_eq!(
+            sniff_mime(b"<?xml version=\"1.0\"?>\n<svg/>"),
+            "image/svg+xml"
+        );
+        // A multi-byte character cut off by the sniffing window is still text
+        let mut text = "a".repeat(crate::filters::SNIFF_LEN - 1).into_bytes();
+        text.extend_from_slice("\u{e9}".as_bytes());
+        assert_eq!(sniff_mime(&text), "text/plain");
+
+        assert!(MimeFilter::from_string("image").is_err());
+        assert!(MimeFilter::from_string("image/png,").is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_realpath_cache_matches_to_full_path() {
+        use crate::fs::RealpathCache;
+
+        let temp_dir = temp_dir().join("realpath_cache_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("real/nested")).unwrap();
+        for file in ["real/a", "real/b", "real/nested/c", "real/nested/d"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+        symlink(temp_dir.join("real"), temp_dir.join("alias")).unwrap();
+        symlink(temp_dir.join("real/a"), temp_dir.join("link_to_a")).unwrap();
+        symlink(temp_dir.join("missing"), temp_dir.join("dangling")).unwrap();
+
+        let cache = RealpathCache::new();
+        let mut resolved = 0;
+        for entry in Finder::init(temp_dir.join("alias"))
+            .follow_symlinks(true)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+        {
+            let expected = entry.to_full_path().unwrap();
+            let cached = cache.to_full_path(&entry).unwrap();
+            assert_eq!(cached.as_bytes(), expected.as_bytes());
+            assert_eq!(cached.file_name(), expected.file_name());
+            assert_eq!(cached.file_type(), expected.file_type());
+            assert!(
+                cached
+                    .as_path()
+                    .starts_with(fs::canonicalize(temp_dir.join("real")).unwrap())
+            );
+            resolved += 1;
+        }
+        // a, b, nested and nested's two files, sharing two resolved parents
+        assert_eq!(resolved, 5);
+        assert_eq!(cache.len(), 2);
+
+        // Symlinks resolve to their targets, and unresolvable entries are errors as with `to_full_path`
+        let link = DirEntry::new(temp_dir.join("link_to_a")).unwrap();
+        assert_eq!(
+            cache.to_full_path(&link).unwrap().as_path(),
+            fs::canonicalize(temp_dir.join("real/a")).unwrap()
+        );
+        let dangling = DirEntry::new(temp_dir.join("dangling")).unwrap();
+        assert!(cache.to_full_path(&dangling).is_err());
+
+        cache.clear();
+        assert!(cache.is_empty());
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_canonical_root_gives_canonical_results_without_following() {
+        let temp_dir = temp_dir().join("canonical_root_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("tree/sub")).unwrap();
+        File::create(temp_dir.join("tree/sub/file")).unwrap();
+        File::create(temp_dir.join("tree/top")).unwrap();
+        symlink(temp_dir.join("tree/sub"), temp_dir.join("tree/sub_link")).unwrap();
+        // The root itself is reached through a symlink, only it needs resolving
+        symlink(temp_dir.join("tree"), temp_dir.join("root_link")).unwrap();
+
+        let canonical_root = fs::canonicalize(temp_dir.join("tree")).unwrap();
+        let entries: Vec<_> = Finder::init(temp_dir.join("root_link"))
+            .canonicalise_root(true)
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .collect();
+        assert_eq!(entries.len(), 4);
+
+        for entry in entries {
+            assert!(entry.as_path().starts_with(&canonical_root));
+            if entry.is_symlink() {
+                // The link is listed where it is, not where it points
+                assert_eq!(entry.as_path(), canonical_root.join("sub_link"));
+            } else {
+                assert_eq!(
+                    entry.as_bytes(),
+                    entry.to_full_path().unwrap().as_bytes(),
+                    "{entry:?} should already be canonical"
+                );
+            }
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_group_by_inode_finds_hard_links() {
+        let temp_dir = temp_dir().join("group_by_inode_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("nested/deeper")).unwrap();
+        fs::write(temp_dir.join("first"), "one").unwrap();
+        fs::hard_link(temp_dir.join("first"), temp_dir.join("nested/first_link")).unwrap();
+        fs::hard_link(temp_dir.join("first"), temp_dir.join("nested/deeper/again")).unwrap();
+        fs::write(temp_dir.join("second"), "two").unwrap();
+        fs::hard_link(temp_dir.join("second"), temp_dir.join("nested/second_link")).unwrap();
+        fs::write(temp_dir.join("single"), "three").unwrap();
+        // Linked from outside the search, so only one path is found
+        fs::write(temp_dir.join("nested/outside"), "four").unwrap();
+        fs::hard_link(
+            temp_dir.join("nested/outside"),
+            temp_dir.join("outside_link"),
+        )
+        .unwrap();
+        symlink(temp_dir.join("first"), temp_dir.join("symlink")).unwrap();
+
+        let groups = Finder::init(temp_dir.join("nested"))
+            .thread_count(core::num::NonZeroUsize::new(4))
+            .build()
+            .unwrap()
+            .group_by_inode()
+            .unwrap();
+        // Only `first` has more than one path inside nested
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].entries.len(), 2);
+        assert_eq!(groups[0].links, 3);
+
+        let groups = Finder::init(&temp_dir)
+            .thread_count(core::num::NonZeroUsize::new(4))
+            .build()
+            .unwrap()
+            .group_by_inode()
+            .unwrap();
+        let names: Vec<Vec<String>> = groups
+            .iter()
+            .map(|group| {
+                group
+                    .entries
+                    .iter()
+                    .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                    .collect()
+            })
+            .collect();
+        // Sorted by path within each group, then by each group's first path
+        assert_eq!(
+            names,
+            [
+                vec!["first", "again", "first_link"],
+                vec!["outside", "outside_link"],
+                vec!["second_link", "second"],
+            ]
+        );
+
+        let first = fs::metadata(temp_dir.join("first")).unwrap();
+        assert_eq!(groups[0].links, 3);
+        assert_eq!(groups[0].inode, std::os::unix::fs::MetadataExt::ino(&first));
+        assert_eq!(
+            groups[0].device,
+            std::os::unix::fs::MetadataExt::dev(&first)
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(feature = "xxhash", feature = "blake3"))]
+    fn test_find_duplicates_by_size_then_hash() {
+        use crate::dedupe::HashAlgo;
+
+        let temp_dir = temp_dir().join("find_duplicates_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("nested")).unwrap();
+        let large = "0123456789".repeat(20_000);
+        fs::write(temp_dir.join("large_a"), &large).unwrap();
+        fs::write(temp_dir.join("nested/large_b"), &large).unwrap();
+        // Same size, differing only in the last byte
+        fs::write(temp_dir.join("large_c"), format!("{}x", &large[1..])).unwrap();
+        fs::write(temp_dir.join("small_a"), "hello").unwrap();
+        fs::write(temp_dir.join("nested/small_b"), "hello").unwrap();
+        fs::write(temp_dir.join("nested/small_c"), "hello").unwrap();
+        fs::write(temp_dir.join("unique"), "a different size").unwrap();
+        File::create(temp_dir.join("empty_a")).unwrap();
+        File::create(temp_dir.join("empty_b")).unwrap();
+
+        let algos = [
+            #[cfg(feature = "xxhash")]
+            HashAlgo::Xxh64,
+            #[cfg(feature = "blake3")]
+            HashAlgo::Blake3,
+        ];
+        for algo in algos {
+            let groups = Finder::init(&temp_dir)
+                .thread_count(core::num::NonZeroUsize::new(4))
+                .build()
+                .unwrap()
+                .find_duplicates(algo)
+                .unwrap();
+
+            let names: Vec<(u64, Vec<String>)> = groups
+                .iter()
+                .map(|group| {
+                    let names = group
+                        .entries
+                        .iter()
+                        .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                        .collect();
+                    (group.size, names)
+                })
+                .collect();
+            assert_eq!(
+                names,
+                [
+                    (200_000, vec!["large_a".to_owned(), "large_b".to_owned()]),
+                    (
+                        5,
+                        vec![
+                            "small_b".to_owned(),
+                            "small_c".to_owned(),
+                            "small_a".to_owned()
+                        ]
+                    ),
+                ],
+                "{algo:?}"
+            );
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_dir_depth_and_hidden() {
+        use crate::{ListOptions, list_dir};
+        use core::num::NonZeroU32;
+
+        let temp_dir = temp_dir().join("list_dir_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub/deeper")).unwrap();
+        fs::create_dir_all(temp_dir.join(".hidden_dir")).unwrap();
+        for file in [
+            "top",
+            ".hidden",
+            "sub/middle",
+            "sub/deeper/bottom",
+            ".hidden_dir/inside",
+        ] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+        symlink(temp_dir.join("sub"), temp_dir.join("sub_link")).unwrap();
+
+        let names = |options: ListOptions| -> Vec<String> {
+            list_dir(&temp_dir, options.sort(true))
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    let path = entry.as_path().strip_prefix(&temp_dir).unwrap();
+                    path.to_string_lossy().into_owned()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            names(ListOptions::new()),
+            [".hidden", ".hidden_dir", "sub", "sub_link", "top"]
+        );
+        assert_eq!(
+            names(ListOptions::new().keep_hidden(false)),
+            ["sub", "sub_link", "top"]
+        );
+        // The symlinked directory isn't followed
+        assert_eq!(
+            names(
+                ListOptions::new()
+                    .keep_hidden(false)
+                    .max_depth(NonZeroU32::new(2).unwrap())
+            ),
+            ["sub", "sub/deeper", "sub/middle", "sub_link", "top"]
+        );
+        let everything = list_dir(
+            &temp_dir,
+            ListOptions::new().max_depth(NonZeroU32::new(10).unwrap()),
+        )
+        .unwrap();
+        assert_eq!(everything.len(), 9);
+        assert!(
+            everything
+                .iter()
+                .any(|entry| entry.file_name() == b"bottom" && entry.depth() == 3)
+        );
+
+        assert!(list_dir(temp_dir.join("top"), ListOptions::new()).is_err());
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverse_with_parent_handles_outlive_traversal() {
+        use std::os::fd::AsRawFd as _;
+
+        let temp_dir = temp_dir().join("traverse_with_parent_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("inner")).unwrap();
+        fs::write(temp_dir.join("inner/file"), "contents").unwrap();
+        fs::write(temp_dir.join("top"), "").unwrap();
+
+        let results: Vec<_> = Finder::init(&temp_dir)
+            .build()
+            .unwrap()
+            .traverse_with_parent()
+            .unwrap()
+            .collect();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result.parent().is_some()));
+
+        // The traversal is over and the directory has moved, the handle still finds the file by name alone
+        fs::rename(temp_dir.join("inner"), temp_dir.join("moved")).unwrap();
+        let file = results
+            .iter()
+            .find(|result| result.entry().file_name() == b"file")
+            .unwrap();
+        let parent = file.parent().unwrap();
+        let mut statted: libc::stat = unsafe { core::mem::zeroed() };
+        let code = unsafe {
+            libc::fstatat(
+                parent.as_raw_fd(),
+                file.file_name_cstr().as_ptr(),
+                &raw mut statted,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+        assert_eq!(code, 0);
+        assert_eq!(statted.st_size, 8);
+        assert!(file.entry().get_lstat().is_err());
+
+        let (entry, parent) = file.clone().into_parts();
+        assert_eq!(entry.file_name(), b"file");
+        assert_eq!(
+            entry
+                .get_lstatat(parent.unwrap().as_file_des())
+                .unwrap()
+                .st_ino,
+            statted.st_ino
+        );
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_collect_stats_counts_results() {
+        use crate::walk::TraversalStats;
+
+        let temp_dir = temp_dir().join("collect_stats_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a/b/c")).unwrap();
+        fs::write(temp_dir.join("small"), "12").unwrap();
+        fs::write(temp_dir.join("a/large"), "0123456789").unwrap();
+        fs::write(temp_dir.join("a/b/c/empty"), "").unwrap();
+        symlink(temp_dir.join("small"), temp_dir.join("link")).unwrap();
+
+        let finder = Finder::init(&temp_dir).collect_stats(true).build().unwrap();
+        let stats = finder.stats().unwrap();
+        assert_eq!(finder.traverse().unwrap().count(), 7);
+
+        assert_eq!(stats.count(FileType::RegularFile), 3);
+        assert_eq!(stats.count(FileType::Directory), 3);
+        assert_eq!(stats.count(FileType::Symlink), 1);
+        assert_eq!(stats.total(), 7);
+        assert_eq!(stats.total_bytes(), 12);
+        assert_eq!(stats.errors(), 0);
+        let deepest = stats.deepest().unwrap();
+        assert_eq!(deepest.file_name(), b"empty");
+        assert_eq!(deepest.depth(), 4);
+        let (largest, size) = stats.largest().unwrap();
+        assert_eq!((largest.file_name(), size), (&b"large"[..], 10));
+
+        // Nothing is counted unless asked for
+        assert!(Finder::init(&temp_dir).build().unwrap().stats().is_none());
+        assert_eq!(TraversalStats::new().total(), 0);
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dirfd_outlives_iterators() {
+        let dir = temp_dir().join("test_dirfd_outlives_iterators");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("regular.txt")).unwrap();
+        let dir_entry = DirEntry::new(&dir).unwrap();
+
+        let entries = dir_entry.readdir().unwrap();
+        let from_readdir = entries.dirfd().clone();
+        assert!(!from_readdir.is_unique());
+        let file = entries.into_iter().next().unwrap();
+        // The iterator has gone, its descriptor is still open and now only ours
+        assert!(from_readdir.is_open());
+        assert!(from_readdir.is_unique());
+        assert!(file.get_lstatat(&from_readdir).is_ok());
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let entries = dir_entry.getdents().unwrap();
+            let from_getdents = entries.dirfd().clone();
+            drop(entries);
+            assert!(from_getdents.is_open());
+            assert!(from_getdents.is_unique());
+            assert!(file.get_lstatat(&from_getdents).is_ok());
+            assert_ne!(
+                from_getdents.as_borrowed_fd(),
+                from_readdir.as_borrowed_fd()
+            );
+        }
+
+        // Clones share the one descriptor rather than duplicating it
+        let shared = from_readdir.clone();
+        assert_eq!(shared.as_borrowed_fd(), from_readdir.as_borrowed_fd());
+        assert!(!shared.is_unique());
+        drop(from_readdir);
+        assert!(shared.is_unique() && shared.is_open());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_full_path_matches_relative_to_root() {
+        let temp_dir = temp_dir().join("full_path_relative_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src/lib")).unwrap();
+        fs::create_dir_all(temp_dir.join("docs")).unwrap();
+        for file in ["main.rs", "src/main.rs", "src/lib/mod.rs", "docs/readme.md"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+
+        let search = |root: &OsStr, pattern: &str, glob: bool| -> Vec<String> {
+            let mut found: Vec<String> = Finder::init(root)
+                .pattern(pattern)
+                .use_glob(glob)
+                .file_name_only(false)
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.as_path().strip_prefix(&temp_dir).unwrap();
+                    path.to_string_lossy().into_owned()
+                })
+                .collect();
+            found.sort();
+            found
+        };
+
+        // The expected results are what `fd --full-path` gives for the same tree and patterns
+        for root in [
+            temp_dir.as_os_str(),
+            &OsString::from(format!("{}/", temp_dir.display())),
+        ] {
+            assert_eq!(search(root, "src/*.rs", true), ["src/main.rs"]);
+            assert_eq!(search(root, "**/main.rs", true), ["main.rs", "src/main.rs"]);
+            assert_eq!(
+                search(root, "src/**", true),
+                ["src/lib", "src/lib/mod.rs", "src/main.rs"]
+            );
+            assert_eq!(
+                search(root, "src/**/*.rs", true),
+                ["src/lib/mod.rs", "src/main.rs"]
+            );
+            assert_eq!(search(root, "*/*/*", true), ["src/lib/mod.rs"]);
+            // Regexes can be anchored at the root too
+            assert_eq!(
+                search(root, "^src/", false),
+                ["src/lib", "src/lib/mod.rs", "src/main.rs"]
+            );
+            assert_eq!(search(root, r"^[^/]+\.rs$", false), ["main.rs"]);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_watch_reports_filtered_changes() {
+        use crate::walk::{CancellationToken, WatchEvent};
+
+        let temp_dir = temp_dir().join("watch_changes_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("existing")).unwrap();
+        fs::write(temp_dir.join("existing/old.txt"), "old").unwrap();
+
+        let token = CancellationToken::new();
+        let events = Finder::init(&temp_dir)
+            .pattern("*.txt")
+            .use_glob(true)
+            .cancellation_token(token.clone())
+            .build()
+            .unwrap()
+            .watch()
+            .unwrap();
+
+        // Waits for the given change, every change reported on the way must match the pattern too
+        let expect = |kind: &str, path: &str| {
+            loop {
+                let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+                let found = event.entry().as_path().strip_prefix(&temp_dir).unwrap();
+                assert!(
+                    found.extension().is_some_and(|ext| ext == "txt"),
+                    "{event:?}"
+                );
+                if event.kind() == kind && found == Path::new(path) {
+                    return event;
+                }
+            }
+        };
+
+        fs::write(temp_dir.join("skipped.log"), "").unwrap();
+        fs::write(temp_dir.join("existing/old.txt"), "changed").unwrap();
+        assert!(matches!(
+            expect("modified", "existing/old.txt"),
+            WatchEvent::Modified(_)
+        ));
+        fs::remove_file(temp_dir.join("existing/old.txt")).unwrap();
+        let deleted = expect("deleted", "existing/old.txt");
+        assert_eq!(deleted.entry().file_type(), FileType::Unknown);
+        // New directories are watched too, along with whatever they already hold
+        fs::create_dir(temp_dir.join("fresh")).unwrap();
+        fs::write(temp_dir.join("fresh/also.log"), "").unwrap();
+        fs::write(temp_dir.join("fresh/new.txt"), "").unwrap();
+        let created = expect("created", "fresh/new.txt");
+        assert_eq!(created.entry().depth(), 2);
+        token.cancel();
+
+        // The watching thread hangs up once cancelled
+        assert!(
+            events
+                .iter()
+                .all(|event| event.entry().file_name().ends_with(b".txt"))
+        );
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_follow_depth_limit_applies_without_max_depth() {
+        let temp_dir = temp_dir().join("follow_depth_limit_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a/b/c/d/e")).unwrap();
+
+        let walk = |builder: crate::walk::FinderBuilder| {
+            let finder = builder.build().unwrap();
+            let limit = finder.follow_depth_limit();
+            let deepest = finder.traverse().unwrap().map(|entry| entry.depth()).max();
+            (deepest, limit.map(|limit| (limit.limit(), limit.was_hit())))
+        };
+
+        let following = || Finder::init(&temp_dir).follow_symlinks(true);
+        assert_eq!(
+            walk(following().follow_depth_limit(Some(3))),
+            (Some(3), Some((3, true)))
+        );
+        assert_eq!(
+            walk(following().follow_depth_limit(Some(10))),
+            (Some(5), Some((10, false)))
+        );
+        // Only applies when symlinks are followed, and an explicit maximum depth takes its place
+        assert_eq!(walk(following().follow_depth_limit(None)), (Some(5), None));
+        assert_eq!(
+            walk(following().follow_depth_limit(Some(3)).max_depth(Some(4))),
+            (Some(4), None)
+        );
+        assert_eq!(
+            walk(Finder::init(&temp_dir).follow_depth_limit(Some(3))),
+            (Some(5), None)
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_path_index_rereads_only_changed_directories() {
+        use crate::walk::PathIndex;
+
+        let temp_dir = temp_dir().join("path_index_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in ["a/deep", "b", "c"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+        }
+        for file in ["a/one.txt", "a/deep/two.txt", "b/three.log", "c/four.txt"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+        // Kept outside the tree, writing it would change the root's modification time
+        let file = temp_dir.with_extension("index");
+
+        let finder = || Finder::init(&temp_dir).build().unwrap();
+        let index = finder().build_index().unwrap();
+        assert_eq!(index.len(), 5);
+        assert_eq!(index.reread(), 5);
+        index.write_to(&file).unwrap();
+
+        let query = |index: &mut PathIndex, pattern: &str| -> Vec<Vec<u8>> {
+            let mut found: Vec<_> = Finder::init(&temp_dir)
+                .pattern(pattern)
+                .build()
+                .unwrap()
+                .query_index(index)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.file_name().to_vec())
+                .collect();
+            found.sort();
+            found
+        };
+
+        // Nothing changed, so every listing comes from the index
+        let mut index = PathIndex::read_from(&temp_dir, &file).unwrap();
+        assert_eq!(
+            query(&mut index, "txt$"),
+            [&b"four.txt"[..], b"one.txt", b"two.txt"]
+        );
+        assert_eq!(index.reread(), 0);
+
+        File::create(temp_dir.join("b/five.txt")).unwrap();
+        fs::remove_dir_all(temp_dir.join("c")).unwrap();
+        assert_eq!(
+            query(&mut index, "txt$"),
+            [&b"five.txt"[..], b"one.txt", b"two.txt"]
+        );
+        // `b` and the root changed
+        assert_eq!(index.reread(), 2);
+
+        // Updating drops the directories which are gone
+        assert_eq!(finder().update_index(&mut index).unwrap(), 0);
+        assert_eq!(index.len(), 4);
+        assert!(
+            Finder::init(temp_dir.join("a"))
+                .build()
+                .unwrap()
+                .query_index(&mut index)
+                .is_err()
+        );
+
+        // Setting the modification time back doesn't hide a change, the status change time still moves
+        let deep = temp_dir.join("a/deep");
+        let modified = fs::metadata(&deep).unwrap().modified().unwrap();
+        File::create(deep.join("six.txt")).unwrap();
+        File::open(&deep)
+            .unwrap()
+            .set_times(fs::FileTimes::new().set_modified(modified))
+            .unwrap();
+        assert_eq!(fs::metadata(&deep).unwrap().modified().unwrap(), modified);
+        assert_eq!(
+            query(&mut index, "txt$"),
+            [&b"five.txt"[..], b"one.txt", b"six.txt", b"two.txt"]
+        );
+
+        fs::remove_file(&file).unwrap();
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(feature = "dir-cache")]
+    #[test]
+    fn test_dir_cache_answers_until_expired() {
+        use crate::walk::DirCache;
+
+        let temp_dir = temp_dir().join("dir_cache_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        File::create(temp_dir.join("sub/one.txt")).unwrap();
+
+        let query = |cache: &DirCache| {
+            Finder::init(&temp_dir)
+                .pattern("txt$")
+                .build()
+                .unwrap()
+                .query_cached(cache)
+                .unwrap()
+                .len()
+        };
+
+        let cache = DirCache::new(Duration::from_secs(3600));
+        assert_eq!(query(&cache), 1);
+        assert_eq!(cache.reads(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // Changes aren't seen until the listing expires or is invalidated
+        File::create(temp_dir.join("sub/two.txt")).unwrap();
+        assert_eq!(query(&cache), 1);
+        assert_eq!(cache.reads(), 2);
+        cache.invalidate(temp_dir.join("sub"));
+        assert_eq!(query(&cache), 2);
+        assert_eq!(cache.reads(), 3);
+
+        // Without a time to live every search reads every directory
+        let expired = DirCache::new(Duration::ZERO);
+        assert_eq!(query(&expired), 2);
+        assert_eq!(query(&expired), 2);
+        assert_eq!(expired.reads(), 4);
+        expired.purge_expired();
+        assert!(expired.is_empty());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_time_filter_intersect_within_and_before() {
+        let within = TimeFilter::changed_within("1w").unwrap();
+        let before = TimeFilter::changed_before("1d").unwrap();
+        let window = within.intersect(before);
+        assert!(matches!(window, TimeFilter::Between(..)));
+        // Either order gives the same window
+        assert_eq!(before.intersect(within), window);
+
+        let ago = |secs: u64| SystemTime::now() - Duration::from_secs(secs);
+        assert!(window.matches_time(ago(3 * 86_400)));
+        assert!(!window.matches_time(ago(3600)));
+        assert!(!window.matches_time(ago(8 * 86_400)));
+
+        // A window narrows further, and disjoint filters match nothing
+        let narrower = window.intersect(TimeFilter::changed_within("2d").unwrap());
+        assert!(narrower.matches_time(ago(36 * 3600)));
+        assert!(!narrower.matches_time(ago(3 * 86_400)));
+        let disjoint = TimeFilter::changed_before("1w")
+            .unwrap()
+            .intersect(TimeFilter::changed_within("1d").unwrap());
+        assert!(!disjoint.matches_time(ago(3 * 86_400)));
+        assert!(!disjoint.matches_time(ago(3600)));
+        assert!(!disjoint.matches_time(ago(8 * 86_400)));
+    }
+
+    #[test]
+    fn test_ls_colours_override_builtin_scheme() {
+        use crate::util::{LsColours, OutputContext, write_coloured};
+
+        let temp_dir = temp_dir().join("ls_colours_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("dir")).unwrap();
+        for name in ["notes.TXT", "archive.tar.gz", "plain", "backup~"] {
+            fs::write(temp_dir.join(name), b"").unwrap();
+        }
+        let entry = |name: &str| DirEntry::new(temp_dir.join(name)).unwrap();
+
+        let colours =
+            LsColours::parse(b"di=01;35:fi=36:*.txt=32:*.tar.gz=31:*~=90:ex=01;32:ln=target:bogus");
+        assert_eq!(colours.colour(&entry("dir")), b"\x1b[01;35m");
+        // Extensions match case insensitively, and longer suffixes are checked first
+        assert_eq!(colours.colour(&entry("notes.TXT")), b"\x1b[32m");
+        assert_eq!(colours.colour(&entry("archive.tar.gz")), b"\x1b[31m");
+        assert_eq!(colours.colour(&entry("backup~")), b"\x1b[90m");
+        assert_eq!(colours.colour(&entry("plain")), b"\x1b[36m");
+
+        // Anything LS_COLORS doesn't cover keeps the built in colours
+        let empty = LsColours::parse(b"");
+        let mut builtin = Vec::new();
+        let mut parsed = Vec::new();
+        let context = OutputContext::default();
+        write_coloured(&mut builtin, [entry("dir")], &empty, &context).unwrap();
+        write_coloured(
+            &mut parsed,
+            [entry("dir")],
+            &LsColours::parse(b"*.txt=32"),
+            &context,
+        )
+        .unwrap();
+        assert_eq!(builtin, parsed);
+        assert!(builtin.ends_with(b"/\x1b[0m\n"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_hyperlinks_percent_encode_paths() {
+        use crate::util::{Hyperlinks, LsColours, OutputContext, write_coloured, write_nocolour};
+
+        let temp_dir = temp_dir().join("hyperlinks_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let name = OsStr::from_bytes(b"hyper link\xff#1");
+        fs::write(temp_dir.join(name), b"").unwrap();
+        let entry = || DirEntry::new(temp_dir.join(name)).unwrap();
+        let url = format!("{}/hyper%20link%FF%231\x1b\\", temp_dir.to_str().unwrap());
+
+        let context = OutputContext {
+            quoted: true,
+            hyperlinks: Some(Hyperlinks::new(&temp_dir).unwrap()),
+            ..OutputContext::default()
+        };
+        let mut out = Vec::new();
+        write_nocolour(&mut out, [entry()], &context).unwrap();
+        let out = String::from_utf8_lossy(&out);
+        assert!(out.starts_with("\x1b]8;;file://"), "{out:?}");
+        assert!(out.contains(&url), "{out:?}");
+        // The quotes are part of the link, which closes before the newline
+        assert!(out.ends_with("\"\x1b]8;;\x1b\\\n"), "{out:?}");
+
+        // Colours sit inside the link
+        let context = OutputContext {
+            quoted: false,
+            ..context
+        };
+        let mut out = Vec::new();
+        write_coloured(&mut out, [entry()], &LsColours::parse(b"fi=36"), &context).unwrap();
+        let out = String::from_utf8_lossy(&out);
+        assert!(out.contains(&format!("{url}\x1b[36m")), "{out:?}");
+        assert!(out.ends_with("\x1b[0m\x1b]8;;\x1b\\\n"), "{out:?}");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_time_filter_parses_absolute_timestamps() {
+        let at = |seconds: u64| UNIX_EPOCH + Duration::from_secs(seconds);
+        let new_year = 1_704_067_200; // 2024-01-01T00:00:00Z
+
+        // Without an offset, timestamps are UTC
+        assert_eq!(
+            TimeFilter::from_string("2024-01-01").unwrap(),
+            TimeFilter::After(at(new_year))
+        );
+        for timestamp in [
+            "+2024-01-01T10:00:00Z",
+            "+2024-01-01T10:00:00",
+            "+2024-01-01 10:00",
+            "+2024-01-01T12:00:00+02:00",
+            "+2024-01-01T05:00:00-05:00",
+        ] {
+            assert_eq!(
+                TimeFilter::from_string(timestamp).unwrap(),
+                TimeFilter::Before(at(new_year + 10 * 3600)),
+                "{timestamp}"
+            );
+        }
+        assert_eq!(
+            TimeFilter::changed_within("2024-01-01T00:00:00.5Z").unwrap(),
+            TimeFilter::After(at(new_year) + Duration::from_millis(500))
+        );
+        // Before the epoch still works
+        assert_eq!(
+            TimeFilter::changed_before("1969-12-31").unwrap(),
+            TimeFilter::Before(UNIX_EPOCH - Duration::from_secs(86_400))
+        );
+
+        // Ranges can mix timestamps and durations, in either order
+        let january = TimeFilter::from_string("2024-02-01..2024-01-01").unwrap();
+        assert_eq!(
+            january,
+            TimeFilter::Between(at(new_year), at(new_year + 31 * 86_400))
+        );
+        assert!(january.matches_time(at(new_year + 86_400)));
+        assert!(!january.matches_time(at(new_year + 40 * 86_400)));
+        assert!(matches!(
+            TimeFilter::from_string("2024-01-01..1d"),
+            Ok(TimeFilter::Between(..))
+        ));
+
+        for invalid in [
+            "2024-13-01",
+            "2024-01-01T25:00",
+            "01/01/2024",
+            "2024-01-01T10:00:00+2",
+        ] {
+            assert!(TimeFilter::from_string(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_relative_output_rewrites_printed_paths() {
+        use crate::util::{OutputContext, OutputFormat, RelativePaths, write_json, write_nocolour};
+
+        let temp_dir = temp_dir().join("relative_output_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("root/sub")).unwrap();
+        fs::create_dir_all(temp_dir.join("other/deep")).unwrap();
+        fs::write(temp_dir.join("root/sub/file"), b"").unwrap();
+
+        // The root is given with a trailing slash, results don't repeat it
+        let root = format!("{}/", temp_dir.join("root").display());
+        let finder = Finder::init(&root).build().unwrap();
+        let file = || DirEntry::new(temp_dir.join("root/sub/file")).unwrap();
+        let print = |relative: &RelativePaths, separator: Option<&[u8]>| {
+            let context = OutputContext {
+                relative: Some(relative.clone()),
+                separator: separator.map(Box::from),
+                ..OutputContext::default()
+            };
+            let mut out = Vec::new();
+            write_nocolour(&mut out, [file()], &context).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let stripped = finder.relative_output(finder.root_dir()).unwrap();
+        assert_eq!(print(&stripped, None), "sub/file\n");
+        let nested = finder.relative_output(temp_dir.join("root/sub")).unwrap();
+        assert_eq!(print(&nested, None), "../sub/file\n");
+        let sibling = finder
+            .relative_output(temp_dir.join("other/deep/../deep"))
+            .unwrap();
+        assert_eq!(print(&sibling, None), "../../root/sub/file\n");
+        // The separator applies to the rewritten prefix too
+        assert_eq!(print(&sibling, Some(b"\\")), "..\\..\\root\\sub\\file\n");
+
+        let mut out = Vec::new();
+        write_json(&mut out, [file()], OutputFormat::Ndjson, Some(&sibling)).unwrap();
+        assert!(out.starts_with(b"{\"path\":\"../../root/sub/file\""));
+
+        // Paths outside the root are left alone
+        let outside = DirEntry::new(temp_dir.join("other/deep")).unwrap();
+        let outside_path = outside.as_bytes().to_vec();
+        assert_eq!(sibling.split(&outside_path), (&b""[..], &outside_path[..]));
+
+        assert!(finder.relative_output(temp_dir.join("missing")).is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pattern_files_match_any_pattern() {
+        let temp_dir = temp_dir().join("pattern_file_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("keys")).unwrap();
+        for name in [
+            "keys/id_rsa",
+            "keys/cert.PEM",
+            "keys/notes.txt",
+            ".env",
+            "#hash",
+        ] {
+            fs::write(temp_dir.join(name), b"").unwrap();
+        }
+        let list = temp_dir.with_extension("patterns");
+        fs::write(&list, "# private keys\nid_rsa$\n\n   \\.pem$   \n\\#hash\n").unwrap();
+
+        let names = |builder: crate::walk::FinderBuilder| {
+            let mut names: Vec<_> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Any pattern in the file (or given directly) is enough, comments and blank lines are skipped
+        let found = names(
+            Finder::init(&temp_dir)
+                .keep_hidden(false) // ie show hidden files
+                .case_insensitive(true)
+                .pattern_files(vec![list.clone().into_os_string()])
+                .any_patterns(vec![r"^\.env$".into()]),
+        );
+        assert_eq!(found, ["#hash", ".env", "cert.PEM", "id_rsa"]);
+
+        // The set applies on top of the search pattern
+        let found = names(
+            Finder::init(&temp_dir)
+                .pattern("^id")
+                .pattern_files(vec![list.clone().into_os_string()]),
+        );
+        assert_eq!(found, ["id_rsa"]);
+
+        assert!(
+            Finder::init(&temp_dir)
+                .pattern_files(vec![temp_dir.join("missing").into_os_string()])
+                .build()
+                .is_err()
+        );
+
+        fs::remove_file(&list).unwrap();
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_presets_match_with_any_other_options() {
+        let temp_dir = temp_dir().join("preset_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("app/node_modules")).unwrap();
+        fs::create_dir_all(temp_dir.join("app/target")).unwrap();
+        for name in [
+            "app/.env.local",
+            "app/server.pem",
+            "app/main.o",
+            "app/core.1234",
+            "app/id_rsa.pub",
+            "app/notes.txt",
+        ] {
+            fs::write(temp_dir.join(name), b"").unwrap();
+        }
+
+        let names = |builder: crate::walk::FinderBuilder| {
+            let mut names: Vec<_> = builder
+                .keep_hidden(false) // ie show hidden files
+                .max_depth(Some(2))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let secrets = ["secrets".parse::<crate::Preset>().unwrap()];
+        assert_eq!(
+            names(Finder::init(&temp_dir).presets(secrets.to_vec())),
+            [".env.local", "server.pem"]
+        );
+
+        // The preset patterns are regexes whether or not globs are used, and match relative paths too
+        let artifacts = vec![crate::Preset::Artifacts];
+        let expected = ["core.1234", "main.o", "node_modules", "target"];
+        assert_eq!(
+            names(Finder::init(&temp_dir).presets(artifacts.clone())),
+            expected
+        );
+        assert_eq!(
+            names(
+                Finder::init(&temp_dir)
+                    .presets(artifacts.clone())
+                    .pattern("*")
+                    .use_glob(true)
+            ),
+            expected
+        );
+        assert_eq!(
+            names(
+                Finder::init(&temp_dir)
+                    .presets(artifacts)
+                    .file_name_only(false)
+            ),
+            expected
+        );
+
+        assert!("passwords".parse::<crate::Preset>().is_err());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_min_depth_skips_shallow_entries() {
+        let temp_dir = temp_dir().join("min_depth_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a/b/c")).unwrap();
+        fs::write(temp_dir.join("top.txt"), b"").unwrap();
+        fs::write(temp_dir.join("a/mid.txt"), b"").unwrap();
+        fs::write(temp_dir.join("a/b/c/deep.txt"), b"").unwrap();
+
+        let depths = |builder: crate::walk::FinderBuilder| {
+            let mut depths: Vec<_> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| {
+                    (
+                        entry.depth(),
+                        String::from_utf8_lossy(entry.file_name()).into_owned(),
+                    )
+                })
+                .collect();
+            depths.sort();
+            depths
+        };
+
+        // Shallow directories are still descended into, only their entries are suppressed
+        let found = depths(Finder::init(&temp_dir).min_depth(Some(2)));
+        assert!(found.iter().all(|(depth, _)| *depth >= 2));
+        assert!(found.contains(&(2, "mid.txt".into())));
+        assert!(found.contains(&(4, "deep.txt".into())));
+
+        assert_eq!(
+            depths(
+                Finder::init(&temp_dir)
+                    .min_depth(Some(2))
+                    .max_depth(Some(3))
+            ),
+            [(2, "b".into()), (2, "mid.txt".into()), (3, "c".into())]
+        );
+
+        // Depth 0 and 1 both mean every entry
+        assert_eq!(
+            depths(Finder::init(&temp_dir).min_depth(Some(0))),
+            depths(Finder::init(&temp_dir).min_depth(Some(1)))
+        );
+        assert!(
+            depths(Finder::init(&temp_dir).min_depth(Some(1))).contains(&(1, "top.txt".into()))
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_stops_at_matching_directories() {
+        let temp_dir = temp_dir().join("prune_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("app/node_modules/dep/node_modules")).unwrap();
+        fs::create_dir_all(temp_dir.join("node_modules")).unwrap();
+        fs::write(temp_dir.join("app/node_modules/dep/node_modules.txt"), b"").unwrap();
+
+        let paths = |builder: crate::walk::FinderBuilder| {
+            let mut paths: Vec<_> = builder
+                .pattern("node_modules")
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| {
+                    String::from_utf8_lossy(entry.as_bytes())[temp_dir.as_os_str().len()..]
+                        .to_owned()
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(
+            paths(Finder::init(&temp_dir).prune(true)),
+            ["/app/node_modules", "/node_modules"]
+        );
+        // Without pruning, matches inside a matching directory are found too
+        assert_eq!(
+            paths(Finder::init(&temp_dir)),
+            [
+                "/app/node_modules",
+                "/app/node_modules/dep/node_modules",
+                "/app/node_modules/dep/node_modules.txt",
+                "/node_modules"
+            ]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_dirs_stops_descent() {
+        let temp_dir = temp_dir().join("prune_dirs_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("keep/deny/inner")).unwrap();
+        fs::create_dir_all(temp_dir.join("deny")).unwrap();
+        fs::write(temp_dir.join("keep/a.txt"), b"").unwrap();
+        fs::write(temp_dir.join("keep/deny/b.txt"), b"").unwrap();
+        fs::write(temp_dir.join("deny/c.txt"), b"").unwrap();
+
+        let finder = || {
+            Finder::init(&temp_dir)
+                .prune_dirs(|dir| dir.file_name() == b"deny")
+                .build()
+                .unwrap()
+        };
+        let mut paths: Vec<_> = finder()
+            .traverse()
+            .unwrap()
+            .map(|entry| {
+                String::from_utf8_lossy(entry.as_bytes())[temp_dir.as_os_str().len()..].to_owned()
+            })
+            .collect();
+        paths.sort();
+        // The pruned directories are still emitted, but not read
+        assert_eq!(paths, ["/deny", "/keep", "/keep/a.txt", "/keep/deny"]);
+
+        // The index is walked the same way
+        let index = finder().build_index().unwrap();
+        assert_eq!(index.len(), 2);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_follow_symlinks_cycle_visits_once() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = temp_dir().join("symlink_cycle_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a/b")).unwrap();
+        fs::create_dir_all(temp_dir.join("other")).unwrap();
+        fs::write(temp_dir.join("a/f.txt"), b"").unwrap();
+        fs::write(temp_dir.join("other/g.txt"), b"").unwrap();
+        // Loops back to the root and to its own parent, plus a second way into `other`
+        symlink(&temp_dir, temp_dir.join("a/b/up")).unwrap();
+        symlink("../a", temp_dir.join("a/self")).unwrap();
+        symlink(temp_dir.join("other"), temp_dir.join("link")).unwrap();
+
+        let paths = |max_symlink_depth| {
+            let mut paths: Vec<_> = Finder::init(&temp_dir)
+                .follow_symlinks(true)
+                .max_symlink_depth(max_symlink_depth)
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| {
+                    String::from_utf8_lossy(entry.as_bytes())[temp_dir.as_os_str().len()..]
+                        .to_owned()
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // Every directory is read once however many ways there are into it
+        let followed = paths(None);
+        let names = |suffix| {
+            followed
+                .iter()
+                .filter(|path| path.ends_with(suffix))
+                .count()
+        };
+        assert_eq!(names("/f.txt"), 1);
+        assert_eq!(names("/g.txt"), 1);
+        let mut unique = followed.clone();
+        unique.dedup();
+        assert_eq!(unique, followed);
+
+        // Without following any, the symlinks are emitted as they are
+        let unfollowed = paths(Some(0));
+        for link in ["/a/b/up", "/a/self", "/link"] {
+            assert!(unfollowed.iter().any(|path| path == link), "{link} missing");
+        }
+        assert!(unfollowed.iter().any(|path| path == "/other/g.txt"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn test_injected_faults() {
+        use crate::fs::fault::Faults;
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("fault_injection_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..4 {
+            fs::create_dir_all(temp_dir.join(format!("dir{dir}"))).unwrap();
+            fs::write(temp_dir.join(format!("dir{dir}/file.txt")), b"").unwrap();
+        }
+        for file in 0..200 {
+            fs::write(temp_dir.join(format!("dir0/{file:0>100}")), b"").unwrap();
+        }
+        let finder = || {
+            Finder::init(&temp_dir)
+                .collect_errors(true)
+                .build()
+                .unwrap()
+        };
+
+        // Every other subdirectory fails to open, whichever order they're walked in
+        let guard = Faults::new(&temp_dir)
+            .deny_open_every(NonZeroUsize::new(2).unwrap())
+            .install();
+        let (found, errors): (Vec<_>, Vec<_>) = finder()
+            .traverse_with_errors()
+            .unwrap()
+            .partition(Result::is_ok);
+        assert_eq!(guard.denied(), 2);
+        assert_eq!(errors.len(), 2);
+        assert!(
+            errors
+                .into_iter()
+                .all(|error| error.unwrap_err().raw_os_error() == Some(libc::EACCES))
+        );
+        assert!(found.len() < 4 + 4 + 200);
+        drop(guard);
+
+        // Reading a single entry per call still finds everything, and the faults are gone once dropped
+        let _guard = Faults::new(&temp_dir).short_reads(1).install();
+        assert_eq!(finder().traverse().unwrap().count(), 4 + 4 + 200);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        use core::{num::NonZeroUsize, time::Duration};
+
+        let temp_dir = temp_dir().join("resource_limits_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in ["a/inner", "b", "c"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+            fs::write(temp_dir.join(dir).join("file.txt"), b"").unwrap();
+        }
+        let finder = || Finder::init(&temp_dir).collect_errors(true);
+        let mut everything: Vec<_> = finder()
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.as_bytes().to_vec())
+            .collect();
+        everything.sort();
+
+        // A single descriptor means a single thread and no directories held open, but nothing is missed
+        let limited = finder().max_open_fds(NonZeroUsize::new(1)).build().unwrap();
+        assert_eq!(limited.thread_count.get(), 1);
+        let mut found: Vec<_> = limited
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.as_bytes().to_vec())
+            .collect();
+        found.sort();
+        assert_eq!(found, everything);
+
+        // While the root is being read the queue is full, so its subdirectories are emitted but not read
+        let queued = finder()
+            .max_queued_dirs(NonZeroUsize::new(1))
+            .build()
+            .unwrap();
+        let errors = queued.error_store().unwrap();
+        let mut found: Vec<_> = queued
+            .traverse()
+            .unwrap()
+            .map(|entry| entry.file_name().to_vec())
+            .collect();
+        found.sort();
+        assert_eq!(found, [&b"a"[..], b"b", b"c"]);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 3);
+        assert!(
+            errors
+                .iter()
+                .all(|error| matches!(error.error(), crate::DirEntryError::QueueFull))
+        );
+
+        // Out of time before the root is read, which is reported once
+        let timed = finder().timeout(Some(Duration::ZERO)).build().unwrap();
+        let root = timed.root_dir().as_bytes().to_vec();
+        let errors = timed.error_store().unwrap();
+        assert_eq!(timed.traverse().unwrap().count(), 0);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_timed_out());
+        assert_eq!(errors[0].path().as_bytes(), root);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn test_parse_dirents_bounds() {
+        use crate::fs::parse_dirents;
+        use core::mem::offset_of;
+
+        /// A record laid out as the kernel does, name padded with zeros to the next multiple of 8
+        fn record(inode: u64, d_type: u8, name: &[u8]) -> Vec<u8> {
+            let name_offset = offset_of!(libc::dirent64, d_name);
+            let reclen = (name_offset + name.len() + 1).next_multiple_of(8);
+            let mut record = vec![0; reclen];
+            let ino = offset_of!(libc::dirent64, d_ino);
+            record[ino..ino + 8].copy_from_slice(&inode.to_ne_bytes());
+            let len = offset_of!(libc::dirent64, d_reclen);
+            record[len..len + 2].copy_from_slice(&u16::try_from(reclen).unwrap().to_ne_bytes());
+            record[offset_of!(libc::dirent64, d_type)] = d_type;
+            record[name_offset..name_offset + name.len()].copy_from_slice(name);
+            record
+        }
+
+        let names: [&[u8]; 4] = [b"", b"abcd", b"abcde", &[b'x'; 255]];
+        let buffer: Vec<u8> = names
+            .iter()
+            .enumerate()
+            .flat_map(|(inode, name)| record(inode as u64, libc::DT_REG, name))
+            .collect();
+        let parsed = parse_dirents(&buffer).unwrap();
+        assert_eq!(parsed.len(), names.len());
+        for (inode, (record, name)) in parsed.iter().zip(names).enumerate() {
+            assert_eq!(
+                (record.inode, record.d_type, &*record.name),
+                (inode as u64, libc::DT_REG, name)
+            );
+        }
+
+        // Truncated, a record length past the end, one of zero, and a name without its terminator
+        let first = record(0, libc::DT_REG, b"").len();
+        assert_eq!(parse_dirents(&buffer[..first - 1]), Err(0));
+        let mut bad = buffer.clone();
+        bad[offset_of!(libc::dirent64, d_reclen)] = u8::MAX - 7;
+        assert_eq!(parse_dirents(&bad), Err(0));
+        let mut bad = buffer.clone();
+        bad[first + offset_of!(libc::dirent64, d_reclen)] = 0;
+        assert_eq!(parse_dirents(&bad), Err(first));
+        let mut bad = record(1, libc::DT_DIR, b"abcd");
+        let end = bad.len();
+        bad[end - 4..].fill(b'x');
+        assert_eq!(parse_dirents(&bad), Err(0));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_thread_pool_reused_across_searches() {
+        use crate::walk::ControlFlow;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap(),
+        );
+        let finder = |pool: Option<&Arc<rayon::ThreadPool>>| {
+            let builder = Finder::init(env!("CARGO_MANIFEST_DIR")).pattern(r"\.rs$");
+            match pool {
+                Some(pool) => builder.with_thread_pool(Arc::clone(pool)),
+                None => builder,
+            }
+            .build()
+            .unwrap()
+        };
+        let expected = finder(None).traverse().unwrap().count();
+
+        let pooled = finder(Some(&pool));
+        assert_eq!(pooled.thread_count.get(), 2);
+        for _ in 0..3 {
+            assert_eq!(pooled.clone().traverse().unwrap().count(), expected);
+
+            let visited = AtomicUsize::new(0);
+            pooled
+                .clone()
+                .visit(|_| {
+                    visited.fetch_add(1, Ordering::Relaxed);
+                    ControlFlow::Continue
+                })
+                .unwrap();
+            assert_eq!(visited.into_inner(), expected);
+        }
+    }
+
+    #[test]
+    fn test_colour_mode_detects_environment() {
+        use crate::util::ColourMode;
+
+        let detect = |vars: &[(&str, &str)]| {
+            ColourMode::detect_with(|name| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| OsString::from(value))
+            })
+        };
+
+        assert_eq!(detect(&[]), ColourMode::Auto);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ColourMode::Auto);
+        assert_eq!(detect(&[("NO_COLOR", "1")]), ColourMode::Never);
+        assert_eq!(detect(&[("NO_COLOUR", "true")]), ColourMode::Never);
+        // An empty NO_COLOR doesn't count
+        assert_eq!(detect(&[("NO_COLOR", "")]), ColourMode::Auto);
+        assert_eq!(detect(&[("CLICOLOR_FORCE", "1")]), ColourMode::Always);
+        assert_eq!(detect(&[("CLICOLOR_FORCE", "0")]), ColourMode::Auto);
+        assert_eq!(
+            detect(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]),
+            ColourMode::Never
+        );
+        assert_eq!(
+            detect(&[("CLICOLOR_FORCE", "1"), ("TERM", "dumb")]),
+            ColourMode::Always
+        );
+        assert_eq!(detect(&[("CLICOLOR", "0")]), ColourMode::Never);
+        assert_eq!(detect(&[("TERM", "dumb")]), ColourMode::Never);
+
+        // Explicit modes don't look at the environment
+        assert!(ColourMode::Always.enabled(false));
+        assert!(!ColourMode::Never.enabled(true));
+    }
+
+    #[test]
+    fn test_type_filters_combine_with_or() {
+        use crate::filters::FileTypeFilter;
+
+        let temp_dir = temp_dir().join("multiple_type_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("dir")).unwrap();
+        fs::write(temp_dir.join("file"), b"data").unwrap();
+        fs::write(temp_dir.join("empty"), b"").unwrap();
+        symlink(temp_dir.join("file"), temp_dir.join("link")).unwrap();
+
+        let names = |filters: &[FileTypeFilter]| {
+            let mut names: Vec<_> = Finder::init(&temp_dir)
+                .type_filter(FileTypeFilter::any_of(filters.iter().copied()))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            names(&[FileTypeFilter::File, FileTypeFilter::Symlink]),
+            ["empty", "file", "link"]
+        );
+        assert_eq!(
+            names(&[FileTypeFilter::Directory, FileTypeFilter::Empty]),
+            ["dir", "empty"]
+        );
+        assert_eq!(names(&[FileTypeFilter::Symlink]), ["link"]);
+        assert_eq!(names(&[]), ["dir", "empty", "file", "link"]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_context_hides_control_characters_on_terminals() {
+        use crate::util::{OutputContext, write_nocolour};
+
+        let temp_dir = temp_dir().join("output_context_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let name = OsStr::from_bytes(b"evil\x1b[31m\tname");
+        fs::write(temp_dir.join(name), b"").unwrap();
+        let entry = || DirEntry::new(temp_dir.join(name)).unwrap();
+        let print = |context: &OutputContext| {
+            let mut out = Vec::new();
+            write_nocolour(&mut out, [entry()], context).unwrap();
+            out
+        };
+
+        let terminal = OutputContext {
+            terminal: true,
+            ..OutputContext::default()
+        };
+        assert!(print(&terminal).ends_with(b"/evil?[31m?name\n"));
+        assert!(terminal.buffer_capacity() < OutputContext::default().buffer_capacity());
+
+        // Pipes, and null terminated output even on a terminal, get the raw bytes
+        assert!(print(&OutputContext::default()).ends_with(b"/evil\x1b[31m\tname\n"));
+        let null_terminated = OutputContext {
+            terminal: true,
+            null_terminated: true,
+            ..OutputContext::default()
+        };
+        assert!(print(&null_terminated).ends_with(b"/evil\x1b[31m\tname\0"));
+
+        // The separator still applies alongside the replacement
+        let separated = OutputContext {
+            terminal: true,
+            separator: Some(Box::from(&b"\\"[..])),
+            ..OutputContext::default()
+        };
+        assert!(print(&separated).ends_with(b"\\evil?[31m?name\n"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_cloned_finders_traverse_independently() {
+        const fn assert_shareable<T: Clone + Send + Sync>() {}
+        assert_shareable::<Finder>();
+
+        let temp_dir = temp_dir().join("cloned_finder_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        fs::write(temp_dir.join("a.rs"), b"").unwrap();
+        fs::write(temp_dir.join("sub/b.rs"), b"").unwrap();
+        fs::write(temp_dir.join("sub/c.txt"), b"").unwrap();
+
+        let finder = Finder::init(&temp_dir)
+            .pattern("*.rs")
+            .use_glob(true)
+            .collect_stats(true)
+            .build()
+            .unwrap();
+        let names = |finder: Finder| {
+            let mut names: Vec<_> = finder
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // The compiled configuration is shared, the record of each traversal isn't
+        let first = finder.clone();
+        let first_stats = first.stats().unwrap();
+        assert_eq!(names(first), ["a.rs", "b.rs"]);
+        assert_eq!(first_stats.total(), 2);
+
+        let second = finder.clone();
+        assert!(Arc::ptr_eq(&second.search_config, &finder.search_config));
+        let second_stats = second.stats().unwrap();
+        assert_eq!(names(second), ["a.rs", "b.rs"]);
+        assert_eq!(second_stats.total(), 2);
+        assert_eq!(finder.stats().unwrap().total(), 0);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_extensions_match_any() {
+        let temp_dir = temp_dir().join("extensions_match_any_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        for name in ["a.rs", "b.TOML", "c.txt", "d.md", "rs"] {
+            fs::write(temp_dir.join(name), "").unwrap();
+        }
+
+        let names = |builder: crate::walk::FinderBuilder| {
+            let mut names: Vec<_> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let extensions = names(Finder::init(&temp_dir).extensions(vec![
+            "rs".into(),
+            ".toml".into(),
+            "RS".into(),
+            String::new(),
+        ]));
+        assert_eq!(extensions, ["a.rs", "b.TOML"]);
+
+        // A single extension behaves as it always has, and no extensions filter nothing
+        assert_eq!(names(Finder::init(&temp_dir).extension("txt")), ["c.txt"]);
+        assert_eq!(
+            names(Finder::init(&temp_dir).extensions(Vec::new())),
+            ["a.rs", "b.TOML", "c.txt", "d.md", "rs"]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_filters_run_after_name_filters() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let temp_dir = temp_dir().join("metadata_filters_stage_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        for name in ["a.rs", "b.txt", "c.rs", "d.txt"] {
+            fs::write(temp_dir.join(name), "data").unwrap();
+        }
+        fs::hard_link(temp_dir.join("c.rs"), temp_dir.join("linked")).unwrap();
+
+        let linked: crate::walk::StatFilter = |_, statted| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            statted.st_nlink > 1
+        };
+        let names = |builder: crate::walk::FinderBuilder| {
+            let mut names: Vec<_> = builder
+                .extension("rs")
+                .filter_by_metadata(Some(linked))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Only the entries passing the extension filter are statted
+        assert_eq!(names(Finder::init(&temp_dir)), ["c.rs"]);
+        assert_eq!(CALLS.swap(0, Ordering::Relaxed), 2);
+
+        // Sharing the lstat with the size filter gives the same answer
+        let sized = Finder::init(&temp_dir).filter_by_size(Some(SizeFilter::Equals(4)));
+        assert_eq!(names(sized), ["c.rs"]);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_exclude_pattern_negates_matches() {
+        let temp_dir = temp_dir().join("exclude_pattern_negates_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("tests")).unwrap();
+        for name in ["lib.rs", "lib_test.rs", "notes.md", "tests/walk.rs"] {
+            fs::write(temp_dir.join(name), "").unwrap();
+        }
+
+        let names = |builder: crate::walk::FinderBuilder| {
+            let mut names: Vec<_> = builder
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Matching directories are still searched, only the names are negated
+        let negated = names(
+            Finder::init(&temp_dir)
+                .pattern(r"\.rs$")
+                .exclude_pattern("test"),
+        );
+        assert_eq!(negated, ["lib.rs", "walk.rs"]);
+
+        // Several negations are joined, any of them excluding a result
+        let both = names(
+            Finder::init(&temp_dir)
+                .pattern(r"\.rs$")
+                .not_patterns(vec!["test".into(), "^w".into()]),
+        );
+        assert_eq!(both, ["lib.rs"]);
+
+        // Negations are escaped along with the other patterns, "b." would otherwise match "b_"
+        let fixed = names(
+            Finder::init(&temp_dir)
+                .pattern(".rs")
+                .exclude_pattern("b.")
+                .fixed_string(true),
+        );
+        assert_eq!(fixed, ["lib_test.rs", "walk.rs"]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_filter_expression() {
+        use crate::filters::{Filter, ParseExprError};
+
+        let temp_dir = temp_dir().join("filter_expression_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("logs")).unwrap();
+        fs::write(temp_dir.join("logs/big.log"), vec![b'x'; 2048]).unwrap();
+        fs::write(temp_dir.join("logs/small.log"), "x").unwrap();
+        fs::write(temp_dir.join("notes.md"), "x").unwrap();
+        fs::write(temp_dir.join("main.rs"), "x").unwrap();
+
+        let names = |expression: Filter| {
+            let mut names: Vec<_> = Finder::init(&temp_dir)
+                .filter_expr(Some(expression))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| String::from_utf8_lossy(entry.file_name()).into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let parsed = |expression: &str| names(Filter::from_string(expression).unwrap());
+
+        let built = Filter::glob("*.log")
+            .unwrap()
+            .and(Filter::size_gt(1024))
+            .or(Filter::extension("rs"));
+        assert_eq!(names(built), ["big.log", "main.rs"]);
+        assert_eq!(
+            parsed("(name:*.log & size:+1k) | ext:rs"),
+            ["big.log", "main.rs"]
+        );
+
+        // `&` binds tighter than `|`, and juxtaposed tests are and-ed
+        assert_eq!(
+            parsed("ext:md | name:*.log size:+1k"),
+            ["big.log", "notes.md"]
+        );
+        assert_eq!(parsed("!type:f"), ["logs"]);
+        // Path tests see the path below the root
+        assert_eq!(parsed("path:logs/* & !name:big*"), ["small.log"]);
+        assert_eq!(parsed("name:'main.rs'"), ["main.rs"]);
+
+        assert_eq!(Filter::from_string("").err(), Some(ParseExprError::Empty));
+        assert_eq!(
+            Filter::from_string("(ext:rs").err(),
+            Some(ParseExprError::Unclosed)
+        );
+        assert_eq!(
+            Filter::from_string("ext:rs |").err(),
+            Some(ParseExprError::Empty)
+        );
+        assert_eq!(
+            Filter::from_string("colour:red").err(),
+            Some(ParseExprError::UnknownKey("colour".into()))
+        );
+        assert!(matches!(
+            Filter::from_string("size:lots"),
+            Err(ParseExprError::InvalidValue(..))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_total_size_budget() {
+        use crate::filters::FileTypeFilter;
+
+        let temp_dir = temp_dir().join("max_total_size_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        for index in 0..10 {
+            fs::write(temp_dir.join(format!("file{index}")), [0u8; 100]).unwrap();
+            fs::write(temp_dir.join(format!("sub/file{index}")), [0u8; 100]).unwrap();
+        }
+
+        let sizes = |budget: u64| -> Vec<u64> {
+            Finder::init(&temp_dir)
+                .type_filter(Some(FileTypeFilter::File))
+                .max_total_size(Some(budget))
+                .thread_count(core::num::NonZeroUsize::new(4))
+                .build()
+                .unwrap()
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.file_size().unwrap())
+                .collect()
+        };
+
+        // The budget is never overrun, however the threads race for it
+        let sized = sizes(550);
+        assert_eq!(sized.len(), 5);
+        assert_eq!(sized.iter().sum::<u64>(), 500);
+        assert_eq!(sizes(2000).len(), 20);
+        assert!(sizes(99).is_empty());
+
+        // Directories don't count towards it
+        let with_dirs = Finder::init(&temp_dir)
+            .max_total_size(Some(2000))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(with_dirs, 21);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverse_chunks_fixed_size() {
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("traverse_chunks_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..5 {
+            let sub = temp_dir.join(format!("dir{dir}"));
+            fs::create_dir_all(&sub).unwrap();
+            for file in 0..20 {
+                File::create(sub.join(format!("file{file}"))).unwrap();
+            }
+        }
+
+        let chunks = |size: usize| {
+            Finder::init(&temp_dir)
+                .thread_count(NonZeroUsize::new(3))
+                .build()
+                .unwrap()
+                .traverse_chunks(NonZeroUsize::new(size).unwrap())
+                .unwrap()
+        };
+
+        // 105 entries make fifteen full chunks of 7, all of which reach a slow consumer
+        let mut sizes = Vec::new();
+        for chunk in chunks(7) {
+            std::thread::sleep(Duration::from_millis(1));
+            sizes.push(chunk.len());
+        }
+        assert_eq!(sizes, [7; 15]);
+
+        // Only the last chunk comes up short
+        let sizes: Vec<_> = chunks(10).map(|chunk| chunk.len()).collect();
+        assert_eq!(sizes.len(), 11);
+        assert!(sizes[..10].iter().all(|&len| len == 10));
+        assert_eq!(sizes[10], 5);
+
+        // Dropping the iterator early stops the workers rather than blocking forever
+        let mut early = chunks(4);
+        assert_eq!(early.next().map(|chunk| chunk.len()), Some(4));
+        drop(early);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_stateful_metadata_filters() {
+        use core::sync::atomic::{AtomicU64, Ordering};
+        use std::collections::HashSet;
+
+        let temp_dir = temp_dir().join("stateful_metadata_filter_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        for index in 0..10u8 {
+            fs::write(temp_dir.join(format!("file{index}")), vec![0; 10]).unwrap();
+            fs::hard_link(
+                temp_dir.join(format!("file{index}")),
+                temp_dir.join(format!("sub/link{index}")),
+            )
+            .unwrap();
+        }
+
+        // A quota on the total size, counted only for entries passing the other filters
+        let used = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&used);
+        let kept = Finder::init(&temp_dir)
+            .pattern("^file")
+            .filter_by_metadata_shared(move |_, statted| {
+                let size = statted.map_or(0, |statted| statted.st_size as u64);
+                counted
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                        (total + size <= 45).then_some(total + size)
+                    })
+                    .is_ok()
+            })
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(kept, 4);
+        assert_eq!(used.load(Ordering::Relaxed), 40);
+
+        // Mutable state on the stat: one path per inode, so each hard linked pair is reported once
+        let mut inodes = HashSet::new();
+        let unique = Finder::init(&temp_dir)
+            .filter_by_metadata_mut(move |entry, statted| {
+                !entry.is_dir() && statted.is_some_and(|statted| inodes.insert(statted.st_ino))
+            })
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count();
+        assert_eq!(unique, 10);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_traverse_ordered_is_deterministic() {
+        use crate::walk::ResultOrder;
+        use core::num::NonZeroUsize;
+
+        let temp_dir = temp_dir().join("traverse_ordered_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in ["b", "a/y", "a/x"] {
+            fs::create_dir_all(temp_dir.join(dir)).unwrap();
+        }
+        for file in ["c", "a.txt", "a/z", "a/x/1", "a/y/2", "b/3"] {
+            File::create(temp_dir.join(file)).unwrap();
+        }
+        let ordered = |order, threads| -> Vec<String> {
+            Finder::init(&temp_dir)
+                .thread_count(NonZeroUsize::new(threads))
+                .build()
+                .unwrap()
+                .traverse_ordered(order)
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.as_path().strip_prefix(&temp_dir).unwrap();
+                    path.to_string_lossy().into_owned()
+                })
+                .collect()
+        };
+
+        // Each directory is followed by its contents, siblings by name
+        let dfs = ordered(ResultOrder::DepthFirst, 1);
+        assert_eq!(
+            dfs,
+            [
+                "a", "a/x", "a/x/1", "a/y", "a/y/2", "a/z", "a.txt", "b", "b/3", "c"
+            ]
+        );
+        let bfs = ordered(ResultOrder::BreadthFirst, 1);
+        assert_eq!(
+            bfs,
+            [
+                "a", "a.txt", "b", "c", "a/x", "a/y", "a/z", "b/3", "a/x/1", "a/y/2"
+            ]
+        );
+        for threads in [2, 8] {
+            assert_eq!(ordered(ResultOrder::DepthFirst, threads), dfs);
+            assert_eq!(ordered(ResultOrder::BreadthFirst, threads), bfs);
+        }
+
+        // The result limit takes the first results in order
+        let first: Vec<_> = Finder::init(&temp_dir)
+            .max_results(Some(3))
+            .build()
+            .unwrap()
+            .traverse_ordered(ResultOrder::DepthFirst)
+            .unwrap()
+            .map(|entry| entry.file_name().to_vec())
+            .collect();
+        assert_eq!(first, [&b"a"[..], b"x", b"1"]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    /// A random file name, biased towards the lengths and bytes which trip up the fast name length
+    fn random_file_name(rng: &mut StdRng) -> Vec<u8> {
+        let len = match rng.random_range(0..4) {
+            0 => rng.random_range(1..=8),
+            // Either side of a word boundary
+            1 => rng.random_range(1..=31usize).next_multiple_of(8) + rng.random_range(0..=2) - 1,
+            2 => rng.random_range(250..=255),
+            _ => rng.random_range(1..=255),
+        };
+        let mut name: Vec<u8> = (0..len.max(1))
+            .map(|_| match rng.random_range(0..4) {
+                0 => rng.random_range(b'a'..=b'z'),
+                1 => [b'.', b'-', b' ', b'\n', 0x01, 0x80, 0xFF][rng.random_range(0..7)],
+                // Anything but NUL and `/`, which can't be part of a name
+                _ => rng.random_range(1..=u8::MAX),
+            })
+            .map(|byte| if byte == b'/' { b'_' } else { byte })
+            .collect();
+        if name == b"." || name == b".." {
+            name.push(b'x');
+        }
+        name
+    }
+
+    /// Fills `dir` with a random tree of files, directories and symlinks (some dangling), `depth` levels deep
+    fn generate_random_tree(rng: &mut StdRng, dir: &Path, depth: u32) {
+        // Now and then enough entries to need several reads with any buffer size
+        let count = if rng.random_range(0..6) == 0 {
+            rng.random_range(100..300)
+        } else {
+            rng.random_range(0..20)
+        };
+        for _ in 0..count {
+            let path = dir.join(OsStr::from_bytes(&random_file_name(rng)));
+            if fs::symlink_metadata(&path).is_ok() {
+                continue;
+            }
+            match rng.random_range(0..16) {
+                0 if depth > 0 => {
+                    fs::create_dir(&path).unwrap();
+                    generate_random_tree(rng, &path, depth - 1);
+                }
+                1 | 2 => symlink(OsStr::from_bytes(&random_file_name(rng)), &path).unwrap(),
+                _ => {
+                    File::create(&path).unwrap();
+                }
+            }
+        }
+    }
+
+    /// The name, type and inode of every entry of `dir` as listed by `std::fs::read_dir`, sorted
+    fn std_listing(dir: &Path) -> Vec<(Vec<u8>, FileType, u64)> {
+        use std::os::unix::fs::DirEntryExt as _;
+
+        let mut listing: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let file_type = entry.file_type().unwrap();
+                let file_type = if file_type.is_dir() {
+                    FileType::Directory
+                } else if file_type.is_symlink() {
+                    FileType::Symlink
+                } else {
+                    FileType::RegularFile
+                };
+                (
+                    entry.file_name().as_bytes().to_vec(),
+                    file_type,
+                    entry.ino(),
+                )
+            })
+            .collect();
+        listing.sort();
+        listing
+    }
+
+    /// Checks every backend lists each directory of the tree at `dir` exactly like `std::fs::read_dir`
+    fn assert_backends_agree(dir: &Path) {
+        let expected = std_listing(dir);
+        let entry = DirEntry::new(dir.as_os_str()).unwrap();
+        let listing = |entries: &mut dyn Iterator<Item = DirEntry>| {
+            let mut listing: Vec<_> = entries
+                .map(|entry| (entry.file_name().to_vec(), entry.file_type(), entry.ino()))
+                .collect();
+            listing.sort();
+            listing
+        };
+        let path = dir.display();
+        assert_eq!(
+            listing(&mut entry.readdir().unwrap()),
+            expected,
+            "readdir of {path}"
+        );
+        assert_eq!(
+            listing(&mut crate::fs::PortableReadDir::new(&entry).unwrap()),
+            expected,
+            "portable readdir of {path}"
+        );
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris",
+            target_os = "macos",
+            target_os = "freebsd"
+        ))]
+        for buffer_size in [None, Some(crate::fs::MIN_BUFFER_SIZE)] {
+            let mut entries = match buffer_size {
+                Some(size) => entry.getdents_with_buffer_size(size).unwrap(),
+                None => entry.getdents().unwrap(),
+            };
+            assert_eq!(
+                listing(&mut entries),
+                expected,
+                "getdents of {path} with a buffer of {buffer_size:?}"
+            );
+        }
+
+        for (name, file_type, _) in expected {
+            if file_type == FileType::Directory {
+                assert_backends_agree(&dir.join(OsStr::from_bytes(&name)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_backends_agree_on_generated_trees() {
+        use crate::walk::Backend;
+
+        for case in 0..8 {
+            let mut rng = StdRng::seed_from_u64(RANDOM_SEED + case);
+            let temp_dir = temp_dir().join(format!("backends_agree_test_{case}"));
+            let _ = fs::remove_dir_all(&temp_dir);
+            fs::create_dir_all(&temp_dir).unwrap();
+            generate_random_tree(&mut rng, &temp_dir, 3);
+
+            assert_backends_agree(&temp_dir);
+
+            // The walk finds every entry of the tree too, with either backend on any number of threads
+            let mut expected = Vec::new();
+            let mut dirs = vec![temp_dir.clone()];
+            while let Some(dir) = dirs.pop() {
+                for (name, file_type, _) in std_listing(&dir) {
+                    let path = dir.join(OsStr::from_bytes(&name));
+                    if file_type == FileType::Directory {
+                        dirs.push(path.clone());
+                    }
+                    expected.push(path.as_os_str().as_bytes().to_vec());
+                }
+            }
+            expected.sort();
+            for (backend, threads) in [
+                (Backend::Native, 1),
+                (Backend::Native, 4),
+                (Backend::Portable, 4),
+            ] {
+                // Hidden entries are hidden by default, despite the name
+                let mut found: Vec<_> = Finder::init(&temp_dir)
+                    .keep_hidden(false)
+                    .backend(backend)
+                    .thread_count(core::num::NonZeroUsize::new(threads))
+                    .build()
+                    .unwrap()
+                    .traverse()
+                    .unwrap()
+                    .map(|entry| entry.as_bytes().to_vec())
+                    .collect();
+                found.sort();
+                assert_eq!(
+                    found, expected,
+                    "case {case}, {backend:?} on {threads} threads"
+                );
+            }
+
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_breadth_first_keeps_shallowest_results() {
+        use crate::walk::TraversalStrategy;
+
+        let temp_dir = temp_dir().join("breadth_first_test");
+        let _ = fs::remove_dir_all(&temp_dir);
+        for dir in 0..4 {
+            let deep = temp_dir.join(format!("dir{dir}/a/b"));
+            fs::create_dir_all(&deep).unwrap();
+            File::create(temp_dir.join(format!("dir{dir}/hit{dir}"))).unwrap();
+            for file in 0..50 {
+                File::create(deep.join(format!("hit{file}"))).unwrap();
+            }
+        }
+        let finder = |strategy, max_results| {
+            Finder::init(&temp_dir)
+                .pattern("^hit")
+                .strategy(strategy)
+                .max_results(max_results)
+                .thread_count(core::num::NonZeroUsize::new(8))
+                .build()
+                .unwrap()
+        };
+
+        // Every directory of a level is read before any below it, so the limit is reached on the shallowest matches
+        for _ in 0..20 {
+            let depths: Vec<_> = finder(TraversalStrategy::BreadthFirst, Some(4))
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.depth())
+                .collect();
+            assert_eq!(depths, [2; 4]);
+        }
+
+        let all = |strategy| {
+            let mut paths: Vec<_> = finder(strategy, None)
+                .traverse()
+                .unwrap()
+                .map(|entry| entry.as_bytes().to_vec())
+                .collect();
+            paths.sort();
+            paths
+        };
+        let breadth_first = all(TraversalStrategy::BreadthFirst);
+        assert_eq!(breadth_first.len(), 4 + 4 * 50);
+        assert_eq!(breadth_first, all(TraversalStrategy::DepthFirst));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_backend_is_native_on_tested_platforms() {
+        use crate::walk::Backend;
+        // Only unusual platforms (eg the Hurd) fall back to the portable backend by default
+        assert_eq!(Backend::default(), Backend::Native);
+        let finder = Finder::init(".").build().unwrap();
+        assert_eq!(finder.backend, Backend::Native);
+    }
 }
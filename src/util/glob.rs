@@ -368,9 +368,13 @@ fn close_alternate(gathered: Vec<String>) -> String {
 /// Iterate over a glob pattern's characters, build up a regular expression.
 struct GlobIterator<I: Iterator<Item = char>> {
     /// The iterator over the glob pattern's characters.
-    pattern: I,
+    pattern: core::iter::Peekable<I>,
     /// The current state of the glob pattern parser.
     state: State,
+    /// Whether `*` stops at a slash, with `**` as a whole component matching any number of directories.
+    literal_separator: bool,
+    /// Whether the next character starts a path component (the start of the pattern or after a slash).
+    component_start: bool,
 }
 
 /// Either a piece of the regular expression or an error.
@@ -394,7 +398,11 @@ where
                 Some("$".to_owned())
             }
             Some(chr) => {
+                let component_start = mem::replace(&mut self.component_start, chr == '/');
                 let (new_state, res) = match chr {
+                    '*' if self.literal_separator => {
+                        (State::Literal, Some(self.separator_star(component_start)))
+                    }
                     '\\' => (State::Escape, None),
                     '[' => (State::ClassStart, None),
                     '{' => (State::Alternate(String::new(), Vec::new()), None),
@@ -409,6 +417,24 @@ where
         }
     }
 
+    /// Translate a `*` (or `**`) which doesn't match across a slash, see [`glob_to_path_regex`].
+    fn separator_star(&mut self, component_start: bool) -> String {
+        let double = self.pattern.next_if_eq(&'*').is_some();
+        if !double || !component_start {
+            // A `**` within a component is no different from a `*`
+            return "[^/]*".to_owned();
+        }
+        match self.pattern.peek() {
+            None => ".*".to_owned(),
+            Some('/') => {
+                self.pattern.next();
+                self.component_start = true;
+                "(?:.*/)?".to_owned()
+            }
+            Some(_) => "[^/]*".to_owned(),
+        }
+    }
+
     /// Handle an escaped character.
     fn handle_escape(&mut self) -> StringResult {
         match self.pattern.next() {
@@ -656,9 +682,26 @@ where
 ///
 #[allow(clippy::missing_inline_in_public_items)]
 pub fn glob_to_regex(pattern: &str) -> Result<String, Error> {
+    convert(pattern, false)
+}
+
+/// Parse a shell glob-like pattern for matching whole paths into a regular expression(String)
+///
+/// Unlike [`glob_to_regex`], `*` never matches a slash, so each one stays within a path component.
+/// A `**` making up a whole component matches any number of directories instead:
+/// `**/name` matches `name` at any depth, `dir/**` everything below `dir` and `a/**/b` zero or more
+/// directories between `a` and `b`.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn glob_to_path_regex(pattern: &str) -> Result<String, Error> {
+    convert(pattern, true)
+}
+
+fn convert(pattern: &str, literal_separator: bool) -> Result<String, Error> {
     let parser = GlobIterator {
-        pattern: pattern.chars(),
+        pattern: pattern.chars().peekable(),
         state: State::Start,
+        literal_separator,
+        component_start: true,
     };
 
     let mut result = Vec::new();
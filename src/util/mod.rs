@@ -1,9 +1,10 @@
 mod glob;
 mod memchr_derivations;
 mod printer;
+pub mod swar;
 mod unique;
 mod utils;
-pub use glob::{Error, glob_to_regex};
+pub use glob::{Error, glob_to_path_regex, glob_to_regex};
 pub use memchr_derivations::memrchr;
 pub use unique::Unique;
 
@@ -37,5 +38,14 @@ pub use utils::getdirentries64;
 
 pub(crate) use utils::BytePath;
 pub use utils::dirent_name_length;
+pub(crate) use utils::file_stem;
 
-pub use printer::PrinterBuilder;
+pub use printer::{
+    ColourMode, ErrorFormat, OutputFormat, PrinterBuilder, RelativePaths, SizeFormat, format_size,
+    write_errors,
+};
+#[cfg(test)]
+pub(crate) use printer::{
+    Hyperlinks, LsColours, OutputContext, Summary, long_format, write_coloured, write_json,
+    write_nocolour,
+};
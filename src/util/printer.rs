@@ -4,11 +4,16 @@ use crate::{
     SearchConfigError, TraversalError,
     fs::{DirEntry, FileType},
     util::BytePath,
+    walk::{DEFAULT_SORT_MEMORY, SortedEntries},
 };
-use compile_time_ls_colours::file_type_colour;
+use compile_time_ls_colours::{colour_path_or_reset, file_type_colour};
 
 use std::{
+    collections::HashMap,
+    ffi::OsString,
     io::{BufWriter, IsTerminal as _, Write, stdout},
+    os::unix::ffi::{OsStrExt as _, OsStringExt as _},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 const NEWLINE: &[u8] = b"\n";
@@ -39,18 +44,441 @@ const RESET_QUOTED_NEWLINE: &[u8] = b"\x1b[0m\"\n";
 const DIR_RESET_NEWLINE: &[u8] = b"/\x1b[0m\n";
 const DIR_RESET_QUOTED_NEWLINE: &[u8] = b"/\x1b[0m\"\n";
 
+/// Closes an OSC 8 hyperlink opened by [`Hyperlinks::open`]
+const LINK_CLOSE: &[u8] = b"\x1b]8;;\x1b\\";
+
+/**
+ The output format used by the [`PrinterBuilder`].
+
+ The JSON formats write one object per entry with the keys `path`, `type` and `depth` and are streamed,
+ the full result set is never buffered (unless sorting). Paths which aren't valid UTF-8 are converted lossily.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// One path per line (or null terminated), optionally coloured
+    #[default]
+    Plain,
+    /// A single JSON array of entries
+    Json,
+    /// Newline delimited JSON, one entry per line
+    Ndjson,
+}
+
+impl core::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!(
+                "unknown format '{s}', expected one of: plain, json, ndjson"
+            )),
+        }
+    }
+}
+
+/**
+ How the [`PrinterBuilder`] writes errors to stderr when [`print_errors`](PrinterBuilder::print_errors) is set,
+ see [`write_errors`].
+
+ The JSON format writes one object per line with the keys `path`, `errno` (`null` unless the error came from the
+ OS), `operation` (what failed, `read_dir` as only reading directories is reported) and `message`.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorFormat {
+    /// One human readable message per line
+    #[default]
+    Text,
+    /// Newline delimited JSON, one error per line
+    Json,
+}
+
+impl core::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown error format '{s}', expected one of: text, json"
+            )),
+        }
+    }
+}
+
+/**
+ Writes `errors` one per line in `format`.
+
+ # Errors
+ Returns an error if `writer` can't be written to.
+*/
+#[inline]
+pub fn write_errors<W: Write>(
+    writer: &mut W,
+    errors: &[TraversalError],
+    format: ErrorFormat,
+) -> std::io::Result<()> {
+    for error in errors {
+        match format {
+            ErrorFormat::Text => writeln!(writer, "{error}")?,
+            ErrorFormat::Json => {
+                writer.write_all(b"{\"path\":")?;
+                write_json_string(writer, EMPTY, error.path().as_bytes())?;
+                match error.raw_os_error() {
+                    Some(errno) => write!(writer, ",\"errno\":{errno}")?,
+                    None => writer.write_all(b",\"errno\":null")?,
+                }
+                writer.write_all(b",\"operation\":\"read_dir\",\"message\":")?;
+                write_json_string(writer, EMPTY, error.error().to_string().as_bytes())?;
+                writer.write_all(b"}\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+ When the [`PrinterBuilder`] colours its output, only applies to [`OutputFormat::Plain`] without null termination.
+
+ Colours are taken from the `LS_COLORS` environment variable (as set by `dircolors`), anything it doesn't cover
+ falls back to the built in scheme.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ColourMode {
+    /// Colour when writing to a terminal, unless the environment says otherwise (see [`detect`](Self::detect))
+    #[default]
+    Auto,
+    /// Colour even when piped, regardless of the environment
+    Always,
+    /// Never colour
+    Never,
+}
+
+impl core::str::FromStr for ColourMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!(
+                "unknown colour mode '{s}', expected one of: auto, always, never"
+            )),
+        }
+    }
+}
+
+impl ColourMode {
+    /**
+    Reads the colour preference from the environment, what [`Auto`](Self::Auto) defers to.
+
+    In order of precedence:
+    - `NO_COLOR` (or `NO_COLOUR`) set to anything non-empty gives [`Never`](Self::Never)
+    - `CLICOLOR_FORCE` set to anything but `0` gives [`Always`](Self::Always)
+    - `CLICOLOR=0` or `TERM=dumb` gives [`Never`](Self::Never)
+
+    Otherwise it's [`Auto`](Self::Auto), ie colour only when writing to a terminal.
+    */
+    #[inline]
+    #[must_use]
+    pub fn detect() -> Self {
+        Self::detect_with(|name| std::env::var_os(name))
+    }
+
+    /// As [`detect`](Self::detect), looking variables up with `var`
+    pub(crate) fn detect_with(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let set = |name| var(name).filter(|value| !value.is_empty());
+
+        if set("NO_COLOR").or_else(|| set("NO_COLOUR")).is_some() {
+            Self::Never
+        } else if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+            Self::Always
+        } else if var("CLICOLOR").is_some_and(|value| value == "0")
+            || var("TERM").is_some_and(|value| value == "dumb")
+        {
+            Self::Never
+        } else {
+            Self::Auto
+        }
+    }
+
+    /// Whether to colour output written to a stream, an explicit [`Always`](Self::Always) or [`Never`](Self::Never) overrides the environment
+    #[inline]
+    #[must_use]
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Auto => match Self::detect() {
+                Self::Auto => is_terminal,
+                Self::Always => true,
+                Self::Never => false,
+            },
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/**
+ How sizes are written by the [`PrinterBuilder`] (and [`format_size`]).
+
+ The scaled formats keep one decimal place below 10, eg `1.5K`, like `ls -h` and `ls --si`.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SizeFormat {
+    /// The exact number of bytes
+    #[default]
+    Bytes,
+    /// Powers of 1000, eg `1.5k` or `23M`
+    Si,
+    /// Powers of 1024, eg `1.5K` or `23M`
+    Binary,
+}
+
+impl core::str::FromStr for SizeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(Self::Bytes),
+            "si" => Ok(Self::Si),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!(
+                "unknown size format '{s}', expected one of: bytes, si, binary"
+            )),
+        }
+    }
+}
+
+/**
+ Rewrites printed paths relative to another directory, see [`Finder::relative_output`](crate::walk::Finder::relative_output).
+
+ Every result starts with the search root, so printing it relative to the base only means swapping the root (and the
+ `/` after it) for the path from the base to the root, eg `../src/`, or nothing when the base is the root itself.
+ This is a prefix swap on the bytes as they're written, nothing is allocated per path.
+ Results which don't start with the root (eg those resolved to their real paths through symlinks) are printed unchanged.
+
+ ```
+ use fdf::util::RelativePaths;
+
+ let manifest_dir = env!("CARGO_MANIFEST_DIR");
+ let relative = RelativePaths::new(format!("{manifest_dir}/src/"), format!("{manifest_dir}/benches")).unwrap();
+ let path = format!("{manifest_dir}/src/lib.rs");
+ assert_eq!(relative.split(path.as_bytes()), (&b"../src/"[..], &b"lib.rs"[..]));
+ ```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelativePaths {
+    /// The root as results start with it, followed by a single `/`
+    root: Box<[u8]>,
+    /// What the root is replaced with, either empty or ending in `/`
+    prefix: Box<[u8]>,
+}
+
+impl RelativePaths {
+    /**
+     Rewrites paths found under `root` (as it was given to the [`Finder`](crate::walk::Finder)) relative to `base`.
+
+     Both are resolved to their real paths to work out how they relate, so `..` and symlinks in either are fine.
+
+     # Errors
+     Returns an error if either directory can't be resolved, eg it doesn't exist.
+    */
+    pub fn new<R: AsRef<Path>, B: AsRef<Path>>(root: R, base: B) -> std::io::Result<Self> {
+        let real_root = std::fs::canonicalize(&root)?;
+        let real_base = std::fs::canonicalize(base)?;
+        let common = real_root
+            .components()
+            .zip(real_base.components())
+            .take_while(|(root_component, base_component)| root_component == base_component)
+            .count();
+
+        let mut relative = PathBuf::new();
+        for _ in real_base.components().skip(common) {
+            relative.push("..");
+        }
+        relative.extend(real_root.components().skip(common));
+        let mut prefix = relative.into_os_string().into_vec();
+        if !prefix.is_empty() {
+            prefix.push(b'/');
+        }
+
+        Ok(Self {
+            root: Self::with_slash(root.as_ref().as_os_str().as_bytes()),
+            prefix: prefix.into(),
+        })
+    }
+
+    /// Strips the leading `./` when the root is the current directory, see [`PrinterBuilder::strip_leading_dot_slash`]
+    pub(crate) fn dot_slash() -> Self {
+        Self {
+            root: Self::with_slash(b"."),
+            prefix: EMPTY.into(),
+        }
+    }
+
+    /// `root` without any trailing slashes, followed by one
+    fn with_slash(root: &[u8]) -> Box<[u8]> {
+        let end = root
+            .iter()
+            .rposition(|&byte| byte != b'/')
+            .map_or(0, |last| last + 1);
+        [&root[..end], b"/"].concat().into()
+    }
+
+    /// Splits `path` into the prefix to write in place of the root and the rest of the path
+    #[inline]
+    #[must_use]
+    pub fn split<'path>(&'path self, path: &'path [u8]) -> (&'path [u8], &'path [u8]) {
+        match path.strip_prefix(&*self.root) {
+            Some(rest) if !rest.is_empty() => (&self.prefix, rest),
+            _ => (EMPTY, path),
+        }
+    }
+}
+
+/// The `LS_COLORS` keys for file types, in the order of [`LsColours::types`]
+const TYPE_KEYS: [&[u8]; 7] = [b"di", b"ln", b"pi", b"so", b"bd", b"cd", b"fi"];
+
+/// A complete escape sequence for a colour, eg `\x1b[01;34m`
+type Escape = Box<[u8]>;
+
+/**
+ Colours parsed from `LS_COLORS`, a `:` separated list of `key=SGR` entries (eg `di=01;34:*.rs=33`).
+
+ The file type keys in [`TYPE_KEYS`] and `*suffix` patterns are used, suffixes matching case insensitively like GNU `ls`.
+ Keys which would need a `stat` per entry (eg `ex`, `or` or `su`) are ignored, as is `ln=target`,
+ so those entries keep the built in colours.
+*/
+#[derive(Debug, Default)]
+pub(crate) struct LsColours {
+    /// Escape sequences for each key in [`TYPE_KEYS`]
+    types: [Option<Escape>; TYPE_KEYS.len()],
+    /// `*.ext` patterns, keyed by the lowercased extension
+    extensions: HashMap<Box<[u8]>, Escape>,
+    /// Any other `*suffix` patterns (eg `*~` or `*.tar.gz`), lowercased, checked before the extensions
+    suffixes: Vec<(Box<[u8]>, Escape)>,
+}
+
+impl LsColours {
+    /// Extensions longer than this are never looked up, which keeps lowercasing them off the heap
+    const MAX_EXTENSION: usize = 32;
+
+    /// Parses `LS_COLORS`, empty if it's unset
+    pub(crate) fn from_env() -> Self {
+        std::env::var_os("LS_COLORS")
+            .map(|spec| Self::parse(spec.as_bytes()))
+            .unwrap_or_default()
+    }
+
+    /// Parses an `LS_COLORS` value, skipping malformed and unsupported entries
+    pub(crate) fn parse(spec: &[u8]) -> Self {
+        let mut colours = Self::default();
+        for (key, value) in spec.split(|&byte| byte == b':').filter_map(|entry| {
+            memchr::memchr(b'=', entry).map(|equals| (&entry[..equals], &entry[equals + 1..]))
+        }) {
+            if key == b"ln" && value == b"target" {
+                continue;
+            }
+            let escape: Escape = [b"\x1b[".as_slice(), value, b"m"].concat().into();
+
+            if let Some(suffix) = key.strip_prefix(b"*") {
+                let suffix = suffix.to_ascii_lowercase().into_boxed_slice();
+                match suffix.strip_prefix(b".") {
+                    Some(extension) if !extension.is_empty() && !extension.contains(&b'.') => {
+                        colours.extensions.insert(extension.into(), escape);
+                    }
+                    _ if !suffix.is_empty() => colours.suffixes.push((suffix, escape)),
+                    _ => {}
+                }
+            } else if let Some(slot) = TYPE_KEYS.iter().position(|&type_key| type_key == key) {
+                colours.types[slot] = Some(escape);
+            }
+        }
+        colours
+    }
+
+    /// The colour for `key` in [`TYPE_KEYS`], or `fallback` if `LS_COLORS` doesn't set it
+    fn type_colour<'colours>(
+        &'colours self,
+        key: &[u8],
+        fallback: &'colours [u8],
+    ) -> &'colours [u8] {
+        TYPE_KEYS
+            .iter()
+            .position(|&type_key| type_key == key)
+            .and_then(|slot| self.types[slot].as_deref())
+            .unwrap_or(fallback)
+    }
+
+    /// The colour set by a `*suffix` pattern matching the entry's name
+    fn suffix_colour(&self, entry: &DirEntry) -> Option<&[u8]> {
+        let name = entry.file_name();
+        if let Some((_, escape)) = self.suffixes.iter().find(|(suffix, _)| {
+            name.len() >= suffix.len()
+                && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        }) {
+            return Some(escape);
+        }
+
+        let extension = BytePath::extension(entry)?;
+        if extension.len() > Self::MAX_EXTENSION {
+            return None;
+        }
+        let mut lowered = [0; Self::MAX_EXTENSION];
+        let lowered = &mut lowered[..extension.len()];
+        lowered.copy_from_slice(extension);
+        lowered.make_ascii_lowercase();
+        self.extensions.get(&*lowered).map(|escape| &**escape)
+    }
+
+    /// The escape sequence to write before `entry`
+    pub(crate) fn colour<'colours>(&'colours self, entry: &DirEntry) -> &'colours [u8] {
+        match entry.file_type {
+            FileType::RegularFile | FileType::Unknown => self
+                .suffix_colour(entry)
+                .unwrap_or_else(|| self.type_colour(b"fi", extension_colour(entry))),
+            FileType::Directory => self.type_colour(b"di", extension_colour(entry)),
+            FileType::Symlink => match entry.is_traversible_cache.get() {
+                Some(true) => self.type_colour(b"di", extension_colour(entry)),
+                _ => self.type_colour(b"ln", extension_colour(entry)),
+            },
+            FileType::BlockDevice => self.type_colour(b"bd", extension_colour(entry)),
+            FileType::CharDevice => self.type_colour(b"cd", extension_colour(entry)),
+            FileType::Socket => self.type_colour(b"so", extension_colour(entry)),
+            FileType::Pipe => self.type_colour(b"pi", extension_colour(entry)),
+        }
+    }
+}
+
 #[allow(clippy::struct_excessive_bools)]
 pub struct PrinterBuilder<I>
 where
     I: Iterator<Item = DirEntry>,
 {
     limit: usize,
-    nocolour: bool,
+    colour: ColourMode,
     sort: bool,
+    sort_memory: usize,
     print_errors: bool,
+    error_format: ErrorFormat,
     null_terminated: bool,
     strip_leading_dot_slash: bool,
+    relative_to: Option<RelativePaths>,
     quoted: bool,
+    summary: bool,
+    long_format: bool,
+    size_format: SizeFormat,
+    hyperlinks: bool,
+    path_separator: Option<Box<[u8]>>,
+    format: OutputFormat,
     errors: Option<Arc<Mutex<Vec<TraversalError>>>>,
     paths: I,
 }
@@ -60,15 +488,26 @@ where
     I: Iterator<Item = DirEntry>,
 {
     #[inline]
-    pub(crate) const fn new(paths: I) -> Self {
+    #[must_use]
+    /// Create a printer for `paths`, see [`Finder::build_printer`](crate::walk::Finder::build_printer) for the usual way in
+    pub const fn new(paths: I) -> Self {
         Self {
             limit: usize::MAX,
-            nocolour: false,
+            colour: ColourMode::Auto,
             sort: false,
+            sort_memory: DEFAULT_SORT_MEMORY,
             print_errors: false,
+            error_format: ErrorFormat::Text,
             null_terminated: false,
             strip_leading_dot_slash: false,
+            relative_to: None,
             quoted: false,
+            summary: false,
+            long_format: false,
+            size_format: SizeFormat::Bytes,
+            hyperlinks: false,
+            path_separator: None,
+            format: OutputFormat::Plain,
             errors: None,
             paths,
         }
@@ -85,9 +524,18 @@ where
     }
 
     #[must_use]
-    /// Print with no colour if enabled (always disabled with "`NO_COLOUR`" or "`NO_COLOR`" environment variables)
+    /// Print with no colour if enabled, shorthand for [`colour`](Self::colour) with [`ColourMode::Never`]
     pub const fn nocolour(mut self, nocolour: bool) -> Self {
-        self.nocolour = nocolour;
+        if nocolour {
+            self.colour = ColourMode::Never;
+        }
+        self
+    }
+
+    #[must_use]
+    /// Set when to colour the output, defaults to [`ColourMode::Auto`]
+    pub const fn colour(mut self, colour: ColourMode) -> Self {
+        self.colour = colour;
         self
     }
 
@@ -98,6 +546,13 @@ where
         self
     }
 
+    #[must_use]
+    /// Set roughly how many bytes of results sorting holds in memory before spilling them to disk, see [`SortedEntries`]
+    pub const fn sort_memory(mut self, sort_memory: usize) -> Self {
+        self.sort_memory = sort_memory;
+        self
+    }
+
     #[must_use]
     /// Print errors(if errors were requested to be collected)
     pub const fn print_errors(mut self, print_errors: bool) -> Self {
@@ -106,7 +561,15 @@ where
     }
 
     #[must_use]
-    /// Print results being null terminated(useful for xargs)
+    /// Set how errors are printed, defaults to [`ErrorFormat::Text`]
+    pub const fn error_format(mut self, format: ErrorFormat) -> Self {
+        self.error_format = format;
+        self
+    }
+
+    #[must_use]
+    /// Print results being null terminated(useful for xargs), this disables colouring.
+    /// Paths are written as raw bytes in every mode, so names containing newlines survive `xargs -0` intact.
     pub const fn null_terminated(mut self, null_terminated: bool) -> Self {
         self.null_terminated = null_terminated;
         self
@@ -119,6 +582,14 @@ where
         self
     }
 
+    #[must_use]
+    /// Print paths relative to another directory, eg from [`Finder::relative_output`](crate::walk::Finder::relative_output).
+    /// Takes precedence over [`strip_leading_dot_slash`](Self::strip_leading_dot_slash)
+    pub fn relative_to(mut self, relative_to: Option<RelativePaths>) -> Self {
+        self.relative_to = relative_to;
+        self
+    }
+
     #[must_use]
     /// Wrap printed file paths in double quotes
     pub const fn quoted(mut self, quoted: bool) -> Self {
@@ -127,7 +598,67 @@ where
     }
 
     #[must_use]
-    pub(crate) fn errors(mut self, errors: Option<Arc<Mutex<Vec<TraversalError>>>>) -> Self {
+    /// Write a footer with the number of entries printed and their cumulative size, only applies to [`OutputFormat::Plain`].
+    /// The size is the sum of `lstat` sizes of every non directory entry, so this costs a `stat` per entry.
+    pub const fn summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+
+    #[must_use]
+    /// Print an `ls -l` style listing (permissions, links, owner, group, size, modification time and path), only applies to [`OutputFormat::Plain`].
+    /// Every result is collected and `lstat`ed (in parallel) first so the columns can be aligned.
+    /// With [`summary`](Self::summary), the listing starts with the `total` block count like `ls`.
+    pub const fn long_format(mut self, long_format: bool) -> Self {
+        self.long_format = long_format;
+        self
+    }
+
+    #[must_use]
+    /// Print sizes in the long format (and summaries) as eg `1.5K` or `23M` rather than bytes,
+    /// shorthand for [`size_format`](Self::size_format) with [`SizeFormat::Binary`]
+    pub const fn human_sizes(mut self, human_sizes: bool) -> Self {
+        if human_sizes {
+            self.size_format = SizeFormat::Binary;
+        }
+        self
+    }
+
+    #[must_use]
+    /// Set how sizes in the long format and summaries are written, defaults to [`SizeFormat::Bytes`]
+    pub const fn size_format(mut self, size_format: SizeFormat) -> Self {
+        self.size_format = size_format;
+        self
+    }
+
+    #[must_use]
+    /// Make printed paths clickable in terminals which support OSC 8 hyperlinks (eg kitty, iTerm2 or wezterm).
+    /// Each path links to its `file://` URL, relative paths are resolved against the working directory.
+    /// Disabled with [`null_terminated`](Self::null_terminated), and only applies to [`OutputFormat::Plain`].
+    pub const fn hyperlinks(mut self, hyperlinks: bool) -> Self {
+        self.hyperlinks = hyperlinks;
+        self
+    }
+
+    #[must_use]
+    /// Replace every `/` in printed paths with `separator` (eg `\\` for Windows tools over WSL), only applies to [`OutputFormat::Plain`].
+    /// The entries themselves are untouched, the bytes are rewritten as they're written.
+    pub fn path_separator(mut self, separator: Option<Box<[u8]>>) -> Self {
+        self.path_separator = separator;
+        self
+    }
+
+    #[must_use]
+    /// Set the output format, colour, quoting and null termination only apply to [`OutputFormat::Plain`]
+    pub const fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    /// Set the error store printed by [`print_errors`](Self::print_errors), eg from [`Finder::error_store`](crate::walk::Finder::error_store)
+    pub fn errors(mut self, errors: Option<Arc<Mutex<Vec<TraversalError>>>>) -> Self {
         self.errors = errors;
         self
     }
@@ -139,93 +670,485 @@ where
     pub fn print(self) -> Result<(), SearchConfigError> {
         let std_out = stdout();
         let is_terminal = std_out.is_terminal();
-        let use_colour = is_terminal && !Self::colour_disabled(self.nocolour);
-
-        let mut writer = if is_terminal {
-            BufWriter::new(std_out)
+        let hyperlinks = if self.hyperlinks && !self.null_terminated {
+            Some(Hyperlinks::new(&std::env::current_dir()?)?)
         } else {
-            BufWriter::with_capacity(16 * 4096, std_out) //TODO play with these values?
+            None
+        };
+        let context = OutputContext {
+            terminal: is_terminal,
+            // Null terminated output is meant for other programs, never colour it (even on a terminal)
+            colours: (!self.null_terminated && self.colour.enabled(is_terminal))
+                .then(LsColours::from_env),
+            null_terminated: self.null_terminated,
+            relative: self
+                .relative_to
+                .or_else(|| self.strip_leading_dot_slash.then(RelativePaths::dot_slash)),
+            quoted: self.quoted,
+            separator: self.path_separator,
+            hyperlinks,
         };
 
-        if self.sort {
-            let mut collected: Vec<_> = self.paths.collect();
-            // TODO, this algorithm is extremely slow for large collections...
-            // I need to parallelise but it's a lot of work for one function, sign.
-            collected.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        let mut writer = BufWriter::with_capacity(context.buffer_capacity(), std_out);
+
+        let write_long = self.long_format && self.format == OutputFormat::Plain;
+        let write_summary = self.summary && self.format == OutputFormat::Plain && !write_long;
+        let mut summary = Summary::default();
+        let mut tally = |entry: &DirEntry| {
+            if write_summary {
+                summary.add(entry);
+            }
+        };
+
+        if write_long {
+            let collected: Vec<_> = if self.sort {
+                let mut sorted = SortedEntries::new(self.paths, self.sort_memory)?;
+                let collected = sorted.by_ref().take(self.limit).collect();
+                if let Some(error) = sorted.take_error() {
+                    return Err(error.into());
+                }
+                collected
+            } else {
+                self.paths.take(self.limit).collect()
+            };
+            long_format(
+                &mut writer,
+                collected,
+                self.size_format,
+                self.summary,
+                &context,
+            )?;
+        } else if self.sort {
+            let mut sorted = SortedEntries::new(self.paths, self.sort_memory)?;
             Self::write_iter(
                 &mut writer,
-                collected.into_iter().take(self.limit),
-                use_colour,
-                self.null_terminated,
-                self.strip_leading_dot_slash,
-                self.quoted,
+                sorted.by_ref().take(self.limit).inspect(&mut tally),
+                &context,
+                self.format,
             )?;
+            if let Some(error) = sorted.take_error() {
+                writer.flush()?;
+                return Err(error.into());
+            }
         } else {
             Self::write_iter(
                 &mut writer,
-                self.paths.take(self.limit),
-                use_colour,
-                self.null_terminated,
-                self.strip_leading_dot_slash,
-                self.quoted,
+                self.paths.take(self.limit).inspect(&mut tally),
+                &context,
+                self.format,
             )?;
         }
 
+        if write_summary {
+            summary.write(&mut writer, self.null_terminated, self.size_format)?;
+        }
+
         writer.flush()?;
 
         if self.print_errors
             && let Some(errors_arc) = self.errors.as_ref()
             && let Ok(error_vec) = errors_arc.lock()
         {
-            for error in error_vec.iter() {
-                eprintln!("{error}");
-            }
+            write_errors(&mut std::io::stderr().lock(), &error_vec, self.error_format)?;
         }
 
         Ok(())
     }
 
-    fn colour_disabled(nocolour: bool) -> bool {
-        nocolour
-            || std::env::var("NO_COLOUR").is_ok_and(|x| x.eq_ignore_ascii_case("TRUE"))
-            || std::env::var("NO_COLOR").is_ok_and(|x| x.eq_ignore_ascii_case("TRUE"))
-        // BECAUSE IM BRITISH
-    }
-
     #[inline]
-    #[allow(clippy::fn_params_excessive_bools)] // convenience
     fn write_iter<W, J>(
         writer: &mut W,
         iter_paths: J,
-        use_colour: bool,
-        null_terminated: bool,
-        strip_leading_dot_slash: bool,
-        quoted: bool,
+        context: &OutputContext,
+        format: OutputFormat,
     ) -> std::io::Result<()>
     where
         W: Write,
         J: IntoIterator<Item = DirEntry>,
     {
-        if use_colour {
-            write_coloured(writer, iter_paths, strip_leading_dot_slash, quoted)
+        if format != OutputFormat::Plain {
+            write_json(writer, iter_paths, format, context.relative.as_ref())
+        } else if let Some(ls_colours) = context.colours.as_ref() {
+            write_coloured(writer, iter_paths, ls_colours, context)
+        } else {
+            write_nocolour(writer, iter_paths, context)
+        }
+    }
+}
+
+/// Totals for the footer written by [`PrinterBuilder::summary`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Summary {
+    pub(crate) entries: usize,
+    pub(crate) bytes: u64,
+}
+
+impl Summary {
+    /// Counts `entry`, directory sizes are filesystem specific bookkeeping so they are left out of the total
+    #[inline]
+    pub(crate) fn add(&mut self, entry: &DirEntry) {
+        self.entries += 1;
+        if !entry.is_dir()
+            && let Ok(statted) = entry.get_lstat()
+        {
+            self.bytes += statted.st_size.cast_unsigned();
+        }
+    }
+
+    /// Writes `N entries, B bytes` (or eg `N entries, 1.5K`), terminated the same way as the entries
+    pub(crate) fn write<W: Write>(
+        &self,
+        writer: &mut W,
+        null_terminated: bool,
+        size_format: SizeFormat,
+    ) -> std::io::Result<()> {
+        let noun = if self.entries == 1 {
+            "entry"
+        } else {
+            "entries"
+        };
+        if size_format == SizeFormat::Bytes {
+            write!(writer, "{} {noun}, {} bytes", self.entries, self.bytes)?;
         } else {
-            write_nocolour(
+            write!(
                 writer,
-                iter_paths,
-                null_terminated,
-                strip_leading_dot_slash,
-                quoted,
-            )
+                "{} {noun}, {}",
+                self.entries,
+                format_size(self.bytes, size_format)
+            )?;
+        }
+        writer.write_all(if null_terminated {
+            NULL_TERMINATED_NEWLINE
+        } else {
+            NEWLINE
+        })
+    }
+}
+
+/**
+ Writes an `ls -l` style listing of `entries`, the `lstat`s are split across threads as they dominate for large result sets.
+
+ Columns are aligned across every entry, symlinks are followed by ` -> target`.
+ With `summary`, the listing is preceded by `total N` (in 1K blocks like `ls`) and followed by the [`Summary`] footer.
+*/
+pub(crate) fn long_format<W: Write>(
+    writer: &mut W,
+    entries: Vec<DirEntry>,
+    size_format: SizeFormat,
+    summary: bool,
+    context: &OutputContext,
+) -> std::io::Result<()> {
+    let statted = lstat_in_parallel(entries);
+
+    let mut users: HashMap<libc::uid_t, String> = HashMap::new();
+    let mut groups: HashMap<libc::gid_t, String> = HashMap::new();
+
+    // Every column but the mode, time and path needs its width before anything is written
+    let rows: Vec<_> = statted
+        .iter()
+        .map(|(_, statted)| {
+            statted.as_ref().map(|statted| {
+                let owner = users
+                    .entry(statted.st_uid)
+                    .or_insert_with(|| user_name(statted.st_uid))
+                    .clone();
+                let group = groups
+                    .entry(statted.st_gid)
+                    .or_insert_with(|| group_name(statted.st_gid))
+                    .clone();
+                [
+                    statted.st_nlink.to_string(),
+                    owner,
+                    group,
+                    format_size(statted.st_size.cast_unsigned(), size_format),
+                ]
+            })
+        })
+        .collect();
+
+    let mut widths = [0; 4];
+    for row in rows.iter().flatten() {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    let terminator = if context.null_terminated {
+        b"\0"
+    } else {
+        NEWLINE
+    };
+
+    if summary {
+        let blocks: u64 = statted
+            .iter()
+            .filter_map(|(_, statted)| statted.as_ref())
+            .map(|statted| -> u64 { access_stat!(statted, st_blocks) })
+            .sum();
+        // st_blocks is in 512 byte units
+        let total = if size_format == SizeFormat::Bytes {
+            (blocks / 2).to_string()
+        } else {
+            format_size(blocks * 512, size_format)
+        };
+        write!(writer, "total {total}")?;
+        writer.write_all(terminator)?;
+    }
+
+    let mut totals = Summary::default();
+    for ((entry, statted), row) in statted.iter().zip(&rows) {
+        totals.entries += 1;
+        match (statted, row) {
+            (Some(statted), Some([links, owner, group, size])) => {
+                #[allow(clippy::useless_conversion)] // mode_t isn't u32 everywhere
+                let mode = u32::from(statted.st_mode);
+                if !entry.is_dir() {
+                    totals.bytes += statted.st_size.cast_unsigned();
+                }
+                writer.write_all(&mode_string(FileType::from_stat(statted), mode))?;
+                write!(
+                    writer,
+                    " {links:>links_w$} {owner:<owner_w$} {group:<group_w$} {size:>size_w$} {} ",
+                    format_time(access_stat!(statted, st_mtime)),
+                    links_w = widths[0],
+                    owner_w = widths[1],
+                    group_w = widths[2],
+                    size_w = widths[3],
+                )?;
+            }
+            // The entry vanished (or can't be statted), keep the path so nothing is silently dropped
+            _ => writer.write_all(b"?????????? ? ")?,
+        }
+
+        if let Some(hyperlinks) = context.hyperlinks.as_ref() {
+            hyperlinks.open(writer, entry)?;
+        }
+        write_relative(writer, entry, context)?;
+        if context.hyperlinks.is_some() {
+            writer.write_all(LINK_CLOSE)?;
+        }
+        if entry.is_symlink()
+            && let Ok(target) = std::fs::read_link(entry.as_path())
+        {
+            writer.write_all(b" -> ")?;
+            write_path_bytes(writer, target.as_os_str().as_bytes(), context)?;
+        }
+        writer.write_all(terminator)?;
+    }
+
+    if summary {
+        totals.write(writer, context.null_terminated, size_format)?;
+    }
+    Ok(())
+}
+
+/// `lstat`s every entry, keeping the order, chunks are only split across threads when there are enough entries
+fn lstat_in_parallel(mut entries: Vec<DirEntry>) -> Vec<(DirEntry, Option<libc::stat>)> {
+    const MIN_CHUNK: usize = 1024;
+
+    let stat_chunk = |chunk: Vec<DirEntry>| -> Vec<(DirEntry, Option<libc::stat>)> {
+        chunk
+            .into_iter()
+            .map(|entry| {
+                let statted = entry.get_lstat().ok();
+                (entry, statted)
+            })
+            .collect()
+    };
+
+    let threads = crate::fs::limits::cpu_count().get();
+    let chunk_size = entries.len().div_ceil(threads).max(MIN_CHUNK);
+    if entries.len() <= chunk_size {
+        return stat_chunk(entries);
+    }
+
+    // `DirEntry` isn't `Sync`, so each thread owns its chunk and hands it back
+    let mut chunks = Vec::with_capacity(threads);
+    while entries.len() > chunk_size {
+        let rest = entries.split_off(chunk_size);
+        chunks.push(core::mem::replace(&mut entries, rest));
+    }
+    chunks.push(entries);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || stat_chunk(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+            })
+            .collect()
+    })
+}
+
+/// The `drwxr-xr-x` column, including the setuid/setgid/sticky letters
+fn mode_string(file_type: FileType, mode: u32) -> [u8; 10] {
+    let type_char = match file_type {
+        FileType::RegularFile => b'-',
+        FileType::Directory => b'd',
+        FileType::Symlink => b'l',
+        FileType::BlockDevice => b'b',
+        FileType::CharDevice => b'c',
+        FileType::Pipe => b'p',
+        FileType::Socket => b's',
+        FileType::Unknown => b'?',
+    };
+
+    let mut out = [b'-'; 10];
+    out[0] = type_char;
+    // (shift of the class, special bit, letter for special with/without execute)
+    let classes = [
+        (6, 0o4000, b's', b'S'),
+        (3, 0o2000, b's', b'S'),
+        (0, 0o1000, b't', b'T'),
+    ];
+    for (index, (shift, special, with_exec, without_exec)) in classes.into_iter().enumerate() {
+        let bits = (mode >> shift) & 0o7;
+        let base = 1 + index * 3;
+        if bits & 0o4 != 0 {
+            out[base] = b'r';
+        }
+        if bits & 0o2 != 0 {
+            out[base + 1] = b'w';
+        }
+        out[base + 2] = match (mode & special != 0, bits & 0o1 != 0) {
+            (true, true) => with_exec,
+            (true, false) => without_exec,
+            (false, true) => b'x',
+            (false, false) => b'-',
+        };
+    }
+    out
+}
+
+/**
+ Formats a size in bytes for display, as used by the long listing and summaries.
+
+ ```
+ use fdf::util::{SizeFormat, format_size};
+
+ assert_eq!(format_size(1536, SizeFormat::Bytes), "1536");
+ assert_eq!(format_size(1536, SizeFormat::Si), "1.5k");
+ assert_eq!(format_size(1536, SizeFormat::Binary), "1.5K");
+ assert_eq!(format_size(23 * 1024 * 1024, SizeFormat::Binary), "23M");
+ assert_eq!(format_size(999, SizeFormat::Binary), "999");
+ ```
+*/
+#[must_use]
+pub fn format_size(bytes: u64, format: SizeFormat) -> String {
+    match format {
+        SizeFormat::Bytes => bytes.to_string(),
+        SizeFormat::Si => scaled_size(bytes, 1000, ['k', 'M', 'G', 'T', 'P', 'E']),
+        SizeFormat::Binary => scaled_size(bytes, 1024, ['K', 'M', 'G', 'T', 'P', 'E']),
+    }
+}
+
+/// Scales `bytes` down by `base` until it's below it, one decimal place is kept for values under 10
+#[allow(clippy::cast_precision_loss)] // Only a display approximation
+fn scaled_size(bytes: u64, base: u64, units: [char; 6]) -> String {
+    if bytes < base {
+        return bytes.to_string();
+    }
+
+    let base = base as f64;
+    let mut value = bytes as f64 / base;
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+
+    if value < 10.0 {
+        format!("{value:.1}{}", units[unit])
+    } else {
+        format!("{value:.0}{}", units[unit])
+    }
+}
+
+/// Formats a modification time as `YYYY-MM-DD HH:MM` in local time
+fn format_time(secs: i64) -> String {
+    let time = secs as libc::time_t;
+    let mut tm = core::mem::MaybeUninit::<libc::tm>::zeroed();
+    // SAFETY: both pointers are valid for the duration of the call, localtime_r is thread safe
+    if unsafe { libc::localtime_r(&raw const time, tm.as_mut_ptr()) }.is_null() {
+        return String::from("????-??-?? ??:??");
+    }
+    // SAFETY: localtime_r succeeded, so it filled in the struct
+    let tm = unsafe { tm.assume_init() };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+/// Looks up the user name for `uid`, falling back to the number
+fn user_name(uid: libc::uid_t) -> String {
+    lookup_name(
+        |pwd: *mut libc::passwd, buf, len, result| {
+            // SAFETY: every pointer is valid and `len` is the buffer's length
+            unsafe { libc::getpwuid_r(uid, pwd, buf, len, result) }
+        },
+        |pwd| pwd.pw_name,
+    )
+    .unwrap_or_else(|| uid.to_string())
+}
+
+/// Looks up the group name for `gid`, falling back to the number
+fn group_name(gid: libc::gid_t) -> String {
+    lookup_name(
+        |grp: *mut libc::group, buf, len, result| {
+            // SAFETY: every pointer is valid and `len` is the buffer's length
+            unsafe { libc::getgrgid_r(gid, grp, buf, len, result) }
+        },
+        |grp| grp.gr_name,
+    )
+    .unwrap_or_else(|| gid.to_string())
+}
+
+/// Runs one of the reentrant `get*_r` lookups, growing the buffer on `ERANGE`
+fn lookup_name<T>(
+    lookup: impl Fn(*mut T, *mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+    name: impl Fn(&T) -> *const libc::c_char,
+) -> Option<String> {
+    const MAX_BUFFER: usize = 1 << 20;
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut record = core::mem::MaybeUninit::<T>::zeroed();
+        let mut result: *mut T = core::ptr::null_mut();
+        let code = lookup(
+            record.as_mut_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &raw mut result,
+        );
+        if code == libc::ERANGE && buffer.len() < MAX_BUFFER {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 || result.is_null() {
+            return None;
         }
+        // SAFETY: the lookup succeeded, so the record is initialised and its name points into `buffer`
+        let name = unsafe { core::ffi::CStr::from_ptr(name(record.assume_init_ref())) };
+        return Some(name.to_string_lossy().into_owned());
     }
 }
 
 #[inline]
-fn extension_colour(entry: &DirEntry) -> &[u8] {
+fn extension_colour(entry: &DirEntry) -> &'static [u8] {
     match entry.file_type {
         FileType::RegularFile | FileType::Unknown => {
             BytePath::extension(entry) // Use the trait to do this, since root will never be sent down the iterator
-                .map_or(RESET, |pos| file_type_colour!(pos))
+                .map_or(RESET, colour_path_or_reset)
         }
         FileType::Directory => file_type_colour!(directory),
         FileType::Symlink => match entry.is_traversible_cache.get() {
@@ -239,33 +1162,68 @@ fn extension_colour(entry: &DirEntry) -> &[u8] {
     }
 }
 
+/**
+ How paths are written, decided once by [`PrinterBuilder::print`] from its options and whether stdout is a terminal.
+
+ Output to a terminal is for reading: it's coloured (in [`ColourMode::Auto`]), flushed in small chunks and
+ control characters in names are shown as `?` like `ls`, so a name can't move the cursor or change the colours.
+ Output to anything else gets the raw bytes of every path in large writes.
+*/
+#[derive(Default)]
+pub(crate) struct OutputContext {
+    /// Whether stdout is a terminal
+    pub(crate) terminal: bool,
+    pub(crate) colours: Option<LsColours>,
+    pub(crate) null_terminated: bool,
+    pub(crate) relative: Option<RelativePaths>,
+    pub(crate) quoted: bool,
+    /// Written in place of every `/`
+    pub(crate) separator: Option<Box<[u8]>>,
+    pub(crate) hyperlinks: Option<Hyperlinks>,
+}
+
+impl OutputContext {
+    /// Capacity of the buffer in front of stdout, a terminal shows results promptly while a pipe gets large writes
+    pub(crate) const fn buffer_capacity(&self) -> usize {
+        if self.terminal {
+            8 * 1024
+        } else {
+            16 * 4096 //TODO play with these values?
+        }
+    }
+
+    /// Whether control characters in paths are replaced, null terminated output is always raw
+    const fn hides_control_characters(&self) -> bool {
+        self.terminal && !self.null_terminated
+    }
+}
+
 /// A convenient function to print results
 #[inline]
-fn write_nocolour<W, I>(
+pub(crate) fn write_nocolour<W, I>(
     writer: &mut W,
     iter_paths: I,
-    null_terminated: bool,
-    strip_leading_dot_slash: bool,
-    quoted: bool,
+    context: &OutputContext,
 ) -> std::io::Result<()>
 where
     W: Write,
     I: IntoIterator<Item = DirEntry>,
 {
-    // Branchless offset: 2 when stripping `./`, 0 otherwise.
-    // Every path is guaranteed to start with `./` when the root was `./`.
-    let start = usize::from(strip_leading_dot_slash) * 2;
-    let prefix = PREFIXES[usize::from(quoted)];
-    let suffixes = [PLAIN_SUFFIXES, NULL_SUFFIXES][usize::from(null_terminated)];
+    let prefix = PREFIXES[usize::from(context.quoted)];
+    let suffixes = [PLAIN_SUFFIXES, NULL_SUFFIXES][usize::from(context.null_terminated)];
 
     for path in iter_paths {
-        // SAFETY: when strip_leading_dot_slash is true the root was `./`, so every
-        // emitted path is guaranteed to start with `./` (len >= 2). When false,
-        // start == 0 so we just take the full slice, which is always valid.
-        let bytes = unsafe { path.get_unchecked(start..) };
+        if let Some(hyperlinks) = context.hyperlinks.as_ref() {
+            hyperlinks.open(writer, &path)?;
+        }
         writer.write_all(prefix)?;
-        writer.write_all(bytes)?;
-        writer.write_all(suffixes[(usize::from(path.is_dir()) << 1) | usize::from(quoted)])?;
+        write_relative(writer, &path, context)?;
+        let dir_offset = trailing_slash(&path, writer, context.separator.as_deref())?;
+        write_suffix(
+            writer,
+            suffixes[dir_offset | usize::from(context.quoted)],
+            context.hyperlinks.as_ref(),
+        )?;
         // I don't append a slash for symlinks that are directories when not sending to stdout
         // This is to avoid calling stat on symlinks. It seems extremely wasteful.
     }
@@ -273,28 +1231,273 @@ where
 }
 
 #[inline]
-fn write_coloured<W, I>(
+pub(crate) fn write_coloured<W, I>(
     writer: &mut W,
     iter_paths: I,
-    strip_leading_dot_slash: bool,
-    quoted: bool,
+    ls_colours: &LsColours,
+    context: &OutputContext,
 ) -> std::io::Result<()>
 where
     W: Write,
     I: IntoIterator<Item = DirEntry>,
 {
-    // as above.
-    let start = usize::from(strip_leading_dot_slash) * 2;
-    let prefix = PREFIXES[usize::from(quoted)];
+    let prefix = PREFIXES[usize::from(context.quoted)];
     for path in iter_paths {
-        // SAFETY: same guarantee as write_nocolour — root was `./` so len >= 2.
-        let bytes = unsafe { path.get_unchecked(start..) };
+        if let Some(hyperlinks) = context.hyperlinks.as_ref() {
+            hyperlinks.open(writer, &path)?;
+        }
         writer.write_all(prefix)?;
-        writer.write_all(extension_colour(&path))?;
-        writer.write_all(bytes)?;
-        writer.write_all(
-            COLOURED_SUFFIXES[(usize::from(path.is_dir()) << 1) | usize::from(quoted)],
+        writer.write_all(ls_colours.colour(&path))?;
+        write_relative(writer, &path, context)?;
+        let dir_offset = trailing_slash(&path, writer, context.separator.as_deref())?;
+        write_suffix(
+            writer,
+            COLOURED_SUFFIXES[dir_offset | usize::from(context.quoted)],
+            context.hyperlinks.as_ref(),
         )?;
     }
     Ok(())
 }
+
+/**
+ Writes OSC 8 hyperlinks around printed paths, see [`PrinterBuilder::hyperlinks`].
+
+ The link target is a `file://hostname/path` URL, relative paths are resolved against the working directory
+ and every byte outside the URI unreserved set (besides `/`) is percent encoded, so names which aren't valid UTF-8 survive.
+*/
+pub(crate) struct Hyperlinks {
+    /// The start of every link, up to the (absolute) path
+    prefix: Vec<u8>,
+    /// The working directory, percent encoded, for relative paths
+    cwd: Vec<u8>,
+}
+
+impl Hyperlinks {
+    /// Looks up the hostname, relative paths are resolved against `cwd`
+    pub(crate) fn new(cwd: &std::path::Path) -> std::io::Result<Self> {
+        let mut hostname = [0_u8; 256];
+        // SAFETY: the buffer is valid for its full length, one byte is held back so it's always NUL terminated
+        let host: &[u8] = if unsafe {
+            libc::gethostname(hostname.as_mut_ptr().cast(), hostname.len() - 1)
+        } == 0
+        {
+            let length = memchr::memchr(0, &hostname).unwrap_or(0);
+            &hostname[..length]
+        } else {
+            // An empty host means the local machine anyway
+            EMPTY
+        };
+
+        let mut prefix = b"\x1b]8;;file://".to_vec();
+        write_percent_encoded(&mut prefix, host)?;
+        let mut encoded_cwd = Vec::new();
+        write_percent_encoded(&mut encoded_cwd, cwd.as_os_str().as_bytes())?;
+        Ok(Self {
+            prefix,
+            cwd: encoded_cwd,
+        })
+    }
+
+    /// Opens a hyperlink to `entry`, closed with [`LINK_CLOSE`]
+    pub(crate) fn open<W: Write>(&self, writer: &mut W, entry: &DirEntry) -> std::io::Result<()> {
+        writer.write_all(&self.prefix)?;
+        let path = entry.as_bytes();
+        if !path.starts_with(b"/") {
+            writer.write_all(&self.cwd)?;
+            writer.write_all(b"/")?;
+        }
+        write_percent_encoded(writer, path.strip_prefix(b"./").unwrap_or(path))?;
+        writer.write_all(b"\x1b\\")
+    }
+}
+
+/// Writes `bytes` with every byte besides `/` and the URI unreserved characters percent encoded
+fn write_percent_encoded<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    let mut start = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        if !(byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/')) {
+            writer.write_all(&bytes[start..index])?;
+            write!(writer, "%{byte:02X}")?;
+            start = index + 1;
+        }
+    }
+    writer.write_all(&bytes[start..])
+}
+
+/// Writes `suffix`, closing the hyperlink (if any) before its final newline or NUL
+#[inline]
+fn write_suffix<W: Write>(
+    writer: &mut W,
+    suffix: &[u8],
+    hyperlinks: Option<&Hyperlinks>,
+) -> std::io::Result<()> {
+    match (hyperlinks, suffix.split_last()) {
+        (Some(_), Some((terminator, rest))) => {
+            writer.write_all(rest)?;
+            writer.write_all(LINK_CLOSE)?;
+            writer.write_all(core::slice::from_ref(terminator))
+        }
+        _ => writer.write_all(suffix),
+    }
+}
+
+/// Writes the path of `entry`, relative to another directory if set
+#[inline]
+fn write_relative<W: Write>(
+    writer: &mut W,
+    entry: &DirEntry,
+    context: &OutputContext,
+) -> std::io::Result<()> {
+    let (prefix, rest) = context
+        .relative
+        .as_ref()
+        .map_or((EMPTY, entry.as_bytes()), |relative| {
+            relative.split(entry.as_bytes())
+        });
+    write_path_bytes(writer, prefix, context)?;
+    write_path_bytes(writer, rest, context)
+}
+
+/// Writes part of a path with the separator applied, replacing control characters with `?` on a terminal
+#[inline]
+fn write_path_bytes<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    context: &OutputContext,
+) -> std::io::Result<()> {
+    let separator = context.separator.as_deref();
+    if !context.hides_control_characters() {
+        return write_with_separator(writer, bytes, separator);
+    }
+
+    let mut start = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        let replacement = match (byte, separator) {
+            (b'/', Some(separator)) => separator,
+            _ if byte.is_ascii_control() => b"?",
+            _ => continue,
+        };
+        writer.write_all(&bytes[start..index])?;
+        writer.write_all(replacement)?;
+        start = index + 1;
+    }
+    writer.write_all(&bytes[start..])
+}
+
+/// Writes `bytes`, replacing every `/` with `separator` if one is set
+#[inline]
+fn write_with_separator<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    separator: Option<&[u8]>,
+) -> std::io::Result<()> {
+    let Some(separator) = separator else {
+        return writer.write_all(bytes);
+    };
+
+    let mut start = 0;
+    for slash in memchr::memchr_iter(b'/', bytes) {
+        writer.write_all(&bytes[start..slash])?;
+        writer.write_all(separator)?;
+        start = slash + 1;
+    }
+    writer.write_all(&bytes[start..])
+}
+
+/**
+ Returns the offset into the suffix tables for a directory's trailing slash (2 for directories, 0 otherwise).
+
+ With a custom separator the suffix tables can't be used for the slash, so the separator is written here instead and 0 is returned.
+*/
+#[inline]
+fn trailing_slash<W: Write>(
+    path: &DirEntry,
+    writer: &mut W,
+    separator: Option<&[u8]>,
+) -> std::io::Result<usize> {
+    match separator {
+        None => Ok(usize::from(path.is_dir()) << 1),
+        Some(separator) => {
+            if path.is_dir() {
+                writer.write_all(separator)?;
+            }
+            Ok(0)
+        }
+    }
+}
+
+/// Writes `prefix` then `bytes` as a single JSON string literal, converting invalid UTF-8 lossily
+fn write_json_string<W: Write>(writer: &mut W, prefix: &[u8], bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(QUOTE)?;
+    // Prefixes end in `/`, so splitting there never splits a UTF-8 sequence
+    write_json_escaped(writer, prefix)?;
+    write_json_escaped(writer, bytes)?;
+    writer.write_all(QUOTE)
+}
+
+/// Writes `bytes` escaped for a JSON string literal, converting invalid UTF-8 lossily
+fn write_json_escaped<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut start = 0;
+    for (index, byte) in text.bytes().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..0x20 => b"",
+            _ => continue,
+        };
+        writer.write_all(&text.as_bytes()[start..index])?;
+        if escaped.is_empty() {
+            write!(writer, "\\u{byte:04x}")?;
+        } else {
+            writer.write_all(escaped)?;
+        }
+        start = index + 1;
+    }
+    writer.write_all(&text.as_bytes()[start..])
+}
+
+/// Streams entries as a JSON array or as newline delimited JSON
+pub(crate) fn write_json<W, I>(
+    writer: &mut W,
+    iter_paths: I,
+    format: OutputFormat,
+    relative: Option<&RelativePaths>,
+) -> std::io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = DirEntry>,
+{
+    let is_array = format == OutputFormat::Json;
+    let separator: &[u8] = if is_array { b",\n" } else { NEWLINE };
+
+    if is_array {
+        writer.write_all(b"[\n")?;
+    }
+    for (index, path) in iter_paths.into_iter().enumerate() {
+        if is_array && index != 0 {
+            writer.write_all(separator)?;
+        }
+        writer.write_all(b"{\"path\":")?;
+        let (prefix, rest) = relative.map_or((EMPTY, path.as_bytes()), |relative| {
+            relative.split(path.as_bytes())
+        });
+        write_json_string(writer, prefix, rest)?;
+        write!(
+            writer,
+            ",\"type\":\"{}\",\"depth\":{}}}",
+            path.file_type.as_str(),
+            path.depth()
+        )?;
+        if !is_array {
+            writer.write_all(separator)?;
+        }
+    }
+    if is_array {
+        writer.write_all(b"\n]\n")?;
+    }
+    Ok(())
+}
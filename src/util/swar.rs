@@ -0,0 +1,196 @@
+/*!
+ SWAR (SIMD within a register) helpers used for fast filename length calculation.
+
+ [`first_zero_byte_mask_u64`] is the building block behind [`dirent_const_time_strlen`]. It and the rest are
+ published separately so other filesystem crates can reuse them without depending on the traversal machinery.
+
+ The word level helpers operate on plain `u64`s and are platform independent, they report byte
+ positions in *memory order* (ie the order the bytes would appear in a buffer) on both little and big
+ endian targets. Every SWAR routine has a scalar fallback (suffixed `_scalar`) with identical semantics,
+ useful as a reference implementation or for platforms where the bit tricks are undesirable.
+
+ # References
+ - [Stanford Bit Twiddling Hacks find 0 byte ](http://www.icodeguru.com/Embedded/Hacker%27s-Delight/043.htm)
+ - [Wojciech Muła ] (<http://0x80.pl/notesen/2016-11-28-simd-strfind.html#algorithm-1-generic-simd>)
+*/
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "emscripten",
+    target_os = "redox",
+    target_os = "hermit",
+    target_os = "fuchsia",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "aix",
+    target_os = "hurd"
+))]
+pub use super::utils::dirent_const_time_strlen;
+/// Portable `d_name` length calculation, uses [`dirent_const_time_strlen`] where supported and `strlen` otherwise.
+pub use super::utils::dirent_name_length;
+
+/// `0x01` repeated in every byte
+pub const LO_U64: u64 = repeat_byte_u64(0x01);
+/// `0x80` repeated in every byte
+pub const HI_U64: u64 = repeat_byte_u64(0x80);
+
+/**
+ Broadcasts `byte` into every byte of a `u64`.
+
+ ```
+ use fdf::util::swar::repeat_byte_u64;
+ assert_eq!(repeat_byte_u64(0x2F), 0x2F2F_2F2F_2F2F_2F2F);
+ ```
+*/
+#[inline]
+#[must_use]
+pub const fn repeat_byte_u64(byte: u8) -> u64 {
+    u64::from_ne_bytes([byte; size_of::<u64>()])
+}
+
+/**
+ Returns a mask with the high bit (`0x80`) set in each byte of `word` which is zero, and nothing else.
+
+ Unlike the classic `(x - 0x01..) & !x & 0x80..` trick, this never produces false positives from
+ borrows propagating across bytes (eg a `0x01` byte next to a `0x00`), so the mask is exact and can be
+ used to locate *any* zero byte, not just the first.
+*/
+#[inline]
+#[must_use]
+pub const fn zero_byte_mask_u64(word: u64) -> u64 {
+    // Clearing the high bits first means the addition can never carry between bytes.
+    (!word & !HI_U64).wrapping_add(LO_U64) & (!word & HI_U64)
+}
+
+/**
+ Returns a mask whose first set bit in memory order is the high bit (`0x80`) of the first zero byte of `word`,
+ or `0` if there is none.
+
+ This is the cheapest mask for finding the *first* zero byte, the one [`dirent_const_time_strlen`] uses.
+ On little endian targets it's the classic `(x - 0x01..) & !x & 0x80..` trick, whose only false positives come from
+ borrows and so sit after a real zero in memory order. On big endian targets those would come first, so this is
+ [`zero_byte_mask_u64`] there (one more instruction), as a filename may well contain `0x01` bytes.
+*/
+#[inline]
+#[must_use]
+pub const fn first_zero_byte_mask_u64(word: u64) -> u64 {
+    #[cfg(target_endian = "little")]
+    {
+        word.wrapping_sub(LO_U64) & !word & HI_U64
+    }
+    #[cfg(target_endian = "big")]
+    {
+        zero_byte_mask_u64(word)
+    }
+}
+
+/**
+ Returns true if any byte in `word` is zero.
+
+ ```
+ use fdf::util::swar::contains_zero_byte_u64;
+ assert!(contains_zero_byte_u64(u64::from_ne_bytes(*b"abc\0defg")));
+ assert!(!contains_zero_byte_u64(u64::from_ne_bytes(*b"abcdefgh")));
+ ```
+*/
+#[inline]
+#[must_use]
+pub const fn contains_zero_byte_u64(word: u64) -> bool {
+    // The classic test is exact for a boolean answer (false positives only occur alongside a real zero)
+    word.wrapping_sub(LO_U64) & !word & HI_U64 != 0
+}
+
+/**
+ Returns the index (in memory order) of the first zero byte in `word`, if any.
+
+ ```
+ use fdf::util::swar::find_zero_byte_u64;
+ assert_eq!(find_zero_byte_u64(u64::from_ne_bytes(*b"ab\0\x01\0fgh")), Some(2));
+ assert_eq!(find_zero_byte_u64(u64::from_ne_bytes(*b"abcdefgh")), None);
+ ```
+*/
+#[inline]
+#[must_use]
+pub const fn find_zero_byte_u64(word: u64) -> Option<usize> {
+    let mask = first_zero_byte_mask_u64(word);
+    if mask == 0 {
+        return None;
+    }
+    #[cfg(target_endian = "little")]
+    {
+        Some((mask.trailing_zeros() >> 3) as usize)
+    }
+    #[cfg(target_endian = "big")]
+    {
+        Some((mask.leading_zeros() >> 3) as usize)
+    }
+}
+
+/**
+ Returns the index (in memory order) of the last zero byte in `word`, if any.
+
+ ```
+ use fdf::util::swar::find_last_zero_byte_u64;
+ assert_eq!(find_last_zero_byte_u64(u64::from_ne_bytes(*b"ab\0\x01\0fgh")), Some(4));
+ ```
+*/
+#[inline]
+#[must_use]
+pub const fn find_last_zero_byte_u64(word: u64) -> Option<usize> {
+    let mask = zero_byte_mask_u64(word);
+    if mask == 0 {
+        return None;
+    }
+    #[cfg(target_endian = "little")]
+    {
+        Some(size_of::<u64>() - 1 - (mask.leading_zeros() >> 3) as usize)
+    }
+    #[cfg(target_endian = "big")]
+    {
+        Some(size_of::<u64>() - 1 - (mask.trailing_zeros() >> 3) as usize)
+    }
+}
+
+/// Scalar fallback for [`find_zero_byte_u64`]
+#[inline]
+#[must_use]
+pub const fn find_zero_byte_u64_scalar(word: u64) -> Option<usize> {
+    let bytes = word.to_ne_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == 0 {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Scalar fallback for [`find_last_zero_byte_u64`]
+#[inline]
+#[must_use]
+pub const fn find_last_zero_byte_u64_scalar(word: u64) -> Option<usize> {
+    let bytes = word.to_ne_bytes();
+    let mut i = bytes.len();
+    while i != 0 {
+        i -= 1;
+        if bytes[i] == 0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+// Cheap compile time sanity checks for the borrow case (0x01 following a 0x00)
+const _: () = assert!(matches!(
+    find_last_zero_byte_u64(u64::from_ne_bytes([0, 1, 1, 1, 1, 1, 1, 1])),
+    Some(0)
+));
+const _: () = assert!(matches!(
+    find_zero_byte_u64(u64::from_ne_bytes([1, 1, 1, 1, 1, 1, 1, 0])),
+    Some(7)
+));
@@ -191,6 +191,10 @@ impl Unique<dirent64> {
     #[inline]
     #[must_use]
     /// Returns the inode of the `dirent64`, returns 0 if `d_ino` is not a struct member on your OS.
+    #[cfg_attr(
+        not(has_d_ino),
+        allow(unused_unsafe, reason = "the field is a constant 0 there")
+    )]
     pub const fn d_ino(self) -> u64 {
         // SAFETY: TRIVIALLY VALID BY CONSTRUCTION
         unsafe { access_dirent!(self.as_ptr(), d_ino) }
@@ -1,7 +1,18 @@
 use crate::dirent64;
 use crate::util::memchr_derivations::memrchr;
 use core::ffi::CStr;
-use core::ffi::{c_char, c_int, c_void};
+use core::ffi::c_char;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+use core::ffi::{c_int, c_void};
 use core::ops::Deref;
 /**
   Wrapper for direct getdents syscalls
@@ -186,6 +197,19 @@ where
     }
 }
 
+/**
+ Returns a file name without its extension, using the same rules as [`DirEntry::extension`](crate::fs::DirEntry::extension):
+ the extension starts after the last dot, unless that dot is the final byte.
+*/
+#[inline]
+pub(crate) fn file_stem(file_name: &[u8]) -> &[u8] {
+    file_name
+        .len()
+        .checked_sub(1)
+        .and_then(|last| memrchr(b'.', file_name.get(..last)?))
+        .map_or(file_name, |dot| file_name.split_at(dot).0)
+}
+
 #[inline]
 #[must_use]
 /**
@@ -383,12 +407,11 @@ pub const unsafe fn dirent_const_time_strlen(drnt: *const dirent64) -> usize {
         const DIRENT_HEADER_START: usize = offset_of!(dirent64, d_name);
         // Access the last field and then round up to find the minimum struct size
         const MIN_DIRENT_SIZE: usize = DIRENT_HEADER_START.next_multiple_of(8);
-        // Compile time assert to immediately cancel the build if invalidated
-        const { assert!(MIN_DIRENT_SIZE == 24, "dirent min size must be 24!") };
-        const { assert!(align_of::<dirent64>() == align_of::<u64>()) };
-        const LO_U64: u64 = 0x0101_0101_0101_0101;
-        const HI_U64: u64 = 0x8080_8080_8080_8080;
-
+        // The trick below relies on this layout, anything else (eg a cross build which couldn't detect `d_namlen`) just counts
+        if MIN_DIRENT_SIZE != 24 || align_of::<dirent64>() != align_of::<u64>() {
+            // SAFETY: `dirent` is valid by precondition, so its name is NUL terminated
+            return unsafe { strlen((&raw const (*drnt).d_name).cast()) };
+        }
         /*  SAFETY: `dirent` is valid by precondition */
         let reclen = unsafe { (*drnt).d_reclen } as usize;
         debug_assert!(reclen.is_multiple_of(8));
@@ -446,25 +469,10 @@ pub const unsafe fn dirent_const_time_strlen(drnt: *const dirent64) -> usize {
         This allows us to skip a 0 check which then allows us to use tzcnt/lzcnt on most cpu's (well x86_64, not knowledgeable on ARM/etc)
          */
 
+        // The classic trick on LE, a borrow free one on BE (see `first_zero_byte_mask_u64`)
         //SAFETY: The u64 can never be all 0's post-SWAR
-        #[cfg(target_endian = "little")]
-        let masked_word = unsafe {
-            NonZeroU64::new_unchecked(last_word.wrapping_sub(LO_U64) & !last_word & HI_U64)
-        };
-
-        //http://0x80.pl/notesen/2016-11-28-simd-strfind.html#algorithm-1-generic-simd
-        // ^ Reference for the BE algorithm
-        // Use a borrow free algorithm to do this on BE safely(1 more instruction than LE)
-        // This is overly precautious, mostly because we can't use the typical `HASZERO` due to the possible
-        // present of 0x01 bytes in a filename, given POSIX paths are raw bytes
-        // and the POSIX standard only dictates 1. a filename cannot contain a slash and 2. cannot be empty.
-        #[cfg(target_endian = "big")]
-        //SAFETY: as in LE version.
-        let masked_word = unsafe {
-            NonZeroU64::new_unchecked(
-                (!last_word & !HI_U64).wrapping_add(LO_U64) & (!last_word & HI_U64),
-            )
-        };
+        let masked_word =
+            unsafe { NonZeroU64::new_unchecked(super::swar::first_zero_byte_mask_u64(last_word)) };
 
         // Find the position of the null terminator
         #[cfg(target_endian = "little")]
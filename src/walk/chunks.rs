@@ -0,0 +1,58 @@
+/*!
+ Fixed size chunks of results, see [`Finder::traverse_chunks`](crate::walk::Finder::traverse_chunks).
+
+ The workers hand their batches over through a rendezvous channel, so each one waits until the consumer takes
+ its batch. Batches are then regrouped into chunks of exactly the requested size, which is what a consumer
+ handing work to a pool (hashing, uploading) wants.
+*/
+
+use crate::fs::DirEntry;
+use crossbeam_channel::Receiver;
+use std::mem;
+
+/**
+ An iterator over the results of a traversal in chunks of a fixed size, returned by
+ [`Finder::traverse_chunks`](crate::walk::Finder::traverse_chunks).
+
+ Every chunk but the last holds exactly the requested number of entries. Dropping the iterator stops the traversal.
+*/
+#[derive(Debug)]
+pub struct Chunks {
+    receiver: Receiver<Vec<DirEntry>>,
+    /// Entries received but not yet handed out, never more than two chunks' worth
+    pending: Vec<DirEntry>,
+    chunk_size: usize,
+}
+
+impl Chunks {
+    pub(crate) const fn new(receiver: Receiver<Vec<DirEntry>>, chunk_size: usize) -> Self {
+        Self {
+            receiver,
+            pending: Vec::new(),
+            chunk_size,
+        }
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = Vec<DirEntry>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pending.len() >= self.chunk_size {
+                let rest = self.pending.split_off(self.chunk_size);
+                return Some(mem::replace(&mut self.pending, rest));
+            }
+
+            match self.receiver.recv() {
+                // A full batch needn't be copied
+                Ok(batch) if self.pending.is_empty() && batch.len() == self.chunk_size => {
+                    return Some(batch);
+                }
+                Ok(batch) => self.pending.extend(batch),
+                Err(_) => return (!self.pending.is_empty()).then(|| mem::take(&mut self.pending)),
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+/*!
+ An in-process cache of directory listings which expire after a fixed time, enabled by the `dir-cache` feature.
+
+ Meant for long running processes (eg language servers) searching the same subtrees over and over, where a
+ listing a few seconds old is good enough and reading every directory again for each query is wasted work.
+ Unlike a [`PathIndex`](crate::walk::PathIndex) nothing is checked against the filesystem until a listing expires,
+ so a cached directory costs no syscalls at all, and one cache can serve searches of any root from any thread.
+*/
+
+use crate::{
+    DirEntryError, SearchConfigError,
+    fs::DirEntry,
+    walk::{
+        Finder,
+        index::{Child, Listings},
+    },
+};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{
+    collections::HashMap,
+    os::unix::ffi::OsStrExt as _,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+/// A directory's entries and when they were read
+#[derive(Debug)]
+struct Cached {
+    read_at: Instant,
+    children: Arc<[Child]>,
+}
+
+/**
+ Directory listings keyed by path, each kept for a fixed time to live, see the [module documentation](self).
+
+ Paths are cached as they're traversed, so searches should spell their roots the same way (eg both absolute)
+ to share listings.
+
+ # Examples
+ ```
+ use fdf::walk::{DirCache, Finder};
+ use std::time::Duration;
+
+ let cache = DirCache::new(Duration::from_secs(30));
+ let search = |pattern: &str| {
+     Finder::init(env!("CARGO_MANIFEST_DIR")).pattern(pattern).build().unwrap().query_cached(&cache).unwrap()
+ };
+
+ let sources = search(r"\.rs$");
+ let read = cache.reads();
+ // Within the time to live, the second search is answered without reading any directories
+ assert_eq!(search(r"\.rs$").len(), sources.len());
+ assert_eq!(cache.reads(), read);
+ ```
+*/
+#[derive(Debug)]
+pub struct DirCache {
+    ttl: Duration,
+    dirs: Mutex<HashMap<Box<[u8]>, Cached>>,
+    /// Directories read since the cache was created
+    reads: AtomicUsize,
+}
+
+impl DirCache {
+    /// Creates an empty cache, keeping each listing for `ttl` after it was read
+    #[inline]
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            dirs: Mutex::new(HashMap::new()),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    /// How long a listing is kept after it was read
+    #[inline]
+    #[must_use]
+    pub const fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The number of directories cached, including any which have expired but haven't been dropped yet
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no directories are cached
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// The number of directories read (rather than answered from the cache) since it was created
+    #[inline]
+    #[must_use]
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Drops the listing of `dir` (as traversed), so the next search reads it again, eg after being told it changed
+    #[inline]
+    pub fn invalidate<P: AsRef<Path>>(&self, dir: P) {
+        self.lock().remove(dir.as_ref().as_os_str().as_bytes());
+    }
+
+    /// Drops the listings which have expired, freeing their memory
+    #[inline]
+    pub fn purge_expired(&self) {
+        let ttl = self.ttl;
+        self.lock()
+            .retain(|_, cached| cached.read_at.elapsed() < ttl);
+    }
+
+    /// Drops every listing
+    #[inline]
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Box<[u8]>, Cached>> {
+        self.dirs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Listings for &DirCache {
+    type Children<'listings>
+        = Arc<[Child]>
+    where
+        Self: 'listings;
+
+    /// The entries of `dir`, read unless a listing younger than the time to live is cached
+    fn listing(
+        &mut self,
+        _key: &[u8],
+        dir: &DirEntry,
+        buffer_size: usize,
+    ) -> Result<Arc<[Child]>, DirEntryError> {
+        if let Some(cached) = self.lock().get(dir.as_bytes())
+            && cached.read_at.elapsed() < self.ttl
+        {
+            return Ok(Arc::clone(&cached.children));
+        }
+
+        // Read without holding the lock, so other searches aren't held up by this one
+        let read_at = Instant::now();
+        let children: Arc<[Child]> = Child::read_all(dir, buffer_size)?.into();
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(
+            dir.as_bytes().into(),
+            Cached {
+                read_at,
+                children: Arc::clone(&children),
+            },
+        );
+        Ok(children)
+    }
+}
+
+impl Finder {
+    /**
+    Searches as a traversal would, answering from `cache` for the directories it has a fresh listing of and
+    caching the listings of those it reads.
+
+    Like [`query_index`](Self::query_index) this runs on the calling thread, results come in the order of a
+    single threaded depth first walk, each directory after its contents.
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn query_cached(self, mut cache: &DirCache) -> Result<Vec<DirEntry>, SearchConfigError> {
+        self.query_listings(&mut cache)
+    }
+}
@@ -0,0 +1,103 @@
+/*!
+ Totals of the file extensions in a tree, see [`Finder::extensions_summary`].
+
+ Each result is reduced to its extension and size on the worker threads (like
+ [`traverse_projected`](Finder::traverse_projected)), so paths are never sent to the consumer, which only adds
+ up the records.
+*/
+
+use crate::{
+    SearchConfigError,
+    fs::DirEntry,
+    walk::{
+        Fields, Finder,
+        projection::{FromEntry, take_array},
+    },
+};
+use std::collections::BTreeMap;
+
+/// The number and total size of the files with one extension, as returned by [`Finder::extensions_summary`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtensionSummary {
+    /// The number of regular files with the extension
+    pub count: usize,
+    /// Their total size in bytes (files which couldn't be statted count as empty)
+    pub bytes: u64,
+}
+
+/// The extension and size of a regular file, `None` for anything else
+struct ExtensionRecord(Option<(Box<[u8]>, u64)>);
+
+impl FromEntry for ExtensionRecord {
+    fn from_entry(entry: DirEntry, _fields: Fields) -> Self {
+        if !entry.is_regular_file() {
+            return Self(None);
+        }
+        Self(
+            entry
+                .extension()
+                .map(|extension| (extension.into(), entry.file_size().unwrap_or(0))),
+        )
+    }
+
+    // Extensions are bounded by NAME_MAX, so never truncate
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode(&self, out: &mut Vec<u8>) {
+        let Some((extension, bytes)) = self.0.as_ref() else {
+            out.extend_from_slice(&u32::MAX.to_le_bytes());
+            return;
+        };
+        out.extend_from_slice(&(extension.len() as u32).to_le_bytes());
+        out.extend_from_slice(extension);
+        out.extend_from_slice(&bytes.to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = u32::from_le_bytes(take_array(input)?);
+        if len == u32::MAX {
+            return Some(Self(None));
+        }
+        let (extension, rest) = input.split_at_checked(usize::try_from(len).ok()?)?;
+        *input = rest;
+        let bytes = u64::from_le_bytes(take_array(input)?);
+        Some(Self(Some((extension.into(), bytes))))
+    }
+}
+
+impl Finder {
+    /**
+    Walks the filesystem and totals the regular files matching the search by extension, eg to decide which
+    toolchains a build needs.
+
+    Extensions are compared exactly (so `rs` and `RS` are counted apart) and files without one aren't counted.
+    The map is ordered by extension, so iterating it is stable from one run to the next.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let summary = Finder::init(env!("CARGO_MANIFEST_DIR")).build().unwrap().extensions_summary().unwrap();
+    let rust = summary[&b"rs"[..]];
+    assert!(rust.count > 0 && rust.bytes > 0);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn extensions_summary(
+        self,
+    ) -> Result<BTreeMap<Box<[u8]>, ExtensionSummary>, SearchConfigError> {
+        let mut summary: BTreeMap<Box<[u8]>, ExtensionSummary> = BTreeMap::new();
+        for (extension, bytes) in self
+            .traverse_as::<ExtensionRecord>(Fields::SIZE)?
+            .filter_map(|record| record.0)
+        {
+            let totals = summary.entry(extension).or_default();
+            totals.count += 1;
+            totals.bytes += bytes;
+        }
+        Ok(summary)
+    }
+}
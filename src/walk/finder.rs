@@ -1,23 +1,32 @@
 use crate::{
-    DirEntryError, FilesystemIOError, SearchConfig, SearchConfigError, TraversalError,
-    fs::{DirEntry, FileDes, FileType},
-    util::PrinterBuilder,
-    walk::{DirEntryFilter, FilterType, finder_builder::FinderBuilder},
+    DirEntryError, SearchConfig, SearchConfigError, TraversalError,
+    exec::{CommandTemplate, ExecMode, ExecSummary},
+    filters::{ContentFilter, MimeFilter},
+    fs::{DirEntry, FileDes, FileType, PortableReadDir},
+    util::{PrinterBuilder, RelativePaths},
+    walk::{
+        Backend, CancellationToken, Chunks, ControlFlow, DepthLimit, DirEntryFilter, Fields,
+        FilterType, FlushPolicy, ProjectedEntry, StatefulFilter, TraversalStats, TraversalStrategy,
+        finder_builder::FinderBuilder, projection::FromEntry, sample, spill::SpillFile,
+    },
 };
 use core::{
-    mem,
+    iter, mem,
     num::NonZeroUsize,
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicIsize, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
 };
-use crossbeam_channel::{Receiver, SendError, Sender, bounded};
+use crossbeam_channel::{Receiver, SendError, SendTimeoutError, Sender, TrySendError, bounded};
 use crossbeam_deque::{Injector, Steal, Stealer, Worker};
 use dashmap::DashSet;
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use std::{
     ffi::OsStr,
+    os::fd::AsRawFd as _,
     path::Path,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, OnceLock, PoisonError},
     thread,
+    time::Instant,
 };
 
 /**
@@ -29,77 +38,566 @@ The main entry point for file system search operations.
 `Finder` provides a high-performance, parallel file system traversal API
 with configurable filtering and search criteria. It uses a worker pool for
 parallel execution and provides both synchronous and asynchronous result handling.
+
+A built `Finder` is `Send + Sync` and cheap to clone, the compiled patterns and ignore rules are shared rather than
+rebuilt, so one configuration can be traversed many times (one after another, or at once from several threads)
+by traversing clones of it. See the [`Clone`] implementation for what a clone shares.
 */
 #[derive(Debug)]
 pub struct Finder {
     /// Root directory path for the search operation
     pub(crate) root: Box<OsStr>,
-    /// Configuration for search criteria and filtering options
-    pub(crate) search_config: SearchConfig,
+    /// Any further roots searched in the same traversal, see [`Finder::init_multi`]
+    pub(crate) extra_roots: Box<[Box<OsStr>]>,
+    /// Configuration for search criteria and filtering options, shared between clones
+    pub(crate) search_config: Arc<SearchConfig>,
     /// Optional custom filter function for advanced entry filtering
     pub(crate) custom_filter: Option<DirEntryFilter>,
+    /// Optional closure filter which can keep state, run after every other filter
+    pub(crate) stateful_filter: Option<StatefulFilter>,
+    /// MIME types sniffed from the start of regular files (optional)
+    pub(crate) mime_filter: Option<MimeFilter>,
+    /// Regex searched for in the contents of regular files (optional)
+    pub(crate) content_filter: Option<ContentFilter>,
+    /// Largest sampling key kept by a ratio sample (optional)
+    pub(crate) sample_threshold: Option<u64>,
+    /// Seed for the sampling keys
+    pub(crate) seed: u64,
     /// Internal filter logic combining all filtering criteria
     pub(crate) file_filter: FilterType,
-    /// Filesystem device ID for same-filesystem constraint (optional)
-    pub(crate) starting_filesystem: Option<u64>,
+    /// Filesystem device IDs of the roots for same-filesystem constraint (optional)
+    pub(crate) starting_filesystems: Option<Box<[u64]>>,
     /// Cache for (device, inode) pairs to prevent duplicate traversal with symlinks
     /// Uses `DashSet` for lock-free concurrent access
     pub(crate) inode_cache: Option<DashSet<(u64, u64)>>,
     /// Optionally Collected errors encountered during traversal
     pub(crate) errors: Option<Arc<Mutex<Vec<TraversalError>>>>,
+    /// Totals of the results, counted as they're emitted (optional)
+    pub(crate) stats: Option<Arc<TraversalStats>>,
+    /// The depth limit applied when following symlinks without a maximum depth (optional)
+    pub(crate) follow_depth_limit: Option<Arc<DepthLimit>>,
     /// Maximum worker threads used for traversal
     pub(crate) thread_count: NonZeroUsize,
     /// Custom ignore-file matchers added via CLI (`--ignore-file`).
     pub(crate) custom_ignore_matchers: Vec<Arc<Gitignore>>,
+    /// Size in bytes of the kernel buffer used by `getdents`/`getdirentries`
+    pub(crate) getdents_buffer_size: usize,
+    /// Order in which directories are emitted relative to their contents
+    pub(crate) strategy: TraversalStrategy,
+    /// Maximum number of entries to emit across all threads (optional)
+    pub(crate) max_results: Option<usize>,
+    /// Maximum total size of the regular files emitted across all threads (optional)
+    pub(crate) max_total_size: Option<u64>,
+    /// Whether to descend into mount points below the root (including bind mounts)
+    pub(crate) follow_mounts: bool,
+    /// Sorted device IDs of the virtual filesystems which aren't descended into (optional)
+    pub(crate) virtual_devices: Option<Box<[u64]>>,
+    /// Whether directories on pseudo filesystems (eg `/proc`) aren't descended into
+    pub(crate) skip_pseudo_fs: bool,
+    /// Whether matching directories are emitted without being descended into
+    pub(crate) prune: bool,
+    /// Closure deciding which directories aren't descended into (optional)
+    pub(crate) prune_dirs: Option<StatefulFilter>,
+    /// Maximum number of directory symlinks followed on the way to any directory (optional)
+    pub(crate) max_symlink_depth: Option<u32>,
+    /// Maximum number of file descriptors held open by a traversal (optional)
+    pub(crate) max_open_fds: Option<NonZeroUsize>,
+    /// Maximum number of directories waiting to be read (optional)
+    pub(crate) max_queued_dirs: Option<NonZeroUsize>,
+    /// How long a traversal may run (optional)
+    pub(crate) timeout: Option<Duration>,
+    /// Fields kept by [`Finder::traverse_projected`]
+    pub(crate) projection: Fields,
+    /// What the workers do when the consumer falls behind
+    pub(crate) flush_policy: FlushPolicy,
+    /// How directories are read
+    pub(crate) backend: Backend,
+    /// Aborts the traversal once cancelled (optional)
+    pub(crate) cancellation: Option<CancellationToken>,
+    /// Pool the workers run on instead of threads of their own (optional)
+    #[cfg(feature = "rayon")]
+    pub(crate) thread_pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+/**
+ Clones the configuration for another traversal, without compiling anything again.
+
+ Each clone starts with its own record of the traversal: empty [`errors`](Finder::errors) and
+ [`stats`](Finder::stats) (when enabled), a fresh [`follow_depth_limit`](Finder::follow_depth_limit) and an empty
+ cache of the directories visited through symlinks, so fetch those from the clone being traversed.
+ A [`filter_mut`](crate::walk::FinderBuilder::filter_mut) closure and the cancellation token are shared, so the
+ closure's state carries over between clones and cancelling stops every traversal of them.
+
+ ```
+ use fdf::walk::Finder;
+ use std::thread;
+
+ let finder = Finder::init(env!("CARGO_MANIFEST_DIR")).pattern(r"\.rs$").collect_stats(true).build().unwrap();
+ let counts: Vec<usize> = thread::scope(|scope| {
+     let handles: Vec<_> = (0..2)
+         .map(|_| {
+             let finder = finder.clone();
+             scope.spawn(move || finder.traverse().unwrap().count())
+         })
+         .collect();
+     handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+ });
+ assert_eq!(counts[0], counts[1]);
+
+ // The original hasn't been traversed, so its totals are still empty
+ assert_eq!(finder.stats().unwrap().total(), 0);
+ ```
+*/
+impl Clone for Finder {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            extra_roots: self.extra_roots.clone(),
+            search_config: Arc::clone(&self.search_config),
+            custom_filter: self.custom_filter,
+            stateful_filter: self.stateful_filter.clone(),
+            mime_filter: self.mime_filter.clone(),
+            content_filter: self.content_filter.clone(),
+            sample_threshold: self.sample_threshold,
+            seed: self.seed,
+            file_filter: self.file_filter,
+            starting_filesystems: self.starting_filesystems.clone(),
+            inode_cache: self.inode_cache.as_ref().map(|_| DashSet::new()),
+            errors: self.errors.as_ref().map(|_| Arc::default()),
+            stats: self.stats.as_ref().map(|_| Arc::default()),
+            follow_depth_limit: self
+                .follow_depth_limit
+                .as_ref()
+                .map(|limit| Arc::new(limit.reset())),
+            thread_count: self.thread_count,
+            custom_ignore_matchers: self.custom_ignore_matchers.clone(),
+            getdents_buffer_size: self.getdents_buffer_size,
+            strategy: self.strategy,
+            max_results: self.max_results,
+            max_total_size: self.max_total_size,
+            follow_mounts: self.follow_mounts,
+            virtual_devices: self.virtual_devices.clone(),
+            skip_pseudo_fs: self.skip_pseudo_fs,
+            prune: self.prune,
+            prune_dirs: self.prune_dirs.clone(),
+            max_symlink_depth: self.max_symlink_depth,
+            max_open_fds: self.max_open_fds,
+            max_queued_dirs: self.max_queued_dirs,
+            timeout: self.timeout,
+            projection: self.projection,
+            flush_policy: self.flush_policy,
+            backend: self.backend,
+            cancellation: self.cancellation.clone(),
+            #[cfg(feature = "rayon")]
+            thread_pool: self.thread_pool.clone(),
+        }
+    }
 }
 
 /// Maximum size of a result batch before flushing to the receiver.
 const RESULT_BATCH_LIMIT: usize = 256; //TODO TEST DIFFERENT VALUES FOR THIS (256 seems to perform best?)
 /// Channel capacity multiplier for result buffering.
 const RESULT_CHANNEL_FACTOR: usize = 2;
+/// How often a worker waiting on a full channel checks whether the traversal has been cancelled.
+pub(super) const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ A result budget shared between all worker threads, a number of results and/or their total size.
+
+ Each emission reserves its share *before* the entry is batched, so the budget is never overrun
+ regardless of how many threads race on the final few slots.
+*/
+pub(super) struct ResultLimit {
+    emitted: AtomicUsize,
+    max: Option<usize>,
+    bytes: AtomicU64,
+    max_bytes: Option<u64>,
+}
+
+impl ResultLimit {
+    const fn new(max: Option<usize>, max_bytes: Option<u64>) -> Self {
+        Self {
+            emitted: AtomicUsize::new(0),
+            max,
+            bytes: AtomicU64::new(0),
+            max_bytes,
+        }
+    }
+
+    /// Reserves a slot for one result, returning false once the budget is exhausted.
+    ///
+    /// Regular files count their size towards the byte budget, one which doesn't fit exhausts it.
+    #[inline]
+    pub(super) fn try_reserve(&self, entry: &DirEntry) -> bool {
+        // Relaxed is fine, we only need the totals themselves to be consistent (RMW operations are totally ordered)
+        self.max.is_none_or(|max| {
+            self.emitted
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    (count < max).then_some(count + 1)
+                })
+                .is_ok()
+        }) && self.max_bytes.is_none_or(|max_bytes| {
+            let size = if entry.is_regular_file() {
+                entry.file_size().unwrap_or(0)
+            } else {
+                0
+            };
+            self.bytes
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                    total.checked_add(size).filter(|&total| total <= max_bytes)
+                })
+                .is_ok()
+        })
+    }
+}
+
+/// What a [`BatchSender`] does with a batch that doesn't fit in the channel, see [`FlushPolicy`]
+enum Overflow<T> {
+    Block,
+    /// A handle on the receiving end, used to evict the oldest batch
+    DropOldest(Receiver<Vec<T>>),
+    /// Shared between every worker, so batches are read back in the order they were sent
+    Spill(Arc<Mutex<SpillFile>>),
+}
+
+impl<T> Clone for Overflow<T> {
+    fn clone(&self) -> Self {
+        match *self {
+            Self::Block => Self::Block,
+            Self::DropOldest(ref receiver) => Self::DropOldest(receiver.clone()),
+            Self::Spill(ref spill) => Self::Spill(Arc::clone(spill)),
+        }
+    }
+}
 
 /// Wrapper that sends batches of items at once over a channel.
-struct BatchSender {
-    items: Vec<DirEntry>,
-    tx: Sender<Vec<DirEntry>>,
+/// Entries are converted to `T` (eg a [`ProjectedEntry`]) as they are batched.
+struct BatchSender<T: FromEntry = DirEntry> {
+    items: Vec<T>,
+    tx: Sender<Vec<T>>,
     limit: usize,
+    result_limit: Option<Arc<ResultLimit>>,
+    fields: Fields,
+    overflow: Overflow<T>,
+    cancellation: Option<CancellationToken>,
 }
 
-impl BatchSender {
-    fn new(tx: Sender<Vec<DirEntry>>, limit: usize) -> Self {
+impl<T: FromEntry> BatchSender<T> {
+    fn new(
+        tx: Sender<Vec<T>>,
+        limit: usize,
+        result_limit: Option<Arc<ResultLimit>>,
+        fields: Fields,
+        overflow: Overflow<T>,
+        cancellation: Option<CancellationToken>,
+    ) -> Self {
         Self {
             items: Vec::with_capacity(limit),
             tx,
             limit,
+            result_limit,
+            fields,
+            overflow,
+            cancellation,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Waits for room in the channel, giving up if the traversal is cancelled in the meantime
+    fn send_blocking(&self, mut batch: Vec<T>) -> Result<(), SendError<Vec<T>>> {
+        if self.cancellation.is_none() {
+            return self.tx.send(batch);
+        }
+
+        loop {
+            match self.tx.send_timeout(batch, CANCELLATION_POLL_INTERVAL) {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Timeout(returned)) if !self.is_cancelled() => {
+                    batch = returned
+                }
+                Err(error) => return Err(SendError(error.into_inner())),
+            }
+        }
+    }
+
+    /// Sends a full batch, applying the overflow policy if the channel is full
+    fn send_batch(&self, mut batch: Vec<T>) -> Result<(), SendError<Vec<T>>> {
+        match self.overflow {
+            Overflow::Block => self.send_blocking(batch),
+            Overflow::DropOldest(ref receiver) => loop {
+                match self.tx.try_send(batch) {
+                    Ok(()) => return Ok(()),
+                    Err(TrySendError::Full(returned)) => {
+                        // The consumer may have emptied the channel in the meantime, which is fine
+                        let _ = receiver.try_recv();
+                        batch = returned;
+                    }
+                    Err(TrySendError::Disconnected(returned)) => return Err(SendError(returned)),
+                }
+            },
+            Overflow::Spill(ref spill) => {
+                let mut spill = spill.lock().unwrap_or_else(PoisonError::into_inner);
+                self.drain_spill(&mut spill, false)?;
+
+                if spill.is_empty() {
+                    match self.tx.try_send(batch) {
+                        Ok(()) => return Ok(()),
+                        Err(TrySendError::Full(returned)) => batch = returned,
+                        Err(TrySendError::Disconnected(returned)) => {
+                            return Err(SendError(returned));
+                        }
+                    }
+                }
+
+                if spill.push(&batch).is_err() {
+                    return self.send_blocking(batch);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Hands spilled batches to the consumer oldest first, only waiting for room if `block` is set
+    fn drain_spill(&self, spill: &mut SpillFile, block: bool) -> Result<(), SendError<Vec<T>>> {
+        loop {
+            let (batch, len) = match spill.front() {
+                Ok(Some(front)) => front,
+                Ok(None) => return Ok(()),
+                // Nothing sensible can be done with a spill file we can't read back
+                Err(_) => {
+                    spill.clear();
+                    return Ok(());
+                }
+            };
+
+            if block {
+                self.send_blocking(batch)?;
+            } else {
+                match self.tx.try_send(batch) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => return Ok(()),
+                    Err(TrySendError::Disconnected(returned)) => return Err(SendError(returned)),
+                }
+            }
+            spill.advance(len);
         }
     }
+}
+/// Returned by a [`Sink`] once the traversal should stop (eg the receiver hung up)
+pub(super) struct Stopped;
+
+/// Where the worker threads put results, either a channel ([`BatchSender`]) or a closure ([`Visitor`])
+pub(super) trait Sink {
+    /// Whether directories are handed to [`enter`](Self::enter) before they're read, and matching ones aren't
+    /// sent once they have been
+    const ENTERS_DIRECTORIES: bool = false;
+
+    /// Whether results are sent with [`send_at`](Self::send_at), keeping a handle to their parent directory
+    const WANTS_PARENT: bool = false;
+
+    /// Whether results are unwanted (only the directories read matter), so entries aren't filtered or sent
+    const DISCARDS_RESULTS: bool = false;
+
+    fn send(&mut self, entry: DirEntry) -> Result<(), Stopped>;
+
+    /// Sends a result along with its parent directory, if a handle to it could be kept
+    fn send_at(
+        &mut self,
+        entry: DirEntry,
+        _parent: Option<&Arc<DirHandle>>,
+    ) -> Result<(), Stopped> {
+        self.send(entry)
+    }
+
+    /// Hands over anything buffered
+    fn flush(&mut self) -> Result<(), Stopped>;
+
+    /// Called before `dir` is read, whether or not it matches, `is_result` being whether it does (and so isn't sent)
+    fn enter(&mut self, _dir: &DirEntry, _is_result: bool) -> ControlFlow {
+        ControlFlow::Continue
+    }
+
+    /// Called once `dir` has been opened, before any of its entries are read
+    fn opened(&mut self, _dir: &DirEntry) {}
 
-    fn send(&mut self, item: DirEntry) -> Result<(), SendError<Vec<DirEntry>>> {
-        self.items.push(item);
+    /// Called when a worker takes `dir` from the queue, before anything else is done with it
+    fn begin(&mut self, _dir: &DirEntry) {}
+
+    /// Called when `dir` is queued to be read, by the worker reading its parent
+    fn queued(&mut self, _dir: &DirEntry) {}
+
+    /// Called once the directory passed to [`begin`](Self::begin) is done with, whether or not it could be read
+    fn end(&mut self) -> Result<(), Stopped> {
+        Ok(())
+    }
+}
+
+impl<T: FromEntry> BatchSender<T> {
+    /// Reserves room for `entry` in the result limit, stopping once it has been reached
+    fn reserve(&self, entry: &DirEntry) -> Result<(), Stopped> {
+        match self.result_limit.as_ref() {
+            Some(result_limit) if !result_limit.try_reserve(entry) => Err(Stopped),
+            _ => Ok(()),
+        }
+    }
+
+    /// Batches an item to be sent, stopping once the receiver has hung up
+    fn push(&mut self, convert: impl FnOnce(Fields) -> T) -> Result<(), Stopped> {
+        self.items.push(convert(self.fields));
         if self.items.len() >= self.limit {
             let batch = mem::take(&mut self.items);
-            self.tx.send(batch)?;
+            self.send_batch(batch).map_err(|_| Stopped)?;
             self.items = Vec::with_capacity(self.limit);
         }
 
         Ok(())
     }
+}
+
+impl<T: FromEntry> Sink for BatchSender<T> {
+    const WANTS_PARENT: bool = T::WANTS_PARENT;
+
+    fn send(&mut self, item: DirEntry) -> Result<(), Stopped> {
+        self.reserve(&item)?;
+        self.push(|fields| T::from_entry(item, fields))
+    }
+
+    fn send_at(&mut self, item: DirEntry, parent: Option<&Arc<DirHandle>>) -> Result<(), Stopped> {
+        self.reserve(&item)?;
+        self.push(|fields| T::from_entry_at(item, fields, parent.cloned()))
+    }
 
-    fn flush(&mut self) -> Result<(), SendError<Vec<DirEntry>>> {
+    fn flush(&mut self) -> Result<(), Stopped> {
         if self.items.is_empty() {
             return Ok(());
         }
 
         let batch = mem::take(&mut self.items);
-        self.tx.send(batch)?;
+        self.send_batch(batch).map_err(|_| Stopped)?;
         self.items = Vec::with_capacity(self.limit);
         Ok(())
     }
 }
-// on drop, we need to flush the buffers.
-impl Drop for BatchSender {
+
+/// A [`Sink`] calling a closure on each result, see [`Finder::visit`]
+struct Visitor<'visit, F> {
+    visit: &'visit F,
+    /// Set once any thread's closure returns [`ControlFlow::Stop`], so no more calls are made
+    stopped: &'visit AtomicBool,
+    result_limit: Option<&'visit ResultLimit>,
+    /// Whether directories which don't match are visited too (so they can be pruned), or only results
+    every_directory: bool,
+}
+
+impl<F: Fn(&DirEntry) -> ControlFlow> Visitor<'_, F> {
+    /// Calls the closure on `entry`, counting it towards the result limit if it's a result
+    fn call(&self, entry: &DirEntry, is_result: bool) -> ControlFlow {
+        if self.stopped.load(Ordering::Relaxed)
+            || is_result
+                && self
+                    .result_limit
+                    .is_some_and(|result_limit| !result_limit.try_reserve(entry))
+        {
+            return ControlFlow::Stop;
+        }
+
+        let flow = (self.visit)(entry);
+        if flow == ControlFlow::Stop {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        flow
+    }
+}
+
+impl<F: Fn(&DirEntry) -> ControlFlow> Sink for Visitor<'_, F> {
+    const ENTERS_DIRECTORIES: bool = true;
+
+    fn send(&mut self, entry: DirEntry) -> Result<(), Stopped> {
+        match self.call(&entry, true) {
+            ControlFlow::Stop => Err(Stopped),
+            ControlFlow::Continue | ControlFlow::SkipSubtree => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Stopped> {
+        Ok(())
+    }
+
+    fn enter(&mut self, dir: &DirEntry, is_result: bool) -> ControlFlow {
+        if !is_result && !self.every_directory {
+            return ControlFlow::Continue;
+        }
+        self.call(dir, is_result)
+    }
+}
+
+/// A [`Sink`] counting each result in the [`TraversalStats`] before handing it on
+struct Recorder<'stats, S> {
+    inner: &'stats mut S,
+    stats: &'stats TraversalStats,
+}
+
+impl<S: Sink> Sink for Recorder<'_, S> {
+    const ENTERS_DIRECTORIES: bool = S::ENTERS_DIRECTORIES;
+    const WANTS_PARENT: bool = S::WANTS_PARENT;
+    const DISCARDS_RESULTS: bool = S::DISCARDS_RESULTS;
+
+    fn send(&mut self, entry: DirEntry) -> Result<(), Stopped> {
+        self.stats.record(&entry);
+        self.inner.send(entry)
+    }
+
+    fn send_at(&mut self, entry: DirEntry, parent: Option<&Arc<DirHandle>>) -> Result<(), Stopped> {
+        self.stats.record(&entry);
+        self.inner.send_at(entry, parent)
+    }
+
+    fn flush(&mut self) -> Result<(), Stopped> {
+        self.inner.flush()
+    }
+
+    fn enter(&mut self, dir: &DirEntry, is_result: bool) -> ControlFlow {
+        if is_result {
+            self.stats.record(dir);
+        }
+        self.inner.enter(dir, is_result)
+    }
+
+    fn opened(&mut self, dir: &DirEntry) {
+        self.inner.opened(dir);
+    }
+
+    fn begin(&mut self, dir: &DirEntry) {
+        self.inner.begin(dir);
+    }
+
+    fn queued(&mut self, dir: &DirEntry) {
+        self.inner.queued(dir);
+    }
+
+    fn end(&mut self) -> Result<(), Stopped> {
+        self.inner.end()
+    }
+}
+
+// on drop, we need to flush the buffers (including anything this worker spilled).
+impl<T: FromEntry> Drop for BatchSender<T> {
     fn drop(&mut self) {
-        if self.flush().is_err() {}
+        // Whatever is left over isn't wanted any more
+        if self.is_cancelled() {
+            return;
+        }
+        let _ = self.flush();
+        if let Overflow::Spill(ref spill) = self.overflow {
+            let mut spill = spill.lock().unwrap_or_else(PoisonError::into_inner);
+            let _ = self.drain_spill(&mut spill, true);
+        }
     }
 }
 
@@ -128,16 +626,172 @@ impl Drop for PendingGuard<'_> {
     }
 }
 
+/// An open directory, read by the chosen [`Backend`]
+enum DirReader<N> {
+    Native(N),
+    Portable(PortableReadDir),
+}
+
+impl<N: Iterator<Item = DirEntry>> Iterator for DirReader<N> {
+    type Item = DirEntry;
+
+    #[inline]
+    fn next(&mut self) -> Option<DirEntry> {
+        match *self {
+            Self::Native(ref mut native) => native.next(),
+            Self::Portable(ref mut portable) => portable.next(),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct WorkItem {
     dir: DirEntry,
     ignore_ctx: Arc<IgnoreContext>,
+    /// The parent directory's node, only used for [`TraversalStrategy::PostOrder`]
+    postorder_parent: Option<Arc<PostOrderNode>>,
+    /// The open parent directory, `dir` is opened relative to it when present
+    parent_handle: Option<Arc<DirHandle>>,
+    /// Directory symlinks followed on the way to `dir` (including `dir` itself)
+    symlinks: u32,
 }
 
-struct IgnoreContext {
+/**
+ An open directory whose subdirectories are opened relative to it with `openat`.
+
+ Opening by name saves the kernel resolving every component of the full path again, and keeps working
+ once paths grow past `PATH_MAX`. Entries still carry their full path (which is only ever extended by
+ one name per entry), so only the open itself is relative.
+
+ Every queued directory holding its parent open could exhaust the file descriptor limit on very wide
+ trees, so handles are only kept while the process can spare them (and the traversal's own
+ [`max_open_fds`](crate::walk::FinderBuilder::max_open_fds) allows) and directories fall back to being
+ opened by their full path otherwise.
+*/
+#[derive(Debug)]
+pub(super) struct DirHandle(pub(super) FileDes, Option<Arc<AtomicIsize>>);
+
+impl DirHandle {
+    /**
+    Shares the descriptor of a directory being read (keeping it open), or returns `None` if no descriptors can be
+    spared, from the process or from the traversal's `budget` if it has one
+    */
+    fn share(fd: &FileDes, budget: Option<&Arc<AtomicIsize>>) -> Option<Arc<Self>> {
+        if !Self::take(Self::budget()) {
+            return None;
+        }
+        if let Some(budget) = budget
+            && !Self::take(budget)
+        {
+            Self::budget().fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(Arc::new(Self(fd.clone(), budget.cloned())))
+    }
+
+    /// Takes a descriptor from `budget`, returning false if there are none left
+    fn take(budget: &AtomicIsize) -> bool {
+        if budget.fetch_sub(1, Ordering::Relaxed) <= 0 {
+            budget.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Returns the handle in `slot`, sharing `fd` into it the first time it's asked for
+    fn shared<'slot>(
+        slot: &'slot mut Option<Option<Arc<Self>>>,
+        fd: Option<&FileDes>,
+        ctx: &WorkerContext<'_>,
+    ) -> Option<&'slot Arc<Self>> {
+        slot.get_or_insert_with(|| fd.and_then(|fd| Self::share(fd, ctx.handles)))
+            .as_ref()
+    }
+
+    /// Descriptors which may still be held by handles, shared by every traversal as the limit is per process
+    fn budget() -> &'static AtomicIsize {
+        static BUDGET: OnceLock<AtomicIsize> = OnceLock::new();
+        BUDGET.get_or_init(|| {
+            let mut limit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            // SAFETY: `limit` is a valid pointer to write to
+            let soft_limit = if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &raw mut limit) } == 0
+            {
+                limit.rlim_cur
+            } else {
+                1024
+            };
+            // Leave half for reading directories and whatever else the process is doing
+            AtomicIsize::new(isize::try_from(soft_limit / 2).unwrap_or(isize::MAX))
+        })
+    }
+}
+
+impl Drop for DirHandle {
+    fn drop(&mut self) {
+        // The descriptor itself closes with its last clone, which may be this one
+        Self::budget().fetch_add(1, Ordering::Relaxed);
+        if let Some(budget) = self.1.as_deref() {
+            budget.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/**
+ Tracks how many subdirectories of a directory are still being walked, for postorder traversal.
+
+ The count starts at 1 (held by the worker processing the directory itself) and is incremented
+ for every subdirectory enqueued. Whoever decrements it to 0 emits the directory and then
+ releases its parent in turn.
+*/
+struct PostOrderNode {
+    /// The directory to emit once every descendant has been emitted (if it passed the filters)
+    dir: Mutex<Option<DirEntry>>,
+    remaining: AtomicUsize,
+    parent: Option<Arc<PostOrderNode>>,
+}
+
+impl PostOrderNode {
+    fn new(parent: Option<Arc<Self>>) -> Arc<Self> {
+        Arc::new(Self {
+            dir: Mutex::new(None),
+            remaining: AtomicUsize::new(1),
+            parent,
+        })
+    }
+
+    /// Releases one hold on `node`, emitting it (and any ancestors) whose subtrees are now complete.
+    fn complete<S: Sink>(mut node: Option<Arc<Self>>, sender: &mut S, ctx: &WorkerContext<'_>) {
+        while let Some(current) = node.take() {
+            // Anything this thread has buffered may be a descendant of `current`, it must reach the
+            // channel before another thread is allowed to emit the ancestor.
+            if sender.flush().is_err() {
+                ctx.shutdown_flag.store(true, Ordering::Relaxed);
+            }
+
+            if current.remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+                return;
+            }
+
+            let finished = current.dir.lock().ok().and_then(|mut dir| dir.take());
+            if let Some(dir) = finished
+                && sender.send(dir).is_err()
+            {
+                ctx.shutdown_flag.store(true, Ordering::Relaxed);
+            }
+            node.clone_from(&current.parent);
+        }
+    }
+}
+
+pub(super) struct IgnoreContext {
     parent: Option<Arc<IgnoreContext>>,
     matcher: Option<Arc<Gitignore>>,
     repo_active: bool,
+    /// Whether this node or any ancestor has a matcher, lets us skip walking the chain entirely
+    has_matchers: bool,
 }
 
 impl IgnoreContext {
@@ -146,11 +800,13 @@ impl IgnoreContext {
             parent: None,
             matcher: None,
             repo_active: false,
+            has_matchers: false,
         })
     }
 
     fn child(parent: Arc<Self>, matcher: Option<Arc<Gitignore>>, repo_active: bool) -> Arc<Self> {
         Arc::new(Self {
+            has_matchers: parent.has_matchers || matcher.is_some(),
             parent: Some(parent),
             matcher,
             repo_active,
@@ -158,6 +814,10 @@ impl IgnoreContext {
     }
 }
 
+/// Ignore files which apply regardless of whether we're inside a git repository (same as `fd`).
+/// Later files take precedence over earlier ones (and over `.gitignore`).
+const IGNORE_FILE_NAMES: [&str; 2] = [".ignore", ".fdignore"];
+
 fn find_task(
     local: &Worker<WorkItem>,
     injector: &Injector<WorkItem>,
@@ -188,10 +848,56 @@ fn find_task(
         }
     }
 }
+/// The queues and counters shared by every worker thread of one traversal
+struct WorkQueue {
+    injector: Injector<WorkItem>,
+    stealers: Vec<Stealer<WorkItem>>,
+    pending: AtomicUsize,
+    shutdown_flag: AtomicBool,
+    /// Descriptors this traversal's handles may still hold, when it has a limit
+    handles: Option<Arc<AtomicIsize>>,
+    /// When the traversal runs out of time, if it has a timeout
+    deadline: Option<Instant>,
+    /// Set once the deadline has passed and been reported
+    timed_out: AtomicBool,
+    /// The directories of the next level, for [`TraversalStrategy::BreadthFirst`]
+    levels: Option<Levels>,
+}
+
+/// Holds back the directories of the next level until every directory of the current one has been read
+struct Levels {
+    next: Injector<WorkItem>,
+    /// Directories of the current level not read yet
+    remaining: AtomicUsize,
+}
+
+impl Levels {
+    /// Marks a directory of the current level as read, starting the next level if it was the last one
+    fn finish_one(&self, injector: &Injector<WorkItem>) {
+        if self.remaining.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        let mut level = Vec::new();
+        loop {
+            match self.next.steal() {
+                Steal::Success(item) => level.push(item),
+                Steal::Retry => {}
+                Steal::Empty => break,
+            }
+        }
+        // Counted before any of them can be taken, so none can be finished first
+        self.remaining.store(level.len(), Ordering::Release);
+        level.into_iter().for_each(|item| injector.push(item));
+    }
+}
+
 struct WorkerContext<'ctx> {
     local: &'ctx Worker<WorkItem>,
     pending: &'ctx AtomicUsize,
     shutdown_flag: &'ctx AtomicBool,
+    handles: Option<&'ctx Arc<AtomicIsize>>,
+    /// Where subdirectories wait for their level, instead of the local queue
+    next_level: Option<&'ctx Injector<WorkItem>>,
 }
 
 /// The Finder struct is used to find files in your filesystem
@@ -203,13 +909,78 @@ impl Finder {
         FinderBuilder::new(root)
     }
 
-    /// Returns a reference to the underlying root
+    /**
+    Create a new Finder instance searching several roots in one traversal.
+
+    The roots share the filters and the worker threads, and their directories are read interleaved.
+    Depths are counted from whichever root an entry is under. A root inside another (or repeated) is dropped
+    when the finder is built, as it's searched as part of that one, and no roots means the current directory.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let manifest = env!("CARGO_MANIFEST_DIR");
+    let src = format!("{manifest}/src");
+    let finder = Finder::init_multi([format!("{manifest}/benches"), src.clone(), format!("{src}/fs")])
+        .build()
+        .unwrap();
+    // `src/fs` is searched as part of `src`
+    assert_eq!(finder.roots().count(), 2);
+
+    let results: Vec<_> = finder.traverse().unwrap().collect();
+    assert!(results.iter().any(|entry| entry.as_bytes().ends_with(b"src/lib.rs")));
+    assert!(results.iter().any(|entry| entry.as_bytes().starts_with(format!("{manifest}/benches/").as_bytes())));
+    ```
+    */
+    #[must_use]
+    #[inline]
+    pub fn init_multi<I>(roots: I) -> FinderBuilder
+    where
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        let mut roots = roots.into_iter();
+        let first = roots.next();
+        let mut builder = FinderBuilder::new(first.as_ref().map_or(OsStr::new(""), AsRef::as_ref));
+        builder.extra_roots = roots.map(|root| root.as_ref().to_owned()).collect();
+        builder
+    }
+
+    /// Returns a reference to the underlying root, the first of them if there are several
     #[inline]
     #[must_use]
     pub const fn root_dir(&self) -> &OsStr {
         &self.root
     }
 
+    /// Returns every root searched, in the order given to [`init_multi`](Self::init_multi) (less any overlapping)
+    #[inline]
+    pub fn roots(&self) -> impl Iterator<Item = &OsStr> {
+        iter::once(&*self.root).chain(self.extra_roots.iter().map(|root| &**root))
+    }
+
+    /**
+    Returns the device ID of the filesystem the root is on (`st_dev`, following symlinks)
+
+    With [`same_filesystem`](crate::walk::FinderBuilder::same_filesystem) this is the device the traversal
+    stays on, recorded when the finder was built, otherwise the root is statted.
+
+    # Errors
+    Returns an error if the root can't be statted
+    */
+    #[inline]
+    pub fn root_device(&self) -> std::io::Result<u64> {
+        use std::os::unix::fs::MetadataExt as _;
+        self.starting_filesystems
+            .as_deref()
+            .and_then(<[u64]>::first)
+            .map_or_else(
+                || std::fs::metadata(self.root_dir()).map(|meta| meta.dev()),
+                |&device| Ok(device),
+            )
+    }
+
     /**
     Returns the collected errors from the traversal
 
@@ -228,9 +999,7 @@ impl Finder {
                     .iter()
                     .map(|te| TraversalError {
                         dir: te.dir.clone(),
-                        error: DirEntryError::IOError(FilesystemIOError::from_io_error(
-                            std::io::Error::other(te.error.to_string()),
-                        )),
+                        error: te.error.duplicate(),
                     })
                     .collect()
             })
@@ -243,6 +1012,45 @@ impl Finder {
         self.errors.clone()
     }
 
+    /**
+    Returns the totals of the traversal when [`FinderBuilder::collect_stats`] is enabled.
+
+    Take it before starting the traversal, the counts are complete once the traversal has finished.
+    */
+    #[inline]
+    #[must_use]
+    pub fn stats(&self) -> Option<Arc<TraversalStats>> {
+        self.stats.clone()
+    }
+
+    /**
+    Returns the depth limit applied because symlinks are followed without a maximum depth, see
+    [`FinderBuilder::follow_depth_limit`].
+
+    Take it before starting the traversal, check [`DepthLimit::was_hit`] once it has finished to warn that
+    the results may be incomplete.
+
+    # Examples
+    ```
+    use fdf::walk::{DEFAULT_FOLLOW_DEPTH_LIMIT, Finder};
+
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR")).follow_symlinks(true).build().unwrap();
+    let limit = finder.follow_depth_limit().unwrap();
+    assert_eq!(limit.limit(), DEFAULT_FOLLOW_DEPTH_LIMIT);
+    finder.traverse().unwrap().for_each(drop);
+    assert!(!limit.was_hit());
+
+    // An explicit maximum depth takes its place
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR")).follow_symlinks(true).max_depth(Some(2)).build().unwrap();
+    assert!(finder.follow_depth_limit().is_none());
+    ```
+    */
+    #[inline]
+    #[must_use]
+    pub fn follow_depth_limit(&self) -> Option<Arc<DepthLimit>> {
+        self.follow_depth_limit.clone()
+    }
+
     /**
     Traverse the directory tree starting from the root and return an iterator for the found entries.
 
@@ -269,13 +1077,417 @@ impl Finder {
     pub fn traverse(
         self,
     ) -> core::result::Result<impl Iterator<Item = DirEntry>, SearchConfigError> {
-        let thread_count = self.thread_count.get();
-        let result_buffer = thread_count.saturating_mul(RESULT_CHANNEL_FACTOR).max(1);
-        let (sender, receiver): (_, Receiver<Vec<DirEntry>>) = bounded(result_buffer);
-        let injector = Arc::new(Injector::new());
-        let pending = Arc::new(AtomicUsize::new(1));
-        let shutdown_flag = Arc::new(AtomicBool::new(false));
+        self.traverse_as::<DirEntry>(Fields::ALL)
+    }
+
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), yielding the directories which couldn't be read
+    alongside the results rather than only collecting them (see [`FinderBuilder::collect_errors`]).
+
+    Each error is handed over shortly after it happens, ahead of any later results, with the directory which
+    failed and (via [`TraversalError::raw_os_error`]) the errno. Errors are yielded whether or not
+    [`FinderBuilder::collect_errors`] is enabled, and aren't kept for [`errors`](Self::errors).
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let (mut found, mut failed) = (0, 0);
+    for result in Finder::init(env!("CARGO_MANIFEST_DIR")).build().unwrap().traverse_with_errors().unwrap() {
+        match result {
+            Ok(_) => found += 1,
+            Err(error) => {
+                eprintln!("{} (errno {:?})", error.path(), error.raw_os_error());
+                failed += 1;
+            }
+        }
+    }
+    assert!(found > 0);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[inline]
+    pub fn traverse_with_errors(
+        mut self,
+    ) -> core::result::Result<
+        impl Iterator<Item = core::result::Result<DirEntry, TraversalError>>,
+        SearchConfigError,
+    > {
+        let store = Arc::clone(self.errors.get_or_insert_default());
+        let mut results = self.traverse()?;
+        let mut pending = std::collections::VecDeque::new();
+        let mut finished = false;
+
+        Ok(core::iter::from_fn(move || {
+            loop {
+                if let Some(error) = pending.pop_front() {
+                    return Some(Err(error));
+                }
+                // Taken between results, so an error never waits behind the rest of the traversal
+                pending.extend(mem::take(
+                    &mut *store.lock().unwrap_or_else(PoisonError::into_inner),
+                ));
+                if !pending.is_empty() {
+                    continue;
+                }
+                if finished {
+                    return None;
+                }
+                match results.next() {
+                    Some(entry) => return Some(Ok(entry)),
+                    // The workers are done, so one last look for errors
+                    None => finished = true,
+                }
+            }
+        }))
+    }
+
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), but yields compact records holding only the
+    fields set with [`FinderBuilder::project`].
+
+    This is cheaper when the consumer only needs counts or sizes, as paths are dropped on the worker threads
+    rather than being sent through the result channel.
+
+    # Examples
+    ```
+    use fdf::walk::{Fields, Finder};
+
+    let total: u64 = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .project(Fields::SIZE)
+        .build()
+        .unwrap()
+        .traverse_projected()
+        .unwrap()
+        .filter(|entry| entry.file_type().is_regular_file())
+        .filter_map(|entry| entry.size())
+        .sum();
+    assert!(total > 0);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[inline]
+    pub fn traverse_projected(
+        self,
+    ) -> core::result::Result<impl Iterator<Item = ProjectedEntry>, SearchConfigError> {
+        let fields = self.projection;
+        self.traverse_as::<ProjectedEntry>(fields)
+    }
+
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), but hands out the batches themselves through a
+    channel whose bounds are chosen by the caller.
+
+    At most `channel_capacity` batches of `batch_size` entries wait in the channel, with at most one partially
+    filled batch per worker thread on top, so memory stays bounded however large the tree is.
+    Once the channel is full the workers block until the consumer catches up (a capacity of 0 hands every batch
+    over directly). Dropping the receiver stops the traversal.
+
+    # Examples
+    ```
+    use core::num::NonZeroUsize;
+    use fdf::walk::Finder;
+
+    let receiver = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .build()
+        .unwrap()
+        .traverse_streaming(NonZeroUsize::new(64).unwrap(), 4)
+        .unwrap();
+
+    for batch in receiver {
+        assert!(!batch.is_empty() && batch.len() <= 64);
+    }
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[inline]
+    pub fn traverse_streaming(
+        self,
+        batch_size: NonZeroUsize,
+        channel_capacity: usize,
+    ) -> core::result::Result<Receiver<Vec<DirEntry>>, SearchConfigError> {
+        self.spawn_workers(Fields::ALL, batch_size.get(), channel_capacity)
+    }
+
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), yielding the results in chunks of `chunk_size`
+    entries (the last may be smaller), for consumers doing heavy work on each chunk.
+
+    Batches are handed over through a rendezvous channel, so a worker with a full batch waits until the consumer
+    asks for more. At most one chunk per worker thread (plus one being assembled) is in flight, so a slow consumer
+    holds the walk back rather than results piling up in memory.
+
+    # Examples
+    ```
+    use core::num::NonZeroUsize;
+    use fdf::walk::Finder;
+
+    let chunks: Vec<_> = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .build()
+        .unwrap()
+        .traverse_chunks(NonZeroUsize::new(16).unwrap())
+        .unwrap()
+        .collect();
+
+    let (last, full) = chunks.split_last().unwrap();
+    assert!(full.iter().all(|chunk| chunk.len() == 16));
+    assert!(!last.is_empty() && last.len() <= 16);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[inline]
+    pub fn traverse_chunks(
+        self,
+        chunk_size: NonZeroUsize,
+    ) -> core::result::Result<Chunks, SearchConfigError> {
+        let chunk_size = chunk_size.get();
+        self.spawn_workers(Fields::ALL, chunk_size, 0)
+            .map(|receiver| Chunks::new(receiver, chunk_size))
+    }
+
+    pub(super) fn traverse_as<T: FromEntry>(
+        self,
+        fields: Fields,
+    ) -> core::result::Result<impl Iterator<Item = T>, SearchConfigError> {
+        let result_buffer = self
+            .thread_count
+            .get()
+            .saturating_mul(RESULT_CHANNEL_FACTOR)
+            .max(1);
+        Ok(self
+            .spawn_workers(fields, RESULT_BATCH_LIMIT, result_buffer)?
+            .into_iter()
+            .flatten())
+    }
+
+    /// Starts the worker threads, which send batches of at most `batch_size` results through a channel holding `channel_capacity` batches
+    fn spawn_workers<T: FromEntry>(
+        self,
+        fields: Fields,
+        batch_size: usize,
+        channel_capacity: usize,
+    ) -> core::result::Result<Receiver<Vec<T>>, SearchConfigError> {
+        // Evicting or spilling needs somewhere for a batch to wait, which a rendezvous channel doesn't have
+        let channel_capacity = match self.flush_policy {
+            FlushPolicy::Block => channel_capacity,
+            FlushPolicy::DropOldest | FlushPolicy::SpillToDisk => channel_capacity.max(1),
+        };
+        let (sender, receiver): (_, Receiver<Vec<T>>) = bounded(channel_capacity);
+        let overflow = match self.flush_policy {
+            FlushPolicy::Block => Overflow::Block,
+            FlushPolicy::DropOldest => Overflow::DropOldest(receiver.clone()),
+            FlushPolicy::SpillToDisk => SpillFile::create().map_or(Overflow::Block, |spill| {
+                Overflow::Spill(Arc::new(Mutex::new(spill)))
+            }),
+        };
+        let result_limit = self.result_limit().map(Arc::new);
+        let cancellation = self.cancellation.clone();
+        self.spawn_sinks(|| {
+            BatchSender::new(
+                sender.clone(),
+                batch_size,
+                result_limit.clone(),
+                fields,
+                overflow.clone(),
+                cancellation.clone(),
+            )
+        })?;
+
+        Ok(receiver)
+    }
+
+    /// Starts the worker threads without waiting for them, each putting its results in a sink from `make_sink`
+    pub(super) fn spawn_sinks<S: Sink + Send + 'static>(
+        self,
+        mut make_sink: impl FnMut() -> S,
+    ) -> core::result::Result<(), SearchConfigError> {
+        let (queue, workers) = self.work_queue()?;
+        let queue = Arc::new(queue);
+        let finder = Arc::new(self);
+
+        for (index, worker) in workers.into_iter().enumerate() {
+            let finder_shared = Arc::clone(&finder);
+            let queue_shared = Arc::clone(&queue);
+            let mut sink = make_sink();
+
+            finder.spawn(move || {
+                finder_shared.run_worker(index, &worker, &queue_shared, &mut sink);
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Runs `work` on the thread pool if there is one, or a thread of its own otherwise
+    fn spawn(&self, work: impl FnOnce() + Send + 'static) {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = self.thread_pool.as_deref() {
+            pool.spawn(work);
+            return;
+        }
+        thread::spawn(work);
+    }
+
+    /**
+    Walks the filesystem, calling `visit` on every result directly from the worker threads instead of sending
+    the results through a channel.
+
+    Directories are visited *before* they're read (whatever the [`TraversalStrategy`]), so returning
+    [`ControlFlow::SkipSubtree`] prunes them, which makes this a good fit for interactive browsers.
+    Only results are visited, see [`visit_all`](Self::visit_all) to prune directories which don't match.
+    Returning [`ControlFlow::Stop`] ends the whole traversal, although calls already in progress on other
+    threads still complete. The closure runs on several threads at once, so any state it keeps needs to be
+    shared (eg behind an atomic or a `Mutex`).
+
+    This returns once the traversal is complete.
+
+    # Examples
+    ```
+    use fdf::walk::{ControlFlow, Finder};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let files = AtomicUsize::new(0);
+    Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .build()
+        .unwrap()
+        .visit(|entry| {
+            if entry.file_name() == b"target" {
+                return ControlFlow::SkipSubtree;
+            }
+            if entry.is_regular_file() {
+                files.fetch_add(1, Ordering::Relaxed);
+            }
+            ControlFlow::Continue
+        })
+        .unwrap();
+    assert!(files.load(Ordering::Relaxed) > 0);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn visit<F>(self, visit: F) -> core::result::Result<(), SearchConfigError>
+    where
+        F: Fn(&DirEntry) -> ControlFlow + Sync,
+    {
+        self.visit_with(&visit, false)
+    }
+
+    /**
+    Like [`visit`](Self::visit), but also calling `visit` on every directory about to be read, so that any of them
+    can be pruned.
+
+    A directory which doesn't match the pattern or filters is still visited before it's read (it isn't a result,
+    so it doesn't count towards [`max_results`](crate::walk::FinderBuilder::max_results)). Other entries are
+    only visited if they match, as is the root.
+
+    # Examples
+    ```
+    use fdf::walk::{ControlFlow, Finder};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let sources = AtomicUsize::new(0);
+    Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .pattern(r"\.rs$")
+        .build()
+        .unwrap()
+        .visit_all(|entry| {
+            // `target` doesn't match the pattern, but is pruned all the same
+            if entry.is_dir() {
+                return if entry.file_name() == b"target" {
+                    ControlFlow::SkipSubtree
+                } else {
+                    ControlFlow::Continue
+                };
+            }
+            sources.fetch_add(1, Ordering::Relaxed);
+            ControlFlow::Continue
+        })
+        .unwrap();
+    assert!(sources.load(Ordering::Relaxed) > 0);
+    ```
 
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn visit_all<F>(self, visit: F) -> core::result::Result<(), SearchConfigError>
+    where
+        F: Fn(&DirEntry) -> ControlFlow + Sync,
+    {
+        self.visit_with(&visit, true)
+    }
+
+    fn visit_with<F>(
+        self,
+        visit: &F,
+        every_directory: bool,
+    ) -> core::result::Result<(), SearchConfigError>
+    where
+        F: Fn(&DirEntry) -> ControlFlow + Sync,
+    {
+        let result_limit = self.result_limit();
+        let stopped = AtomicBool::new(false);
+
+        self.walk_scoped(|| Visitor {
+            visit,
+            stopped: &stopped,
+            result_limit: result_limit.as_ref(),
+            every_directory,
+        })
+    }
+
+    /// The budget for this traversal's results, if there is one
+    pub(super) const fn result_limit(&self) -> Option<ResultLimit> {
+        if self.max_results.is_none() && self.max_total_size.is_none() {
+            return None;
+        }
+        Some(ResultLimit::new(self.max_results, self.max_total_size))
+    }
+
+    /// Walks the filesystem on scoped worker threads, each putting its results in a sink from `make_sink`
+    pub(super) fn walk_scoped<S: Sink + Send>(
+        &self,
+        mut make_sink: impl FnMut() -> S,
+    ) -> core::result::Result<(), SearchConfigError> {
+        let (queue, workers) = self.work_queue()?;
+
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = self.thread_pool.as_deref() {
+            pool.in_place_scope(|scope| {
+                for (index, worker) in workers.into_iter().enumerate() {
+                    let mut sink = make_sink();
+                    let queue = &queue;
+                    scope.spawn(move |_| self.run_worker(index, &worker, queue, &mut sink));
+                }
+            });
+            return Ok(());
+        }
+
+        thread::scope(|scope| {
+            for (index, worker) in workers.into_iter().enumerate() {
+                let mut sink = make_sink();
+                let queue = &queue;
+                scope.spawn(move || self.run_worker(index, &worker, queue, &mut sink));
+            }
+        });
+        Ok(())
+    }
+
+    /// Creates the shared queue holding the root directory, along with one local queue per worker thread
+    fn work_queue(
+        &self,
+    ) -> core::result::Result<(WorkQueue, Vec<Worker<WorkItem>>), SearchConfigError> {
+        let thread_count = self.thread_count.get();
         let mut workers = Vec::with_capacity(thread_count);
         let mut stealers = Vec::with_capacity(thread_count);
         for _ in 0..thread_count {
@@ -283,70 +1495,174 @@ impl Finder {
             stealers.push(worker.stealer());
             workers.push(worker);
         }
-        let stealers_shared = Arc::new(stealers);
 
-        // Construct starting entry
-        let entry = DirEntry::new(self.root_dir()).map_err(SearchConfigError::TraversalError)?;
+        // Construct starting entries
+        let injector = Injector::new();
+        for (index, root) in self.roots().enumerate() {
+            let entry = DirEntry::new(root).map_err(SearchConfigError::TraversalError)?;
+            // Already statted when the finder was built, so the same filesystem check doesn't repeat it
+            entry.device.set(
+                self.starting_filesystems
+                    .as_deref()
+                    .and_then(|devices| devices.get(index).copied()),
+            );
+            if !entry.is_traversible() {
+                return Err(SearchConfigError::NotADirectory);
+            }
 
-        if entry.is_traversible() {
-            let finder = Arc::new(self);
             injector.push(WorkItem {
                 dir: entry,
-                ignore_ctx: finder.initial_ignore_context(),
+                ignore_ctx: self.initial_ignore_context(),
+                postorder_parent: None,
+                parent_handle: None,
+                symlinks: 0,
             });
+        }
+        let roots = 1 + self.extra_roots.len();
+
+        Ok((
+            WorkQueue {
+                injector,
+                stealers,
+                pending: AtomicUsize::new(roots),
+                shutdown_flag: AtomicBool::new(false),
+                // The rest are held by the workers while reading
+                handles: self.max_open_fds.map(|max| {
+                    let spare = max.get().saturating_sub(self.thread_count.get());
+                    Arc::new(AtomicIsize::new(
+                        isize::try_from(spare).unwrap_or(isize::MAX),
+                    ))
+                }),
+                deadline: self
+                    .timeout
+                    .and_then(|timeout| Instant::now().checked_add(timeout)),
+                timed_out: AtomicBool::new(false),
+                levels: (self.strategy == TraversalStrategy::BreadthFirst).then(|| Levels {
+                    next: Injector::new(),
+                    remaining: AtomicUsize::new(roots),
+                }),
+            },
+            workers,
+        ))
+    }
 
-            for (index, worker) in workers.into_iter().enumerate() {
-                let finder_shared = Arc::clone(&finder);
-                let sender_shared = sender.clone();
-                let pending_shared = Arc::clone(&pending);
-                let shutdown_flag_shared = Arc::clone(&shutdown_flag);
-                let injector_shared = Arc::clone(&injector);
-                let stealers_pool = Arc::clone(&stealers_shared);
-
-                thread::spawn(move || {
-                    let mut batch_sender = BatchSender::new(sender_shared, RESULT_BATCH_LIMIT);
-                    let mut local_stealers =
-                        Vec::with_capacity(stealers_pool.len().saturating_sub(1));
-                    for (idx, stealer) in stealers_pool.iter().enumerate() {
-                        if idx != index {
-                            local_stealers.push(stealer.clone());
-                        }
-                    }
+    /// Returns true once the traversal's deadline has passed, reporting it against the root the first time
+    fn has_timed_out(&self, queue: &WorkQueue) -> bool {
+        if queue
+            .deadline
+            .is_none_or(|deadline| Instant::now() < deadline)
+        {
+            return false;
+        }
+        if !queue.timed_out.swap(true, Ordering::Relaxed)
+            && let Ok(root) = DirEntry::new(self.root_dir())
+        {
+            self.record_error(root, DirEntryError::TimedOut);
+        }
+        true
+    }
 
-                    loop {
-                        if shutdown_flag_shared.load(Ordering::Relaxed)
-                            && worker.is_empty()
-                            && injector_shared.is_empty()
-                        {
-                            break;
-                        }
+    /// Counts an error reading `dir` in the stats, and keeps it if errors are collected
+    fn record_error(&self, dir: DirEntry, error: DirEntryError) {
+        if let Some(stats) = self.stats.as_deref() {
+            stats.record_error();
+        }
+        if let Some(errors_arc) = self.errors.as_ref() {
+            // This will only show errors if collect errors is enabled
+            // Generally I don't like this approach due to the locking it can cause
+            // However, errors are VERY small typically hence this create negligible issues.
+            if let Ok(mut errors) = errors_arc.lock() {
+                errors.push(TraversalError { dir, error });
+            }
+        }
+    }
 
-                        let Some(work_item) = find_task(&worker, &injector_shared, &local_stealers)
-                        else {
-                            if shutdown_flag_shared.load(Ordering::Relaxed) {
-                                break;
-                            }
-                            thread::yield_now();
-                            continue;
-                        };
-
-                        let _pending_guard =
-                            PendingGuard::new(&pending_shared, &shutdown_flag_shared);
-
-                        let ctx = WorkerContext {
-                            local: &worker,
-                            pending: &pending_shared,
-                            shutdown_flag: &shutdown_flag_shared,
-                        };
-
-                        finder_shared.process_directory(work_item, &mut batch_sender, &ctx);
-                    }
-                });
+    /// Returns true unless too many directories are queued to queue `dir` too, in which case that's recorded as its error
+    fn has_room_for(&self, dir: &DirEntry, ctx: &WorkerContext<'_>) -> bool {
+        if self
+            .max_queued_dirs
+            .is_none_or(|max| ctx.pending.load(Ordering::Relaxed) < max.get())
+        {
+            return true;
+        }
+        self.record_error(dir.clone(), DirEntryError::QueueFull);
+        false
+    }
+
+    /// Processes directories on the current thread until the traversal is finished
+    fn run_worker<S: Sink>(
+        &self,
+        index: usize,
+        worker: &Worker<WorkItem>,
+        queue: &WorkQueue,
+        sink: &mut S,
+    ) {
+        // Decided once per worker, so traversals without stats don't pay for them per entry
+        match self.stats.as_deref() {
+            Some(stats) => self.work(index, worker, queue, &mut Recorder { inner: sink, stats }),
+            None => self.work(index, worker, queue, sink),
+        }
+    }
+
+    fn work<S: Sink>(
+        &self,
+        index: usize,
+        worker: &Worker<WorkItem>,
+        queue: &WorkQueue,
+        sink: &mut S,
+    ) {
+        let mut local_stealers = Vec::with_capacity(queue.stealers.len().saturating_sub(1));
+        for (idx, stealer) in queue.stealers.iter().enumerate() {
+            if idx != index {
+                local_stealers.push(stealer.clone());
             }
+        }
 
-            Ok(receiver.into_iter().flatten())
-        } else {
-            Err(SearchConfigError::NotADirectory)
+        loop {
+            // Other workers notice by themselves, so there's no need to flag a shutdown
+            if self.is_cancelled() || self.has_timed_out(queue) {
+                break;
+            }
+
+            if queue.shutdown_flag.load(Ordering::Relaxed)
+                && worker.is_empty()
+                && queue.injector.is_empty()
+            {
+                break;
+            }
+
+            let Some(work_item) = find_task(worker, &queue.injector, &local_stealers) else {
+                if queue.shutdown_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                // Hands over this level's results while waiting for the next one to start
+                if queue.levels.is_some() && sink.flush().is_err() {
+                    queue.shutdown_flag.store(true, Ordering::Relaxed);
+                }
+                thread::yield_now();
+                continue;
+            };
+
+            let _pending_guard = PendingGuard::new(&queue.pending, &queue.shutdown_flag);
+
+            // The queues only hold anything after a shutdown if the traversal was stopped early (eg the result
+            // limit was reached), so the remaining directories are dropped rather than read
+            if queue.shutdown_flag.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let ctx = WorkerContext {
+                local: worker,
+                pending: &queue.pending,
+                shutdown_flag: &queue.shutdown_flag,
+                handles: queue.handles.as_ref(),
+                next_level: queue.levels.as_ref().map(|levels| &levels.next),
+            };
+
+            self.process_directory(work_item, sink, &ctx);
+            if let Some(levels) = queue.levels.as_ref() {
+                levels.finish_one(&queue.injector);
+            }
         }
     }
 
@@ -373,16 +1689,94 @@ impl Finder {
         Ok(PrinterBuilder::new(self.traverse()?).errors(errors))
     }
 
+    /**
+    Works out how to print this finder's results relative to `base` rather than as found under the root,
+    for [`PrinterBuilder::relative_to`]. Passing the root itself strips it from every result.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let root = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+    let finder = Finder::init(root).build().unwrap();
+    let relative = finder.relative_output(concat!(env!("CARGO_MANIFEST_DIR"), "/benches")).unwrap();
+    // Prints eg `../src/lib.rs`
+    let printer = finder.build_printer().unwrap().relative_to(Some(relative));
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::IOError`] if the root or `base` can't be resolved, eg `base` doesn't exist.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn relative_output<P: AsRef<Path>>(
+        &self,
+        base: P,
+    ) -> core::result::Result<RelativePaths, SearchConfigError> {
+        Ok(RelativePaths::new(Path::new(self.root_dir()), base)?)
+    }
+
+    /**
+    Traverses the filesystem and prints every result to stdout, one per line or NUL separated.
+
+    This is shorthand for [`build_printer`](Self::build_printer) with only
+    [`null_terminated`](PrinterBuilder::null_terminated) set, paths are always written as raw bytes.
+
+    # Examples
+    ```no_run
+    use fdf::walk::Finder;
+
+    // Equivalent to `fdf -0 . /tmp | xargs -0 ls -ld`
+    Finder::init("/tmp").build().unwrap().print_results(true).unwrap();
+    ```
+
+    # Errors
+    Returns a [`SearchConfigError`] if traversal setup fails or stdout can't be written to.
+    */
+    #[allow(clippy::missing_inline_in_public_items)] // Don't bloat code gen.
+    pub fn print_results(
+        self,
+        null_terminated: bool,
+    ) -> core::result::Result<(), SearchConfigError> {
+        self.build_printer()?
+            .null_terminated(null_terminated)
+            .print()
+    }
+
+    /**
+    Traverses the filesystem and runs `template` for every result, see [`crate::exec`].
+
+    In [`ExecMode::PerResult`] commands run concurrently on as many threads as the traversal uses.
+
+    # Errors
+    Returns a [`SearchConfigError`] if traversal setup fails or a command could not be spawned.
+    */
+    #[allow(clippy::missing_inline_in_public_items)] // Don't bloat code gen.
+    pub fn for_each_exec(
+        self,
+        template: &CommandTemplate,
+        mode: ExecMode,
+    ) -> core::result::Result<ExecSummary, SearchConfigError> {
+        let threads = self.thread_count;
+        template.execute(self.traverse()?, mode, threads)
+    }
+
+    #[inline]
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
     /// Determines if a directory should be sent through the channel
     #[inline]
-    fn should_send_dir(&self, dir: &DirEntry) -> bool {
+    pub(super) fn should_send_dir(&self, dir: &DirEntry) -> bool {
         dir.depth() != 0 && self.file_filter(dir, None)
         // Don't send root
     }
 
     /// Determines if a directory should be traversed and caches the result
     #[inline]
-    fn should_traverse(&self, dir: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+    pub(super) fn should_traverse(&self, dir: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
         match dir.file_type {
             // Regular directory - always traversible
             FileType::Directory => true,
@@ -399,28 +1793,53 @@ impl Finder {
 
     /// Filters out hidden files if configured to do so
     #[inline]
-    const fn keep_hidden(&self, dir: &DirEntry) -> bool {
+    pub(super) fn keep_hidden(&self, dir: &DirEntry) -> bool {
         !self.search_config.hide_hidden || !dir.is_hidden()
         // Some efficient boolean short circuits here to avoid checking
     }
 
     /// Applies custom file filtering logic
     #[inline]
-    fn file_filter(&self, dir: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+    pub(super) fn file_filter(&self, dir: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
         (self.file_filter)(&self.search_config, dir, self.custom_filter, opt_fd)
+            && self
+                .sample_threshold
+                .is_none_or(|threshold| sample::sample_key(self.seed, dir.as_bytes()) <= threshold)
+            && self
+                .mime_filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches_at(dir, opt_fd))
+            && self
+                .content_filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches_at(dir, opt_fd))
+            && self
+                .stateful_filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches_at(dir, opt_fd))
     }
 
+    /// Parses the `.gitignore` (only inside a git repository), `.ignore` and `.fdignore` files in `base_dir`.
     #[inline]
-    fn parse_gitignore_file(base_dir: &Path) -> Option<Arc<Gitignore>> {
-        let ignore_file = base_dir.join(".gitignore");
+    fn parse_ignore_files(base_dir: &Path, repo_active: bool) -> Option<Arc<Gitignore>> {
         let mut builder = GitignoreBuilder::new(base_dir);
-        let _ = builder.add(ignore_file);
+        if repo_active {
+            let _ = builder.add(base_dir.join(".gitignore"));
+        }
+        for name in IGNORE_FILE_NAMES {
+            // Missing files just add nothing to the matcher
+            let _ = builder.add(base_dir.join(name));
+        }
 
-        builder.build().ok().map(Arc::new)
+        builder
+            .build()
+            .ok()
+            .filter(|matcher| !matcher.is_empty())
+            .map(Arc::new)
     }
 
     #[inline]
-    fn initial_ignore_context(&self) -> Arc<IgnoreContext> {
+    pub(super) fn initial_ignore_context(&self) -> Arc<IgnoreContext> {
         self.custom_ignore_matchers
             .iter()
             .fold(IgnoreContext::empty(), |ctx, matcher| {
@@ -429,7 +1848,7 @@ impl Finder {
     }
 
     #[inline]
-    fn build_ignore_context(
+    pub(super) fn build_ignore_context(
         &self,
         dir: &DirEntry,
         parent: Arc<IgnoreContext>,
@@ -448,20 +1867,14 @@ impl Finder {
                 .join(".git")
                 .symlink_metadata()
                 .is_ok_and(|x| x.is_dir());
-        let local_matcher = if repo_active {
-            Self::parse_gitignore_file(dir.as_path())
-        } else {
-            None
-        };
+        let local_matcher = Self::parse_ignore_files(dir.as_path(), repo_active);
 
         IgnoreContext::child(parent, local_matcher, repo_active)
     }
 
     #[inline]
-    fn is_gitignored(&self, dir: &DirEntry, ctx: &Arc<IgnoreContext>) -> bool {
-        if !self.search_config.respect_gitignore
-            || (!ctx.repo_active && self.custom_ignore_matchers.is_empty())
-        {
+    pub(super) fn is_gitignored(&self, dir: &DirEntry, ctx: &Arc<IgnoreContext>) -> bool {
+        if !self.search_config.respect_gitignore || !ctx.has_matchers {
             return false;
         }
 
@@ -487,6 +1900,14 @@ impl Finder {
         false
     }
 
+    /// Returns true if `device` is the filesystem of one of the roots, or the traversal isn't kept to them
+    #[inline]
+    fn is_starting_filesystem(&self, device: u64) -> bool {
+        self.starting_filesystems
+            .as_deref()
+            .is_none_or(|devices| devices.contains(&device))
+    }
+
     /**
     Advanced filtering for directories and symlinks with filesystem constraints.
 
@@ -494,85 +1915,109 @@ impl Finder {
     to prevent infinite loops and duplicate traversal.
     */
     #[inline]
-    fn directory_or_symlink_filter(&self, dir: &DirEntry) -> bool {
+    pub(super) fn directory_or_symlink_filter(&self, dir: &DirEntry) -> bool {
         // This is a beast of a function to read, sorry!
         match dir.file_type {
-        // Normal directories
-        FileType::Directory => {
-            self.inode_cache.as_ref().map_or_else(
-                || {
-                    // Fast path: only calls stat IFF self.starting_filesystem is Some
-                    debug_assert!(!self.search_config.follow_symlinks,"we expect follow symlinks to be disabled when following this path");
-
-
-                    self.starting_filesystem.is_none_or(|start_dev| {
-                        dir.get_stat()
-                            .is_ok_and(|statted| start_dev == access_stat!(statted, st_dev))
-                    })
-                },
-                |cache| {
-                    debug_assert!(self.search_config.follow_symlinks,"we expect follow symlinks to be enabled when following this path");
-
-
-                    dir.get_stat().is_ok_and(|stat| {
-                        // Check same filesystem if enabled
-                        self.starting_filesystem.is_none_or(|start_dev| start_dev == access_stat!(stat, st_dev)) &&
+            // Normal directories
+            FileType::Directory => {
+                self.inode_cache.as_ref().map_or_else(
+                    || {
+                        // Fast path: only calls stat IFF self.starting_filesystems is Some
+                        debug_assert!(
+                            !self.search_config.follow_symlinks,
+                            "we expect follow symlinks to be disabled when following this path"
+                        );
+
+                        self.starting_filesystems.is_none()
+                            || dir
+                                .dev()
+                                .is_ok_and(|device| self.is_starting_filesystem(device))
+                    },
+                    |cache| {
+                        debug_assert!(
+                            self.search_config.follow_symlinks,
+                            "we expect follow symlinks to be enabled when following this path"
+                        );
+
+                        dir.get_stat_caching_device().is_ok_and(|stat| {
+                            // Check same filesystem if enabled
+                            self.is_starting_filesystem(access_stat!(stat, st_dev)) &&
                         // Check if we've already traversed this inode
                         cache.insert((access_stat!(stat, st_dev), access_stat!(stat, st_ino)))
-                    })
-                },
-            )
-        }
-
-        // Symlinks that may point to directories
-        // self.search_config.follow_symlinks <=> inode_cache is some
-        FileType::Symlink
-            if self.inode_cache.as_ref().is_some_and(|cache| {
-                debug_assert!(self.search_config.follow_symlinks,"we expect follow symlinks to be enabled when following this path");
-
+                        })
+                    },
+                )
+            }
 
-                dir.get_stat().is_ok_and(|stat| {
-                    FileType::from_stat(&stat) == FileType::Directory &&
+            // Symlinks that may point to directories
+            // self.search_config.follow_symlinks <=> inode_cache is some
+            FileType::Symlink
+                if self.inode_cache.as_ref().is_some_and(|cache| {
+                    debug_assert!(
+                        self.search_config.follow_symlinks,
+                        "we expect follow symlinks to be enabled when following this path"
+                    );
+
+                    dir.get_stat_caching_device().is_ok_and(|stat| {
+                        FileType::from_stat(&stat) == FileType::Directory &&
                     // Check filesystem boundary
-                    self.starting_filesystem.is_none_or(|start_dev| start_dev == access_stat!(stat, st_dev)) &&
+                    self.is_starting_filesystem(access_stat!(stat, st_dev)) &&
                     // Check if we've already traversed this inode
                     cache.insert((access_stat!(stat, st_dev), access_stat!(stat, st_ino)))
-                })
-            }) =>
-        {
-            true
+                    })
+                }) =>
+            {
+                true
+            }
+
+            // All other file types (files, non-followed symlinks, etc.)
+            _ => false,
         }
+    }
+
+    #[inline]
+    pub(super) fn matches_ignore_path(&self, dir: &DirEntry) -> bool {
+        self.search_config.matches_ignore_path(dir.as_bytes())
+    }
 
-        // All other file types (files, non-followed symlinks, etc.)
-        _ => false,
+    /// Returns true if `dir` is below the root and on one of the virtual (or pseudo) filesystems being skipped, see
+    /// [`skip_virtual_fs`](crate::walk::FinderBuilder::skip_virtual_fs) and
+    /// [`skip_pseudo_fs`](crate::walk::FinderBuilder::skip_pseudo_fs), so it should not be read.
+    #[inline]
+    pub(super) fn is_virtual_fs(&self, dir: &DirEntry) -> bool {
+        dir.depth() != 0
+            && (self.virtual_devices.as_deref().is_some_and(|devices| {
+                dir.dev()
+                    .is_ok_and(|device| devices.binary_search(&device).is_ok())
+            }) || self.skip_pseudo_fs && dir.is_on_pseudo_fs())
     }
+
+    /// Returns true if `dir` is below the root and the [`prune_dirs`](crate::walk::FinderBuilder::prune_dirs) closure
+    /// rejects it, so it should not be read.
+    #[inline]
+    pub(super) fn is_pruned_dir(&self, dir: &DirEntry) -> bool {
+        dir.depth() != 0
+            && self
+                .prune_dirs
+                .as_ref()
+                .is_some_and(|prune| prune.matches_at(dir, None))
     }
 
+    /// Returns true unless `entry` is a symlink which would take a directory reached through `symlinks` of them past
+    /// the [`max_symlink_depth`](crate::walk::FinderBuilder::max_symlink_depth)
     #[inline]
-    fn matches_ignore_path(&self, dir: &DirEntry) -> bool {
-        self.search_config.matches_ignore_path(dir.as_bytes())
+    pub(super) fn within_symlink_depth(&self, entry: &DirEntry, symlinks: u32) -> bool {
+        !entry.is_symlink() || self.max_symlink_depth.is_none_or(|max| symlinks < max)
     }
 
+    /// Returns true if `dir` is at the depth limit and so should not be read.
     #[inline]
-    fn handle_depth_limit(
-        &self,
-        dir: &DirEntry,
-        should_send: bool,
-        sender: &mut BatchSender,
-        ctx: &WorkerContext<'_>,
-    ) -> bool {
-        if self
-            .search_config
+    pub(super) fn is_at_depth_limit(&self, dir: &DirEntry) -> bool {
+        self.search_config
             .depth
             .is_some_and(|depth| dir.depth >= depth.get())
-        {
-            if should_send && sender.send(dir.clone()).is_err() {
-                ctx.shutdown_flag.store(true, Ordering::Relaxed)
-            } // Cloning costs very little here.
-            return false; // Depth limit reached, stop processing
-        }
-        true // Continue processing
     }
+
     /**
     Recursively processes a directory, sending found files to a channel.
 
@@ -581,85 +2026,233 @@ impl Finder {
 
     */
     #[inline]
-    fn process_directory(
+    fn process_directory<S: Sink>(
         &self,
         work_item: WorkItem,
-        sender: &mut BatchSender,
+        sender: &mut S,
         ctx: &WorkerContext<'_>,
     ) {
         let WorkItem {
             dir,
-            ignore_ctx: parent_ignore_ctx,
+            ignore_ctx,
+            postorder_parent,
+            parent_handle,
+            symlinks,
         } = work_item;
+        let parent = parent_handle.as_ref();
+        sender.begin(&dir);
+
+        match self.strategy {
+            // Directories which are visited before they're read can't also be emitted after their contents
+            _ if S::ENTERS_DIRECTORIES => {
+                if let Some(dir) = self.read_directory(
+                    dir,
+                    ignore_ctx,
+                    parent_handle.as_deref(),
+                    None,
+                    symlinks,
+                    sender,
+                    ctx,
+                ) && sender.send_at(dir, parent).is_err()
+                {
+                    ctx.shutdown_flag.store(true, Ordering::Relaxed);
+                }
+            }
+            TraversalStrategy::DepthFirst | TraversalStrategy::BreadthFirst => {
+                if let Some(dir) = self.read_directory(
+                    dir,
+                    ignore_ctx,
+                    parent_handle.as_deref(),
+                    None,
+                    symlinks,
+                    sender,
+                    ctx,
+                ) && sender.send_at(dir, parent).is_err()
+                {
+                    ctx.shutdown_flag.store(true, Ordering::Relaxed);
+                }
+            }
+            TraversalStrategy::PostOrder => {
+                // Emitted once the subtree is done (maybe by another thread), without its parent's handle
+                let node = PostOrderNode::new(postorder_parent);
+                let finished = self.read_directory(
+                    dir,
+                    ignore_ctx,
+                    parent_handle.as_deref(),
+                    Some(&node),
+                    symlinks,
+                    sender,
+                    ctx,
+                );
+                if let Ok(mut slot) = node.dir.lock() {
+                    *slot = finished;
+                }
+                PostOrderNode::complete(Some(node), sender, ctx);
+            }
+        }
 
+        if sender.end().is_err() {
+            ctx.shutdown_flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /**
+    Reads a single directory, sending matching entries and enqueueing subdirectories.
+
+    Returns the directory itself if it should be emitted, this is left to the caller so that
+    the strategy decides *when* it is emitted relative to its contents.
+    */
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    fn read_directory<S: Sink>(
+        &self,
+        dir: DirEntry,
+        parent_ignore_ctx: Arc<IgnoreContext>,
+        parent_handle: Option<&DirHandle>,
+        node: Option<&Arc<PostOrderNode>>,
+        symlinks: u32,
+        sender: &mut S,
+        ctx: &WorkerContext<'_>,
+    ) -> Option<DirEntry> {
         if self.matches_ignore_path(&dir) || self.is_gitignored(&dir, &parent_ignore_ctx) {
-            return;
+            return None;
         }
 
         if !self.directory_or_symlink_filter(&dir) {
-            return; // Check for same filesystem/recursive symlinks etc, if so, return to avoid a loop/unnecessary info
+            return None; // Check for same filesystem/recursive symlinks etc, if so, return to avoid a loop/unnecessary info
+        }
+
+        if !self.follow_mounts && dir.depth() != 0 && dir.is_mount_point()
+            || self.is_virtual_fs(&dir)
+            || self.is_pruned_dir(&dir)
+        {
+            // Still report the mount point (or pruned directory) itself, just don't read it
+            return self.should_send_dir(&dir).then_some(dir);
         }
 
         let current_ignore_ctx = self.build_ignore_context(&dir, parent_ignore_ctx);
 
-        let should_send_dir_or_symlink = self.should_send_dir(&dir); // If we've gotten here, the dir must be a directory!
+        // If we've gotten here, the dir must be a directory!
+        let should_send_dir_or_symlink = !S::DISCARDS_RESULTS && self.should_send_dir(&dir);
 
-        if !self.handle_depth_limit(&dir, should_send_dir_or_symlink, sender, ctx) {
-            return;
+        if self.is_at_depth_limit(&dir) {
+            if let Some(limit) = self.follow_depth_limit.as_deref() {
+                limit.mark_hit();
+            }
+            // Depth limit reached, stop processing
+            return should_send_dir_or_symlink.then_some(dir);
+        }
+
+        // A matching directory is reported without being read, the filter only runs again if results are discarded
+        if self.prune
+            && (should_send_dir_or_symlink || S::DISCARDS_RESULTS && self.should_send_dir(&dir))
+        {
+            return should_send_dir_or_symlink.then_some(dir);
+        }
+
+        // Every directory about to be read, so that even one which doesn't match can be pruned
+        if S::ENTERS_DIRECTORIES && (should_send_dir_or_symlink || dir.depth() != 0) {
+            match sender.enter(&dir, should_send_dir_or_symlink) {
+                ControlFlow::Continue => {}
+                ControlFlow::SkipSubtree => return None,
+                ControlFlow::Stop => {
+                    ctx.shutdown_flag.store(true, Ordering::Relaxed);
+                    return None;
+                }
+            }
         }
+        // Already handed over by `enter`
+        let should_send_dir_or_symlink = should_send_dir_or_symlink && !S::ENTERS_DIRECTORIES;
         // a macro to select the best implementation for your device (simplifying the code here)
         // On Linux/Android/Solaris/Illumos/etc, use getdents
         // on MacOS/FreeBSD, use getdirentries(64)
         // Otherwise use readdir
-        match read_direntries!(dir) {
+        #[allow(clippy::bind_instead_of_map)] // Only `readdir` can fail once the directory is open
+        let opened = if self.backend == Backend::Portable {
+            PortableReadDir::new(&dir).map(DirReader::Portable)
+        } else {
+            match parent_handle {
+                Some(parent) => dir
+                    .open_at(parent.0.as_raw_fd())
+                    .and_then(|fd| read_direntries_from_fd!(dir, fd, self.getdents_buffer_size)),
+                None => read_direntries!(dir, self.getdents_buffer_size),
+            }
+            .map(DirReader::Native)
+        };
+        match opened {
             Ok(entries) => {
-                let opt_fd = Some(&FileDes(entries.fd.0)); //dirty hack, need to revisit my approach
-                // I need to figure out how to use 'openat' style on opening queued file descriptors
-                // Unfortunately queueing file descriptors will fail once file descriptors go past ulimit
-                // but they won't for consequent file descriptors
-                // I can see *why* the std library did it the way it did, I should research how walkdir handles it.
+                sender.opened(&dir);
+                // Shared rather than borrowed, as iterating consumes `entries`. Without one, entries are statted by path
+                let dir_fd = match entries {
+                    DirReader::Native(ref native) => Some(native.dirfd().clone()),
+                    DirReader::Portable(_) => None,
+                };
+                let opt_fd = dir_fd.as_ref();
+                // Shared by every subdirectory of this one, only created once there is one to queue
+                let mut handle: Option<Option<Arc<DirHandle>>> = None;
                 for entry in entries {
                     if !self.keep_hidden(&entry)
                         || self.matches_ignore_path(&entry)
+                        || self.search_config.matches_exclude(&entry) // before queueing, so excluded dirs are never opened
                         || self.is_gitignored(&entry, &current_ignore_ctx)
                     {
                         continue;
                     }
 
-                    let should_traverse = self.should_traverse(&entry, opt_fd);
+                    // A directory which can't be queued is treated like any other entry
+                    let should_traverse = self.within_symlink_depth(&entry, symlinks)
+                        && self.should_traverse(&entry, opt_fd)
+                        && self.has_room_for(&entry, ctx);
                     if should_traverse {
-                        if !Self::enqueue_dir(entry, Arc::clone(&current_ignore_ctx), ctx) {
-                            return;
+                        let parent = DirHandle::shared(&mut handle, opt_fd, ctx).cloned();
+                        let symlinks = symlinks + u32::from(entry.is_symlink());
+                        sender.queued(&entry);
+                        if !Self::enqueue_dir(
+                            entry,
+                            Arc::clone(&current_ignore_ctx),
+                            parent,
+                            node,
+                            symlinks,
+                            ctx,
+                        ) {
+                            return None;
                         }
                         continue;
                     }
 
-                    if self.file_filter(&entry, opt_fd) && sender.send(entry).is_err() {
+                    if S::DISCARDS_RESULTS || !self.file_filter(&entry, opt_fd) {
+                        continue;
+                    }
+                    let sent = if S::WANTS_PARENT {
+                        sender.send_at(entry, DirHandle::shared(&mut handle, opt_fd, ctx))
+                    } else {
+                        sender.send(entry)
+                    };
+                    if sent.is_err() {
                         ctx.shutdown_flag.store(true, Ordering::Relaxed);
-                        return;
+                        return None;
                     }
                 }
 
                 // Checking if we should send directories
-                if should_send_dir_or_symlink && sender.send(dir).is_err() {
-                    ctx.shutdown_flag.store(true, Ordering::Relaxed);
-                }
+                should_send_dir_or_symlink.then_some(dir)
             }
             Err(error) => {
-                if let Some(errors_arc) = self.errors.as_ref() {
-                    // This will only show errors if collect errors is enabled
-                    // Generally I don't like this approach due to the locking it can cause
-                    // However, errors are VERY small typically hence this create negligible issues.
-                    if let Ok(mut errors) = errors_arc.lock() {
-                        errors.push(TraversalError { dir, error });
-                    }
-                }
+                self.record_error(dir, error);
+                None
             }
         }
     }
 
     #[inline]
-    fn enqueue_dir(dir: DirEntry, ignore_ctx: Arc<IgnoreContext>, ctx: &WorkerContext<'_>) -> bool {
+    fn enqueue_dir(
+        dir: DirEntry,
+        ignore_ctx: Arc<IgnoreContext>,
+        parent_handle: Option<Arc<DirHandle>>,
+        node: Option<&Arc<PostOrderNode>>,
+        symlinks: u32,
+        ctx: &WorkerContext<'_>,
+    ) -> bool {
         if ctx.shutdown_flag.load(Ordering::Relaxed) {
             // Release the shutdown as soon as possible.
             return false;
@@ -667,7 +2260,21 @@ impl Finder {
         //atomicity itself ensures that all threads see a consistent modification order for pending,
         // so the final count will be correct even if increments are reordered among themselves.
         ctx.pending.fetch_add(1, Ordering::Relaxed);
-        ctx.local.push(WorkItem { dir, ignore_ctx });
+        if let Some(parent) = node {
+            // The child holds its parent open until its own subtree is complete
+            parent.remaining.fetch_add(1, Ordering::Relaxed);
+        }
+        let item = WorkItem {
+            dir,
+            ignore_ctx,
+            postorder_parent: node.cloned(),
+            parent_handle,
+            symlinks,
+        };
+        match ctx.next_level {
+            Some(next_level) => next_level.push(item),
+            None => ctx.local.push(item),
+        }
 
         true
     }
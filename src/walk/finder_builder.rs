@@ -1,15 +1,24 @@
 #![allow(clippy::missing_inline_in_public_items)]
 use crate::{
+    Preset,
     SearchConfigError,
     config,
-    filters::{FileTypeFilter, SizeFilter, TimeFilter},
+    filters::{
+        ContentFilter, FileTypeFilter, Filter, MimeFilter, PermFilter, SizeFilter, TimeFilter,
+    },
     fs::DirEntry,
     //  util::IgnoreMatcher,
-    walk::{DirEntryFilter, FilterType, finder::Finder},
+    walk::{
+        Backend, CancellationToken, DEFAULT_FOLLOW_DEPTH_LIMIT, DepthLimit, DirEntryFilter, Fields,
+        FilterType, FlushPolicy, StatFilter, StatefulFilter, TraversalStats, TraversalStrategy,
+        finder::Finder, sample,
+    },
 };
 
+use core::iter;
 use core::num::NonZeroU32;
 use core::num::NonZeroUsize;
+use core::time::Duration;
 use dashmap::DashSet;
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use std::{
@@ -34,19 +43,37 @@ use std::{
 )]
 pub struct FinderBuilder {
     pub(crate) root: OsString,
+    pub(crate) extra_roots: Vec<OsString>,
     pub(crate) pattern: Option<String>,
     pub(crate) and_patterns: Vec<String>,
+    pub(crate) not_patterns: Vec<String>,
+    pub(crate) any_patterns: Vec<String>,
+    pub(crate) pattern_files: Vec<PathBuf>,
+    pub(crate) presets: Vec<Preset>,
     pub(crate) hide_hidden: bool,
     pub(crate) case_insensitive: bool,
     pub(crate) file_name_only: bool,
-    pub(crate) extension_match: Option<Box<[u8]>>,
+    pub(crate) extension_match: Vec<Box<[u8]>>,
     pub(crate) max_depth: Option<NonZeroU32>,
+    pub(crate) min_depth: Option<NonZeroU32>,
     pub(crate) follow_symlinks: bool,
+    pub(crate) follow_depth_limit: Option<NonZeroU32>,
     pub(crate) filter: Option<DirEntryFilter>,
+    pub(crate) stat_filter: Option<StatFilter>,
+    pub(crate) expression: Option<Filter>,
+    pub(crate) stateful_filter: Option<StatefulFilter>,
+    pub(crate) mime_filter: Option<MimeFilter>,
+    pub(crate) content_filter: Option<ContentFilter>,
+    pub(crate) sample_ratio: Option<f64>,
+    pub(crate) seed: Option<u64>,
     pub(crate) size_filter: Option<SizeFilter>,
     pub(crate) time_filter: Option<TimeFilter>,
+    pub(crate) created_filter: Option<TimeFilter>,
+    pub(crate) accessed_filter: Option<TimeFilter>,
+    pub(crate) perm_filter: Option<PermFilter>,
     pub(crate) file_type: Option<FileTypeFilter>,
     pub(crate) collect_errors: bool,
+    pub(crate) collect_stats: bool,
     pub(crate) use_glob: bool,
     pub(crate) canonicalise: bool,
     pub(crate) same_filesystem: bool,
@@ -54,28 +81,65 @@ pub struct FinderBuilder {
     pub(crate) respect_gitignore: bool,
     pub(crate) ignore_patterns: Vec<String>,
     pub(crate) ignore_glob_patterns: Vec<String>,
+    pub(crate) exclude_patterns: Vec<String>,
     pub(crate) ignore_files: Vec<PathBuf>,
+    pub(crate) getdents_buffer_size: Option<usize>,
+    pub(crate) strategy: TraversalStrategy,
+    pub(crate) max_results: Option<usize>,
+    pub(crate) max_total_size: Option<u64>,
+    pub(crate) follow_mounts: bool,
+    pub(crate) skip_virtual_fs: bool,
+    pub(crate) skip_pseudo_fs: Option<bool>,
+    pub(crate) prune: bool,
+    pub(crate) prune_dirs: Option<StatefulFilter>,
+    pub(crate) max_symlink_depth: Option<u32>,
+    pub(crate) max_open_fds: Option<NonZeroUsize>,
+    pub(crate) max_queued_dirs: Option<NonZeroUsize>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) projection: Fields,
+    pub(crate) flush_policy: FlushPolicy,
+    pub(crate) backend: Backend,
+    pub(crate) cancellation: Option<CancellationToken>,
+    #[cfg(feature = "rayon")]
+    pub(crate) thread_pool: Option<Arc<rayon::ThreadPool>>,
 }
 
 impl FinderBuilder {
     pub(crate) fn new<A: AsRef<OsStr>>(root: A) -> Self {
-        const MIN_THREADS: NonZeroUsize = NonZeroUsize::MIN;
-        let num_threads = std::thread::available_parallelism().unwrap_or(MIN_THREADS);
+        let num_threads = crate::fs::limits::cpu_count();
         Self {
             root: root.as_ref().to_owned(),
+            extra_roots: Vec::new(),
             pattern: None,
             and_patterns: Vec::new(),
+            not_patterns: Vec::new(),
+            any_patterns: Vec::new(),
+            pattern_files: Vec::new(),
+            presets: Vec::new(),
             hide_hidden: true,
             case_insensitive: true,
             file_name_only: true,
-            extension_match: None,
+            extension_match: Vec::new(),
             max_depth: None,
+            min_depth: None,
             follow_symlinks: false,
+            follow_depth_limit: NonZeroU32::new(DEFAULT_FOLLOW_DEPTH_LIMIT),
             filter: None,
+            stat_filter: None,
+            expression: None,
+            stateful_filter: None,
+            mime_filter: None,
+            content_filter: None,
+            sample_ratio: None,
+            seed: None,
             size_filter: None,
             time_filter: None,
+            created_filter: None,
+            accessed_filter: None,
+            perm_filter: None,
             file_type: None,
             collect_errors: false,
+            collect_stats: false,
             use_glob: false,
             canonicalise: false,
             same_filesystem: false,
@@ -83,7 +147,27 @@ impl FinderBuilder {
             respect_gitignore: true,
             ignore_patterns: Vec::new(),
             ignore_glob_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
             ignore_files: Vec::new(),
+            getdents_buffer_size: None,
+            strategy: TraversalStrategy::DepthFirst,
+            max_results: None,
+            max_total_size: None,
+            follow_mounts: true,
+            skip_virtual_fs: false,
+            skip_pseudo_fs: None,
+            prune: false,
+            prune_dirs: None,
+            max_symlink_depth: None,
+            max_open_fds: None,
+            max_queued_dirs: None,
+            timeout: None,
+            projection: Fields::ALL,
+            flush_policy: FlushPolicy::Block,
+            backend: Backend::default(),
+            cancellation: None,
+            #[cfg(feature = "rayon")]
+            thread_pool: None,
         }
     }
 
@@ -101,6 +185,38 @@ impl FinderBuilder {
         self
     }
 
+    /**
+    Add a pattern which must not match, eg `\.rs$` excluding `test` keeps Rust files without `test` in their name.
+
+    Unlike [`exclude_patterns`](Self::exclude_patterns) this decides what is a result rather than what is
+    traversed, so directories matching it are still descended into. The negated patterns are compiled into one
+    regex, only consulted when every other pattern has matched.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .pattern(r"\.rs$")
+        .exclude_pattern("test")
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().all(|entry| !entry.file_name().ends_with(b"test.rs")));
+    ```
+    */
+    #[must_use]
+    pub fn exclude_pattern<P: AsRef<str>>(mut self, pattern: P) -> Self {
+        self.not_patterns.push(pattern.as_ref().to_owned());
+        self
+    }
+
+    /// Set the patterns which must not match, replacing any added by [`exclude_pattern`](Self::exclude_pattern)
+    #[must_use]
+    pub fn not_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.not_patterns = patterns;
+        self
+    }
+
     /// Set whether to hide hidden files, defaults to true
     #[must_use]
     pub const fn keep_hidden(mut self, hide_hidden: bool) -> Self {
@@ -114,6 +230,42 @@ impl FinderBuilder {
         self
     }
 
+    /**
+    Set patterns of which at least one must match, eg a list of file names worth flagging.
+
+    They're compiled into a single [`RegexSet`](regex::bytes::RegexSet), so a long list costs about one regex.
+    This is on top of (not instead of) [`pattern`](Self::pattern) and [`and_patterns`](Self::and_patterns),
+    and the patterns are globs with [`use_glob`](Self::use_glob). [`fixed_string`](Self::fixed_string) doesn't apply to them.
+    */
+    #[must_use]
+    pub fn any_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.any_patterns = patterns;
+        self
+    }
+
+    /**
+    Set files to read more [`any_patterns`](Self::any_patterns) from when building, one pattern per line.
+
+    Blank lines and lines starting with `#` are skipped, write `\#` for a pattern starting with `#`.
+    */
+    #[must_use]
+    pub fn pattern_files(mut self, files: Vec<OsString>) -> Self {
+        self.pattern_files = files.into_iter().map(PathBuf::from).collect();
+        self
+    }
+
+    /**
+    Set curated lists of patterns to add to [`any_patterns`](Self::any_patterns), eg [`Preset::Secrets`].
+
+    The preset patterns are regexes whatever [`use_glob`](Self::use_glob) is set to, and match either the file
+    name or the relative path, so they don't turn off [`file_name_only`](Self::file_name_only).
+    */
+    #[must_use]
+    pub fn presets(mut self, presets: Vec<Preset>) -> Self {
+        self.presets = presets;
+        self
+    }
+
     /// Set whether to use short paths in regex/glob matching, defaults to true
     /// This is over-ridden if the search term contains a '/'
     #[must_use]
@@ -126,18 +278,28 @@ impl FinderBuilder {
     /// `-e rs` and `-e .rs` both match `*.rs`, and `-e '.'` matches files
     /// whose name ends with a dot (empty extension after the dot).
     #[must_use]
-    pub fn extension<C: AsRef<str>>(mut self, extension: C) -> Self {
-        let input = extension.as_ref().as_bytes();
-        // Strip one leading dot so that `-e rs` and `-e .rs` are equivalent,
-        // and `-e '.'` stores Some(b"") which matches filenames ending in '.'.
-        let ext = input.strip_prefix(b".").unwrap_or(input);
+    pub fn extension<C: AsRef<str>>(self, extension: C) -> Self {
+        self.extensions(vec![extension.as_ref().to_owned()])
+    }
 
-        if input.is_empty() {
-            self.extension_match = None;
-        } else {
-            self.extension_match = Some(ext.into());
-        }
+    /// Set several extensions, of which any must match (case insensitively), as in [`extension`](Self::extension).
+    /// Empty strings are skipped, so no extensions (or only empty ones) means no extension filtering.
+    #[must_use]
+    pub fn extensions(mut self, extensions: Vec<String>) -> Self {
+        let mut matches: Vec<Box<[u8]>> = extensions
+            .iter()
+            .filter(|extension| !extension.is_empty())
+            .map(|extension| {
+                let input = extension.as_bytes();
+                // Strip one leading dot so that `-e rs` and `-e .rs` are equivalent,
+                // and `-e '.'` stores b"" which matches filenames ending in '.'.
+                input.strip_prefix(b".").unwrap_or(input).into()
+            })
+            .collect();
+        matches.sort_unstable_by_key(|ext| ext.to_ascii_lowercase());
+        matches.dedup_by(|left, right| left.eq_ignore_ascii_case(right));
 
+        self.extension_match = matches;
         self
     }
 
@@ -153,6 +315,22 @@ impl FinderBuilder {
         }
     }
 
+    /**
+    Set minimum depth of the results, defaults to none (the root's own entries are depth 1).
+
+    Shallower directories are still traversed, only entries above the minimum depth aren't emitted.
+    */
+    #[must_use]
+    pub const fn min_depth(mut self, min_depth: Option<u32>) -> Self {
+        match min_depth {
+            None => self,
+            Some(num) => {
+                self.min_depth = NonZeroU32::new(num);
+                self
+            }
+        }
+    }
+
     /// Sets size-based filtering criteria.
     #[must_use]
     pub const fn filter_by_size(mut self, size_of: Option<SizeFilter>) -> Self {
@@ -167,23 +345,256 @@ impl FinderBuilder {
         self
     }
 
+    /// Sets time-based filtering criteria for file creation (birth) times, see [`DirEntry::created_time`](crate::fs::DirEntry::created_time).
+    ///
+    /// Entries whose creation time isn't recorded (by the platform or the filesystem) never match.
+    #[must_use]
+    pub const fn filter_by_created(mut self, time_of: Option<TimeFilter>) -> Self {
+        self.created_filter = time_of;
+        self
+    }
+
+    /// Sets time-based filtering criteria for file access times, see [`DirEntry::accessed_time`](crate::fs::DirEntry::accessed_time).
+    #[must_use]
+    pub const fn filter_by_accessed(mut self, time_of: Option<TimeFilter>) -> Self {
+        self.accessed_filter = time_of;
+        self
+    }
+
+    /// Sets permission-based filtering criteria, matched against the `lstat` mode of each entry.
+    #[must_use]
+    pub const fn filter_by_permissions(mut self, perm_of: Option<PermFilter>) -> Self {
+        self.perm_filter = perm_of;
+        self
+    }
+
+    /// Sets MIME type filtering criteria, sniffed from the first few KB of each regular file (see [`sniff_mime`](crate::filters::sniff_mime)).
+    ///
+    /// This opens every candidate file, so it runs after the name and metadata filters. Directories never match.
+    #[must_use]
+    pub fn filter_by_mime(mut self, mime_of: Option<MimeFilter>) -> Self {
+        self.mime_filter = mime_of;
+        self
+    }
+
     /// Sets whether to follow symlinks (default: false).
     ///
-    /// This will not recurse infinitely but can provide more results than expected
+    /// This will not recurse infinitely, each directory is read once (keyed on its device and inode) however many
+    /// symlinks lead to it, but can provide more results than expected. See also [`max_symlink_depth`](Self::max_symlink_depth).
     #[must_use]
     pub const fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
         self.follow_symlinks = follow_symlinks;
         self
     }
 
-    /// Set a custom filter
+    /**
+    Set the depth limit applied when following symlinks without a [`max_depth`](Self::max_depth), defaults to
+    [`DEFAULT_FOLLOW_DEPTH_LIMIT`]. `None` (or 0) removes it.
+
+    Cycle detection already stops symlinks looping back on themselves, this guards against link farms deep enough
+    to look endless (or a loop it misses). Use [`Finder::follow_depth_limit`] to tell if it cut the search short.
+    */
+    #[must_use]
+    pub const fn follow_depth_limit(mut self, limit: Option<u32>) -> Self {
+        self.follow_depth_limit = match limit {
+            Some(limit) => NonZeroU32::new(limit),
+            None => None,
+        };
+        self
+    }
+
+    /**
+    Set the most directory symlinks followed on the way to any directory, defaults to no limit.
+
+    When following symlinks, every directory is read at most once (keyed on its device and inode), so a loop ends
+    as soon as it gets back to a directory already visited. This bounds the chains of symlinks themselves, eg to
+    stay close to the real tree. A symlink past the limit is emitted as a symlink rather than followed, and
+    `Some(0)` follows none below the root.
+    */
+    #[must_use]
+    pub const fn max_symlink_depth(mut self, limit: Option<u32>) -> Self {
+        self.max_symlink_depth = limit;
+        self
+    }
+
+    /**
+    Set a custom filter.
+
+    This is a plain function, so it can't keep any state between calls, see [`filter_shared`](Self::filter_shared)
+    and [`filter_mut`](Self::filter_mut) for closures which can.
+    */
     #[must_use]
     pub const fn filter(mut self, filter: Option<fn(&DirEntry) -> bool>) -> Self {
         self.filter = filter;
         self
     }
 
-    /// Sets file type filtering.
+    /**
+    Set a custom filter on an entry's `lstat` (so symlinks aren't followed).
+
+    Filters run in two stages, the name, depth and type filters first, then the metadata filters on the entries
+    which survived them. This joins the second stage, sharing the one `lstat` with the size, time and permission
+    filters. Entries which can't be statted (eg removed since being read) never match.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    // Files with more than one hard link
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .filter_by_metadata(Some(|entry, statted| entry.is_regular_file() && statted.st_nlink > 1))
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().all(|entry| entry.is_regular_file()));
+    ```
+    */
+    #[must_use]
+    pub const fn filter_by_metadata(mut self, filter: Option<StatFilter>) -> Self {
+        self.stat_filter = filter;
+        self
+    }
+
+    /**
+    Set a filter expression, combining tests with `and`, `or` and `not` (see [`Filter`]).
+
+    It's evaluated after the name and metadata filters, so the options above can still narrow the search down
+    cheaply. Path tests in the expression see the path below the search root.
+
+    # Examples
+    ```
+    use fdf::{filters::Filter, walk::Finder};
+
+    let expression = Filter::extension("rs").or(Filter::extension("toml"));
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .filter_expr(Some(expression))
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().all(|entry| {
+        entry.extension().is_some_and(|ext| ext == b"rs" || ext == b"toml")
+    }));
+    ```
+    */
+    #[must_use]
+    pub fn filter_expr(mut self, expression: Option<Filter>) -> Self {
+        self.expression = expression;
+        self
+    }
+
+    /**
+    Set a filter closure which is called concurrently from every worker thread, so any state it keeps must be
+    thread safe (eg an atomic counter). This is the cheaper option when the state allows it.
+
+    Stateful filters run after every other filter, so they only see (and can count or sample) entries which
+    would otherwise be results. The order entries reach the closure in is not deterministic.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Keep every other match
+    let seen = AtomicUsize::new(0);
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .filter_shared(move |_| seen.fetch_add(1, Ordering::Relaxed).is_multiple_of(2))
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().count() > 0);
+    ```
+    */
+    #[must_use]
+    pub fn filter_shared<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+    {
+        self.stateful_filter = Some(StatefulFilter::shared(filter));
+        self
+    }
+
+    /**
+    Set a filter closure with mutable state. Calls from different worker threads are serialised by a lock,
+    so the closure needn't be thread safe, at the cost of contention on large traversals.
+
+    Like [`filter_shared`](Self::filter_shared), this runs after every other filter. Only one stateful filter
+    can be set, the last call wins.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::collections::HashSet;
+
+    // Only the first file with each name
+    let mut names = HashSet::new();
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .filter_mut(move |entry| names.insert(entry.file_name().to_vec()))
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().count() > 0);
+    ```
+    */
+    #[must_use]
+    pub fn filter_mut<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&DirEntry) -> bool + Send + 'static,
+    {
+        self.stateful_filter = Some(StatefulFilter::exclusive(filter));
+        self
+    }
+
+    /**
+    Set a filter closure given the entry's `lstat` as well, which is called concurrently from every worker thread
+    like [`filter_shared`](Self::filter_shared). The `lstat` is `None` if it failed (eg the entry was removed
+    since being read), so the closure decides whether to keep such entries.
+
+    This is the stateful counterpart of [`filter_by_metadata`](Self::filter_by_metadata), for quotas or sampling on
+    metadata. It runs after every other filter, so only entries which would otherwise be results are counted.
+    Only one stateful filter can be set, the last call wins.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Stop keeping files once they add up to 64KiB, skipping any which would go over
+    let used = AtomicU64::new(0);
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .filter_by_metadata_shared(move |entry, statted| {
+            let Some(statted) = statted.filter(|_| entry.is_regular_file()) else {
+                return false;
+            };
+            let size = statted.st_size as u64;
+            used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                (total + size <= 64 * 1024).then_some(total + size)
+            })
+            .is_ok()
+        })
+        .build()
+        .unwrap();
+    let total: u64 = finder.traverse().unwrap().map(|entry| entry.file_size().unwrap()).sum();
+    assert!(total <= 64 * 1024);
+    ```
+    */
+    #[must_use]
+    pub fn filter_by_metadata_shared<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&DirEntry, Option<&libc::stat>) -> bool + Send + Sync + 'static,
+    {
+        self.stateful_filter = Some(StatefulFilter::shared_with_stat(filter));
+        self
+    }
+
+    /// Set a filter closure with mutable state given the entry's `lstat` as well, see
+    /// [`filter_by_metadata_shared`](Self::filter_by_metadata_shared). Calls are serialised by a lock as with
+    /// [`filter_mut`](Self::filter_mut).
+    #[must_use]
+    pub fn filter_by_metadata_mut<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&DirEntry, Option<&libc::stat>) -> bool + Send + 'static,
+    {
+        self.stateful_filter = Some(StatefulFilter::exclusive_with_stat(filter));
+        self
+    }
+
+    /// Sets file type filtering, see [`FileTypeFilter::any_of`] to match any of several types.
     #[must_use]
     pub const fn type_filter(mut self, filter: Option<FileTypeFilter>) -> Self {
         self.file_type = filter;
@@ -204,7 +615,26 @@ impl FinderBuilder {
         self
     }
 
-    /// Set whether to canonicalise (resolve absolute path) the root directory, defaults to false
+    /**
+    Set whether to count the results as they're emitted, defaults to false
+
+    The totals (counts by file type, bytes, deepest path, largest file and unreadable directories) are read
+    through [`Finder::stats`]. Sizing regular files costs an `lstat` each, with this off nothing is counted.
+    */
+    #[must_use]
+    pub const fn collect_stats(mut self, yesorno: bool) -> Self {
+        self.collect_stats = yesorno;
+        self
+    }
+
+    /**
+    Set whether to canonicalise (resolve absolute path) the root directory, defaults to false
+
+    Only the root is resolved (once, when building). Every result is its parent's path joined with its name,
+    so with a canonical root the results are absolute without calling `realpath` per entry. Unless symlinks
+    are followed no symlinked directory is descended into, so every result's parent is canonical too.
+    When following them, see [`RealpathCache`](crate::fs::RealpathCache) to resolve the results found through them.
+    */
     #[must_use]
     pub const fn canonicalise_root(mut self, canonicalise: bool) -> Self {
         self.canonicalise = canonicalise;
@@ -221,6 +651,11 @@ impl FinderBuilder {
                 .into_iter()
                 .map(|patt| regex::escape(&patt))
                 .collect();
+            self.not_patterns = self
+                .not_patterns
+                .into_iter()
+                .map(|patt| regex::escape(&patt))
+                .collect();
         }
         self
     }
@@ -236,14 +671,148 @@ impl FinderBuilder {
         self
     }
 
+    /**
+    Run the traversal's workers on `pool` rather than spawning threads for each search, enabled by the `rayon`
+    feature.
+
+    Meant for long running processes (eg interactive browsers) making many searches, which can share one pool
+    between them (and keep them apart from the rest of the application's work). A worker is started per thread
+    in the pool, replacing the [`thread_count`](Self::thread_count), and each keeps its thread until the search
+    is over, so searches running at once on a pool take turns rather than sharing it. For the same reason the
+    results of [`traverse`](crate::walk::Finder::traverse) shouldn't be consumed on one of the pool's threads.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::sync::Arc;
+
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+    let search = |pattern: &str| {
+        Finder::init(env!("CARGO_MANIFEST_DIR"))
+            .pattern(pattern)
+            .with_thread_pool(Arc::clone(&pool))
+            .build()
+            .unwrap()
+            .traverse()
+            .unwrap()
+            .count()
+    };
+    assert!(search(r"\.rs$") > 0);
+    assert!(search("Cargo.toml") > 0);
+    ```
+    */
+    #[must_use]
+    #[cfg(feature = "rayon")]
+    pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.thread_count =
+            NonZeroUsize::new(pool.current_num_threads()).unwrap_or(NonZeroUsize::MIN);
+        self.thread_pool = Some(pool);
+        self
+    }
+
     /// Set whether to follow the same filesystem as root
+    ///
+    /// This compares device IDs, so bind mounts of the root's filesystem are still traversed,
+    /// see [`follow_mounts`](Self::follow_mounts) to stop at those too.
     #[must_use]
     pub const fn same_filesystem(mut self, yesorno: bool) -> Self {
         self.same_filesystem = yesorno;
         self
     }
 
-    /// Set whether to respect `.gitignore` rules, defaults to true
+    /**
+    Set the fields kept for each result of [`Finder::traverse_projected`], defaults to [`Fields::ALL`]
+
+    Requesting fewer fields keeps the records sent between threads small, eg `Fields::NONE` for counting
+    or `Fields::SIZE` for summing sizes avoids sending any paths.
+    */
+    #[must_use]
+    pub const fn project(mut self, fields: Fields) -> Self {
+        self.projection = fields;
+        self
+    }
+
+    /**
+    Set whether to descend into mount points (including bind mounts), defaults to true
+
+    This is independent of [`follow_symlinks`](Self::follow_symlinks) and [`same_filesystem`](Self::same_filesystem),
+    a mount point below the root is still emitted but never entered. See [`DirEntry::is_mount_point`]
+    for the caveats on detecting bind mounts.
+    */
+    #[must_use]
+    pub const fn follow_mounts(mut self, yesorno: bool) -> Self {
+        self.follow_mounts = yesorno;
+        self
+    }
+
+    /**
+    Set whether to skip virtual filesystems such as `/proc` and `/sys`, defaults to false
+
+    They're recognised by their type in the mount table (see [`VIRTUAL_FS_TYPES`](crate::fs::mounts::VIRTUAL_FS_TYPES)),
+    not by their path, wherever they're mounted. Their mount points are still emitted but never entered, unless the
+    root is on one of them. Each directory has to be statted for its device ID, unless
+    [`same_filesystem`](Self::same_filesystem) or following symlinks stats it already.
+    */
+    #[must_use]
+    pub const fn skip_virtual_fs(mut self, yesorno: bool) -> Self {
+        self.skip_virtual_fs = yesorno;
+        self
+    }
+
+    /**
+    Set whether to skip pseudo filesystems such as `/proc`, `/sys` and `/dev`, defaults to true when the root is `/`
+
+    Each directory is checked with `statfs` before it's read (see [`DirEntry::is_on_pseudo_fs`]), so unlike
+    [`skip_virtual_fs`](Self::skip_virtual_fs) nothing is read up front, at the cost of a syscall per directory.
+    A directory on a pseudo filesystem is still emitted but never entered, unless it's the root.
+    */
+    #[must_use]
+    pub const fn skip_pseudo_fs(mut self, yesorno: bool) -> Self {
+        self.skip_pseudo_fs = Some(yesorno);
+        self
+    }
+
+    /**
+    Set whether to stop descending into directories which match, defaults to false
+
+    A matching directory is still emitted, but none of its contents are, eg finding every `node_modules`
+    without listing what's inside them. Directories which don't match are read as usual.
+    */
+    #[must_use]
+    pub const fn prune(mut self, yesorno: bool) -> Self {
+        self.prune = yesorno;
+        self
+    }
+
+    /**
+    Set a closure deciding which directories below the root not to descend into, eg mount points or paths on a
+    denylist. It's called from every worker thread, so any state has to be synchronised (eg with atomics).
+
+    Unlike [`filter`](Self::filter) (which only decides what is emitted) this stops recursion: a directory for which
+    it returns true is never read, though it's still emitted if it matches. Only one closure can be set, the last
+    call wins.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .prune_dirs(|dir| dir.file_name() == b"src")
+        .build()
+        .unwrap();
+    assert!(finder.traverse().unwrap().all(|entry| !entry.as_bytes().windows(5).any(|part| part == b"/src/")));
+    ```
+    */
+    #[must_use]
+    pub fn prune_dirs<F>(mut self, prune: F) -> Self
+    where
+        F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+    {
+        self.prune_dirs = Some(StatefulFilter::shared(prune));
+        self
+    }
+
+    /// Set whether to respect `.gitignore` (inside git repositories), `.ignore` and `.fdignore` rules, defaults to true
     #[must_use]
     pub const fn respect_gitignore(mut self, yesorno: bool) -> Self {
         self.respect_gitignore = yesorno;
@@ -264,6 +833,18 @@ impl FinderBuilder {
         self
     }
 
+    /**
+    Set glob patterns that exclude files and whole directory subtrees, eg `node_modules` or `*.o`.
+
+    Patterns use `.gitignore` syntax relative to the root, so a pattern without a slash matches at any depth
+    and a trailing slash only matches directories. Excluded directories are never opened.
+    */
+    #[must_use]
+    pub fn exclude_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.exclude_patterns = patterns;
+        self
+    }
+
     /// Set custom ignore files in `.gitignore` format.
     #[must_use]
     pub fn ignore_files(mut self, files: Vec<OsString>) -> Self {
@@ -271,6 +852,195 @@ impl FinderBuilder {
         self
     }
 
+    /// Set the order in which directories are read and emitted, defaults to [`TraversalStrategy::DepthFirst`]
+    #[must_use]
+    pub const fn strategy(mut self, strategy: TraversalStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /**
+    Set what the worker threads do when the consumer can't keep up with the results, defaults to [`FlushPolicy::Block`].
+
+    Long running embedders which must never stall the walk can choose to lose results ([`FlushPolicy::DropOldest`])
+    or buffer them on disk ([`FlushPolicy::SpillToDisk`]) instead.
+    */
+    #[must_use]
+    pub const fn flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.flush_policy = policy;
+        self
+    }
+
+    /**
+    Set how directories are read, defaults to [`Backend::Native`] ([`Backend::Portable`] on unusual platforms).
+
+    [`Backend::Portable`] trades speed for only relying on `opendir`/`readdir`/`lstat`, for platforms or
+    sandboxes where the native fast paths misbehave.
+    */
+    #[must_use]
+    pub const fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Set a token which aborts the traversal once cancelled, see [`CancellationToken`]
+    #[must_use]
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /**
+    Only keep regular files (or symlinks to them) whose contents match a [`ContentFilter`], defaults to no content search.
+
+    This runs on the worker threads after the cheaper name and metadata filters, so narrowing the search
+    with an extension or a size filter first avoids opening most files. Directories never match.
+    */
+    #[must_use]
+    pub fn search_content(mut self, filter: Option<ContentFilter>) -> Self {
+        self.content_filter = filter;
+        self
+    }
+
+    /**
+    Keep each result with probability `ratio`, useful for estimating distributions on huge trees.
+
+    Values of 1 or above keep everything. The sample is applied during the walk, so memory use doesn't grow
+    and every traversal method honours it. Directories are still descended into when they aren't sampled.
+    */
+    #[must_use]
+    pub const fn sample_ratio(mut self, ratio: Option<f64>) -> Self {
+        self.sample_ratio = ratio;
+        self
+    }
+
+    /**
+    Seed the randomness used by [`sample_ratio`](Self::sample_ratio), [`Finder::sample`] and [`Finder::shuffle`],
+    defaults to a different seed every run.
+
+    With a fixed seed the same tree gives the same sample (and shuffled order) every time, however many threads
+    are used or whichever order they find the entries in.
+    */
+    #[must_use]
+    pub const fn seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /**
+    Set the maximum number of results to emit, defaults to no limit.
+
+    The limit is enforced exactly across all worker threads, once it is reached the traversal shuts down and any
+    directories still queued are dropped without being read, so a small limit on a large tree returns quickly.
+    Which entries make the cut is not deterministic (it depends on thread scheduling).
+    */
+    #[must_use]
+    pub const fn max_results(mut self, max_results: Option<usize>) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /**
+    Set a budget for the total size in bytes of the regular files emitted, defaults to no limit.
+
+    Like [`max_results`](Self::max_results) the budget is shared across all worker threads, and the traversal
+    stops at the first file which doesn't fit in what's left of it, so the results never add up to more.
+    Other entries (eg directories) are emitted without counting towards it. Which files make the cut is not
+    deterministic.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .type_filter(Some(fdf::filters::FileTypeFilter::File))
+        .max_total_size(Some(64 * 1024))
+        .build()
+        .unwrap();
+    let total: u64 = finder.traverse().unwrap().map(|entry| entry.file_size().unwrap()).sum();
+    assert!(total <= 64 * 1024);
+    ```
+    */
+    #[must_use]
+    pub const fn max_total_size(mut self, bytes: Option<u64>) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    /**
+    Set the most file descriptors the traversal holds open at once, defaults to half the process's limit
+    (shared by every traversal).
+
+    Each worker thread holds one while reading a directory, so the [`thread_count`](Self::thread_count) is
+    lowered to fit. The rest are spent keeping directories open for their subdirectories to be opened relative
+    to, beyond that they're opened by their full path instead, so a low limit costs speed but no results.
+    Handles kept by [`ParentDir`](crate::walk::ParentDir) results count until they're dropped.
+    */
+    #[must_use]
+    pub const fn max_open_fds(mut self, limit: Option<NonZeroUsize>) -> Self {
+        self.max_open_fds = limit;
+        self
+    }
+
+    /**
+    Set the most directories waiting to be read (or being read) at once, defaults to no limit.
+
+    The workers check it independently, so it can be overshot by one per thread. This bounds the memory of very wide trees. Subdirectories found while the queue is full are emitted (if they
+    match) but not read, each reported as a [`DirEntryError::QueueFull`](crate::DirEntryError::QueueFull) error
+    (see [`collect_errors`](Self::collect_errors)), so the results are incomplete when it's hit.
+    */
+    #[must_use]
+    pub const fn max_queued_dirs(mut self, limit: Option<NonZeroUsize>) -> Self {
+        self.max_queued_dirs = limit;
+        self
+    }
+
+    /**
+    Set how long the traversal may run, defaults to no limit.
+
+    Once the time is up the workers stop after the directory they're reading, so the results found so far are
+    returned, and a [`DirEntryError::TimedOut`](crate::DirEntryError::TimedOut) error is reported for the root
+    (see [`collect_errors`](Self::collect_errors) and [`TraversalError::is_timed_out`](crate::TraversalError::is_timed_out)).
+    The clock starts when the traversal does, not when the finder is built.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::time::Duration;
+
+    let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .timeout(Some(Duration::ZERO))
+        .collect_errors(true)
+        .build()
+        .unwrap();
+    let errors = finder.error_store().unwrap();
+    assert_eq!(finder.traverse().unwrap().count(), 0);
+    assert!(errors.lock().unwrap()[0].is_timed_out());
+    ```
+    */
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /**
+    Set the size (in bytes) of the kernel buffer used for `getdents`/`getdirentries` calls.
+
+    Defaults to the compile-time [`BUFFER_SIZE`](crate::fs::BUFFER_SIZE) for your platform (chosen by tracing
+    what libc uses), rounded up to a whole number of the running machine's pages. Larger buffers can help on network filesystems or
+    directories with huge numbers of entries, smaller buffers reduce memory use per worker thread.
+
+    The size is rounded up to a multiple of 8 and must be at least
+    [`MIN_BUFFER_SIZE`](crate::fs::MIN_BUFFER_SIZE), otherwise [`build`](Self::build) will fail.
+    This has no effect on platforms which fall back to `readdir`.
+    */
+    #[must_use]
+    pub const fn getdents_buffer_size(mut self, size: usize) -> Self {
+        self.getdents_buffer_size = Some(size);
+        self
+    }
+
     /**
     Builds a [`Finder`] instance with the configured options.
 
@@ -288,46 +1058,101 @@ impl FinderBuilder {
     - The root path cannot be canonicalised (when enabled)
     - The search pattern cannot be compiled to a valid regular expression
     - File system metadata cannot be retrieved (for same-filesystem tracking)
+    - The configured `getdents` buffer size is too small to hold a directory entry
     */
     pub fn build(self) -> core::result::Result<Finder, SearchConfigError> {
-        // Resolve and validate the root directory
-        let resolved_root = self.resolve_directory()?;
+        // Resolve and validate the root directories
+        let mut resolved_roots = self.resolve_roots()?.into_iter();
+        let resolved_root = resolved_roots
+            .next()
+            .ok_or(SearchConfigError::NotADirectory)?;
+        let extra_roots: Box<[Box<OsStr>]> = resolved_roots.collect();
+        let all_roots = || iter::once(&resolved_root).chain(&extra_roots);
+        let getdents_buffer_size = self.resolve_buffer_size()?;
         let custom_ignore_matchers = self.compile_ignore_files()?;
+        // With several roots the patterns are matched against the path below whichever root it's under
+        let exclude_match = self.compile_exclude_patterns(if extra_roots.is_empty() {
+            &resolved_root
+        } else {
+            OsStr::new("")
+        })?;
+        let any_patterns = self.read_pattern_files()?;
 
-        let starting_filesystem = if self.same_filesystem {
-            // Get the filesystem ID of the root directory directly
-            let metadata = metadata(resolved_root.as_ref())?;
-            Some(metadata.dev()) // dev() returns the filesystem ID on Unix
+        let root_devices = || {
+            all_roots()
+                .map(|root| metadata(root.as_ref()).map(|meta| meta.dev())) // dev() returns the filesystem ID on Unix
+                .collect::<io::Result<Box<[u64]>>>()
+        };
+        let starting_filesystems = if self.same_filesystem {
+            // Get the filesystem ID of each root directory directly
+            Some(root_devices()?)
         } else {
             None
         };
 
-        let search_config = config::SearchConfig::new(
+        // A missing mount table (eg /proc isn't mounted) leaves nothing to skip, as does searching within one of them
+        let virtual_devices = if self.skip_virtual_fs {
+            let root_devices = starting_filesystems.clone().map_or_else(root_devices, Ok)?;
+            let devices: Box<[u64]> = crate::fs::mounts::virtual_devices()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|device| !root_devices.contains(device))
+                .collect();
+            (!devices.is_empty()).then_some(devices)
+        } else {
+            None
+        };
+
+        // Walking the whole system finds nothing of interest in them, so they're skipped unless asked for
+        let skip_pseudo_fs = self.skip_pseudo_fs.unwrap_or_else(|| {
+            all_roots().any(|root| {
+                Path::new(&**root)
+                    .canonicalize()
+                    .is_ok_and(|root| root == Path::new("/"))
+            })
+        });
+
+        // Only applies when nothing else stops the walk going deeper
+        let follow_depth_limit = self
+            .follow_depth_limit
+            .filter(|_| self.follow_symlinks && self.max_depth.is_none());
+
+        let mut search_config = config::SearchConfig::new(
             self.pattern.as_ref(),
             self.hide_hidden,
             self.case_insensitive,
             self.file_name_only,
-            self.extension_match,
-            self.max_depth,
+            self.extension_match.into_boxed_slice(),
+            self.max_depth.or(follow_depth_limit),
             self.follow_symlinks,
             self.size_filter,
             self.file_type,
             self.time_filter,
+            self.created_filter,
+            self.accessed_filter,
+            self.perm_filter,
             self.use_glob,
             self.and_patterns,
+            self.not_patterns,
+            any_patterns,
+            &self.presets,
             self.respect_gitignore,
             self.ignore_patterns,
             self.ignore_glob_patterns,
+            exclude_match,
         )?;
+        search_config.root_len = resolved_root.len();
+        search_config.multiple_roots = !extra_roots.is_empty();
+        search_config.min_depth = self.min_depth;
+        search_config.stat_filter = self.stat_filter;
+        search_config.expression = self.expression;
 
         let lambda: FilterType = |rconfig, rdir, rfilter, opt_fd| {
             {
-                // arrange the filters by order of costliness
-                rconfig.matches_extension(&rdir.file_name())
-                    && rconfig.matches_path(rdir, !rconfig.file_name_only)
-                    && rconfig.matches_type_at(rdir, opt_fd)
-                    && rconfig.matches_size_at(rdir, opt_fd)
-                    && rconfig.matches_time_at(rdir, opt_fd)
+                // the metadata stage only runs for entries surviving the cheap name and type stage
+                rconfig.matches_name_stage(rdir, opt_fd)
+                    && rconfig.matches_metadata_stage(rdir, opt_fd)
+                    && rconfig.matches_expression_at(rdir, opt_fd)
                     && rfilter.is_none_or(|func| func(rdir)) // put the custom filter last because it's almost always unlikely
             }
         };
@@ -337,54 +1162,207 @@ impl FinderBuilder {
         let errors = self
             .collect_errors
             .then(|| Arc::new(Mutex::new(Vec::new())));
+        let stats = self.collect_stats.then(|| Arc::new(TraversalStats::new()));
 
         Ok(Finder {
             root: resolved_root,
-            search_config,
+            extra_roots,
+            search_config: Arc::new(search_config),
             custom_filter: self.filter,
+            stateful_filter: self.stateful_filter,
+            mime_filter: self.mime_filter,
+            content_filter: self.content_filter,
+            sample_threshold: self.sample_ratio.map(sample::ratio_threshold),
+            seed: self.seed.unwrap_or_else(sample::random_seed),
             file_filter: lambda,
-            starting_filesystem,
+            starting_filesystems,
             inode_cache,
             errors,
-            thread_count: self.thread_count,
+            stats,
+            follow_depth_limit: follow_depth_limit.map(|limit| Arc::new(DepthLimit::new(limit))),
+            // Every worker holds a descriptor while reading
+            thread_count: self
+                .max_open_fds
+                .map_or(self.thread_count, |max| self.thread_count.min(max)),
             custom_ignore_matchers,
+            getdents_buffer_size,
+            strategy: self.strategy,
+            max_results: self.max_results,
+            max_total_size: self.max_total_size,
+            follow_mounts: self.follow_mounts,
+            virtual_devices,
+            skip_pseudo_fs,
+            prune: self.prune,
+            prune_dirs: self.prune_dirs,
+            max_symlink_depth: self.max_symlink_depth,
+            max_open_fds: self.max_open_fds,
+            max_queued_dirs: self.max_queued_dirs,
+            timeout: self.timeout,
+            projection: self.projection,
+            flush_policy: self.flush_policy,
+            backend: self.backend,
+            cancellation: self.cancellation,
+            #[cfg(feature = "rayon")]
+            thread_pool: self.thread_pool,
         })
     }
 
+    /// Validates the requested `getdents` buffer size, falling back to the platform default.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "macos",
+        target_os = "freebsd"
+    ))]
+    fn resolve_buffer_size(&self) -> core::result::Result<usize, SearchConfigError> {
+        use crate::fs::{MIN_BUFFER_SIZE, limits::default_buffer_size};
+        match self.getdents_buffer_size {
+            None => Ok(default_buffer_size()),
+            Some(size) if size < MIN_BUFFER_SIZE => Err(SearchConfigError::InvalidBufferSize(size)),
+            Some(size) => match size.checked_next_multiple_of(8) {
+                Some(aligned) => Ok(aligned),
+                None => Err(SearchConfigError::InvalidBufferSize(size)),
+            },
+        }
+    }
+
+    /// `readdir` manages its own buffer, so there is nothing to validate.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "macos",
+        target_os = "freebsd"
+    )))]
+    const fn resolve_buffer_size(&self) -> core::result::Result<usize, SearchConfigError> {
+        Ok(0)
+    }
+
+    /// Compiles the ignore files, merging consecutive files in the same directory into one matcher
+    /// so each entry is checked against one glob set per directory rather than one per file.
     fn compile_ignore_files(&self) -> core::result::Result<Vec<Arc<Gitignore>>, SearchConfigError> {
-        self.ignore_files
-            .iter()
-            .map(|path| {
-                let base = path.parent().unwrap_or_else(|| Path::new("."));
-                let mut builder = GitignoreBuilder::new(base);
-
-                if let Some(error) = builder.add(path) {
-                    return Err(SearchConfigError::IOError(io::Error::other(
-                        error.to_string(),
-                    )));
+        let to_error =
+            |error: ignore::Error| SearchConfigError::IOError(io::Error::other(error.to_string()));
+        let mut matchers = Vec::new();
+        let mut files = self.ignore_files.iter().peekable();
+
+        while let Some(path) = files.next() {
+            let base = path.parent().unwrap_or_else(|| Path::new("."));
+            let mut builder = GitignoreBuilder::new(base);
+            if let Some(error) = builder.add(path) {
+                return Err(to_error(error));
+            }
+            // Later files take precedence, which still holds within a single builder
+            while let Some(next) =
+                files.next_if(|next| next.parent().unwrap_or_else(|| Path::new(".")) == base)
+            {
+                if let Some(error) = builder.add(next) {
+                    return Err(to_error(error));
                 }
+            }
 
-                builder.build().map(Arc::new).map_err(|error| {
-                    SearchConfigError::IOError(io::Error::other(error.to_string()))
-                })
+            matchers.push(builder.build().map(Arc::new).map_err(to_error)?);
+        }
+
+        Ok(matchers)
+    }
+
+    /// Appends the patterns read from the pattern files to [`any_patterns`](Self::any_patterns)
+    fn read_pattern_files(&self) -> core::result::Result<Vec<String>, SearchConfigError> {
+        let mut patterns = self.any_patterns.clone();
+        for path in &self.pattern_files {
+            let text = std::fs::read_to_string(path).map_err(|error| {
+                io::Error::new(error.kind(), format!("{}: {error}", path.display()))
+            })?;
+            patterns.extend(config::parse_pattern_list(&text));
+        }
+        Ok(patterns)
+    }
+
+    /// Compiles the exclude globs into a single matcher rooted at `root`, `None` if there are none
+    fn compile_exclude_patterns(
+        &self,
+        root: &OsStr,
+    ) -> core::result::Result<Option<Gitignore>, SearchConfigError> {
+        if self.exclude_patterns.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &self.exclude_patterns {
+            builder
+                .add_line(None, pattern)
+                .map_err(|error| SearchConfigError::IOError(io::Error::other(error.to_string())))?;
+        }
+
+        builder
+            .build()
+            .map(Some)
+            .map_err(|error| SearchConfigError::IOError(io::Error::other(error.to_string())))
+    }
+
+    /**
+     Resolves every root directory, in the order given.
+
+     A root inside another (or the same directory spelt differently) is dropped, as it's searched as part of that one.
+    */
+    fn resolve_roots(&self) -> core::result::Result<Vec<Box<OsStr>>, SearchConfigError> {
+        let roots = iter::once(&self.root)
+            .chain(&self.extra_roots)
+            .map(|root| self.resolve_directory(root))
+            .collect::<core::result::Result<Vec<_>, _>>()?;
+        if roots.len() == 1 {
+            return Ok(roots);
+        }
+
+        let real: Vec<PathBuf> = roots
+            .iter()
+            .map(|root| {
+                Path::new(root)
+                    .canonicalize()
+                    .unwrap_or_else(|_| root.into())
             })
-            .collect()
+            .collect();
+        // Equal roots keep the first, otherwise the outer one is kept
+        let covered = |index: usize| {
+            real.iter().enumerate().any(|(other, outer)| {
+                other != index
+                    && real[index].starts_with(outer)
+                    && (real[index] != *outer || other < index)
+            })
+        };
+        Ok(roots
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| !covered(index))
+            .map(|(_, root)| root)
+            .collect())
     }
 
     /**
-     Resolves and validates the root directory path.
+     Resolves and validates a root directory path.
 
       This function handles:
       - Default to current directory (".") if root is empty
       - Validates that the path is a directory
       - Optionally canonicalises the path if canonicalise flag is set
     */
-    fn resolve_directory(&self) -> core::result::Result<Box<OsStr>, SearchConfigError> {
-        let dir_to_use = if self.root.is_empty() {
+    fn resolve_directory(
+        &self,
+        root: &OsString,
+    ) -> core::result::Result<Box<OsStr>, SearchConfigError> {
+        let dir_to_use = if root.is_empty() {
             // Get current directory and canonicalise it for consistency
             std::env::current_dir().map(PathBuf::into_os_string)?
         } else {
-            self.root.clone()
+            root.clone()
         };
 
         let path_check = Path::new(&dir_to_use);
@@ -0,0 +1,122 @@
+/*!
+ Finding hard links, see [`Finder::group_by_inode`].
+
+ Every path to a file shares its device and inode number, so grouping the results on `(st_dev, st_ino)` finds
+ each set of hard links. Only files whose link count says another path exists are recorded, each worker thread
+ keeping its own map from `(st_dev, st_ino)` to the paths it found, and the maps are merged once the walk ends.
+*/
+
+use crate::{
+    SearchConfigError,
+    fs::DirEntry,
+    walk::{ControlFlow, Finder},
+};
+use core::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use thread_local::ThreadLocal;
+
+/// The paths found to one file, as returned by [`Finder::group_by_inode`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct InodeGroup {
+    /// The device ID of the filesystem holding the file, as found in `st_dev`
+    pub device: u64,
+    /// The inode number shared by every path in the group
+    pub inode: u64,
+    /// How many hard links the file has in total (`st_nlink`), some may be outside the search
+    pub links: u64,
+    /// The paths found, sorted
+    pub entries: Vec<DirEntry>,
+}
+
+impl Finder {
+    /**
+    Walks the filesystem and groups the results which are hard links to the same file.
+
+    Directories are skipped (they can't be hard linked), as are files with only one link. Only files found
+    under more than one path are returned, each group sorted by path and the groups sorted by their first path.
+    Compare a group's [`links`](InodeGroup::links) with its number of entries to tell if any links are
+    outside the search.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join("fdf_group_by_inode_doctest");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("original"), "shared").unwrap();
+    fs::hard_link(dir.join("original"), dir.join("link")).unwrap();
+    fs::write(dir.join("alone"), "").unwrap();
+
+    let groups = Finder::init(&dir).build().unwrap().group_by_inode().unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].links, 2);
+    let names: Vec<_> = groups[0].entries.iter().map(|entry| entry.file_name()).collect();
+    assert_eq!(names, [&b"link"[..], &b"original"[..]]);
+    fs::remove_dir_all(&dir).unwrap();
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn group_by_inode(self) -> Result<Vec<InodeGroup>, SearchConfigError> {
+        let per_thread: ThreadLocal<RefCell<HashMap<(u64, u64), InodeGroup>>> = ThreadLocal::new();
+
+        self.visit(|entry| {
+            if entry.is_dir() {
+                return ControlFlow::Continue;
+            }
+
+            // Entries which can't be statted (eg removed mid traversal) can't be grouped
+            if let Ok(statted) = entry.get_lstat() {
+                #[allow(clippy::useless_conversion)] // nlink_t isn't u64 everywhere
+                let links = u64::from(statted.st_nlink);
+                if links > 1 {
+                    let device = access_stat!(statted, st_dev);
+                    let inode = access_stat!(statted, st_ino);
+                    per_thread
+                        .get_or_default()
+                        .borrow_mut()
+                        .entry((device, inode))
+                        .or_insert_with(|| InodeGroup {
+                            device,
+                            inode,
+                            links,
+                            entries: Vec::new(),
+                        })
+                        .entries
+                        .push(entry.clone());
+                }
+            }
+            ControlFlow::Continue
+        })?;
+
+        let mut merged: HashMap<(u64, u64), InodeGroup> = HashMap::new();
+        for (key, group) in per_thread.into_iter().flat_map(RefCell::into_inner) {
+            match merged.entry(key) {
+                Entry::Occupied(mut found) => found.get_mut().entries.extend(group.entries),
+                Entry::Vacant(vacant) => {
+                    vacant.insert(group);
+                }
+            }
+        }
+
+        let mut groups: Vec<InodeGroup> = merged
+            .into_values()
+            .filter(|group| group.entries.len() > 1)
+            .collect();
+        for group in &mut groups {
+            group
+                .entries
+                .sort_unstable_by(|left, right| left.as_bytes().cmp(right.as_bytes()));
+        }
+        groups.sort_unstable_by(|left, right| {
+            left.entries[0].as_bytes().cmp(right.entries[0].as_bytes())
+        });
+        Ok(groups)
+    }
+}
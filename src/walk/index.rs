@@ -0,0 +1,609 @@
+/*!
+ A persistent index of directory listings, so repeated searches of a tree skip reading the directories which
+ haven't changed, see [`Finder::query_index`].
+
+ The index maps each directory read (by its path relative to the root) to its modification and status change
+ times, inode and entries (name, type and inode). Creating, removing or renaming an entry updates its directory's
+ modification time, so a search only needs to `stat` each directory, reading it again when that has changed.
+ The status change time is compared too, as unlike the modification time it can't be set back (eg by `touch -d`
+ or an archive extractor restoring times), so a directory changed behind such a reset is still read again. Directories
+ missing from the index (eg hidden ones, when it was built without them) are read and added as they're reached.
+
+ Indexes are saved as compact binary files under `$XDG_CACHE_HOME/fdf` (or `~/.cache/fdf`), one per root.
+
+ Searching the index runs on the calling thread, as most directories then cost a `stat` rather than a read.
+ Entry metadata (size, times, permissions) isn't stored, so filters on it still `stat` each candidate.
+*/
+
+use crate::{
+    DirEntryError, SearchConfigError, TraversalError,
+    fs::{DirEntry, FileType},
+    walk::{Finder, finder::IgnoreContext, projection::take_array, sample},
+};
+use core::{cell::Cell, ops::Deref};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::CString,
+    fs, io,
+    os::unix::ffi::OsStrExt as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The start of every index file, the last byte is the format version
+const MAGIC: &[u8; 8] = b"FDFINDX2";
+
+/// An entry of an indexed directory
+#[derive(Clone, Debug)]
+pub(super) struct Child {
+    name: Box<[u8]>,
+    file_type: FileType,
+    inode: u64,
+}
+
+impl Child {
+    /// Reads the entries of `dir`
+    pub(super) fn read_all(dir: &DirEntry, buffer_size: usize) -> Result<Vec<Self>, DirEntryError> {
+        Ok(read_direntries!(dir, buffer_size)?
+            .map(|entry| Self {
+                name: entry.file_name().into(),
+                file_type: entry.file_type,
+                inode: entry.inode,
+            })
+            .collect())
+    }
+}
+
+/// Somewhere the entries of directories are kept between searches, so they needn't all be read again
+pub(super) trait Listings {
+    /// The entries of a directory, borrowed or shared
+    type Children<'listings>: Deref<Target = [Child]>
+    where
+        Self: 'listings;
+
+    /// The entries of `dir` (`key` being its path relative to the root), read if they aren't kept or are stale
+    fn listing(
+        &mut self,
+        key: &[u8],
+        dir: &DirEntry,
+        buffer_size: usize,
+    ) -> Result<Self::Children<'_>, DirEntryError>;
+}
+
+/// A directory's entries, along with the modification and status change times and inode they were read at
+#[derive(Clone, Debug)]
+struct Listing {
+    modified: (i64, i64),
+    changed: (i64, i64),
+    inode: u64,
+    children: Vec<Child>,
+}
+
+/**
+ The directory listings found under one root, see the [module documentation](self).
+
+ # Examples
+ ```
+ use fdf::walk::{Finder, PathIndex};
+ use std::fs;
+
+ let dir = std::env::temp_dir().join("fdf_path_index_doctest");
+ let _ = fs::remove_dir_all(&dir);
+ fs::create_dir_all(dir.join("sub")).unwrap();
+ fs::write(dir.join("sub/old.txt"), "").unwrap();
+ let file = dir.with_extension("index");
+
+ let index = Finder::init(&dir).build().unwrap().build_index().unwrap();
+ index.write_to(&file).unwrap();
+
+ // Only `sub` has changed since, so it's the only directory read again
+ fs::write(dir.join("sub/new.txt"), "").unwrap();
+ let mut index = PathIndex::read_from(&dir, &file).unwrap();
+ let found = Finder::init(&dir).pattern("txt$").build().unwrap().query_index(&mut index).unwrap();
+ assert_eq!(found.len(), 2);
+ assert_eq!(index.reread(), 1);
+ fs::remove_file(&file).unwrap();
+ fs::remove_dir_all(&dir).unwrap();
+ ```
+*/
+#[derive(Clone, Debug)]
+pub struct PathIndex {
+    /// The canonical root the paths are relative to
+    root: PathBuf,
+    dirs: HashMap<Box<[u8]>, Listing>,
+    /// Directories read since the index was created or loaded
+    reread: usize,
+    /// Whether anything has changed since the index was loaded
+    dirty: bool,
+}
+
+impl PathIndex {
+    /**
+    Creates an empty index for `root`.
+
+    # Errors
+    Returns an error if `root` can't be canonicalised (eg it doesn't exist).
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        Ok(Self {
+            root: fs::canonicalize(root)?,
+            dirs: HashMap::new(),
+            reread: 0,
+            dirty: false,
+        })
+    }
+
+    /**
+    Loads the index saved for `root` by [`save`](Self::save), or an empty one if there isn't one.
+
+    An index which can't be read (or was written by an incompatible version) is treated as missing,
+    it's only a cache.
+
+    # Errors
+    Returns an error if `root` can't be canonicalised (eg it doesn't exist).
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn load<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let mut index = Self::new(root)?;
+        if let Some(dirs) = index
+            .cache_file()
+            .and_then(|file| fs::read(file).ok())
+            .and_then(|bytes| index.decode(&bytes))
+        {
+            index.dirs = dirs;
+        }
+        Ok(index)
+    }
+
+    /**
+    Loads an index for `root` written to `file` by [`write_to`](Self::write_to).
+
+    # Errors
+    Returns an error if `root` can't be canonicalised, `file` can't be read, or it isn't an index of `root`.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn read_from<P: AsRef<Path>, F: AsRef<Path>>(root: P, file: F) -> io::Result<Self> {
+        let mut index = Self::new(root)?;
+        let bytes = fs::read(file)?;
+        index.dirs = index.decode(&bytes).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an index of this root (or written by an incompatible version)",
+            )
+        })?;
+        Ok(index)
+    }
+
+    /**
+    Saves the index to its [`cache_file`](Self::cache_file) if anything has changed, creating the directory if needed.
+
+    # Errors
+    Returns an error if there is no cache directory (neither `XDG_CACHE_HOME` nor `HOME` are set) or the file
+    can't be written.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let file = self.cache_file().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither XDG_CACHE_HOME nor HOME are set",
+            )
+        })?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.write_to(&file)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /**
+    Writes the index to `file`, replacing it atomically so a concurrent reader never sees half an index.
+
+    # Errors
+    Returns an error if the file can't be written.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn write_to<F: AsRef<Path>>(&self, file: F) -> io::Result<()> {
+        let file = file.as_ref();
+        let mut partial = file.as_os_str().to_owned();
+        partial.push(format!(".{}.tmp", std::process::id()));
+
+        fs::write(&partial, self.encode())?;
+        fs::rename(&partial, file).inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })
+    }
+
+    /// Where [`save`](Self::save) and [`load`](Self::load) keep the index of this root, `None` without a cache directory
+    #[inline]
+    #[must_use]
+    pub fn cache_file(&self) -> Option<PathBuf> {
+        let cache_dir = env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        let key = sample::sample_key(0, self.root.as_os_str().as_bytes());
+        Some(cache_dir.join("fdf").join(format!("index-{key:016x}")))
+    }
+
+    /// The canonical root of the index
+    #[inline]
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The number of directories indexed
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// Returns true if no directories are indexed
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    /// The number of directories read (rather than answered from the index) since it was created or loaded
+    #[inline]
+    #[must_use]
+    pub const fn reread(&self) -> usize {
+        self.reread
+    }
+
+    /// Drops the directories which aren't in `visited`
+    fn retain(&mut self, visited: &HashSet<Box<[u8]>>) {
+        let before = self.dirs.len();
+        self.dirs.retain(|key, _| visited.contains(key));
+        self.dirty |= self.dirs.len() != before;
+    }
+
+    // Lengths are bounded by PATH_MAX/NAME_MAX and the number of directories in practice, so never truncate
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode(&self) -> Vec<u8> {
+        fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        put_bytes(&mut out, self.root.as_os_str().as_bytes());
+        out.extend_from_slice(&(self.dirs.len() as u64).to_le_bytes());
+        for (key, listing) in &self.dirs {
+            put_bytes(&mut out, key);
+            out.extend_from_slice(&listing.modified.0.to_le_bytes());
+            out.extend_from_slice(&listing.modified.1.to_le_bytes());
+            out.extend_from_slice(&listing.changed.0.to_le_bytes());
+            out.extend_from_slice(&listing.changed.1.to_le_bytes());
+            out.extend_from_slice(&listing.inode.to_le_bytes());
+            out.extend_from_slice(&(listing.children.len() as u32).to_le_bytes());
+            for child in &listing.children {
+                out.push(child.file_type as u8);
+                out.extend_from_slice(&child.inode.to_le_bytes());
+                put_bytes(&mut out, &child.name);
+            }
+        }
+        out
+    }
+
+    /// Reads back the listings written by [`encode`](Self::encode), if it's an index of this root
+    fn decode(&self, mut input: &[u8]) -> Option<HashMap<Box<[u8]>, Listing>> {
+        fn take_bytes<'input>(input: &mut &'input [u8]) -> Option<&'input [u8]> {
+            let len = usize::try_from(u32::from_le_bytes(take_array(input)?)).ok()?;
+            let (bytes, rest) = input.split_at_checked(len)?;
+            *input = rest;
+            Some(bytes)
+        }
+        let input = &mut input;
+
+        if take_array(input)? != *MAGIC || take_bytes(input)? != self.root.as_os_str().as_bytes() {
+            return None;
+        }
+        let count = u64::from_le_bytes(take_array(input)?);
+        let mut dirs = HashMap::new();
+        for _ in 0..count {
+            let key = take_bytes(input)?;
+            let modified = (
+                i64::from_le_bytes(take_array(input)?),
+                i64::from_le_bytes(take_array(input)?),
+            );
+            let changed = (
+                i64::from_le_bytes(take_array(input)?),
+                i64::from_le_bytes(take_array(input)?),
+            );
+            let inode = u64::from_le_bytes(take_array(input)?);
+            let children_count = u32::from_le_bytes(take_array(input)?);
+            let mut children = Vec::new();
+            for _ in 0..children_count {
+                let [file_type] = take_array(input)?;
+                let inode = u64::from_le_bytes(take_array(input)?);
+                let name = take_bytes(input)?;
+                children.push(Child {
+                    name: name.into(),
+                    file_type: FileType::from_dtype(file_type),
+                    inode,
+                });
+            }
+            dirs.insert(
+                key.into(),
+                Listing {
+                    modified,
+                    changed,
+                    inode,
+                    children,
+                },
+            );
+        }
+        input.is_empty().then_some(dirs)
+    }
+}
+
+impl Listings for PathIndex {
+    type Children<'listings> = &'listings [Child];
+
+    /// The entries of `dir`, read again if it has changed since it was indexed (or wasn't indexed)
+    fn listing(
+        &mut self,
+        key: &[u8],
+        dir: &DirEntry,
+        buffer_size: usize,
+    ) -> Result<&[Child], DirEntryError> {
+        // Stat before reading, so a change made during the read shows up as a change next time
+        let statted = dir.get_stat()?;
+        let modified = (
+            access_stat!(statted, st_mtime),
+            access_stat!(statted, st_mtimensec),
+        );
+        let changed = (
+            access_stat!(statted, st_ctime),
+            access_stat!(statted, st_ctimensec),
+        );
+        let inode = access_stat!(statted, st_ino);
+
+        let fresh = self.dirs.get(key).is_some_and(|listing| {
+            listing.modified == modified && listing.changed == changed && listing.inode == inode
+        });
+        if !fresh {
+            let children = Child::read_all(dir, buffer_size)?;
+            self.dirs.insert(
+                key.into(),
+                Listing {
+                    modified,
+                    changed,
+                    inode,
+                    children,
+                },
+            );
+            self.reread += 1;
+            self.dirty = true;
+        }
+
+        Ok(self
+            .dirs
+            .get(key)
+            .map_or(&[][..], |listing| &listing.children))
+    }
+}
+
+impl Finder {
+    /**
+    Walks the filesystem, recording the listing of every directory read in a new [`PathIndex`].
+
+    Which directories are read follows the same rules as a traversal (hidden, ignored, depth etc), so build
+    it with the least restrictive settings later searches will use.
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory, or
+    [`SearchConfigError::IOError`] if it can't be canonicalised.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn build_index(self) -> Result<PathIndex, SearchConfigError> {
+        let mut index = PathIndex::new(self.root_dir())?;
+        self.update_index(&mut index)?;
+        Ok(index)
+    }
+
+    /**
+    Brings `index` up to date, reading only the directories which changed since they were indexed and
+    dropping those which weren't reached (eg since removed).
+
+    Returns the number of directories read.
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory, or
+    [`SearchConfigError::IOError`] if `index` belongs to another root (or the finder has several).
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn update_index(self, index: &mut PathIndex) -> Result<usize, SearchConfigError> {
+        self.check_index_root(index)?;
+        let before = index.reread;
+        let visited = self.walk_index(index, |_| true)?;
+        if !self.is_cancelled() {
+            index.retain(&visited);
+        }
+        Ok(index.reread - before)
+    }
+
+    /**
+    Searches `index` as a traversal would search the filesystem, reading only the directories which changed
+    since they were indexed (or weren't indexed), which are updated in the index.
+
+    Results come in the order of a single threaded depth first walk, each directory after its contents.
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory, or
+    [`SearchConfigError::IOError`] if `index` belongs to another root (or the finder has several).
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn query_index(self, index: &mut PathIndex) -> Result<Vec<DirEntry>, SearchConfigError> {
+        self.check_index_root(index)?;
+        self.query_listings(index)
+    }
+
+    /// Searches through `listings`, collecting the results as [`query_index`](Self::query_index) does
+    pub(super) fn query_listings<L: Listings>(
+        &self,
+        listings: &mut L,
+    ) -> Result<Vec<DirEntry>, SearchConfigError> {
+        let mut found = Vec::new();
+        let result_limit = self.result_limit();
+        self.walk_index(listings, |entry| {
+            if result_limit
+                .as_ref()
+                .is_some_and(|result_limit| !result_limit.try_reserve(&entry))
+            {
+                return false;
+            }
+            if let Some(stats) = self.stats.as_deref() {
+                stats.record(&entry);
+            }
+            found.push(entry);
+            true
+        })?;
+        Ok(found)
+    }
+
+    /// Returns an error unless `index` is an index of this root
+    fn check_index_root(&self, index: &PathIndex) -> Result<(), SearchConfigError> {
+        if !self.extra_roots.is_empty() {
+            return Err(SearchConfigError::IOError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "an index covers a single root",
+            )));
+        }
+        if fs::canonicalize(self.root_dir())? != index.root {
+            return Err(SearchConfigError::IOError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the index belongs to another root",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Walks the tree through `listings`, stopping once `emit` returns false, and returns the keys of the directories listed
+    fn walk_index<L: Listings>(
+        &self,
+        listings: &mut L,
+        mut emit: impl FnMut(DirEntry) -> bool,
+    ) -> Result<HashSet<Box<[u8]>>, SearchConfigError> {
+        // Reversed, so the first root is walked first
+        let mut stack: Vec<(DirEntry, Arc<IgnoreContext>, u32)> = Vec::new();
+        for root in self.roots().collect::<Vec<_>>().into_iter().rev() {
+            let root = DirEntry::new(root).map_err(SearchConfigError::TraversalError)?;
+            if !root.is_traversible() {
+                return Err(SearchConfigError::NotADirectory);
+            }
+            stack.push((root, self.initial_ignore_context(), 0));
+        }
+
+        let mut visited = HashSet::new();
+
+        while let Some((dir, parent_ctx, symlinks)) = stack.pop() {
+            if self.is_cancelled() {
+                break;
+            }
+            // The same checks as reading a directory in a traversal
+            if self.matches_ignore_path(&dir)
+                || self.is_gitignored(&dir, &parent_ctx)
+                || !self.directory_or_symlink_filter(&dir)
+            {
+                continue;
+            }
+
+            let should_send_dir = self.should_send_dir(&dir);
+            let readable = (self.follow_mounts || dir.depth() == 0 || !dir.is_mount_point())
+                && !(self.prune && should_send_dir
+                    || self.is_virtual_fs(&dir)
+                    || self.is_pruned_dir(&dir));
+            if readable && self.is_at_depth_limit(&dir) {
+                if let Some(limit) = self.follow_depth_limit.as_deref() {
+                    limit.mark_hit();
+                }
+            } else if readable {
+                let ctx = self.build_ignore_context(&dir, parent_ctx);
+                let key: &[u8] = if dir.depth() == 0 {
+                    b""
+                } else {
+                    self.search_config.relative_path(&dir)
+                };
+
+                match listings.listing(key, &dir, self.getdents_buffer_size) {
+                    Ok(children) => {
+                        visited.insert(key.into());
+                        for child in children.iter() {
+                            let Some(entry) = child_entry(&dir, child) else {
+                                continue;
+                            };
+                            if !self.keep_hidden(&entry)
+                                || self.matches_ignore_path(&entry)
+                                || self.search_config.matches_exclude(&entry)
+                                || self.is_gitignored(&entry, &ctx)
+                            {
+                                continue;
+                            }
+
+                            if self.within_symlink_depth(&entry, symlinks)
+                                && self.should_traverse(&entry, None)
+                            {
+                                let symlinks = symlinks + u32::from(entry.is_symlink());
+                                stack.push((entry, Arc::clone(&ctx), symlinks));
+                            } else if self.file_filter(&entry, None) && !emit(entry) {
+                                return Ok(visited);
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        if let Some(stats) = self.stats.as_deref() {
+                            stats.record_error();
+                        }
+                        if let Some(errors_arc) = self.errors.as_ref()
+                            && let Ok(mut errors) = errors_arc.lock()
+                        {
+                            errors.push(TraversalError {
+                                dir: dir.clone(),
+                                error,
+                            });
+                        }
+                    }
+                }
+            }
+
+            if should_send_dir && !emit(dir) {
+                break;
+            }
+        }
+        Ok(visited)
+    }
+}
+
+/// The entry for `child` within `dir`
+fn child_entry(dir: &DirEntry, child: &Child) -> Option<DirEntry> {
+    let parent = dir.as_bytes();
+    let mut path = Vec::with_capacity(parent.len() + 1 + child.name.len());
+    path.extend_from_slice(parent);
+    if !parent.ends_with(b"/") {
+        path.push(b'/');
+    }
+    let file_name_index = path.len();
+    path.extend_from_slice(&child.name);
+
+    Some(DirEntry {
+        path: CString::new(path).ok()?.into_boxed_c_str(),
+        file_type: child.file_type,
+        inode: child.inode,
+        depth: dir.depth + 1,
+        file_name_index,
+        is_traversible_cache: Cell::new(None),
+        device: Cell::new(None),
+    })
+}
@@ -1,7 +1,42 @@
+mod chunks;
+#[cfg(feature = "dir-cache")]
+mod dir_cache;
+mod extensions;
 mod finder;
 mod finder_builder;
+mod hardlinks;
+mod index;
+mod ordered;
+mod parent;
+mod projection;
+mod sample;
+mod sort;
+mod spill;
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
 mod types;
+mod watch;
 
+pub use chunks::Chunks;
+#[cfg(feature = "dir-cache")]
+pub use dir_cache::DirCache;
+pub use extensions::ExtensionSummary;
 pub use finder::Finder;
 pub use finder_builder::FinderBuilder;
-pub(crate) use types::{DirEntryFilter, FilterType};
+pub use hardlinks::InodeGroup;
+pub use index::PathIndex;
+pub use ordered::ResultOrder;
+pub use parent::{EntryWithParent, ParentDir};
+pub use projection::{Fields, ProjectedEntry};
+pub use sample::SampleSize;
+pub use sort::{DEFAULT_SORT_MEMORY, SortedEntries};
+pub use stats::TraversalStats;
+#[cfg(feature = "async")]
+pub use stream::EntryStream;
+pub use types::{
+    Backend, CancellationToken, ControlFlow, DEFAULT_FOLLOW_DEPTH_LIMIT, DepthLimit, FlushPolicy,
+    StatFilter, TraversalStrategy,
+};
+pub(crate) use types::{DirEntryFilter, FilterType, StatefulFilter};
+pub use watch::WatchEvent;
@@ -0,0 +1,273 @@
+/*!
+ Results in a deterministic order without sorting them all, see [`Finder::traverse_ordered`].
+
+ The directories are still read in parallel, but each worker hands over a whole directory at a time: its
+ entries sorted by name, with the subdirectories it queued standing in for their own listings. A single
+ consumer then merges the listings in depth first or breadth first order, holding on to any which arrive
+ before they're due. Only siblings are ever compared, and results stream out as soon as everything before them
+ has been read, unlike sorting the complete set of paths (`--sort`).
+*/
+
+use crate::{
+    SearchConfigError,
+    fs::DirEntry,
+    walk::{
+        CancellationToken, ControlFlow, Finder,
+        finder::{CANCELLATION_POLL_INTERVAL, ResultLimit, Sink, Stopped},
+    },
+};
+use crossbeam_channel::{Receiver, SendTimeoutError, Sender, bounded};
+use std::{collections::HashMap, collections::VecDeque, vec};
+
+/// Listings the channel holds per worker thread, each is a whole directory so a few are plenty
+const LISTING_CHANNEL_FACTOR: usize = 4;
+
+/// The order [`Finder::traverse_ordered`] emits results in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResultOrder {
+    /// Each directory is followed by its contents, sorted by name (preorder)
+    #[default]
+    DepthFirst,
+    /// Level by level, each level in the order of the directories it was read from, then by name
+    BreadthFirst,
+}
+
+/// An entry of a directory, or one of its subdirectories whose contents have a listing of their own
+enum Item {
+    Entry(DirEntry),
+    Dir(Box<[u8]>),
+}
+
+impl Item {
+    fn path(&self) -> &[u8] {
+        match *self {
+            Self::Entry(ref entry) => entry.as_bytes(),
+            Self::Dir(ref path) => path,
+        }
+    }
+}
+
+/// Everything a worker found in one directory
+struct Listing {
+    dir: Box<[u8]>,
+    /// The directory itself, if it's a result
+    this: Option<DirEntry>,
+    items: Vec<Item>,
+}
+
+/// A [`Sink`] collecting the results of each directory into a [`Listing`], sent once the directory is done with
+struct ListingSender {
+    tx: Sender<Listing>,
+    current: Option<Listing>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl ListingSender {
+    fn push(&mut self, item: Item) {
+        if let Some(listing) = self.current.as_mut() {
+            listing.items.push(item);
+        }
+    }
+
+    /// Waits for room in the channel, giving up if the traversal is cancelled in the meantime
+    fn send_blocking(&self, mut listing: Listing) -> Result<(), Stopped> {
+        loop {
+            match self.tx.send_timeout(listing, CANCELLATION_POLL_INTERVAL) {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Timeout(returned))
+                    if !self
+                        .cancellation
+                        .as_ref()
+                        .is_some_and(CancellationToken::is_cancelled) =>
+                {
+                    listing = returned;
+                }
+                Err(_) => return Err(Stopped),
+            }
+        }
+    }
+}
+
+impl Sink for ListingSender {
+    // The directory is then only ever sent by the worker reading it, whatever the traversal strategy
+    const ENTERS_DIRECTORIES: bool = true;
+
+    fn send(&mut self, entry: DirEntry) -> Result<(), Stopped> {
+        // A directory which couldn't (or shouldn't) be read is sent once it has been tried
+        match self.current.as_mut() {
+            Some(listing) if *listing.dir == *entry.as_bytes() => listing.this = Some(entry),
+            _ => self.push(Item::Entry(entry)),
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Stopped> {
+        Ok(())
+    }
+
+    fn enter(&mut self, dir: &DirEntry, is_result: bool) -> ControlFlow {
+        if is_result && let Some(listing) = self.current.as_mut() {
+            listing.this = Some(dir.clone());
+        }
+        ControlFlow::Continue
+    }
+
+    fn begin(&mut self, dir: &DirEntry) {
+        self.current = Some(Listing {
+            dir: dir.as_bytes().into(),
+            this: None,
+            items: Vec::new(),
+        });
+    }
+
+    fn queued(&mut self, dir: &DirEntry) {
+        self.push(Item::Dir(dir.as_bytes().into()));
+    }
+
+    fn end(&mut self) -> Result<(), Stopped> {
+        let Some(mut listing) = self.current.take() else {
+            return Ok(());
+        };
+        // Sorted here, so the consumer only has to merge
+        listing
+            .items
+            .sort_unstable_by(|left, right| left.path().cmp(right.path()));
+        self.send_blocking(listing)
+    }
+}
+
+/// Merges the listings sent by the workers into a single ordered stream of results
+struct OrderedResults {
+    rx: Receiver<Listing>,
+    order: ResultOrder,
+    /// Listings which arrived before they were due, by directory
+    arrived: HashMap<Box<[u8]>, Listing>,
+    /// The listings being emitted, the front one first
+    cursors: VecDeque<vec::IntoIter<Item>>,
+    result_limit: Option<ResultLimit>,
+}
+
+impl OrderedResults {
+    /// Waits for the listing of `dir`, or returns `None` if the traversal ended without reading it (eg it was stopped)
+    fn listing(&mut self, dir: &[u8]) -> Option<Listing> {
+        if let Some(listing) = self.arrived.remove(dir) {
+            return Some(listing);
+        }
+        loop {
+            let listing = self.rx.recv().ok()?;
+            if *listing.dir == *dir {
+                return Some(listing);
+            }
+            self.arrived.insert(listing.dir.clone(), listing);
+        }
+    }
+
+    fn next_unlimited(&mut self) -> Option<DirEntry> {
+        loop {
+            let Some(item) = self.cursors.front_mut()?.next() else {
+                self.cursors.pop_front();
+                continue;
+            };
+            let dir = match item {
+                Item::Entry(entry) => return Some(entry),
+                Item::Dir(dir) => dir,
+            };
+            let Some(listing) = self.listing(&dir) else {
+                continue;
+            };
+            let items = listing.items.into_iter();
+            match self.order {
+                ResultOrder::DepthFirst => self.cursors.push_front(items),
+                ResultOrder::BreadthFirst => self.cursors.push_back(items),
+            }
+            if listing.this.is_some() {
+                return listing.this;
+            }
+        }
+    }
+}
+
+impl Iterator for OrderedResults {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<DirEntry> {
+        let entry = self.next_unlimited()?;
+        if let Some(limit) = self.result_limit.as_ref()
+            && !limit.try_reserve(&entry)
+        {
+            // Dropping the receiver stops the workers
+            self.cursors.clear();
+            self.rx = crossbeam_channel::never();
+            return None;
+        }
+        Some(entry)
+    }
+}
+
+impl Finder {
+    /**
+    Walks the filesystem like [`traverse`](Self::traverse), but emits the results in a deterministic `order`,
+    the same from one run to the next whatever the number of threads.
+
+    Siblings are ordered by name (byte by byte). This is much cheaper than sorting every result, as directories
+    are still read in parallel and only siblings are compared, but a result can't be emitted until every
+    directory before it has been read, so it's slower than the unordered walk. Directories are emitted before
+    their contents, whatever the [`TraversalStrategy`](crate::walk::TraversalStrategy).
+
+    # Examples
+    ```
+    use fdf::walk::{Finder, ResultOrder};
+    use std::num::NonZeroUsize;
+
+    let walk = |threads| {
+        Finder::init(env!("CARGO_MANIFEST_DIR"))
+            .thread_count(NonZeroUsize::new(threads))
+            .build()
+            .unwrap()
+            .traverse_ordered(ResultOrder::DepthFirst)
+            .unwrap()
+            .map(|entry| entry.as_path().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(walk(1), walk(4));
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn traverse_ordered(
+        self,
+        order: ResultOrder,
+    ) -> Result<impl Iterator<Item = DirEntry>, SearchConfigError> {
+        let roots = self
+            .roots()
+            .map(|root| {
+                DirEntry::new(root)
+                    .map(|root| Item::Dir(root.as_bytes().into()))
+                    .map_err(SearchConfigError::TraversalError)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let (tx, rx) = bounded(
+            self.thread_count
+                .get()
+                .saturating_mul(LISTING_CHANNEL_FACTOR),
+        );
+        let result_limit = self.result_limit();
+        let cancellation = self.cancellation.clone();
+        self.spawn_sinks(|| ListingSender {
+            tx: tx.clone(),
+            current: None,
+            cancellation: cancellation.clone(),
+        })?;
+
+        Ok(OrderedResults {
+            rx,
+            order,
+            arrived: HashMap::new(),
+            cursors: VecDeque::from([roots.into_iter()]),
+            result_limit,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+/*!
+ Results which keep their parent directory open, see [`Finder::traverse_with_parent`].
+
+ Each directory is already open while it's read, so rather than closing it once its entries are sent, its
+ descriptor is shared (reference counted) by every result found in it. Consumers can then run
+ `fstatat`/`openat` style calls on the parent and the entry's name without the kernel resolving the full path
+ again, long after the traversal has moved on.
+*/
+
+use crate::{
+    fs::{DirEntry, FileDes},
+    walk::{Fields, Finder, finder::DirHandle, projection::FromEntry},
+};
+use core::ffi::CStr;
+use core::fmt;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::sync::Arc;
+
+/**
+ A shared handle to an open directory, closed once the last clone is dropped.
+
+ Handles count against the same descriptor budget as the traversal's own (half of `RLIMIT_NOFILE`), so holding
+ on to a great many of them makes the rest of a traversal fall back to opening directories by their full path.
+*/
+#[derive(Clone)]
+pub struct ParentDir(Arc<DirHandle>);
+
+impl ParentDir {
+    /// Returns the descriptor of the directory, for use with [`DirEntry::get_lstatat`] and friends
+    #[inline]
+    #[must_use]
+    pub fn as_file_des(&self) -> &FileDes {
+        &self.0.0
+    }
+}
+
+impl AsRawFd for ParentDir {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.0.as_raw_fd()
+    }
+}
+
+impl AsFd for ParentDir {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.0.as_fd()
+    }
+}
+
+impl fmt::Debug for ParentDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ParentDir").field(&self.as_raw_fd()).finish()
+    }
+}
+
+/**
+ A result of [`Finder::traverse_with_parent`], the entry along with its parent directory when a handle to it
+ could be kept.
+
+ The root has no parent handle, nor do directories emitted after their contents
+ ([`TraversalStrategy::PostOrder`](crate::walk::TraversalStrategy::PostOrder)), and any entry can be
+ without one once the descriptor budget runs out or after being spilled to disk.
+*/
+#[derive(Clone, Debug)]
+pub struct EntryWithParent {
+    entry: DirEntry,
+    parent: Option<ParentDir>,
+}
+
+impl EntryWithParent {
+    /// The entry itself
+    #[inline]
+    #[must_use]
+    pub const fn entry(&self) -> &DirEntry {
+        &self.entry
+    }
+
+    /// The directory holding the entry, if a handle to it was kept
+    #[inline]
+    #[must_use]
+    pub const fn parent(&self) -> Option<&ParentDir> {
+        self.parent.as_ref()
+    }
+
+    /// The entry's name within its parent, to pass to `*at` calls alongside [`parent`](Self::parent)
+    #[inline]
+    #[must_use]
+    pub const fn file_name_cstr(&self) -> &CStr {
+        self.entry.file_name_cstr()
+    }
+
+    /// Splits the result into the entry and its parent handle
+    #[inline]
+    #[must_use]
+    pub fn into_parts(self) -> (DirEntry, Option<ParentDir>) {
+        (self.entry, self.parent)
+    }
+}
+
+impl FromEntry for EntryWithParent {
+    const WANTS_PARENT: bool = true;
+
+    #[inline]
+    fn from_entry(entry: DirEntry, _fields: Fields) -> Self {
+        Self {
+            entry,
+            parent: None,
+        }
+    }
+
+    #[inline]
+    fn from_entry_at(entry: DirEntry, _fields: Fields, parent: Option<Arc<DirHandle>>) -> Self {
+        Self {
+            entry,
+            parent: parent.map(ParentDir),
+        }
+    }
+
+    // A descriptor can't be written to disk, so spilled results come back without their parent
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.entry.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        DirEntry::decode(input).map(|entry| Self {
+            entry,
+            parent: None,
+        })
+    }
+}
+
+impl Finder {
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), keeping each result's parent directory open.
+
+    Relative calls such as [`DirEntry::get_lstatat`] on the [`ParentDir`] avoid resolving the full path again,
+    and keep working when the directory has since been renamed.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    for result in Finder::init(env!("CARGO_MANIFEST_DIR")).build().unwrap().traverse_with_parent().unwrap() {
+        if let Some(parent) = result.parent() {
+            let relative = result.entry().get_lstatat(parent.as_file_des()).unwrap();
+            let full = result.entry().get_lstat().unwrap();
+            assert_eq!(relative.st_ino, full.st_ino);
+        }
+    }
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`](crate::SearchConfigError::NotADirectory) if the root is not a directory.
+    */
+    #[inline]
+    pub fn traverse_with_parent(
+        self,
+    ) -> core::result::Result<impl Iterator<Item = EntryWithParent>, crate::SearchConfigError> {
+        self.traverse_as::<EntryWithParent>(Fields::ALL)
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    fs::{DirEntry, FileType},
+    walk::finder::DirHandle,
+};
+use chrono::{DateTime, Utc};
+use core::cell::Cell;
+use core::ffi::CStr;
+use core::ops::{BitOr, BitOrAssign};
+use std::sync::Arc;
+
+/**
+ The set of attributes to keep for each result of [`Finder::traverse_projected`](crate::walk::Finder::traverse_projected).
+
+ Fields are combined with `|`, the file type and depth are always available as they cost nothing to keep.
+
+ ```
+ use fdf::walk::Fields;
+
+ let fields = Fields::PATH | Fields::SIZE;
+ assert!(fields.contains(Fields::SIZE));
+ assert!(!fields.contains(Fields::MODIFIED));
+ ```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Fields(u8);
+
+impl Fields {
+    /// Only the file type and depth, useful for counting
+    pub const NONE: Self = Self(0);
+    /// The full path
+    pub const PATH: Self = Self(1);
+    /// The size in bytes (requires an `lstat`)
+    pub const SIZE: Self = Self(1 << 1);
+    /// The modification time (requires an `lstat`)
+    pub const MODIFIED: Self = Self(1 << 2);
+    /// Every field
+    pub const ALL: Self = Self(Self::PATH.0 | Self::SIZE.0 | Self::MODIFIED.0);
+
+    /// Returns true if every field in `other` is also in `self`
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if no optional fields are requested
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if any requested field needs the entry to be statted
+    #[inline]
+    const fn needs_stat(self) -> bool {
+        self.0 & (Self::SIZE.0 | Self::MODIFIED.0) != 0
+    }
+}
+
+impl BitOr for Fields {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Fields {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/**
+ A compact search result holding only the requested [`Fields`].
+
+ Records are built on the worker threads, so unrequested data (including the path allocation)
+ is dropped before anything crosses the result channel.
+ Fields that were requested but could not be read (eg the file vanished before it was statted) are `None`.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProjectedEntry {
+    path: Option<Box<CStr>>,
+    size: Option<u64>,
+    modified: Option<DateTime<Utc>>,
+    depth: u32,
+    file_type: FileType,
+}
+
+impl ProjectedEntry {
+    /// The path as bytes, if [`Fields::PATH`] was requested
+    #[inline]
+    #[must_use]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        self.path.as_deref().map(CStr::to_bytes)
+    }
+
+    /// The path as a `CStr`, if [`Fields::PATH`] was requested
+    #[inline]
+    #[must_use]
+    pub fn as_cstr(&self) -> Option<&CStr> {
+        self.path.as_deref()
+    }
+
+    /// The size in bytes, if [`Fields::SIZE`] was requested, see [`DirEntry::file_size`]
+    #[inline]
+    #[must_use]
+    pub const fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// The modification time, if [`Fields::MODIFIED`] was requested, see [`DirEntry::modified_time`]
+    #[inline]
+    #[must_use]
+    pub const fn modified(&self) -> Option<DateTime<Utc>> {
+        self.modified
+    }
+
+    /// The file type of the entry
+    #[inline]
+    #[must_use]
+    pub const fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The depth of the entry relative to the root
+    #[inline]
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.depth as _
+    }
+}
+
+/// Serialises every field, with unrequested ones as `null`. Paths which aren't valid UTF-8 are converted lossily.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ProjectedEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct as _;
+        let mut state = serializer.serialize_struct("ProjectedEntry", 5)?;
+        state.serialize_field("path", &self.as_bytes().map(String::from_utf8_lossy))?;
+        state.serialize_field("file_type", &self.file_type)?;
+        state.serialize_field("depth", &self.depth)?;
+        state.serialize_field("size", &self.size)?;
+        state.serialize_field("modified", &self.modified)?;
+        state.end()
+    }
+}
+
+/// Conversion applied to each result on the worker thread before it is sent to the receiver
+pub(crate) trait FromEntry: Send + Sized + 'static {
+    /// Whether the result keeps a handle to its parent directory, built with [`from_entry_at`](Self::from_entry_at)
+    const WANTS_PARENT: bool = false;
+
+    fn from_entry(entry: DirEntry, fields: Fields) -> Self;
+
+    /// Like [`from_entry`](Self::from_entry), given the parent directory's handle if one was kept
+    fn from_entry_at(entry: DirEntry, fields: Fields, _parent: Option<Arc<DirHandle>>) -> Self {
+        Self::from_entry(entry, fields)
+    }
+
+    /// Appends a compact binary form of the result, used to spill batches to disk
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Reads back a result written by [`encode`](Self::encode), advancing `input` past it
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+pub(super) fn take_array<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, rest) = input.split_first_chunk::<N>()?;
+    *input = rest;
+    Some(*bytes)
+}
+
+fn take_path(input: &mut &[u8]) -> Option<Box<CStr>> {
+    let len = u32::from_le_bytes(take_array(input)?) as usize;
+    let (bytes, rest) = input.split_at_checked(len)?;
+    *input = rest;
+    CStr::from_bytes_with_nul(bytes).ok().map(Box::from)
+}
+
+fn put_path(out: &mut Vec<u8>, path: &CStr) {
+    let bytes = path.to_bytes_with_nul();
+    // Paths are bounded by PATH_MAX in practice, so this never truncates
+    #[allow(clippy::cast_possible_truncation)]
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+impl FromEntry for DirEntry {
+    #[inline]
+    fn from_entry(entry: DirEntry, _fields: Fields) -> Self {
+        entry
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_path(out, &self.path);
+        out.push(self.file_type as u8);
+        out.extend_from_slice(&self.inode.to_le_bytes());
+        out.extend_from_slice(&self.depth.to_le_bytes());
+        out.extend_from_slice(&(self.file_name_index as u64).to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let path = take_path(input)?;
+        let [file_type] = take_array(input)?;
+        let inode = u64::from_le_bytes(take_array(input)?);
+        let depth = u32::from_le_bytes(take_array(input)?);
+        let file_name_index = usize::try_from(u64::from_le_bytes(take_array(input)?)).ok()?;
+        (file_name_index <= path.to_bytes().len()).then(|| Self {
+            path,
+            file_type: FileType::from_dtype(file_type),
+            inode,
+            depth,
+            file_name_index,
+            is_traversible_cache: Cell::new(None),
+            device: Cell::new(None),
+        })
+    }
+}
+
+impl FromEntry for ProjectedEntry {
+    #[expect(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        reason = "needs to be in u32 for chrono"
+    )]
+    fn from_entry(entry: DirEntry, fields: Fields) -> Self {
+        let statted = fields
+            .needs_stat()
+            .then(|| entry.get_lstat().ok())
+            .flatten();
+
+        let size = statted
+            .filter(|_| fields.contains(Fields::SIZE))
+            .map(|s| s.st_size.cast_unsigned() as _);
+        let modified = statted
+            .filter(|_| fields.contains(Fields::MODIFIED))
+            .and_then(|s| {
+                DateTime::from_timestamp(access_stat!(s, st_mtime), access_stat!(s, st_mtimensec))
+            });
+
+        Self {
+            depth: entry.depth,
+            file_type: entry.file_type,
+            size,
+            modified,
+            path: fields.contains(Fields::PATH).then(|| entry.to_inner()),
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let present = u8::from(self.path.is_some())
+            | (u8::from(self.size.is_some()) << 1)
+            | (u8::from(self.modified.is_some()) << 2);
+        out.push(present);
+        out.push(self.file_type as u8);
+        out.extend_from_slice(&self.depth.to_le_bytes());
+        if let Some(path) = self.path.as_deref() {
+            put_path(out, path);
+        }
+        if let Some(size) = self.size {
+            out.extend_from_slice(&size.to_le_bytes());
+        }
+        if let Some(modified) = self.modified {
+            out.extend_from_slice(&modified.timestamp().to_le_bytes());
+            out.extend_from_slice(&modified.timestamp_subsec_nanos().to_le_bytes());
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let [present, file_type] = take_array(input)?;
+        let depth = u32::from_le_bytes(take_array(input)?);
+        let path = if present & 1 == 0 {
+            None
+        } else {
+            Some(take_path(input)?)
+        };
+        let size = if present & (1 << 1) == 0 {
+            None
+        } else {
+            Some(u64::from_le_bytes(take_array(input)?))
+        };
+        let modified = if present & (1 << 2) == 0 {
+            None
+        } else {
+            let seconds = i64::from_le_bytes(take_array(input)?);
+            let nanos = u32::from_le_bytes(take_array(input)?);
+            Some(DateTime::from_timestamp(seconds, nanos)?)
+        };
+        Some(Self {
+            path,
+            size,
+            modified,
+            depth,
+            file_type: FileType::from_dtype(file_type),
+        })
+    }
+}
@@ -0,0 +1,233 @@
+/*!
+ Uniform random sampling and shuffling of search results, see [`Finder::sample`], [`Finder::shuffle`] and
+ [`FinderBuilder::sample_ratio`](crate::walk::FinderBuilder::sample_ratio).
+
+ Every result is given a pseudo random key derived from its path and a per traversal seed.
+ A ratio sample keeps the results whose key falls below a threshold, a count sample keeps the results with the
+ smallest keys ("bottom-k" sampling, a form of reservoir sampling). As a key depends on nothing but the path and
+ the seed, each worker thread samples what it finds independently and the per thread samples are merged at the
+ end, giving the same sample whichever thread happened to find which entry. Shuffling sorts by the same keys,
+ so a fixed [`seed`](crate::walk::FinderBuilder::seed) makes both reproducible.
+*/
+
+use crate::{
+    SearchConfigError,
+    fs::DirEntry,
+    walk::{ControlFlow, Finder},
+};
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher as _, Hasher as _};
+use core::num::NonZeroUsize;
+use core::str::FromStr;
+use std::collections::BinaryHeap;
+use std::collections::hash_map::RandomState;
+use thread_local::ThreadLocal;
+
+/**
+ How many results to sample, as parsed from `--sample`.
+
+ ```
+ use fdf::walk::SampleSize;
+ use core::num::NonZeroUsize;
+
+ assert_eq!("0.25".parse(), Ok(SampleSize::Ratio(0.25)));
+ assert_eq!("10%".parse(), Ok(SampleSize::Ratio(0.1)));
+ assert_eq!("100".parse(), Ok(SampleSize::Count(NonZeroUsize::new(100).unwrap())));
+ assert!("0".parse::<SampleSize>().is_err());
+ assert!("1.5".parse::<SampleSize>().is_err());
+ ```
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(clippy::exhaustive_enums)]
+pub enum SampleSize {
+    /// Keep each result with this probability, in `(0, 1]`
+    Ratio(f64),
+    /// Keep exactly this many results (or all of them, if there are fewer)
+    Count(NonZeroUsize),
+}
+
+impl FromStr for SampleSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ratio = if let Some(percent) = s.strip_suffix('%') {
+            percent.parse::<f64>().ok().map(|percent| percent / 100.0)
+        } else if s.contains(['.', 'e', 'E']) {
+            s.parse::<f64>().ok()
+        } else {
+            return s.parse::<NonZeroUsize>().map(Self::Count).map_err(|_| {
+                format!(
+                    "invalid sample size '{s}', expected a ratio (0.1, 10%) or a positive count"
+                )
+            });
+        };
+
+        ratio
+            .filter(|ratio| *ratio > 0.0 && *ratio <= 1.0)
+            .map(Self::Ratio)
+            .ok_or_else(|| {
+                format!("invalid sample ratio '{s}', expected a value in (0, 1] or (0%, 100%]")
+            })
+    }
+}
+
+/// A seed which differs from run to run
+pub(crate) fn random_seed() -> u64 {
+    // `RandomState` is seeded from the OS, which saves depending on an RNG crate
+    RandomState::new().build_hasher().finish()
+}
+
+/// The sampling key of a path, uniformly distributed over `u64` for a given seed
+pub(crate) fn sample_key(seed: u64, path: &[u8]) -> u64 {
+    // FNV-1a, then a splitmix64 finaliser to spread the (weak) hash evenly
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    let hash = path.iter().fold(FNV_OFFSET, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+
+    let mut mixed = hash ^ seed;
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    mixed ^ (mixed >> 31)
+}
+
+/// The largest key kept by a ratio sample
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    reason = "the ratio is in (0, 1], so this stays within u64 (saturating at the top)"
+)]
+pub(crate) fn ratio_threshold(ratio: f64) -> u64 {
+    (ratio.clamp(0.0, 1.0) * u64::MAX as f64) as u64
+}
+
+/// A sampled entry, ordered by its key alone
+struct Keyed {
+    key: u64,
+    entry: DirEntry,
+}
+
+impl PartialEq for Keyed {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Keyed {}
+
+impl PartialOrd for Keyed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Keyed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl Finder {
+    /// Returns the seed used for sampling and shuffling, pass it to [`FinderBuilder::seed`](crate::walk::FinderBuilder::seed) to repeat a run
+    #[inline]
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /**
+    Walks the filesystem and returns every result in a random order, which only depends on the
+    [`seed`](Self::seed) and the paths found.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+
+    let shuffle = || {
+        Finder::init(env!("CARGO_MANIFEST_DIR"))
+            .seed(Some(42))
+            .build()
+            .unwrap()
+            .shuffle()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.as_path().to_owned())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(shuffle(), shuffle());
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn shuffle(self) -> Result<Vec<DirEntry>, SearchConfigError> {
+        let seed = self.seed;
+        let mut entries: Vec<DirEntry> = self.traverse()?.collect();
+        entries.sort_by_cached_key(|entry| sample_key(seed, entry.as_bytes()));
+        Ok(entries)
+    }
+
+    /**
+    Walks the filesystem and returns a uniform random sample of `count` results (or every result if there
+    are fewer), in random order.
+
+    Each worker thread keeps its own sample of at most `count` entries, so memory use is bounded by
+    `count` times the number of threads regardless of the size of the tree. The sample and its order only
+    depend on the [`seed`](Self::seed) and the paths found.
+
+    # Examples
+    ```
+    use core::num::NonZeroUsize;
+    use fdf::walk::Finder;
+
+    let sample = Finder::init(env!("CARGO_MANIFEST_DIR"))
+        .build()
+        .unwrap()
+        .sample(NonZeroUsize::new(5).unwrap())
+        .unwrap();
+    assert_eq!(sample.len(), 5);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn sample(self, count: NonZeroUsize) -> Result<Vec<DirEntry>, SearchConfigError> {
+        let count = count.get();
+        let seed = self.seed;
+        let reservoirs: ThreadLocal<RefCell<BinaryHeap<Keyed>>> = ThreadLocal::new();
+
+        self.visit(|entry| {
+            let key = sample_key(seed, entry.as_bytes());
+            let mut reservoir = reservoirs.get_or_default().borrow_mut();
+            // A max heap, so the largest key is the one to evict
+            if reservoir.len() < count {
+                reservoir.push(Keyed {
+                    key,
+                    entry: entry.clone(),
+                });
+            } else if let Some(mut largest) = reservoir.peek_mut()
+                && key < largest.key
+            {
+                *largest = Keyed {
+                    key,
+                    entry: entry.clone(),
+                };
+            }
+            ControlFlow::Continue
+        })?;
+
+        let mut merged: Vec<Keyed> = reservoirs
+            .into_iter()
+            .flat_map(|reservoir| reservoir.into_inner().into_vec())
+            .collect();
+        merged.sort_unstable();
+        merged.truncate(count);
+        Ok(merged.into_iter().map(|keyed| keyed.entry).collect())
+    }
+}
@@ -0,0 +1,221 @@
+/*!
+ Sorting results by path with bounded memory, used by `--sort`.
+
+ Entries are gathered until they'd take more than the memory limit, then sorted and written to a [`SpillFile`]
+ as a run. Once the input is exhausted the runs (and whatever is still in memory) are merged, holding only a
+ batch of each run at a time. Nothing touches the disk if everything fits.
+*/
+
+use crate::fs::DirEntry;
+use crate::walk::spill::SpillFile;
+use core::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::{io, vec};
+
+/// Default memory limit for [`SortedEntries`], 256MiB
+pub const DEFAULT_SORT_MEMORY: usize = 256 << 20;
+
+/// Entries written (and read back) from a run at once
+const RUN_BATCH: usize = 4096;
+
+/// Approximate bytes an entry occupies while it's held in memory
+const fn entry_footprint(entry: &DirEntry) -> usize {
+    size_of::<DirEntry>() + entry.len() + 1
+}
+
+fn sort_by_path(entries: &mut [DirEntry]) {
+    entries.sort_by(|left, right| left.as_bytes().cmp(right.as_bytes()));
+}
+
+/// A sorted run, read back a batch at a time
+#[derive(Debug)]
+struct Run {
+    /// `None` for the run that never left memory
+    file: Option<SpillFile>,
+    batch: vec::IntoIter<DirEntry>,
+}
+
+impl Run {
+    /// Sorts `entries` and writes them to a new spill file, leaving `entries` empty
+    fn spill(entries: &mut Vec<DirEntry>) -> io::Result<Self> {
+        sort_by_path(entries);
+        let mut file = SpillFile::create()?;
+        for batch in entries.chunks(RUN_BATCH) {
+            file.push(batch)?;
+        }
+        entries.clear();
+        Ok(Self {
+            file: Some(file),
+            batch: Vec::new().into_iter(),
+        })
+    }
+
+    fn next(&mut self) -> io::Result<Option<DirEntry>> {
+        if let Some(entry) = self.batch.next() {
+            return Ok(Some(entry));
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(None);
+        };
+        let Some((batch, len)) = file.front()? else {
+            return Ok(None);
+        };
+        file.advance(len);
+        self.batch = batch.into_iter();
+        Ok(self.batch.next())
+    }
+}
+
+/// The smallest unmerged entry of a run
+#[derive(Debug)]
+struct Head {
+    entry: DirEntry,
+    run: usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    // Earlier runs win ties, so equal paths keep the order they were found in
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entry
+            .as_bytes()
+            .cmp(other.entry.as_bytes())
+            .then(self.run.cmp(&other.run))
+    }
+}
+
+#[derive(Debug)]
+enum Sorted {
+    Memory(vec::IntoIter<DirEntry>),
+    Merge {
+        runs: Vec<Run>,
+        heap: BinaryHeap<Reverse<Head>>,
+    },
+}
+
+/**
+ Entries sorted by path, as the bytes compare, spilling to disk rather than holding more than a set amount in memory.
+
+ A read error while merging ends the iteration early, check [`take_error`](Self::take_error) once it's done.
+
+ ```
+ use fdf::walk::{Finder, SortedEntries};
+
+ let finder = Finder::init(env!("CARGO_MANIFEST_DIR")).build().unwrap();
+ // A tiny limit, so every few entries are written out as a run
+ let mut sorted = SortedEntries::new(finder.traverse().unwrap(), 4096).unwrap();
+ assert!(sorted.spilled_runs() > 0);
+
+ let paths: Vec<_> = sorted.by_ref().map(|entry| entry.as_bytes().to_vec()).collect();
+ assert!(sorted.take_error().is_none());
+ assert!(paths.is_sorted());
+ ```
+*/
+#[derive(Debug)]
+pub struct SortedEntries {
+    sorted: Sorted,
+    spilled_runs: usize,
+    error: Option<io::Error>,
+}
+
+impl SortedEntries {
+    /**
+     Sorts `entries`, holding roughly `memory_limit` bytes of them in memory at a time.
+
+     # Errors
+     Returns an error if a run couldn't be written to (or its first batch read back from) a temporary file.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new<I>(entries: I, memory_limit: usize) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = DirEntry>,
+    {
+        let mut runs = Vec::new();
+        let mut chunk = Vec::new();
+        let mut used = 0;
+        for entry in entries {
+            used += entry_footprint(&entry);
+            chunk.push(entry);
+            if used > memory_limit {
+                runs.push(Run::spill(&mut chunk)?);
+                used = 0;
+            }
+        }
+
+        let spilled_runs = runs.len();
+        sort_by_path(&mut chunk);
+        if runs.is_empty() {
+            return Ok(Self {
+                sorted: Sorted::Memory(chunk.into_iter()),
+                spilled_runs,
+                error: None,
+            });
+        }
+
+        runs.push(Run {
+            file: None,
+            batch: chunk.into_iter(),
+        });
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (index, run) in runs.iter_mut().enumerate() {
+            if let Some(entry) = run.next()? {
+                heap.push(Reverse(Head { entry, run: index }));
+            }
+        }
+
+        Ok(Self {
+            sorted: Sorted::Merge { runs, heap },
+            spilled_runs,
+            error: None,
+        })
+    }
+
+    /// Number of sorted runs written to disk, `0` if everything fit in memory
+    #[inline]
+    #[must_use]
+    pub const fn spilled_runs(&self) -> usize {
+        self.spilled_runs
+    }
+
+    /// Takes the error which ended the iteration early, if any
+    #[inline]
+    pub const fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+impl Iterator for SortedEntries {
+    type Item = DirEntry;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (runs, heap) = match &mut self.sorted {
+            Sorted::Memory(entries) => return entries.next(),
+            Sorted::Merge { runs, heap } => (runs, heap),
+        };
+
+        let Reverse(Head { entry, run }) = heap.pop()?;
+        match runs[run].next() {
+            Ok(Some(next)) => heap.push(Reverse(Head { entry: next, run })),
+            Ok(None) => {}
+            Err(error) => {
+                self.error = Some(error);
+                heap.clear();
+            }
+        }
+        Some(entry)
+    }
+}
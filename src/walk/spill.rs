@@ -0,0 +1,122 @@
+/*!
+ On disk overflow storage for result batches, used by [`FlushPolicy::SpillToDisk`](crate::walk::FlushPolicy::SpillToDisk).
+
+ Batches are appended to an anonymous temporary file (it's unlinked as soon as it's created, so nothing is left
+ behind even if the process is killed) and read back in the order they were written.
+*/
+
+use crate::walk::projection::FromEntry;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{FileExt as _, OpenOptionsExt as _};
+
+/// A first in first out queue of batches backed by an unlinked temporary file
+#[derive(Debug)]
+pub(crate) struct SpillFile {
+    file: File,
+    /// Offset of the oldest unread batch
+    read: u64,
+    /// Offset the next batch is written at
+    write: u64,
+    /// Reused encoding buffer
+    buffer: Vec<u8>,
+}
+
+impl SpillFile {
+    /// Creates a new spill file in [`std::env::temp_dir`]
+    pub(crate) fn create() -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+
+        loop {
+            let path = dir.join(format!(
+                "fdf-spill-{pid}-{}",
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => {
+                    fs::remove_file(&path)?;
+                    return Ok(Self {
+                        file,
+                        read: 0,
+                        write: 0,
+                        buffer: Vec::new(),
+                    });
+                }
+                // Left over from a previous process with the same pid
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Returns true if every spilled batch has been read back
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.read == self.write
+    }
+
+    /// Appends a batch to the end of the queue
+    pub(crate) fn push<T: FromEntry>(&mut self, batch: &[T]) -> io::Result<()> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&[0; 4]);
+        for item in batch {
+            item.encode(&mut self.buffer);
+        }
+        let len = u32::try_from(self.buffer.len() - 4)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "batch is too large"))?;
+        self.buffer[..4].copy_from_slice(&len.to_le_bytes());
+
+        self.file.write_all_at(&self.buffer, self.write)?;
+        self.write += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /**
+     Reads the oldest batch without removing it, along with the number of bytes it occupies.
+
+     Pass that to [`advance`](Self::advance) once the batch has been handed over.
+    */
+    pub(crate) fn front<T: FromEntry>(&mut self) -> io::Result<Option<(Vec<T>, u64)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let mut len = [0; 4];
+        self.file.read_exact_at(&mut len, self.read)?;
+        self.buffer.resize(u32::from_le_bytes(len) as usize, 0);
+        self.file.read_exact_at(&mut self.buffer, self.read + 4)?;
+
+        let mut input = self.buffer.as_slice();
+        let mut batch = Vec::new();
+        while !input.is_empty() {
+            batch.push(T::decode(&mut input).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "corrupt spilled batch")
+            })?);
+        }
+        Ok(Some((batch, 4 + self.buffer.len() as u64)))
+    }
+
+    /// Removes the oldest batch, reclaiming the disk space once the queue is empty
+    pub(crate) fn advance(&mut self, len: u64) {
+        self.read += len;
+        if self.is_empty() {
+            self.clear();
+        }
+    }
+
+    /// Discards every spilled batch
+    pub(crate) fn clear(&mut self) {
+        self.read = 0;
+        self.write = 0;
+        // Failing to shrink only wastes space, the offsets are what matter
+        let _ = self.file.set_len(0);
+    }
+}
@@ -0,0 +1,177 @@
+/*!
+ Totals for a traversal, see [`FinderBuilder::collect_stats`](crate::walk::FinderBuilder::collect_stats).
+
+ The workers share one set of relaxed atomic counters, updated as each result is emitted. The deepest path and
+ largest file are tracked with an atomic maximum, so their lock is only taken when a new record is set.
+ With collection disabled the workers don't touch any of this.
+*/
+
+use crate::fs::{DirEntry, FileType};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Every file type, in the order the counts are reported
+const FILE_TYPES: [FileType; 8] = [
+    FileType::RegularFile,
+    FileType::Directory,
+    FileType::Symlink,
+    FileType::BlockDevice,
+    FileType::CharDevice,
+    FileType::Pipe,
+    FileType::Socket,
+    FileType::Unknown,
+];
+
+/// The position of `file_type` in [`FILE_TYPES`]
+const fn slot(file_type: FileType) -> usize {
+    match file_type {
+        FileType::RegularFile => 0,
+        FileType::Directory => 1,
+        FileType::Symlink => 2,
+        FileType::BlockDevice => 3,
+        FileType::CharDevice => 4,
+        FileType::Pipe => 5,
+        FileType::Socket => 6,
+        FileType::Unknown => 7,
+    }
+}
+
+/**
+ Counts of the results of a traversal, shared with the worker threads while it runs.
+
+ Fetch it with [`Finder::stats`](crate::walk::Finder::stats) before starting the traversal, the totals are
+ complete once the traversal has finished (eg the iterator is exhausted).
+
+ ```
+ use fdf::{fs::FileType, walk::Finder};
+
+ let finder = Finder::init(env!("CARGO_MANIFEST_DIR")).collect_stats(true).build().unwrap();
+ let stats = finder.stats().unwrap();
+ let results = finder.traverse().unwrap().count() as u64;
+
+ assert_eq!(stats.total(), results);
+ assert!(stats.count(FileType::RegularFile) > 0);
+ assert!(stats.total_bytes() > 0);
+ assert!(stats.deepest().is_some_and(|entry| entry.depth() >= 1));
+ ```
+*/
+#[derive(Debug, Default)]
+pub struct TraversalStats {
+    counts: [AtomicU64; FILE_TYPES.len()],
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    /// One more than the depth of [`deepest`](Self::deepest), or 0 before anything is recorded
+    max_depth: AtomicU32,
+    deepest: Mutex<Option<DirEntry>>,
+    /// One more than the size of [`largest`](Self::largest), or 0 before anything is recorded
+    max_size: AtomicU64,
+    largest: Mutex<Option<(DirEntry, u64)>>,
+}
+
+impl TraversalStats {
+    /// Creates an empty set of counters
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of results of the given type
+    #[inline]
+    #[must_use]
+    pub fn count(&self, file_type: FileType) -> u64 {
+        self.counts[slot(file_type)].load(Ordering::Relaxed)
+    }
+
+    /// The number of results of each type, including types with none
+    #[inline]
+    pub fn counts(&self) -> impl Iterator<Item = (FileType, u64)> + '_ {
+        FILE_TYPES
+            .into_iter()
+            .map(|file_type| (file_type, self.count(file_type)))
+    }
+
+    /// The number of results of every type
+    #[inline]
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// The combined size of the regular files found, in bytes
+    #[inline]
+    #[must_use]
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The number of directories which couldn't be read (whether or not errors are collected)
+    #[inline]
+    #[must_use]
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// The result furthest below the root, the first found if several share the depth
+    #[inline]
+    #[must_use]
+    pub fn deepest(&self) -> Option<DirEntry> {
+        self.deepest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The largest regular file found along with its size, the first found if several share the size
+    #[inline]
+    #[must_use]
+    pub fn largest(&self) -> Option<(DirEntry, u64)> {
+        self.largest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Counts a result about to be emitted
+    pub(crate) fn record(&self, entry: &DirEntry) {
+        self.counts[slot(entry.file_type)].fetch_add(1, Ordering::Relaxed);
+
+        // Only a new record takes the lock, checking again inside as another thread may have beaten this one
+        if self.max_depth.fetch_max(entry.depth + 1, Ordering::Relaxed) <= entry.depth {
+            let mut deepest = self.deepest.lock().unwrap_or_else(PoisonError::into_inner);
+            if deepest
+                .as_ref()
+                .is_none_or(|found| found.depth < entry.depth)
+            {
+                *deepest = Some(entry.clone());
+            }
+        }
+
+        if !entry.is_regular_file() {
+            return;
+        }
+        // Files removed since being read can't be sized
+        let Ok(size) = entry.file_size() else {
+            return;
+        };
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+        if self
+            .max_size
+            .fetch_max(size.saturating_add(1), Ordering::Relaxed)
+            <= size
+        {
+            let mut largest = self.largest.lock().unwrap_or_else(PoisonError::into_inner);
+            if largest.as_ref().is_none_or(|(_, found)| *found < size) {
+                *largest = Some((entry.clone(), size));
+            }
+        }
+    }
+
+    /// Counts a directory which couldn't be read
+    pub(crate) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,140 @@
+/*!
+ Async integration, enabled by the `async` feature.
+
+ The walk itself stays on the usual worker threads (it's nothing but blocking syscalls), a blocking Tokio task
+ moves the batches they produce into an async channel, so executors are never blocked waiting on the filesystem.
+*/
+
+use crate::{
+    SearchConfigError,
+    fs::DirEntry,
+    walk::{CancellationToken, Finder},
+};
+use core::{
+    num::NonZeroUsize,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+/// Entries per batch handed from the worker threads to the async side
+const STREAM_BATCH_SIZE: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+/// Batches buffered between the worker threads and the async side
+const STREAM_CHANNEL_CAPACITY: usize = 4;
+
+/**
+ A [`Stream`] of the results of [`Finder::traverse_async`].
+
+ Dropping the stream (or calling [`cancel`](Self::cancel)) stops the traversal.
+*/
+#[derive(Debug)]
+pub struct EntryStream {
+    receiver: mpsc::Receiver<Vec<DirEntry>>,
+    batch: std::vec::IntoIter<DirEntry>,
+    cancellation: CancellationToken,
+}
+
+impl EntryStream {
+    /**
+     Stops the traversal, the stream then ends once the results already sent have been yielded.
+
+     This never blocks, so it's fine to call from async code (eg the losing branch of a `select!`).
+    */
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Returns a token which cancels this traversal, to hand to other tasks
+    #[inline]
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Stream for EntryStream {
+    type Item = DirEntry;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(entry) = self.batch.next() {
+                return Poll::Ready(Some(entry));
+            }
+
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(batch)) => self.batch = batch.into_iter(),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Drop for EntryStream {
+    #[inline]
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Finder {
+    /**
+    Traverses the filesystem like [`traverse`](Self::traverse), but yields the results as an async [`Stream`].
+
+    If the finder already has a [`CancellationToken`], cancelling it also ends the stream.
+
+    # Examples
+    ```
+    use fdf::walk::Finder;
+    use std::future::poll_fn;
+    use std::pin::Pin;
+    use futures_core::Stream;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let count = runtime.block_on(async {
+        let mut stream = Finder::init(env!("CARGO_MANIFEST_DIR"))
+            .build()
+            .unwrap()
+            .traverse_async()
+            .unwrap();
+
+        let mut count = 0;
+        while poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await.is_some() {
+            count += 1;
+        }
+        count
+    });
+    assert!(count > 0);
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory.
+
+    # Panics
+    Panics if called outside of a Tokio runtime.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn traverse_async(mut self) -> Result<EntryStream, SearchConfigError> {
+        let cancellation = self.cancellation.get_or_insert_default().clone();
+        let batches = self.traverse_streaming(STREAM_BATCH_SIZE, STREAM_CHANNEL_CAPACITY)?;
+        let (sender, receiver) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+
+        tokio::task::spawn_blocking(move || {
+            // Either side hanging up ends the forwarding (and the walk, once `batches` is dropped)
+            for batch in batches {
+                if sender.blocking_send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(EntryStream {
+            receiver,
+            batch: Vec::new().into_iter(),
+            cancellation,
+        })
+    }
+}
@@ -2,9 +2,316 @@ use crate::{
     SearchConfig,
     fs::{DirEntry, FileDes},
 };
+use core::num::NonZeroU32;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
 /// Filter function type for directory entries,
 pub type FilterType =
     fn(&SearchConfig, &DirEntry, Option<DirEntryFilter>, Option<&FileDes>) -> bool;
 /// Generic filter function type for directory entries
 pub type DirEntryFilter = fn(&DirEntry) -> bool;
+/// Filter function type given an entry and its `lstat`, see [`FinderBuilder::filter_by_metadata`](crate::walk::FinderBuilder::filter_by_metadata)
+pub type StatFilter = fn(&DirEntry, &libc::stat) -> bool;
+
+/// The closure behind a [`StatefulFilter`], given the `lstat` when it asked for one
+type StatefulFn = dyn Fn(&DirEntry, Option<&libc::stat>) -> bool + Send + Sync;
+
+/**
+ A filter closure which may keep state, see [`FinderBuilder::filter_shared`](crate::walk::FinderBuilder::filter_shared)
+ and [`FinderBuilder::filter_mut`](crate::walk::FinderBuilder::filter_mut).
+
+ There is exactly one instance of the closure per traversal, shared by every worker thread. Closures registered
+ with [`FinderBuilder::filter_by_metadata_shared`](crate::walk::FinderBuilder::filter_by_metadata_shared) (or its
+ `_mut` counterpart) are also given the entry's `lstat`. The same wrapper holds the
+ [`FinderBuilder::prune_dirs`](crate::walk::FinderBuilder::prune_dirs) closure.
+*/
+#[derive(Clone)]
+pub(crate) struct StatefulFilter {
+    filter: Arc<StatefulFn>,
+    /// Whether the closure looks at the `lstat`, which is only taken if so
+    wants_stat: bool,
+}
+
+impl StatefulFilter {
+    /// Wraps a closure which handles its own synchronisation (eg by using atomics)
+    pub(crate) fn shared<F>(filter: F) -> Self
+    where
+        F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            filter: Arc::new(move |entry, _| filter(entry)),
+            wants_stat: false,
+        }
+    }
+
+    /// Wraps a closure behind a lock, so calls from different threads take turns
+    pub(crate) fn exclusive<F>(mut filter: F) -> Self
+    where
+        F: FnMut(&DirEntry) -> bool + Send + 'static,
+    {
+        Self::exclusive_with_stat(move |entry, _| filter(entry)).without_stat()
+    }
+
+    /// Wraps a closure given the `lstat` too, which handles its own synchronisation
+    pub(crate) fn shared_with_stat<F>(filter: F) -> Self
+    where
+        F: Fn(&DirEntry, Option<&libc::stat>) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            filter: Arc::new(filter),
+            wants_stat: true,
+        }
+    }
+
+    /// Wraps a closure given the `lstat` too behind a lock
+    pub(crate) fn exclusive_with_stat<F>(filter: F) -> Self
+    where
+        F: FnMut(&DirEntry, Option<&libc::stat>) -> bool + Send + 'static,
+    {
+        let filter = Mutex::new(filter);
+        Self::shared_with_stat(move |entry, statted| {
+            (filter.lock().unwrap_or_else(PoisonError::into_inner))(entry, statted)
+        })
+    }
+
+    const fn without_stat(mut self) -> Self {
+        self.wants_stat = false;
+        self
+    }
+
+    /// Runs the closure, taking the `lstat` (relative to `opt_fd` when provided) if it wants one
+    #[inline]
+    pub(crate) fn matches_at(&self, entry: &DirEntry, opt_fd: Option<&FileDes>) -> bool {
+        if !self.wants_stat {
+            return (self.filter)(entry, None);
+        }
+
+        let statted = opt_fd
+            .map_or_else(|| entry.get_lstat(), |fd| entry.get_lstatat(fd))
+            .ok();
+        (self.filter)(entry, statted.as_ref())
+    }
+}
+
+impl core::fmt::Debug for StatefulFilter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("StatefulFilter(..)")
+    }
+}
+
+/**
+ The order in which the [`Finder`](crate::walk::Finder) reads directories, and emits them relative to their contents.
+
+ Regardless of strategy, the walk itself is parallel, so the relative order of unrelated
+ entries (eg siblings in different subtrees) is not deterministic, see
+ [`Finder::traverse_ordered`](crate::walk::Finder::traverse_ordered) for that.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TraversalStrategy {
+    /// Depth first traversal, a directory is emitted once its own entries have been read.
+    /// Its subdirectories may still be in the process of being walked.
+    #[default]
+    DepthFirst,
+    /**
+     Depth first postorder traversal, a directory is only emitted after *all* of its descendants.
+
+     This is what you want for deletion (`rm -r` style) or `du` style aggregation.
+     It costs some throughput as results have to be flushed eagerly to keep the ordering guarantee.
+    */
+    PostOrder,
+    /**
+     Breadth first traversal, directories are read level by level: none is read until every directory above it
+     has been, so shallower results come first.
+
+     This is what you want to find the shallowest match quickly, or with
+     [`max_results`](crate::walk::FinderBuilder::max_results) to keep the shallowest results. Each level is still
+     read in parallel, but every level waits on its slowest directory, and a whole level is kept queued at once.
+    */
+    BreadthFirst,
+}
+
+/**
+ What the [`Finder`](crate::walk::Finder)'s worker threads do with a batch of results when the consumer isn't keeping up
+ and the result channel is full.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlushPolicy {
+    /// Wait for the consumer to make room, so the walk runs no faster than the results are used.
+    #[default]
+    Block,
+    /**
+     Discard the oldest batch waiting in the channel to make room, so the walk never waits.
+
+     Results are lost whenever the consumer falls behind. As the workers can't tell the consumer apart from
+     their own handle on the channel, dropping the receiver doesn't stop the traversal early.
+    */
+    DropOldest,
+    /**
+     Write batches that don't fit in the channel to an anonymous temporary file, and hand them over
+     (in their original order) once the consumer catches up.
+
+     The walk never waits and nothing is lost, at the cost of disk space in [`std::env::temp_dir`].
+     If the spill file can't be created or written the workers fall back to blocking.
+    */
+    SpillToDisk,
+}
+
+/**
+ How the [`Finder`](crate::walk::Finder) reads directories
+
+ Defaults to [`Native`](Self::Native) on the platforms fdf is tested on (Linux, Android, macOS, the BSDs,
+ Illumos and Solaris) and to [`Portable`](Self::Portable) everywhere else, eg the Hurd or Emscripten.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backend {
+    /**
+     The fastest way the platform offers: `getdents`/`getdirentries` where available (`readdir` otherwise),
+     trusting `d_type` and opening subdirectories relative to their parent.
+    */
+    #[cfg_attr(
+        any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris"
+        ),
+        default
+    )]
+    Native,
+    /**
+     Only `opendir`, `readdir` and `lstat`: every directory is opened by its full path and every entry is
+     `lstat`ed for its type, as `d_type` isn't part of POSIX.
+
+     Much slower, but correct wherever libc is, eg on unusual platforms or in sandboxes which block the raw
+     syscalls. Filters needing metadata still stat the entries the way they always do.
+    */
+    #[cfg_attr(
+        not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "illumos",
+            target_os = "solaris"
+        )),
+        default
+    )]
+    Portable,
+}
+
+/// Returned by the closure passed to [`Finder::visit`](crate::walk::Finder::visit) to steer the traversal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ControlFlow {
+    /// Carry on as normal
+    #[default]
+    Continue,
+    /// Don't descend into this directory (the same as [`Continue`](Self::Continue) for anything else)
+    SkipSubtree,
+    /// End the traversal
+    Stop,
+}
+
+/**
+ A handle for aborting a running traversal from another thread (eg a UI or a signal handler),
+ see [`FinderBuilder::cancellation_token`](crate::walk::FinderBuilder::cancellation_token).
+
+ Clones share the same state, so keep a clone and hand the original to the finder.
+ Once cancelled, the workers stop after the directory they're currently reading, discard anything they have
+ yet to send and close the channel, ending the iterator.
+
+ ```
+ use fdf::walk::{CancellationToken, Finder};
+
+ let token = CancellationToken::new();
+ let finder = Finder::init(env!("CARGO_MANIFEST_DIR"))
+     .cancellation_token(token.clone())
+     .build()
+     .unwrap();
+
+ token.cancel();
+ assert!(token.is_cancelled());
+ assert_eq!(finder.traverse().unwrap().count(), 0);
+ ```
+*/
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token which hasn't been cancelled
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every traversal using this token, this can't be undone
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Returns true once [`cancel`](Self::cancel) has been called on any clone of this token
+    #[inline]
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// The depth limit applied when following symlinks without a maximum depth, see [`FinderBuilder::follow_depth_limit`](crate::walk::FinderBuilder::follow_depth_limit)
+pub const DEFAULT_FOLLOW_DEPTH_LIMIT: u32 = 256;
+
+/**
+ The depth limit applied to a traversal following symlinks without a maximum depth, recording whether it cut
+ the traversal short. See [`Finder::follow_depth_limit`](crate::walk::Finder::follow_depth_limit).
+*/
+#[derive(Debug)]
+pub struct DepthLimit {
+    limit: NonZeroU32,
+    hit: AtomicBool,
+}
+
+impl DepthLimit {
+    pub(crate) const fn new(limit: NonZeroU32) -> Self {
+        Self {
+            limit,
+            hit: AtomicBool::new(false),
+        }
+    }
+
+    /// The depth below which directories aren't read
+    #[inline]
+    #[must_use]
+    pub const fn limit(&self) -> u32 {
+        self.limit.get()
+    }
+
+    /// Returns true once a directory at the limit has been left unread (complete once the traversal has finished)
+    #[inline]
+    #[must_use]
+    pub fn was_hit(&self) -> bool {
+        self.hit.load(Ordering::Relaxed)
+    }
+
+    /// The same limit, not yet hit
+    pub(crate) const fn reset(&self) -> Self {
+        Self::new(self.limit)
+    }
+
+    pub(crate) fn mark_hit(&self) {
+        self.hit.store(true, Ordering::Relaxed);
+    }
+}
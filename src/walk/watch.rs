@@ -0,0 +1,463 @@
+/*!
+ Watching a tree for changes once it has been traversed, see [`Finder::watch`].
+
+ The traversal registers a watch on every directory it reads, so the filters, depth limit etc which decide
+ what is traversed also decide what is watched. A background thread then turns the kernel's events into
+ [`WatchEvent`]s, filtered like the results of a traversal. Directories created later are watched (and their
+ contents reported) as they appear.
+
+ Only inotify (Linux/Android) is supported for now. kqueue (BSD/macOS) only reports that a directory changed,
+ not which entry, so it would need every changed directory read again to tell.
+*/
+
+use crate::{SearchConfigError, fs::DirEntry, walk::Finder};
+use crossbeam_channel::Receiver;
+
+/// A change to the watched tree, as returned by [`Finder::watch`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum WatchEvent {
+    /// An entry was created, or moved into a watched directory
+    Created(DirEntry),
+    /// A regular file was written to
+    Modified(DirEntry),
+    /**
+    An entry was removed, or moved out of a watched directory.
+
+    It can't be statted any more, so its type is only known for directories (otherwise it's
+    [`FileType::Unknown`](crate::fs::FileType::Unknown)) and its inode number is 0.
+    */
+    Deleted(DirEntry),
+}
+
+impl WatchEvent {
+    /// The entry which changed
+    #[inline]
+    #[must_use]
+    #[allow(clippy::pattern_type_mismatch)]
+    pub const fn entry(&self) -> &DirEntry {
+        match self {
+            Self::Created(entry) | Self::Modified(entry) | Self::Deleted(entry) => entry,
+        }
+    }
+
+    /// Takes the entry which changed
+    #[inline]
+    #[must_use]
+    pub fn into_entry(self) -> DirEntry {
+        match self {
+            Self::Created(entry) | Self::Modified(entry) | Self::Deleted(entry) => entry,
+        }
+    }
+
+    /// A lowercase name for the kind of change, eg `"created"`
+    #[inline]
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match *self {
+            Self::Created(_) => "created",
+            Self::Modified(_) => "modified",
+            Self::Deleted(_) => "deleted",
+        }
+    }
+}
+
+impl Finder {
+    /**
+    Traverses the filesystem, watching every directory read, then reports changes to the tree as they happen.
+
+    The existing entries aren't reported, only the changes made once this returns. Changes are filtered like
+    the results of a traversal, except that ignore files aren't consulted and deleted entries (which can't be
+    statted) are only matched on their path (pattern, extension, hidden and exclusions). A burst of writes to
+    a file is reported once, and entries created in a new directory while it's being read may be reported twice.
+
+    The watching thread stops once the [`CancellationToken`](crate::walk::CancellationToken) is cancelled, or
+    when it next has an event to send after the receiver has been dropped. Directories which can't be watched
+    (eg once `fs.inotify.max_user_watches` is reached) are recorded as errors if errors are collected.
+
+    # Examples
+    ```
+    use fdf::walk::{CancellationToken, Finder, WatchEvent};
+    use std::{fs, time::Duration};
+
+    let dir = std::env::temp_dir().join("fdf_watch_doctest");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let token = CancellationToken::new();
+    let events = Finder::init(&dir)
+        .pattern("*.txt")
+        .use_glob(true)
+        .cancellation_token(token.clone())
+        .build()
+        .unwrap()
+        .watch()
+        .unwrap();
+
+    fs::write(dir.join("skipped.log"), "").unwrap();
+    fs::write(dir.join("notes.txt"), "").unwrap();
+    match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+        WatchEvent::Created(entry) => assert_eq!(entry.file_name(), b"notes.txt"),
+        other => panic!("unexpected {other:?}"),
+    }
+    token.cancel();
+    fs::remove_dir_all(&dir).unwrap();
+    ```
+
+    # Errors
+    Returns [`SearchConfigError::NotADirectory`] if the root is not a directory, or
+    [`SearchConfigError::IOError`] if watching isn't supported on this platform or the watcher can't be created.
+    */
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn watch(self) -> Result<Receiver<WatchEvent>, SearchConfigError> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            inotify::watch(self)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            drop(self);
+            Err(SearchConfigError::IOError(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "watching is only supported on Linux and Android",
+            )))
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod inotify {
+    use super::WatchEvent;
+    use crate::{
+        DirEntryError, SearchConfigError, TraversalError,
+        fs::{DirEntry, FileDes, FileType},
+        util::BytePath as _,
+        walk::{
+            Finder,
+            finder::{CANCELLATION_POLL_INTERVAL, Sink, Stopped},
+        },
+    };
+    use core::{cell::Cell, mem};
+    use crossbeam_channel::{Receiver, Sender, unbounded};
+    use dashmap::DashMap;
+    use std::{
+        ffi::{CString, OsStr},
+        io,
+        os::{fd::AsRawFd as _, unix::ffi::OsStrExt as _},
+        thread,
+    };
+
+    /// The events watched for in every directory
+    const MASK: u32 = libc::IN_CREATE
+        | libc::IN_MOVED_TO
+        | libc::IN_MODIFY
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_DONT_FOLLOW
+        | libc::IN_EXCL_UNLINK
+        | libc::IN_ONLYDIR;
+
+    /// The size of an event before its name
+    const HEADER: usize = mem::size_of::<libc::inotify_event>();
+
+    /// A watched directory, looked up by its watch descriptor
+    struct Watched {
+        path: Box<[u8]>,
+        depth: u32,
+    }
+
+    /// An inotify instance and the directories it watches
+    struct Inotify {
+        fd: FileDes,
+        dirs: DashMap<i32, Watched>,
+    }
+
+    /// A [`Sink`] watching every directory the traversal reads, the results themselves aren't needed
+    struct Registrar<'watch> {
+        inotify: &'watch Inotify,
+        finder: &'watch Finder,
+    }
+
+    impl Sink for Registrar<'_> {
+        const DISCARDS_RESULTS: bool = true;
+
+        fn send(&mut self, _entry: DirEntry) -> Result<(), Stopped> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Stopped> {
+            Ok(())
+        }
+
+        fn opened(&mut self, dir: &DirEntry) {
+            self.inotify.add(self.finder, dir);
+        }
+    }
+
+    pub(super) fn watch(finder: Finder) -> Result<Receiver<WatchEvent>, SearchConfigError> {
+        let inotify = Inotify::new()?;
+        finder.walk_scoped(|| Registrar {
+            inotify: &inotify,
+            finder: &finder,
+        })?;
+
+        let (sender, receiver) = unbounded();
+        thread::spawn(move || inotify.run(&finder, &sender));
+        Ok(receiver)
+    }
+
+    impl Inotify {
+        fn new() -> io::Result<Self> {
+            // SAFETY: no pointers are involved
+            let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(Self {
+                // SAFETY: the descriptor was just opened and nothing else owns it
+                fd: unsafe { FileDes::from_raw(fd) },
+                dirs: DashMap::new(),
+            })
+        }
+
+        /// Starts watching `dir`, recording an error if it can't be (eg the watch limit was reached)
+        fn add(&self, finder: &Finder, dir: &DirEntry) {
+            // SAFETY: the path is NUL terminated
+            let wd =
+                unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), dir.path.as_ptr(), MASK) };
+            if wd >= 0 {
+                // Watching a directory again (eg moved elsewhere in the tree) gives the same descriptor, so its path is updated
+                self.dirs.insert(
+                    wd,
+                    Watched {
+                        path: dir.as_bytes().into(),
+                        depth: dir.depth,
+                    },
+                );
+                return;
+            }
+
+            let error = io::Error::last_os_error();
+            if let Some(errors_arc) = finder.errors.as_ref()
+                && let Ok(mut errors) = errors_arc.lock()
+            {
+                errors.push(TraversalError {
+                    dir: dir.clone(),
+                    error: DirEntryError::from(error),
+                });
+            }
+        }
+
+        /// Reads events until the watch is cancelled or the receiver hangs up
+        fn run(&self, finder: &Finder, sender: &Sender<WatchEvent>) {
+            // Aligned for `inotify_event`, and large enough for any single event (whose name is at most NAME_MAX)
+            let mut buffer = [0_u64; 1024];
+            let mut poll_fd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let timeout = i32::try_from(CANCELLATION_POLL_INTERVAL.as_millis()).unwrap_or(i32::MAX);
+
+            while !finder.is_cancelled() {
+                // SAFETY: `poll_fd` is a single valid pollfd
+                if unsafe { libc::poll(&raw mut poll_fd, 1, timeout) } <= 0 {
+                    continue;
+                }
+
+                // SAFETY: at most the buffer's length is written
+                let read = unsafe {
+                    libc::read(
+                        self.fd.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        mem::size_of_val(&buffer),
+                    )
+                };
+                let Ok(read) = usize::try_from(read) else {
+                    match io::Error::last_os_error().kind() {
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => continue,
+                        _ => return,
+                    }
+                };
+
+                // SAFETY: the kernel wrote `read` bytes to the buffer
+                let bytes =
+                    unsafe { core::slice::from_raw_parts(buffer.as_ptr().cast::<u8>(), read) };
+                if self.dispatch(finder, bytes, sender).is_err() {
+                    return;
+                }
+            }
+        }
+
+        /// Handles each event in `bytes`, as read from the inotify descriptor
+        fn dispatch(
+            &self,
+            finder: &Finder,
+            mut bytes: &[u8],
+            sender: &Sender<WatchEvent>,
+        ) -> Result<(), Stopped> {
+            // Writes arrive as a run of IN_MODIFY events, only the first of a run is reported
+            let mut last_modified: Option<(i32, &[u8])> = None;
+
+            while bytes.len() >= HEADER {
+                // SAFETY: the kernel only writes whole events, and the header is plain integers
+                let event = unsafe {
+                    bytes
+                        .as_ptr()
+                        .cast::<libc::inotify_event>()
+                        .read_unaligned()
+                };
+                let name_len = usize::try_from(event.len).unwrap_or(usize::MAX);
+                let Some(padded) = bytes[HEADER..].get(..name_len) else {
+                    break;
+                };
+                bytes = &bytes[HEADER + name_len..];
+                // Names are padded with NULs
+                let name = padded.split(|&byte| byte == 0).next().unwrap_or_default();
+
+                if event.mask & libc::IN_MODIFY != 0 {
+                    if last_modified == Some((event.wd, name)) {
+                        continue;
+                    }
+                    last_modified = Some((event.wd, name));
+                } else {
+                    last_modified = None;
+                }
+
+                self.handle(finder, event.wd, event.mask, name, sender)?;
+            }
+            Ok(())
+        }
+
+        fn handle(
+            &self,
+            finder: &Finder,
+            wd: i32,
+            mask: u32,
+            name: &[u8],
+            sender: &Sender<WatchEvent>,
+        ) -> Result<(), Stopped> {
+            if mask & libc::IN_IGNORED != 0 {
+                // The directory is gone (or unmounted), the kernel has already dropped the watch
+                self.dirs.remove(&wd);
+                return Ok(());
+            }
+
+            // Queue overflows (events lost) have no directory, and events on a watched directory itself have no name
+            if name.is_empty() {
+                return Ok(());
+            }
+            let Some((path, depth)) = self
+                .dirs
+                .get(&wd)
+                .map(|dir| (join(&dir.path, name), dir.depth + 1))
+            else {
+                return Ok(());
+            };
+
+            if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                let Some(entry) = removed(path, depth, mask & libc::IN_ISDIR != 0) else {
+                    return Ok(());
+                };
+                let config = &finder.search_config;
+                if is_excluded(finder, &entry)
+                    || !config.matches_extension(&entry.file_name())
+                    || !config.matches_path(&entry, !config.file_name_only)
+                {
+                    return Ok(());
+                }
+                return sender.send(WatchEvent::Deleted(entry)).map_err(|_| Stopped);
+            }
+
+            // Already gone again, a deletion follows
+            let Ok(mut entry) = DirEntry::new(OsStr::from_bytes(&path)) else {
+                return Ok(());
+            };
+            entry.depth = depth;
+
+            if mask & libc::IN_MODIFY != 0 {
+                if is_excluded(finder, &entry) || !finder.file_filter(&entry, None) {
+                    return Ok(());
+                }
+                return sender
+                    .send(WatchEvent::Modified(entry))
+                    .map_err(|_| Stopped);
+            }
+            self.created(finder, entry, sender)
+        }
+
+        /// Reports a new entry, then watches it (reporting its contents) if it's a directory which would be read
+        fn created(
+            &self,
+            finder: &Finder,
+            entry: DirEntry,
+            sender: &Sender<WatchEvent>,
+        ) -> Result<(), Stopped> {
+            if is_excluded(finder, &entry) {
+                return Ok(());
+            }
+
+            let matched = finder.file_filter(&entry, None);
+            let read = finder.should_traverse(&entry, None)
+                && !(finder.is_at_depth_limit(&entry)
+                    || finder.prune && matched
+                    || finder.is_pruned_dir(&entry));
+            if read {
+                // Watched before being read, so nothing created in the meantime is missed
+                self.add(finder, &entry);
+            }
+            if matched {
+                sender
+                    .send(WatchEvent::Created(entry.clone()))
+                    .map_err(|_| Stopped)?;
+            }
+
+            if read && let Ok(children) = read_direntries!(entry, finder.getdents_buffer_size) {
+                for child in children {
+                    self.created(finder, child, sender)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Whether the entry is skipped by the filters which also stop directories being read
+    fn is_excluded(finder: &Finder, entry: &DirEntry) -> bool {
+        !finder.keep_hidden(entry)
+            || finder.matches_ignore_path(entry)
+            || finder.search_config.matches_exclude(entry)
+    }
+
+    /// Joins a directory's path and a name within it
+    fn join(dir: &[u8], name: &[u8]) -> Vec<u8> {
+        let mut path = Vec::with_capacity(dir.len() + 1 + name.len());
+        path.extend_from_slice(dir);
+        if !dir.ends_with(b"/") {
+            path.push(b'/');
+        }
+        path.extend_from_slice(name);
+        path
+    }
+
+    /// An entry for a path which no longer exists, so can't be statted
+    fn removed(path: Vec<u8>, depth: u32, is_dir: bool) -> Option<DirEntry> {
+        let file_name_index = path.file_name_index();
+        let file_type = if is_dir {
+            FileType::Directory
+        } else {
+            FileType::Unknown
+        };
+
+        Some(DirEntry {
+            path: CString::new(path).ok()?.into_boxed_c_str(),
+            file_type,
+            inode: 0,
+            depth,
+            file_name_index,
+            is_traversible_cache: Cell::new(Some(is_dir)),
+            device: Cell::new(None),
+        })
+    }
+}
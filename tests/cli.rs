@@ -0,0 +1,72 @@
+use std::env::temp_dir;
+use std::fs;
+use std::process::Command;
+
+#[test]
+#[cfg(any(feature = "xxhash", feature = "blake3"))]
+fn test_duplicates_limit_counts_groups_not_files() {
+    let temp_dir = temp_dir().join("cli_duplicates_limit_test");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(temp_dir.join("nested")).unwrap();
+    // Plenty of files which aren't duplicates, so stopping the walk early would almost certainly miss a pair
+    for size in 1..=50 {
+        fs::write(temp_dir.join(format!("unique_{size}")), "x".repeat(size)).unwrap();
+    }
+    fs::write(temp_dir.join("nested/copy_a"), "duplicated contents").unwrap();
+    fs::write(temp_dir.join("nested/copy_b"), "duplicated contents").unwrap();
+    fs::write(temp_dir.join("nested/other_a"), "more duplicated contents").unwrap();
+    fs::write(temp_dir.join("nested/other_b"), "more duplicated contents").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fdf"))
+        .args(["--duplicates", "-n", "1", "."])
+        .arg(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    // Every file is still hashed, `-n` only keeps the largest group
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("2 files of 24 bytes ("),
+        "unexpected output: {stdout}"
+    );
+    assert_eq!(stdout.lines().count(), 3, "unexpected output: {stdout}");
+    assert!(stdout.contains("nested/other_a\n") && stdout.contains("nested/other_b\n"));
+
+    fs::remove_dir_all(&temp_dir).unwrap();
+}
+
+#[test]
+fn test_parallel_exec_output_is_not_interleaved() {
+    let temp_dir = temp_dir().join("cli_parallel_exec_test");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    for index in 0..32 {
+        fs::write(temp_dir.join(format!("file_{index}")), "").unwrap();
+    }
+
+    // Each command writes its output in two parts, with a pause in between for others to run
+    let output = Command::new(env!("CARGO_BIN_EXE_fdf"))
+        .args(["-j", "4", "-t", "f", "."])
+        .arg(&temp_dir)
+        .args(["-x", "sh", "-c"])
+        .arg("printf 'start %s\\n' \"$1\"; sleep 0.01; printf 'end %s\\n' \"$1\"")
+        .args(["sh", "{/}"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 64, "unexpected output: {stdout}");
+    for pair in lines.chunks(2) {
+        let name = pair[0].strip_prefix("start ").unwrap();
+        assert_eq!(
+            pair[1],
+            format!("end {name}"),
+            "unexpected output: {stdout}"
+        );
+    }
+
+    fs::remove_dir_all(&temp_dir).unwrap();
+}